@@ -0,0 +1,250 @@
+//! Compression of chunk payloads.
+
+use std::{
+    hash::Hasher,
+    io::{self, Read, Write},
+};
+
+use flate2::{read::GzDecoder, read::ZlibDecoder, write::GzEncoder, write::ZlibEncoder};
+use thiserror::Error;
+use twox_hash::XxHash32;
+
+/// Magic bytes starting each block of the LZ4 stream format used by vanilla (from lz4-java).
+const LZ4_MAGIC: &[u8; 8] = b"LZ4Block";
+/// Length of an LZ4 block header: magic, token, compressed and original length, checksum.
+const LZ4_HEADER_LEN: usize = LZ4_MAGIC.len() + 1 + 4 + 4 + 4;
+const LZ4_METHOD_RAW: u8 = 0x10;
+const LZ4_METHOD_LZ4: u8 = 0x20;
+/// Block sizes are `1 << (LZ4_LEVEL_BASE + level)`, the level being stored in the token.
+const LZ4_LEVEL_BASE: u8 = 10;
+/// Level of the 64 KiB blocks written by lz4-java by default.
+const LZ4_LEVEL: u8 = 6;
+const LZ4_BLOCK_SIZE: usize = 1 << (LZ4_LEVEL_BASE + LZ4_LEVEL);
+const LZ4_CHECKSUM_SEED: u32 = 0x9747b28c;
+
+/// How a chunk payload is compressed, as stored in region files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(u8)]
+pub enum Compression {
+    Gzip = 1,
+    /// What vanilla uses by default.
+    #[default]
+    Zlib = 2,
+    None = 3,
+    Lz4 = 4,
+}
+
+impl Compression {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::Gzip),
+            2 => Some(Self::Zlib),
+            3 => Some(Self::None),
+            4 => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    /// Parses the name used by the `region-file-compression` server property.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "deflate" => Some(Self::Zlib),
+            "none" => Some(Self::None),
+            "lz4" => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(data)
+                    .expect("writing to a Vec should not fail");
+                encoder.finish().expect("writing to a Vec should not fail")
+            }
+            Self::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(data)
+                    .expect("writing to a Vec should not fail");
+                encoder.finish().expect("writing to a Vec should not fail")
+            }
+            Self::None => data.to_vec(),
+            Self::Lz4 => lz4_compress(data),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+        let mut decompressed = Vec::new();
+        match self {
+            Self::Gzip => {
+                GzDecoder::new(data).read_to_end(&mut decompressed)?;
+            }
+            Self::Zlib => {
+                ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+            }
+            Self::None => decompressed.extend_from_slice(data),
+            Self::Lz4 => decompressed = lz4_decompress(data)?,
+        }
+        Ok(decompressed)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DecompressError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid LZ4 stream: {0}")]
+    Lz4(&'static str),
+    #[error(transparent)]
+    Lz4Block(#[from] lz4_flex::block::DecompressError),
+}
+
+fn lz4_checksum(data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(LZ4_CHECKSUM_SEED);
+    hasher.write(data);
+    // lz4-java only keeps the low 28 bits.
+    hasher.finish() as u32 & 0x0FFF_FFFF
+}
+
+/// Compresses `data` into lz4-java's block stream format, ended by an empty block.
+fn lz4_compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut write_block = |method: u8, block: &[u8], original_len: usize, checksum: u32| {
+        output.extend_from_slice(LZ4_MAGIC);
+        output.push(method | LZ4_LEVEL);
+        output.extend_from_slice(&(block.len() as u32).to_le_bytes());
+        output.extend_from_slice(&(original_len as u32).to_le_bytes());
+        output.extend_from_slice(&checksum.to_le_bytes());
+        output.extend_from_slice(block);
+    };
+
+    for chunk in data.chunks(LZ4_BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(chunk);
+        let checksum = lz4_checksum(chunk);
+        if compressed.len() < chunk.len() {
+            write_block(LZ4_METHOD_LZ4, &compressed, chunk.len(), checksum);
+        } else {
+            write_block(LZ4_METHOD_RAW, chunk, chunk.len(), checksum);
+        }
+    }
+    write_block(LZ4_METHOD_RAW, &[], 0, 0);
+    output
+}
+
+fn lz4_decompress(mut data: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let mut output = Vec::new();
+    loop {
+        if data.len() < LZ4_HEADER_LEN {
+            return Err(DecompressError::Lz4("truncated block header"));
+        }
+        let (header, rest) = data.split_at(LZ4_HEADER_LEN);
+        if &header[..LZ4_MAGIC.len()] != LZ4_MAGIC {
+            return Err(DecompressError::Lz4("invalid magic"));
+        }
+        let token = header[8];
+        let read_u32 = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let compressed_len = read_u32(9) as usize;
+        let original_len = read_u32(13) as usize;
+        let checksum = read_u32(17);
+
+        let block_size = 1usize << (LZ4_LEVEL_BASE + (token & 0x0F));
+        if original_len > block_size || compressed_len > rest.len() {
+            return Err(DecompressError::Lz4("invalid block length"));
+        }
+        if original_len == 0 {
+            // The empty block ends the stream.
+            return Ok(output);
+        }
+
+        let (block, rest) = rest.split_at(compressed_len);
+        let start = output.len();
+        match token & 0xF0 {
+            LZ4_METHOD_RAW if compressed_len == original_len => output.extend_from_slice(block),
+            LZ4_METHOD_LZ4 => {
+                output.extend(lz4_flex::block::decompress(block, original_len)?);
+            }
+            _ => return Err(DecompressError::Lz4("invalid block token")),
+        }
+        if output.len() - start != original_len || lz4_checksum(&output[start..]) != checksum {
+            return Err(DecompressError::Lz4("block checksum mismatch"));
+        }
+        data = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSIONS: [Compression; 4] = [
+        Compression::Gzip,
+        Compression::Zlib,
+        Compression::None,
+        Compression::Lz4,
+    ];
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| (i % 251) as u8 ^ (i / 1000) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        for compression in COMPRESSIONS {
+            for len in [0, 1, 1000, LZ4_BLOCK_SIZE, LZ4_BLOCK_SIZE * 3 + 17] {
+                let data = sample(len);
+                let compressed = compression.compress(&data);
+                assert_eq!(
+                    compression.decompress(&compressed).unwrap(),
+                    data,
+                    "{compression:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lz4_stored_blocks() {
+        // Random-looking data doesn't compress, and is stored raw.
+        let mut state = 0x2545F491u32;
+        let data: Vec<u8> = (0..5000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let compressed = lz4_compress(&data);
+        assert_eq!(compressed[8] & 0xF0, LZ4_METHOD_RAW);
+        assert_eq!(lz4_decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn lz4_corrupted() {
+        let mut compressed = lz4_compress(&sample(1000));
+        let last = compressed.len() - LZ4_HEADER_LEN - 1;
+        compressed[last] ^= 0xFF;
+        assert!(lz4_decompress(&compressed).is_err());
+
+        assert!(lz4_decompress(b"LZ4Bl").is_err());
+        assert!(lz4_decompress(&[0; LZ4_HEADER_LEN]).is_err());
+    }
+
+    #[test]
+    fn ids() {
+        for compression in COMPRESSIONS {
+            assert_eq!(Compression::from_id(compression.id()), Some(compression));
+        }
+        assert_eq!(Compression::from_id(0), None);
+        assert_eq!(Compression::from_id(127), None);
+    }
+}
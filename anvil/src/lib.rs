@@ -0,0 +1,745 @@
+//! Reading and writing Anvil region files (`.mca`), which store the chunks of a world.
+//!
+//! A region file holds 32x32 chunks. It starts with two 4 KiB tables: the location of each
+//! chunk (a sector offset and sector count) and when it was last saved. Chunks are stored in
+//! 4 KiB sectors after them, each as a length, a [`Compression`] type and the compressed NBT.
+//! Chunks too large for a region file (1 MiB) are stored in separate `.mcc` files instead.
+
+pub mod compression;
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+pub use compression::{Compression, DecompressError};
+
+/// Size of a sector, the unit of allocation in region files.
+pub const SECTOR_SIZE: usize = 4096;
+/// Width and depth of a region, in chunks.
+pub const REGION_SIZE: i32 = 32;
+
+/// Number of chunks in a region.
+const CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE) as usize;
+/// Sectors taken by the location and timestamp tables.
+const HEADER_SECTORS: u32 = 2;
+/// Most sectors a chunk can take, as the sector count is a single byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+/// Length of the chunk header: the payload length and the compression type.
+const CHUNK_HEADER_LEN: usize = 5;
+/// Flag of the compression type of chunks stored in `.mcc` files.
+const EXTERNAL_FLAG: u8 = 0x80;
+
+/// The region containing the chunk at `chunk_x` and `chunk_z`, as `(x, z)`.
+pub fn region_of(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+    (chunk_x >> 5, chunk_z >> 5)
+}
+
+/// File name of the region at `region_x` and `region_z`.
+pub fn region_file_name(region_x: i32, region_z: i32) -> String {
+    format!("r.{region_x}.{region_z}.mca")
+}
+
+/// File name of the oversized chunk at `chunk_x` and `chunk_z`.
+pub fn external_file_name(chunk_x: i32, chunk_z: i32) -> String {
+    format!("c.{chunk_x}.{chunk_z}.mcc")
+}
+
+/// Where a chunk is stored in a region file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Location {
+    /// First sector, 0 if the chunk isn't stored.
+    offset: u32,
+    sectors: u32,
+}
+
+impl Location {
+    fn from_u32(value: u32) -> Self {
+        Self {
+            offset: value >> 8,
+            sectors: value & 0xFF,
+        }
+    }
+
+    fn to_u32(self) -> u32 {
+        (self.offset << 8) | self.sectors
+    }
+
+    fn is_empty(self) -> bool {
+        self.offset == 0
+    }
+
+    fn range(self) -> std::ops::Range<usize> {
+        self.offset as usize..(self.offset + self.sectors) as usize
+    }
+}
+
+/// An open region file.
+#[derive(Debug)]
+pub struct RegionFile {
+    file: File,
+    /// Directory of the region file, where `.mcc` files go.
+    directory: PathBuf,
+    region_x: i32,
+    region_z: i32,
+    locations: Box<[Location; CHUNK_COUNT]>,
+    timestamps: Box<[u32; CHUNK_COUNT]>,
+    /// Which sectors are used, by the header or a chunk.
+    used_sectors: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens the region at `region_x` and `region_z` in `directory`, creating it if it doesn't
+    /// exist.
+    pub fn open(directory: &Path, region_x: i32, region_z: i32) -> Result<Self, RegionError> {
+        let path = directory.join(region_file_name(region_x, region_z));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let mut header = vec![0; HEADER_SECTORS as usize * SECTOR_SIZE];
+        let len = file.metadata()?.len() as usize;
+        if len < header.len() {
+            // New (or truncated, like vanilla tolerates) files get an empty header.
+            file.set_len(header.len() as u64)?;
+            file.write_all(&header)?;
+        } else {
+            file.read_exact(&mut header)?;
+        }
+
+        let read_u32 =
+            |index: usize| u32::from_be_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+        let mut region = Self {
+            file,
+            directory: directory.to_owned(),
+            region_x,
+            region_z,
+            locations: Box::new(std::array::from_fn(|index| {
+                Location::from_u32(read_u32(index))
+            })),
+            timestamps: Box::new(std::array::from_fn(|index| read_u32(CHUNK_COUNT + index))),
+            used_sectors: vec![true; HEADER_SECTORS as usize],
+        };
+
+        let sector_count = len.max(region.header_len()).div_ceil(SECTOR_SIZE);
+        for index in 0..CHUNK_COUNT {
+            let location = region.locations[index];
+            if location.is_empty() {
+                continue;
+            }
+            // Chunks overlapping the header or past the end of the file are dropped.
+            let end = location.range().end;
+            if location.offset < HEADER_SECTORS || location.sectors == 0 || end > sector_count {
+                region.locations[index] = Location::default();
+                continue;
+            }
+            region.mark_sectors(location, true);
+        }
+        Ok(region)
+    }
+
+    fn header_len(&self) -> usize {
+        HEADER_SECTORS as usize * SECTOR_SIZE
+    }
+
+    pub fn region_x(&self) -> i32 {
+        self.region_x
+    }
+
+    pub fn region_z(&self) -> i32 {
+        self.region_z
+    }
+
+    /// Index of the chunk at `chunk_x` and `chunk_z` (of which only the position within the
+    /// region is used) in the header tables.
+    fn index(chunk_x: i32, chunk_z: i32) -> usize {
+        ((chunk_x & 31) + (chunk_z & 31) * REGION_SIZE) as usize
+    }
+
+    /// The absolute chunk coordinates of the chunk at `index`.
+    fn chunk_at(&self, index: usize) -> (i32, i32) {
+        let index = index as i32;
+        (
+            self.region_x * REGION_SIZE + index % REGION_SIZE,
+            self.region_z * REGION_SIZE + index / REGION_SIZE,
+        )
+    }
+
+    /// Whether the chunk at `chunk_x` and `chunk_z` is stored.
+    pub fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        !self.locations[Self::index(chunk_x, chunk_z)].is_empty()
+    }
+
+    /// When the chunk at `chunk_x` and `chunk_z` was last saved, in seconds since the Unix
+    /// epoch, or 0 if it isn't stored.
+    pub fn timestamp(&self, chunk_x: i32, chunk_z: i32) -> u32 {
+        self.timestamps[Self::index(chunk_x, chunk_z)]
+    }
+
+    /// Iterates over the absolute coordinates of the stored chunks.
+    pub fn chunks(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..CHUNK_COUNT)
+            .filter(|&index| !self.locations[index].is_empty())
+            .map(|index| self.chunk_at(index))
+    }
+
+    /// Reads the chunk at `chunk_x` and `chunk_z`, returning its uncompressed NBT, or `None` if
+    /// it isn't stored.
+    pub fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Vec<u8>>, RegionError> {
+        let Some((compression, payload)) = self.read_payload(chunk_x, chunk_z)? else {
+            return Ok(None);
+        };
+        let compression = Compression::from_id(compression & !EXTERNAL_FLAG)
+            .ok_or(RegionError::UnknownCompression(compression))?;
+        let nbt = compression
+            .decompress(&payload)
+            .map_err(|source| RegionError::Decompress {
+                chunk_x,
+                chunk_z,
+                source,
+            })?;
+        Ok(Some(nbt))
+    }
+
+    /// Reads the compression type and compressed payload of a chunk, from the `.mcc` file if
+    /// it's stored there.
+    fn read_payload(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<(u8, Vec<u8>)>, RegionError> {
+        let location = self.locations[Self::index(chunk_x, chunk_z)];
+        if location.is_empty() {
+            return Ok(None);
+        }
+        let invalid = |reason| RegionError::InvalidChunk {
+            chunk_x,
+            chunk_z,
+            reason,
+        };
+
+        let mut sectors = vec![0; location.sectors as usize * SECTOR_SIZE];
+        self.file
+            .seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE as u64))?;
+        self.file.read_exact(&mut sectors)?;
+
+        let len = u32::from_be_bytes(sectors[..4].try_into().unwrap()) as usize;
+        if len == 0 || CHUNK_HEADER_LEN - 1 + len > sectors.len() {
+            return Err(invalid("payload length doesn't fit its sectors"));
+        }
+        let compression = sectors[4];
+        if compression & EXTERNAL_FLAG != 0 {
+            if len != 1 {
+                return Err(invalid("chunk in an external file has a payload"));
+            }
+            let path = self.directory.join(external_file_name(chunk_x, chunk_z));
+            return Ok(Some((compression, fs::read(path)?)));
+        }
+        sectors.truncate(CHUNK_HEADER_LEN - 1 + len);
+        sectors.drain(..CHUNK_HEADER_LEN);
+        Ok(Some((compression, sectors)))
+    }
+
+    /// Writes the chunk at `chunk_x` and `chunk_z`, compressing its NBT with `compression`.
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        nbt: &[u8],
+        compression: Compression,
+    ) -> Result<(), RegionError> {
+        let compressed = compression.compress(nbt);
+        let index = Self::index(chunk_x, chunk_z);
+        let previous = self.locations[index];
+        let external = self.write_payload(chunk_x, chunk_z, compression.id(), &compressed)?;
+        self.timestamps[index] = now();
+        self.write_header_entry(index)?;
+
+        // The previous sectors and `.mcc` file are only reused once the header on the disk
+        // points to the new ones, so that the chunk is never lost halfway through.
+        self.file.sync_data()?;
+        self.mark_sectors(previous, false);
+        if !external {
+            remove_if_exists(&self.directory.join(external_file_name(chunk_x, chunk_z)))?;
+        }
+        Ok(())
+    }
+
+    /// Stores a compressed payload in newly allocated sectors, or in an `.mcc` file if it
+    /// doesn't fit in the region file, returning whether it did. The header isn't written.
+    fn write_payload(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        compression: u8,
+        payload: &[u8],
+    ) -> Result<bool, RegionError> {
+        let mut data = Vec::with_capacity(CHUNK_HEADER_LEN + payload.len());
+        let external = CHUNK_HEADER_LEN + payload.len() > MAX_CHUNK_SECTORS * SECTOR_SIZE;
+        if external {
+            // Replaced at once, as the header may already point to the previous file.
+            let path = self.directory.join(external_file_name(chunk_x, chunk_z));
+            let temporary = path.with_extension("mcc.tmp");
+            fs::write(&temporary, payload)?;
+            fs::rename(&temporary, &path)?;
+            data.extend_from_slice(&1u32.to_be_bytes());
+            data.push(compression | EXTERNAL_FLAG);
+        } else {
+            data.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+            data.push(compression);
+            data.extend_from_slice(payload);
+        }
+        data.resize(data.len().next_multiple_of(SECTOR_SIZE), 0);
+
+        let location = self.allocate(data.len() / SECTOR_SIZE);
+        self.file
+            .seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE as u64))?;
+        self.file.write_all(&data)?;
+        self.locations[Self::index(chunk_x, chunk_z)] = location;
+        Ok(external)
+    }
+
+    /// Removes the chunk at `chunk_x` and `chunk_z`.
+    pub fn remove_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<(), RegionError> {
+        let index = Self::index(chunk_x, chunk_z);
+        let location = self.locations[index];
+        if location.is_empty() {
+            return Ok(());
+        }
+        self.locations[index] = Location::default();
+        self.timestamps[index] = 0;
+        self.write_header_entry(index)?;
+        self.file.sync_data()?;
+        self.mark_sectors(location, false);
+        remove_if_exists(&self.directory.join(external_file_name(chunk_x, chunk_z)))?;
+        Ok(())
+    }
+
+    /// Finds `sectors` free consecutive sectors, the first ones that fit or at the end of the
+    /// file, and marks them used.
+    fn allocate(&mut self, sectors: usize) -> Location {
+        let mut start = HEADER_SECTORS as usize;
+        let mut free = 0;
+        for (sector, &used) in self.used_sectors.iter().enumerate() {
+            if used {
+                start = sector + 1;
+                free = 0;
+            } else {
+                free += 1;
+                if free == sectors {
+                    break;
+                }
+            }
+        }
+        let location = Location {
+            offset: start as u32,
+            sectors: sectors as u32,
+        };
+        self.mark_sectors(location, true);
+        location
+    }
+
+    fn mark_sectors(&mut self, location: Location, used: bool) {
+        if location.is_empty() {
+            return;
+        }
+        let range = location.range();
+        if self.used_sectors.len() < range.end {
+            self.used_sectors.resize(range.end, false);
+        }
+        self.used_sectors[range].fill(used);
+    }
+
+    /// Writes the location and timestamp of the chunk at `index` to the file.
+    fn write_header_entry(&mut self, index: usize) -> Result<(), RegionError> {
+        self.file.seek(SeekFrom::Start(index as u64 * 4))?;
+        self.file
+            .write_all(&self.locations[index].to_u32().to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((CHUNK_COUNT + index) as u64 * 4))?;
+        self.file.write_all(&self.timestamps[index].to_be_bytes())?;
+        Ok(())
+    }
+
+    /// Number of sectors used by chunks stored in the region file.
+    pub fn used_sectors(&self) -> usize {
+        self.used_sectors.iter().filter(|&&used| used).count() - HEADER_SECTORS as usize
+    }
+
+    /// Number of sectors of the file, used or not.
+    pub fn file_sectors(&self) -> Result<usize, RegionError> {
+        Ok((self.file.metadata()?.len() as usize).div_ceil(SECTOR_SIZE))
+    }
+
+    /// Moves all chunks next to each other, in the order they are in the header, leaving out the
+    /// free space between them.
+    ///
+    /// The chunks are copied into a new file, which then replaces the region file, so that it
+    /// stays whole if the server stops halfway through.
+    pub fn defragment(&mut self) -> Result<(), RegionError> {
+        let path = self
+            .directory
+            .join(region_file_name(self.region_x, self.region_z));
+        let temporary = path.with_extension("mca.tmp");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temporary)?;
+
+        let mut locations = Box::new([Location::default(); CHUNK_COUNT]);
+        let mut offset = HEADER_SECTORS;
+        file.seek(SeekFrom::Start(self.header_len() as u64))?;
+        for index in 0..CHUNK_COUNT {
+            let location = self.locations[index];
+            if location.is_empty() {
+                continue;
+            }
+            // Chunks in `.mcc` files only have their header in the region file, which is copied
+            // along with the rest.
+            let mut sectors = vec![0; location.sectors as usize * SECTOR_SIZE];
+            self.file
+                .seek(SeekFrom::Start(location.offset as u64 * SECTOR_SIZE as u64))?;
+            self.file.read_exact(&mut sectors)?;
+            file.write_all(&sectors)?;
+            locations[index] = Location {
+                offset,
+                sectors: location.sectors,
+            };
+            offset += location.sectors;
+        }
+
+        let mut header = Vec::with_capacity(self.header_len());
+        for location in locations.iter() {
+            header.extend_from_slice(&location.to_u32().to_be_bytes());
+        }
+        for timestamp in self.timestamps.iter() {
+            header.extend_from_slice(&timestamp.to_be_bytes());
+        }
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+
+        self.file = file;
+        self.locations = locations;
+        self.used_sectors = vec![true; offset as usize];
+        Ok(())
+    }
+
+    /// Flushes writes to the disk.
+    pub fn sync(&self) -> Result<(), RegionError> {
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as u32)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// The region files of a dimension, in a `region` directory, opened as needed.
+#[derive(Debug)]
+pub struct RegionStorage {
+    directory: PathBuf,
+    compression: Compression,
+    regions: HashMap<(i32, i32), RegionFile>,
+}
+
+impl RegionStorage {
+    /// Makes a storage for the region files in `directory`, writing chunks with `compression`.
+    pub fn new(directory: impl Into<PathBuf>, compression: Compression) -> Self {
+        Self {
+            directory: directory.into(),
+            compression,
+            regions: HashMap::new(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the region containing the chunk at `chunk_x` and `chunk_z`, opening it if needed.
+    ///
+    /// If `create` is false and the region file doesn't exist, returns `None`.
+    fn region(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        create: bool,
+    ) -> Result<Option<&mut RegionFile>, RegionError> {
+        let (region_x, region_z) = region_of(chunk_x, chunk_z);
+        if !self.regions.contains_key(&(region_x, region_z)) {
+            let path = self.directory.join(region_file_name(region_x, region_z));
+            if !create && !path.exists() {
+                return Ok(None);
+            }
+            fs::create_dir_all(&self.directory)?;
+            let region = RegionFile::open(&self.directory, region_x, region_z)?;
+            self.regions.insert((region_x, region_z), region);
+        }
+        Ok(self.regions.get_mut(&(region_x, region_z)))
+    }
+
+    /// Reads the chunk at `chunk_x` and `chunk_z`, returning its uncompressed NBT, or `None` if
+    /// it isn't stored.
+    pub fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Vec<u8>>, RegionError> {
+        match self.region(chunk_x, chunk_z, false)? {
+            Some(region) => region.read_chunk(chunk_x, chunk_z),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the uncompressed NBT of the chunk at `chunk_x` and `chunk_z`.
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        nbt: &[u8],
+    ) -> Result<(), RegionError> {
+        let compression = self.compression;
+        self.region(chunk_x, chunk_z, true)?
+            .expect("the region should be created")
+            .write_chunk(chunk_x, chunk_z, nbt, compression)
+    }
+
+    /// Removes the chunk at `chunk_x` and `chunk_z`.
+    pub fn remove_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Result<(), RegionError> {
+        match self.region(chunk_x, chunk_z, false)? {
+            Some(region) => region.remove_chunk(chunk_x, chunk_z),
+            None => Ok(()),
+        }
+    }
+
+    /// Flushes writes of all open regions to the disk.
+    pub fn sync(&self) -> Result<(), RegionError> {
+        self.regions.values().try_for_each(RegionFile::sync)
+    }
+
+    /// Closes the open regions, e.g. to not keep too many files open.
+    pub fn close_all(&mut self) {
+        self.regions.clear();
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RegionError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("unknown chunk compression type {0}")]
+    UnknownCompression(u8),
+    #[error("invalid chunk {chunk_x}, {chunk_z}: {reason}")]
+    InvalidChunk {
+        chunk_x: i32,
+        chunk_z: i32,
+        reason: &'static str,
+    },
+    #[error("unable to decompress chunk {chunk_x}, {chunk_z}: {source}")]
+    Decompress {
+        chunk_x: i32,
+        chunk_z: i32,
+        source: DecompressError,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes recognizable chunk NBT of about `len` bytes, not actually NBT.
+    fn fixture_chunk(chunk_x: i32, chunk_z: i32, len: usize) -> Vec<u8> {
+        let mut data = format!("chunk {chunk_x} {chunk_z};").into_bytes();
+        let mut state = (chunk_x as u32)
+            .wrapping_mul(31)
+            .wrapping_add(chunk_z as u32);
+        while data.len() < len {
+            // Pseudo-random, so that it doesn't compress much.
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            data.push((state >> 16) as u8);
+        }
+        data
+    }
+
+    #[test]
+    fn write_and_read() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut region = RegionFile::open(directory.path(), -1, 0).unwrap();
+        assert_eq!(region.chunks().count(), 0);
+
+        let chunks = [(-32, 0, 100), (-1, 31, 5000), (-17, 4, 20000)];
+        for (chunk_x, chunk_z, len) in chunks {
+            let nbt = fixture_chunk(chunk_x, chunk_z, len);
+            region
+                .write_chunk(chunk_x, chunk_z, &nbt, Compression::Zlib)
+                .unwrap();
+        }
+        assert!(region.timestamp(-1, 31) > 0);
+        assert_eq!(region.timestamp(-2, 31), 0);
+        drop(region);
+
+        let mut region = RegionFile::open(directory.path(), -1, 0).unwrap();
+        let mut stored: Vec<_> = region.chunks().collect();
+        stored.sort();
+        assert_eq!(stored, vec![(-32, 0), (-17, 4), (-1, 31)]);
+        for (chunk_x, chunk_z, len) in chunks {
+            let nbt = region.read_chunk(chunk_x, chunk_z).unwrap();
+            assert_eq!(nbt, Some(fixture_chunk(chunk_x, chunk_z, len)));
+        }
+        assert_eq!(region.read_chunk(-2, 2).unwrap(), None);
+    }
+
+    #[test]
+    fn compressions() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut region = RegionFile::open(directory.path(), 0, 0).unwrap();
+        let compressions = [
+            Compression::Gzip,
+            Compression::Zlib,
+            Compression::None,
+            Compression::Lz4,
+        ];
+        for (x, compression) in compressions.into_iter().enumerate() {
+            let nbt = fixture_chunk(x as i32, 0, 3000);
+            region.write_chunk(x as i32, 0, &nbt, compression).unwrap();
+        }
+        for x in 0..compressions.len() as i32 {
+            assert_eq!(
+                region.read_chunk(x, 0).unwrap(),
+                Some(fixture_chunk(x, 0, 3000))
+            );
+        }
+    }
+
+    #[test]
+    fn reuses_freed_sectors() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut region = RegionFile::open(directory.path(), 0, 0).unwrap();
+        let big = fixture_chunk(0, 0, 3 * SECTOR_SIZE);
+        region.write_chunk(0, 0, &big, Compression::None).unwrap();
+        region.write_chunk(1, 0, &big, Compression::None).unwrap();
+        assert_eq!(region.used_sectors(), 8);
+
+        // Rewriting the first chunk frees its sectors once it's moved, for a small chunk to fit
+        // in between.
+        let small = fixture_chunk(0, 0, 100);
+        region.write_chunk(0, 0, &small, Compression::None).unwrap();
+        assert_eq!(region.file_sectors().unwrap(), 11);
+        region.write_chunk(2, 0, &small, Compression::None).unwrap();
+        assert_eq!(region.used_sectors(), 6);
+        assert_eq!(region.file_sectors().unwrap(), 11);
+        assert_eq!(region.read_chunk(0, 0).unwrap(), Some(small.clone()));
+
+        region.remove_chunk(1, 0).unwrap();
+        assert!(!region.has_chunk(1, 0));
+        assert_eq!(region.read_chunk(1, 0).unwrap(), None);
+        assert_eq!(region.read_chunk(2, 0).unwrap(), Some(small));
+    }
+
+    #[test]
+    fn defragment() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut region = RegionFile::open(directory.path(), 0, 0).unwrap();
+        for x in 0..8 {
+            let nbt = fixture_chunk(x, 0, 2 * SECTOR_SIZE);
+            region.write_chunk(x, 0, &nbt, Compression::None).unwrap();
+        }
+        for x in (0..8).step_by(2) {
+            region.remove_chunk(x, 0).unwrap();
+        }
+        assert_eq!(region.file_sectors().unwrap(), 2 + 8 * 3);
+
+        region.defragment().unwrap();
+        assert_eq!(region.file_sectors().unwrap(), 2 + 4 * 3);
+        drop(region);
+
+        let mut region = RegionFile::open(directory.path(), 0, 0).unwrap();
+        assert_eq!(region.chunks().count(), 4);
+        for x in (1..8).step_by(2) {
+            let nbt = fixture_chunk(x, 0, 2 * SECTOR_SIZE);
+            assert_eq!(region.read_chunk(x, 0).unwrap(), Some(nbt));
+        }
+    }
+
+    #[test]
+    fn external_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut storage = RegionStorage::new(directory.path().join("region"), Compression::None);
+        let huge = fixture_chunk(40, -3, MAX_CHUNK_SECTORS * SECTOR_SIZE);
+        storage.write_chunk(40, -3, &huge).unwrap();
+
+        let external = storage.directory().join(external_file_name(40, -3));
+        assert!(external.exists());
+        assert!(storage.directory().join(region_file_name(1, -1)).exists());
+        assert_eq!(storage.read_chunk(40, -3).unwrap(), Some(huge));
+
+        // Once it fits again, the external file is removed.
+        let small = fixture_chunk(40, -3, 10);
+        storage.write_chunk(40, -3, &small).unwrap();
+        assert!(!external.exists());
+        assert_eq!(storage.read_chunk(40, -3).unwrap(), Some(small));
+    }
+
+    #[test]
+    fn missing_regions() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut storage = RegionStorage::new(directory.path(), Compression::Zlib);
+        assert_eq!(storage.read_chunk(100, 100).unwrap(), None);
+        storage.remove_chunk(100, 100).unwrap();
+        assert!(!directory.path().join(region_file_name(3, 3)).exists());
+    }
+
+    #[test]
+    fn invalid_locations() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut header = vec![0; HEADER_SECTORS as usize * SECTOR_SIZE];
+        // Overlapping the header, and past the end of the file.
+        header[..4].copy_from_slice(
+            &Location {
+                offset: 1,
+                sectors: 1,
+            }
+            .to_u32()
+            .to_be_bytes(),
+        );
+        header[4..8].copy_from_slice(
+            &Location {
+                offset: 5,
+                sectors: 1,
+            }
+            .to_u32()
+            .to_be_bytes(),
+        );
+        fs::write(directory.path().join(region_file_name(0, 0)), header).unwrap();
+
+        let mut region = RegionFile::open(directory.path(), 0, 0).unwrap();
+        assert_eq!(region.chunks().count(), 0);
+        assert_eq!(region.read_chunk(0, 0).unwrap(), None);
+    }
+}
@@ -206,6 +206,8 @@ pub enum NbtParseError {
     InvalidTag { value: u8, pos: usize },
     #[error("invalid NBT list type {tag:?}")]
     InvalidListType { tag: Tag },
+    #[error("invalid NBT list length {len} at position {pos}")]
+    InvalidListLength { len: i32, pos: usize },
     #[error("unexpected NBT compound end tag at position {pos}")]
     UnexpectedEnd { pos: usize },
     #[error("sudden end of data, expected NBT compound end tag")]
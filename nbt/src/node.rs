@@ -203,8 +203,8 @@ where
     container_getter_ref!(byte_array, ByteArray, &'nbt [i8]);
     container_getter_ref!(string, String, &'nbt str);
     container_getter_node!(list, List, NbtList);
-    container_getter_ref!(int_array, IntArray, &'nbt [i32]);
-    container_getter_ref!(long_array, LongArray, &'nbt [i64]);
+    container_getter_ref!(int_array, IntArray, Vec<i32>);
+    container_getter_ref!(long_array, LongArray, Vec<i64>);
     container_getter_node!(compound, Compound, NbtCompound);
 
     pub fn get(&self, key: impl Borrow<<Container as NbtMapRef>::Key>) -> Option<NbtValue> {
@@ -311,8 +311,8 @@ impl<'source, 'nbt> NbtNode<'source, 'nbt, NbtValue> {
     value_getter_ref!(byte_array, ByteArray, &'nbt [i8]);
     value_getter_ref!(string, String, &'nbt str);
     value_getter_node!(list, List, NbtList);
-    value_getter_ref!(int_array, IntArray, &'nbt [i32]);
-    value_getter_ref!(long_array, LongArray, &'nbt [i64]);
+    value_getter_ref!(int_array, IntArray, Vec<i32>);
+    value_getter_ref!(long_array, LongArray, Vec<i64>);
     value_getter_node!(compound, Compound, NbtCompound);
 }
 
@@ -137,7 +137,7 @@ struct ListData(u64);
 impl ListData {
     #[inline]
     fn new(tag: Tag, len: i32) -> Self {
-        debug_assert!(len > 0);
+        debug_assert!(len >= 0);
         Self((u64::from(tag.to_u8()) << 56) | u64::from(len as u32))
     }
 
@@ -352,6 +352,9 @@ impl Tape {
                             value,
                             pos: tag_pos,
                         })?;
+                    if len < 0 {
+                        return Err(NbtParseError::InvalidListLength { len, pos: tag_pos });
+                    }
                     list_data = Some((list_tag, len));
                     let list_data = ListData::new(list_tag, len);
 
@@ -402,6 +405,19 @@ impl Tape {
                 is_list_item,
                 list_data,
             ));
+
+            // Empty lists have no elements after which to end them, so end them right away.
+            if let Some((_, 0)) = list_data {
+                tape[tag_tape_pos].set_data(tag_tape_pos as u64 + 1);
+                tape.push(TapeItem::new(
+                    Tag::End,
+                    0,
+                    pos(source),
+                    tag_tape_pos as u64,
+                    true,
+                    None,
+                ));
+            }
         }
 
         // All the compound/list scopes must be appropriately closed.
@@ -235,18 +235,18 @@ impl<'de, 'a> SeqAccess<'de> for &'a mut Deserializer<'_, 'de> {
     }
 }
 
-struct ArraySeq<'nbt, T> {
-    array: &'nbt [T],
+struct ArraySeq<T> {
+    array: Vec<T>,
     pos: usize,
 }
 
-impl<'de, T> ArraySeq<'de, T> {
-    pub fn new(array: &'de [T]) -> Self {
+impl<T> ArraySeq<T> {
+    pub fn new(array: Vec<T>) -> Self {
         Self { array, pos: 0 }
     }
 }
 
-impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<'de, i32> {
+impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<i32> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
@@ -269,7 +269,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<'de, i32> {
     }
 }
 
-impl<'de> SeqAccess<'de> for ArraySeq<'de, i32> {
+impl<'de> SeqAccess<'de> for ArraySeq<i32> {
     type Error = Error;
 
     fn next_element_seed<T>(
@@ -279,11 +279,14 @@ impl<'de> SeqAccess<'de> for ArraySeq<'de, i32> {
     where
         T: DeserializeSeed<'de>,
     {
+        if self.pos == self.array.len() {
+            return Ok(None);
+        }
         seed.deserialize(self).map(Some)
     }
 }
 
-impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<'de, i64> {
+impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<i64> {
     type Error = Error;
 
     fn deserialize_any<V>(self, visitor: V) -> std::result::Result<V::Value, Self::Error>
@@ -306,7 +309,7 @@ impl<'a, 'de> de::Deserializer<'de> for &'a mut ArraySeq<'de, i64> {
     }
 }
 
-impl<'de> SeqAccess<'de> for ArraySeq<'de, i64> {
+impl<'de> SeqAccess<'de> for ArraySeq<i64> {
     type Error = Error;
 
     fn next_element_seed<T>(
@@ -316,6 +319,9 @@ impl<'de> SeqAccess<'de> for ArraySeq<'de, i64> {
     where
         T: DeserializeSeed<'de>,
     {
+        if self.pos == self.array.len() {
+            return Ok(None);
+        }
         seed.deserialize(self).map(Some)
     }
 }
@@ -30,26 +30,30 @@ impl<'source> Serializer<'source> {
     }
 
     fn serialize_tag(&mut self, tag: Tag) {
-        match tag {
-            Tag::End => {
-                self.output.put_u8(tag.into());
-                self.stack.pop();
-                return;
-            }
-            Tag::Compound => self.stack.push(StackItem::Compound),
-            Tag::List => self.stack.push(StackItem::List),
-            _ => {}
+        if tag == Tag::End {
+            self.output.put_u8(tag.into());
+            self.stack.pop();
+            return;
         }
 
-        match self.stack.last() {
-            Some(StackItem::Compound) => {
-                self.output.put_u8(tag.into());
-                self.output.put_u16(self.current_name.len() as u16);
-                self.output.put_slice(self.current_name.as_bytes());
-            }
-            _ => {}
+        if self.is_named() {
+            self.serialize_header(tag, &self.current_name.clone());
+        }
+        if tag == Tag::Compound {
+            self.stack.push(StackItem::Compound);
         }
     }
+
+    /// Whether the next value is named, which it is in a compound or at the root, but not in a list.
+    fn is_named(&self) -> bool {
+        self.stack.last() != Some(&StackItem::List)
+    }
+
+    fn serialize_header(&mut self, tag: Tag, name: &str) {
+        self.output.put_u8(tag.into());
+        self.output.put_u16(name.len() as u16);
+        self.output.put_slice(name.as_bytes());
+    }
 }
 
 impl<'a, 'source> ser::Serializer for &'a mut Serializer<'source> {
@@ -250,6 +254,8 @@ impl<'a, 'source> ser::Serializer for &'a mut Serializer<'source> {
 
 pub struct SerializerSeq<'a, 'source> {
     serializer: &'a mut Serializer<'source>,
+    /// Name of the list, if it is in a compound.
+    name: Option<Cow<'source, str>>,
     list_len: Option<usize>,
     list_tag: Tag,
     list_elem_tag: Tag,
@@ -259,8 +265,14 @@ pub struct SerializerSeq<'a, 'source> {
 
 impl<'a, 'source> SerializerSeq<'a, 'source> {
     fn new(serializer: &'a mut Serializer<'source>, list_len: Option<usize>) -> Self {
+        let name = serializer
+            .is_named()
+            .then(|| serializer.current_name.clone());
+        // The elements of the list aren't named.
+        serializer.stack.push(StackItem::List);
         Self {
             serializer,
+            name,
             list_len,
             list_tag: Tag::List,
             list_elem_tag: Tag::End,
@@ -268,6 +280,22 @@ impl<'a, 'source> SerializerSeq<'a, 'source> {
             list_len_count: 0,
         }
     }
+
+    /// Starts the list, once the tag of its elements is known.
+    fn serialize_start(&mut self, elem_tag: Tag) {
+        self.list_tag = match elem_tag {
+            Tag::Byte => Tag::ByteArray,
+            Tag::Int => Tag::IntArray,
+            Tag::Long => Tag::LongArray,
+            _ => Tag::List,
+        };
+        if let Some(name) = &self.name {
+            self.serializer.serialize_header(self.list_tag, name);
+        }
+        if self.list_tag == Tag::List {
+            self.serializer.output.put_u8(elem_tag.to_u8());
+        }
+    }
 }
 
 impl ser::SerializeSeq for SerializerSeq<'_, '_> {
@@ -287,25 +315,7 @@ impl ser::SerializeSeq for SerializerSeq<'_, '_> {
             std::mem::swap(&mut self.serializer.output, &mut output_hold);
 
             // Properly start the list.
-            match tag {
-                Tag::Byte => {
-                    self.list_tag = Tag::ByteArray;
-                    self.serializer.serialize_tag(self.list_tag);
-                }
-                Tag::Int => {
-                    self.list_tag = Tag::IntArray;
-                    self.serializer.serialize_tag(self.list_tag);
-                }
-                Tag::Long => {
-                    self.list_tag = Tag::LongArray;
-                    self.serializer.serialize_tag(self.list_tag);
-                }
-                _ => {
-                    self.list_tag = Tag::List;
-                    self.serializer.serialize_tag(self.list_tag);
-                    self.serializer.output.put_u8(tag.to_u8());
-                }
-            }
+            self.serialize_start(tag);
             match self.list_len {
                 Some(list_len) => {
                     self.serializer.output.put_i32(list_len as i32);
@@ -328,7 +338,14 @@ impl ser::SerializeSeq for SerializerSeq<'_, '_> {
         Ok(())
     }
 
-    fn end(self) -> Result<Tag> {
+    fn end(mut self) -> Result<Tag> {
+        if self.list_elem_tag == Tag::End {
+            // Empty list, of end tags like vanilla's.
+            self.serialize_start(Tag::End);
+            self.serializer.output.put_i32(0);
+        }
+        self.serializer.stack.pop();
+
         // Check if we need to fill in the length.
         if let Some(list_len_pos) = self.list_len_pos {
             let mut start = &mut self.serializer.output[list_len_pos..];
@@ -272,7 +272,7 @@ impl NbtMapRef for NbtCompound {
 }
 
 impl NbtRef for NbtIntArray {
-    type Output<'source, 'nbt> = &'nbt [i32] where 'source: 'nbt;
+    type Output<'source, 'nbt> = Vec<i32> where 'source: 'nbt;
 
     fn tape_pos(&self) -> usize {
         self.0
@@ -286,7 +286,11 @@ impl NbtRef for NbtIntArray {
         let source_start_pos = tape_item.get_source_payload_pos() + 4;
         let array =
             &nbt.source()[source_start_pos..source_start_pos + (4 * tape_item.get_data() as usize)];
-        bytemuck::cast_slice(array)
+        // Big-endian and not necessarily aligned, so it can't be cast.
+        array
+            .chunks_exact(4)
+            .map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+            .collect()
     }
 }
 
@@ -319,7 +323,7 @@ impl NbtMapRef for NbtIntArray {
 }
 
 impl NbtRef for NbtLongArray {
-    type Output<'source, 'nbt> = &'nbt [i64] where 'source: 'nbt;
+    type Output<'source, 'nbt> = Vec<i64> where 'source: 'nbt;
 
     fn tape_pos(&self) -> usize {
         self.0
@@ -333,7 +337,11 @@ impl NbtRef for NbtLongArray {
         let source_start_pos = tape_item.get_source_payload_pos() + 4;
         let array =
             &nbt.source()[source_start_pos..source_start_pos + (8 * tape_item.get_data() as usize)];
-        bytemuck::cast_slice(array)
+        // Big-endian and not necessarily aligned, so it can't be cast.
+        array
+            .chunks_exact(8)
+            .map(|bytes| i64::from_be_bytes(bytes.try_into().unwrap()))
+            .collect()
     }
 }
 
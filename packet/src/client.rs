@@ -2,14 +2,20 @@
 
 use std::convert::Infallible;
 
-use crate::{KnownPack, Packet, PacketDecodeContext, PacketDecodeError};
+use crate::{
+    bitflags_protocol, ChatSessionData, KnownPack, Packet, PacketDecodeContext, PacketDecodeError,
+    PlayerAbilityFlags, Slot,
+};
+use bitflags::bitflags;
 use bytes::{Buf, BufMut};
 use delegate_display::DelegateDebug;
 use derive_more::derive::From;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use protocol::{
-    buf::{ArrayProtocolContext, IdentifierProtocolContext, OptionProtocolContext},
+    buf::{ArrayProtocolContext, GetEnumError, IdentifierProtocolContext, OptionProtocolContext},
     identifier::Identifier,
-    Decodable, DecodeError, Encodable, EncodeError,
+    BlockPosition, Decodable, DecodeError, Difficulty, Encodable, EncodeError, GameMode,
+    InteractionHand, NetworkNbt, VarInt, VarLong,
 };
 use protocol_derive::Protocol;
 use serde::{Deserialize, Serialize};
@@ -34,7 +40,10 @@ packets! {
 packets! {
     ClientLoginPacket<'a>
 
-    LoginDisconnectPacket {} = 0x00
+    LoginDisconnectPacket<'a> {
+        /// A JSON text component, unlike in later states.
+        reason: Cow<'a, str>,
+    } = 0x00
     EncryptionRequestPacket<'a> {
         server_id: Cow<'a, str>,
         #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
@@ -89,9 +98,8 @@ packets! {
         #[protocol(ctx = ArrayProtocolContext::Remaining)]
         data: Cow<'a, [u8]>,
     } = 0x01
-    ConfigurationDisconnectPacket {
-        // TODO
-    } = 0x02
+    ConfigurationDisconnectPacket<'a> { reason: NetworkNbt<'a> } = 0x02
+    FinishConfigurationPacket {} = 0x03
     RegistryDataPacket<'a> {
         #[protocol(ctx = IdentifierProtocolContext::SingleString)]
         registry_id: Identifier<'a>,
@@ -107,23 +115,1631 @@ packets! {
 #[derive(Debug, Clone, Eq, PartialEq, Protocol)]
 pub struct RegistryEntry<'a> {
     #[protocol(ctx = IdentifierProtocolContext::SingleString)]
-    id: Identifier<'a>,
+    pub id: Identifier<'a>,
     #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
-    data: Option<Nbt>, // TODO
+    pub data: Option<NetworkNbt<'a>>,
 }
 
 packets! {
-    ClientPlayPacket
+    ClientPlayPacket<'a>
+
+    BundleDelimiterPacket {} = 0x00
+    SpawnEntityPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        entity_uuid: Uuid,
+        #[protocol(varint)]
+        entity_type: i32,
+        x: f64,
+        y: f64,
+        z: f64,
+        pitch: u8,
+        yaw: u8,
+        head_yaw: u8,
+        #[protocol(varint)]
+        data: i32,
+        velocity_x: i16,
+        velocity_y: i16,
+        velocity_z: i16,
+    } = 0x01
+    SpawnExperienceOrbPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        x: f64,
+        y: f64,
+        z: f64,
+        count: i16,
+    } = 0x02
+    EntityAnimationPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        animation: u8,
+    } = 0x03
+    AwardStatisticsPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        statistics: Cow<'a, [Statistic]>,
+    } = 0x04
+    AcknowledgeBlockChangePacket {
+        #[protocol(varint)]
+        sequence_id: i32,
+    } = 0x05
+    SetBlockDestroyStagePacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        location: BlockPosition,
+        destroy_stage: u8,
+    } = 0x06
+    BlockEntityDataPacket<'a> {
+        location: BlockPosition,
+        #[protocol(varint)]
+        block_entity_type: i32,
+        nbt: NetworkNbt<'a>,
+    } = 0x07
+    BlockActionPacket {
+        location: BlockPosition,
+        action_id: u8,
+        action_parameter: u8,
+        #[protocol(varint)]
+        block_type: i32,
+    } = 0x08
+    BlockUpdatePacket {
+        location: BlockPosition,
+        #[protocol(varint)]
+        block_id: i32,
+    } = 0x09
+    BossBarPacket<'a> {
+        uuid: Uuid,
+        #[protocol(varint)]
+        action: i32,
+        // TODO: Action-specific fields.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x0A
+    ClientboundChangeDifficultyPacket {
+        difficulty: Difficulty,
+        difficulty_locked: bool,
+    } = 0x0B
+    ChunkBatchFinishedPacket {
+        #[protocol(varint)]
+        batch_size: i32,
+    } = 0x0C
+    ChunkBatchStartPacket {} = 0x0D
+    ChunkBiomesPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        chunk_biome_data: Cow<'a, [ChunkBiomeData<'a>]>,
+    } = 0x0E
+    ClearTitlesPacket { reset: bool } = 0x0F
+    CommandSuggestionsResponsePacket<'a> {
+        #[protocol(varint)]
+        transaction_id: i32,
+        #[protocol(varint)]
+        start: i32,
+        #[protocol(varint)]
+        length: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        matches: Cow<'a, [CommandSuggestionMatch<'a>]>,
+    } = 0x10
+    CommandsPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        nodes: Cow<'a, [CommandNode<'a>]>,
+        #[protocol(varint)]
+        root_index: i32,
+    } = 0x11
+    ClientboundCloseContainerPacket { window_id: u8 } = 0x12
+    SetContainerContentPacket<'a> {
+        window_id: u8,
+        #[protocol(varint)]
+        state_id: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        slot_data: Cow<'a, [Slot]>,
+        carried_item: Slot,
+    } = 0x13
+    SetContainerPropertyPacket {
+        window_id: u8,
+        property: i16,
+        value: i16,
+    } = 0x14
+    SetContainerSlotPacket {
+        window_id: i8,
+        #[protocol(varint)]
+        state_id: i32,
+        slot: i16,
+        slot_data: Slot,
+    } = 0x15
+    PlayCookieRequestPacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        key: Identifier<'a>,
+    } = 0x16
+    SetCooldownPacket {
+        #[protocol(varint)]
+        item_id: i32,
+        #[protocol(varint)]
+        cooldown_ticks: i32,
+    } = 0x17
+    ChatSuggestionsPacket<'a> {
+        action: ChatSuggestionsAction,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        entries: Cow<'a, [Cow<'a, str>]>,
+    } = 0x18
+    PlayClientboundPluginMessagePacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        channel: Identifier<'a>,
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x19
+    DamageEventPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(varint)]
+        source_type_id: i32,
+        /// Entity ID of the source + 1, or 0 if there is none.
+        #[protocol(varint)]
+        source_cause_id: i32,
+        /// Entity ID of the direct source + 1, or 0 if there is none.
+        #[protocol(varint)]
+        source_direct_id: i32,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        source_position: Option<DamageSourcePosition>,
+    } = 0x1A
+    DebugSamplePacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        sample: Cow<'a, [i64]>,
+        #[protocol(varint)]
+        sample_type: i32,
+    } = 0x1B
+    DeleteMessagePacket<'a> {
+        /// Message ID + 1, or 0 if the message is identified by its signature.
+        #[protocol(varint)]
+        message_id: i32,
+        /// Only present (256 bytes) if `message_id` is 0.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        signature: Cow<'a, [u8]>,
+    } = 0x1C
+    PlayDisconnectPacket<'a> { reason: NetworkNbt<'a> } = 0x1D
+    DisguisedChatMessagePacket<'a> {
+        message: NetworkNbt<'a>,
+        /// Chat type registry ID + 1.
+        #[protocol(varint)]
+        chat_type: i32,
+        sender_name: NetworkNbt<'a>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        target_name: Option<NetworkNbt<'a>>,
+    } = 0x1E
+    EntityEventPacket {
+        entity_id: i32,
+        entity_status: i8,
+    } = 0x1F
+    ExplosionPacket<'a> {
+        x: f64,
+        y: f64,
+        z: f64,
+        strength: f32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        records: Cow<'a, [ExplosionRecord]>,
+        player_motion_x: f32,
+        player_motion_y: f32,
+        player_motion_z: f32,
+        #[protocol(varint)]
+        block_interaction: i32,
+        // TODO: Small/large explosion particles and explosion sound.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x20
+    UnloadChunkPacket {
+        chunk_z: i32,
+        chunk_x: i32,
+    } = 0x21
+    GameEventPacket {
+        event: u8,
+        value: f32,
+    } = 0x22
+    OpenHorseScreenPacket {
+        window_id: u8,
+        #[protocol(varint)]
+        slot_count: i32,
+        entity_id: i32,
+    } = 0x23
+    HurtAnimationPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        yaw: f32,
+    } = 0x24
+    InitializeWorldBorderPacket {
+        x: f64,
+        z: f64,
+        old_diameter: f64,
+        new_diameter: f64,
+        speed: VarLong,
+        #[protocol(varint)]
+        portal_teleport_boundary: i32,
+        #[protocol(varint)]
+        warning_blocks: i32,
+        #[protocol(varint)]
+        warning_time: i32,
+    } = 0x25
+    PlayClientboundKeepAlivePacket { keep_alive_id: i64 } = 0x26
+    ChunkDataAndUpdateLightPacket<'a> {
+        chunk_x: i32,
+        chunk_z: i32,
+        heightmaps: NetworkNbt<'a>,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        data: Cow<'a, [u8]>,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        block_entities: Cow<'a, [ChunkBlockEntity<'a>]>,
+        light: LightData<'a>,
+    } = 0x27
+    WorldEventPacket {
+        event: i32,
+        location: BlockPosition,
+        data: i32,
+        disable_relative_volume: bool,
+    } = 0x28
+    ParticlePacket<'a> {
+        long_distance: bool,
+        x: f64,
+        y: f64,
+        z: f64,
+        offset_x: f32,
+        offset_y: f32,
+        offset_z: f32,
+        max_speed: f32,
+        particle_count: i32,
+        #[protocol(varint)]
+        particle_id: i32,
+        // TODO: Particle-specific data.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x29
+    UpdateLightPacket<'a> {
+        #[protocol(varint)]
+        chunk_x: i32,
+        #[protocol(varint)]
+        chunk_z: i32,
+        light: LightData<'a>,
+    } = 0x2A
+    PlayLoginPacket<'a> {
+        entity_id: i32,
+        is_hardcore: bool,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        dimension_names: Cow<'a, [Cow<'a, str>]>,
+        #[protocol(varint)]
+        max_players: i32,
+        #[protocol(varint)]
+        view_distance: i32,
+        #[protocol(varint)]
+        simulation_distance: i32,
+        reduced_debug_info: bool,
+        enable_respawn_screen: bool,
+        do_limited_crafting: bool,
+        #[protocol(varint)]
+        dimension_type: i32,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        dimension_name: Identifier<'a>,
+        hashed_seed: i64,
+        game_mode: GameMode,
+        /// -1 if there is no previous game mode.
+        previous_game_mode: i8,
+        is_debug: bool,
+        is_flat: bool,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        death_location: Option<DeathLocation<'a>>,
+        #[protocol(varint)]
+        portal_cooldown: i32,
+        enforces_secure_chat: bool,
+    } = 0x2B
+    MapDataPacket<'a> {
+        #[protocol(varint)]
+        map_id: i32,
+        scale: i8,
+        locked: bool,
+        // TODO: Icons and color patch.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x2C
+    MerchantOffersPacket<'a> {
+        #[protocol(varint)]
+        window_id: i32,
+        // TODO: Trades and villager data.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x2D
+    UpdateEntityPositionPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        delta_x: i16,
+        delta_y: i16,
+        delta_z: i16,
+        on_ground: bool,
+    } = 0x2E
+    UpdateEntityPositionAndRotationPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        delta_x: i16,
+        delta_y: i16,
+        delta_z: i16,
+        yaw: u8,
+        pitch: u8,
+        on_ground: bool,
+    } = 0x2F
+    UpdateEntityRotationPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        yaw: u8,
+        pitch: u8,
+        on_ground: bool,
+    } = 0x30
+    ClientboundMoveVehiclePacket {
+        x: f64,
+        y: f64,
+        z: f64,
+        yaw: f32,
+        pitch: f32,
+    } = 0x31
+    OpenBookPacket { hand: InteractionHand } = 0x32
+    OpenScreenPacket<'a> {
+        #[protocol(varint)]
+        window_id: i32,
+        #[protocol(varint)]
+        window_type: i32,
+        window_title: NetworkNbt<'a>,
+    } = 0x33
+    OpenSignEditorPacket {
+        location: BlockPosition,
+        is_front_text: bool,
+    } = 0x34
+    PlayPingPacket { id: i32 } = 0x35
+    PlayPingResponsePacket { payload: i64 } = 0x36
+    PlaceGhostRecipePacket<'a> {
+        window_id: i8,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        recipe: Identifier<'a>,
+    } = 0x37
+    ClientboundPlayerAbilitiesPacket {
+        flags: PlayerAbilityFlags,
+        flying_speed: f32,
+        field_of_view_modifier: f32,
+    } = 0x38
+    PlayerChatMessagePacket<'a> {
+        sender: Uuid,
+        #[protocol(varint)]
+        index: i32,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ArrayProtocolContext::FixedLength(256)))]
+        message_signature: Option<Cow<'a, [u8]>>,
+        message: Cow<'a, str>,
+        timestamp: i64,
+        salt: i64,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        previous_messages: Cow<'a, [PreviousMessage<'a>]>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        unsigned_content: Option<NetworkNbt<'a>>,
+        filter: FilterMask<'a>,
+        /// Chat type registry ID + 1.
+        #[protocol(varint)]
+        chat_type: i32,
+        sender_name: NetworkNbt<'a>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        target_name: Option<NetworkNbt<'a>>,
+    } = 0x39
+    EndCombatPacket {
+        #[protocol(varint)]
+        duration: i32,
+    } = 0x3A
+    EnterCombatPacket {} = 0x3B
+    CombatDeathPacket<'a> {
+        #[protocol(varint)]
+        player_id: i32,
+        message: NetworkNbt<'a>,
+    } = 0x3C
+    PlayerInfoRemovePacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        uuids: Cow<'a, [Uuid]>,
+    } = 0x3D
+    PlayerInfoUpdatePacket<'a> { update: PlayerInfoUpdate<'a> } = 0x3E
+    LookAtPacket {
+        #[protocol(varint)]
+        feet_or_eyes: i32,
+        target_x: f64,
+        target_y: f64,
+        target_z: f64,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        entity: Option<LookAtEntity>,
+    } = 0x3F
+    SynchronizePlayerPositionPacket {
+        x: f64,
+        y: f64,
+        z: f64,
+        yaw: f32,
+        pitch: f32,
+        flags: TeleportFlags,
+        #[protocol(varint)]
+        teleport_id: i32,
+    } = 0x40
+    UpdateRecipeBookPacket<'a> {
+        #[protocol(varint)]
+        action: i32,
+        // TODO: Recipe book settings and recipe IDs.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x41
+    RemoveEntitiesPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        entity_ids: Cow<'a, [VarInt]>,
+    } = 0x42
+    RemoveEntityEffectPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(varint)]
+        effect_id: i32,
+    } = 0x43
+    ResetScorePacket<'a> {
+        entity_name: Cow<'a, str>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        objective_name: Option<Cow<'a, str>>,
+    } = 0x44
+    RemoveResourcePackPacket {
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        uuid: Option<Uuid>,
+    } = 0x45
+    AddResourcePackPacket<'a> {
+        uuid: Uuid,
+        url: Cow<'a, str>,
+        hash: Cow<'a, str>,
+        forced: bool,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        prompt_message: Option<NetworkNbt<'a>>,
+    } = 0x46
+    RespawnPacket<'a> {
+        #[protocol(varint)]
+        dimension_type: i32,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        dimension_name: Identifier<'a>,
+        hashed_seed: i64,
+        game_mode: GameMode,
+        previous_game_mode: i8,
+        is_debug: bool,
+        is_flat: bool,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        death_location: Option<DeathLocation<'a>>,
+        #[protocol(varint)]
+        portal_cooldown: i32,
+        data_kept: u8,
+    } = 0x47
+    SetHeadRotationPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        head_yaw: u8,
+    } = 0x48
+    UpdateSectionBlocksPacket<'a> {
+        chunk_section_position: i64,
+        /// Each entry is `block_state_id << 12 | (local_x << 8 | local_z << 4 | local_y)`.
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        blocks: Cow<'a, [VarLong]>,
+    } = 0x49
+    SelectAdvancementsTabPacket<'a> {
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, IdentifierProtocolContext::SingleString))]
+        identifier: Option<Identifier<'a>>,
+    } = 0x4A
+    ServerDataPacket<'a> {
+        motd: NetworkNbt<'a>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ArrayProtocolContext::LengthPrefixed))]
+        icon: Option<Cow<'a, [u8]>>,
+    } = 0x4B
+    SetActionBarTextPacket<'a> { action_bar_text: NetworkNbt<'a> } = 0x4C
+    SetBorderCenterPacket {
+        x: f64,
+        z: f64,
+    } = 0x4D
+    SetBorderLerpSizePacket {
+        old_diameter: f64,
+        new_diameter: f64,
+        speed: VarLong,
+    } = 0x4E
+    SetBorderSizePacket { diameter: f64 } = 0x4F
+    SetBorderWarningDelayPacket {
+        #[protocol(varint)]
+        warning_time: i32,
+    } = 0x50
+    SetBorderWarningDistancePacket {
+        #[protocol(varint)]
+        warning_blocks: i32,
+    } = 0x51
+    SetCameraPacket {
+        #[protocol(varint)]
+        camera_id: i32,
+    } = 0x52
+    ClientboundSetHeldItemPacket { slot: i8 } = 0x53
+    SetCenterChunkPacket {
+        #[protocol(varint)]
+        chunk_x: i32,
+        #[protocol(varint)]
+        chunk_z: i32,
+    } = 0x54
+    SetRenderDistancePacket {
+        #[protocol(varint)]
+        view_distance: i32,
+    } = 0x55
+    SetDefaultSpawnPositionPacket {
+        location: BlockPosition,
+        angle: f32,
+    } = 0x56
+    DisplayObjectivePacket<'a> {
+        #[protocol(varint)]
+        position: i32,
+        score_name: Cow<'a, str>,
+    } = 0x57
+    SetEntityMetadataPacket<'a> {
+        #[protocol(varint)]
+        entity_id: i32,
+        // TODO: Entity metadata entries.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        metadata: Cow<'a, [u8]>,
+    } = 0x58
+    LinkEntitiesPacket {
+        attached_entity_id: i32,
+        holding_entity_id: i32,
+    } = 0x59
+    SetEntityVelocityPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        velocity_x: i16,
+        velocity_y: i16,
+        velocity_z: i16,
+    } = 0x5A
+    SetEquipmentPacket<'a> {
+        #[protocol(varint)]
+        entity_id: i32,
+        // TODO: Equipment entries.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        equipment: Cow<'a, [u8]>,
+    } = 0x5B
+    SetExperiencePacket {
+        experience_bar: f32,
+        #[protocol(varint)]
+        level: i32,
+        #[protocol(varint)]
+        total_experience: i32,
+    } = 0x5C
+    SetHealthPacket {
+        health: f32,
+        #[protocol(varint)]
+        food: i32,
+        food_saturation: f32,
+    } = 0x5D
+    UpdateObjectivesPacket<'a> {
+        objective_name: Cow<'a, str>,
+        mode: i8,
+        // TODO: Objective value, type and number format.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x5E
+    SetPassengersPacket<'a> {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        passengers: Cow<'a, [VarInt]>,
+    } = 0x5F
+    UpdateTeamsPacket<'a> {
+        team_name: Cow<'a, str>,
+        method: i8,
+        // TODO: Method-specific fields.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x60
+    UpdateScorePacket<'a> {
+        entity_name: Cow<'a, str>,
+        objective_name: Cow<'a, str>,
+        #[protocol(varint)]
+        value: i32,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        display_name: Option<NetworkNbt<'a>>,
+        // TODO: Number format.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        number_format: Cow<'a, [u8]>,
+    } = 0x61
+    SetSimulationDistancePacket {
+        #[protocol(varint)]
+        simulation_distance: i32,
+    } = 0x62
+    SetSubtitleTextPacket<'a> { subtitle_text: NetworkNbt<'a> } = 0x63
+    UpdateTimePacket {
+        world_age: i64,
+        time_of_day: i64,
+    } = 0x64
+    SetTitleTextPacket<'a> { title_text: NetworkNbt<'a> } = 0x65
+    SetTitleAnimationTimesPacket {
+        fade_in: i32,
+        stay: i32,
+        fade_out: i32,
+    } = 0x66
+    EntitySoundEffectPacket<'a> {
+        sound: SoundEvent<'a>,
+        #[protocol(varint)]
+        sound_category: i32,
+        #[protocol(varint)]
+        entity_id: i32,
+        volume: f32,
+        pitch: f32,
+        seed: i64,
+    } = 0x67
+    SoundEffectPacket<'a> {
+        sound: SoundEvent<'a>,
+        #[protocol(varint)]
+        sound_category: i32,
+        /// Effect X multiplied by 8.
+        effect_position_x: i32,
+        /// Effect Y multiplied by 8.
+        effect_position_y: i32,
+        /// Effect Z multiplied by 8.
+        effect_position_z: i32,
+        volume: f32,
+        pitch: f32,
+        seed: i64,
+    } = 0x68
+    StartConfigurationPacket {} = 0x69
+    StopSoundPacket<'a> {
+        flags: i8,
+        // TODO: Optional source and sound.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x6A
+    PlayStoreCookiePacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        key: Identifier<'a>,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        payload: Cow<'a, [u8]>,
+    } = 0x6B
+    SystemChatMessagePacket<'a> {
+        content: NetworkNbt<'a>,
+        overlay: bool,
+    } = 0x6C
+    SetTabListHeaderAndFooterPacket<'a> {
+        header: NetworkNbt<'a>,
+        footer: NetworkNbt<'a>,
+    } = 0x6D
+    TagQueryResponsePacket<'a> {
+        #[protocol(varint)]
+        transaction_id: i32,
+        nbt: NetworkNbt<'a>,
+    } = 0x6E
+    PickupItemPacket {
+        #[protocol(varint)]
+        collected_entity_id: i32,
+        #[protocol(varint)]
+        collector_entity_id: i32,
+        #[protocol(varint)]
+        pickup_item_count: i32,
+    } = 0x6F
+    TeleportEntityPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        x: f64,
+        y: f64,
+        z: f64,
+        yaw: u8,
+        pitch: u8,
+        on_ground: bool,
+    } = 0x70
+    SetTickingStatePacket {
+        tick_rate: f32,
+        is_frozen: bool,
+    } = 0x71
+    StepTickPacket {
+        #[protocol(varint)]
+        tick_steps: i32,
+    } = 0x72
+    PlayTransferPacket<'a> {
+        host: Cow<'a, str>,
+        #[protocol(varint)]
+        port: i32,
+    } = 0x73
+    UpdateAdvancementsPacket<'a> {
+        // TODO: Advancement mapping and progress.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x74
+    UpdateAttributesPacket<'a> {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        properties: Cow<'a, [AttributeProperty<'a>]>,
+    } = 0x75
+    EntityEffectPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(varint)]
+        effect_id: i32,
+        #[protocol(varint)]
+        amplifier: i32,
+        #[protocol(varint)]
+        duration: i32,
+        flags: i8,
+    } = 0x76
+    UpdateRecipesPacket<'a> {
+        // TODO: Recipe definitions.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x77
+    PlayUpdateTagsPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        registries: Cow<'a, [RegistryTags<'a>]>,
+    } = 0x78
+    ProjectilePowerPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        power: f64,
+    } = 0x79
+    CustomReportDetailsPacket<'a> {
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        details: Cow<'a, [CustomReportDetail<'a>]>,
+    } = 0x7A
+    ServerLinksPacket<'a> {
+        // TODO: Links with built-in or custom labels.
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x7B
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Protocol)]
+pub struct Statistic {
+    #[protocol(varint)]
+    pub category_id: i32,
+    #[protocol(varint)]
+    pub statistic_id: i32,
+    #[protocol(varint)]
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct ChunkBiomeData<'a> {
+    pub chunk_z: i32,
+    pub chunk_x: i32,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub data: Cow<'a, [u8]>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct CommandSuggestionMatch<'a> {
+    pub text: Cow<'a, str>,
+    #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+    pub tooltip: Option<NetworkNbt<'a>>,
+}
+
+/// A node of the command graph, sent in [`CommandsPacket`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandNode<'a> {
+    pub node_type: CommandNodeType<'a>,
+    /// Whether the command is complete once this node is parsed.
+    pub executable: bool,
+    /// Indices of the child nodes in [`CommandsPacket::nodes`].
+    pub children: Cow<'a, [VarInt]>,
+    /// Index of the node parsing continues at after this one, instead of the children.
+    pub redirect: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandNodeType<'a> {
+    Root,
+    Literal {
+        name: Cow<'a, str>,
+    },
+    Argument {
+        name: Cow<'a, str>,
+        parser: CommandParser<'a>,
+        /// Suggestion provider, e.g. `minecraft:ask_server` to request suggestions from the server.
+        suggestions: Option<Identifier<'a>>,
+    },
+}
+
+impl CommandNode<'_> {
+    const TYPE_MASK: u8 = 0x03;
+    const EXECUTABLE: u8 = 0x04;
+    const HAS_REDIRECT: u8 = 0x08;
+    const HAS_SUGGESTIONS: u8 = 0x10;
+}
+
+impl Encodable for CommandNode<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        let mut flags = match &self.node_type {
+            CommandNodeType::Root => 0,
+            CommandNodeType::Literal { .. } => 1,
+            CommandNodeType::Argument { .. } => 2,
+        };
+        if self.executable {
+            flags |= Self::EXECUTABLE;
+        }
+        if self.redirect.is_some() {
+            flags |= Self::HAS_REDIRECT;
+        }
+        if let CommandNodeType::Argument {
+            suggestions: Some(_),
+            ..
+        } = &self.node_type
+        {
+            flags |= Self::HAS_SUGGESTIONS;
+        }
+
+        flags.encode(buf, ())?;
+        self.children
+            .encode(buf, ArrayProtocolContext::LengthPrefixed)?;
+        if let Some(redirect) = self.redirect {
+            buf::put_varint(buf, redirect);
+        }
+
+        match &self.node_type {
+            CommandNodeType::Root => {}
+            CommandNodeType::Literal { name } => name.encode(buf, ())?,
+            CommandNodeType::Argument {
+                name,
+                parser,
+                suggestions,
+            } => {
+                name.encode(buf, ())?;
+                parser.encode(buf, ())?;
+                if let Some(suggestions) = suggestions {
+                    suggestions.encode(buf, IdentifierProtocolContext::SingleString)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for CommandNode<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let flags = u8::decode(buf, ())?;
+        let children = Cow::decode(buf, ArrayProtocolContext::LengthPrefixed)?;
+        let redirect = if flags & Self::HAS_REDIRECT != 0 {
+            Some(buf::get_varint(buf)?)
+        } else {
+            None
+        };
+
+        let node_type = match flags & Self::TYPE_MASK {
+            0 => CommandNodeType::Root,
+            1 => CommandNodeType::Literal {
+                name: Cow::decode(buf, ())?,
+            },
+            2 => CommandNodeType::Argument {
+                name: Cow::decode(buf, ())?,
+                parser: CommandParser::decode(buf, ())?,
+                suggestions: if flags & Self::HAS_SUGGESTIONS != 0 {
+                    Some(Identifier::decode(
+                        buf,
+                        IdentifierProtocolContext::SingleString,
+                    )?)
+                } else {
+                    None
+                },
+            },
+            _ => return Err(DecodeError::Specific("invalid command node type")),
+        };
+
+        Ok(CommandNode {
+            node_type,
+            executable: flags & Self::EXECUTABLE != 0,
+            children,
+            redirect,
+        })
+    }
+}
+
+/// An argument parser of a [`CommandNode`], with its properties.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandParser<'a> {
+    Bool,
+    Float {
+        min: Option<f32>,
+        max: Option<f32>,
+    },
+    Double {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    Integer {
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+    Long {
+        min: Option<i64>,
+        max: Option<i64>,
+    },
+    String(StringParserKind),
+    Entity {
+        single: bool,
+        players_only: bool,
+    },
+    ScoreHolder {
+        allow_multiple: bool,
+    },
+    /// Minimum duration, in ticks.
+    Time {
+        min: i32,
+    },
+    ResourceOrTag {
+        registry: Identifier<'a>,
+    },
+    ResourceOrTagKey {
+        registry: Identifier<'a>,
+    },
+    Resource {
+        registry: Identifier<'a>,
+    },
+    ResourceKey {
+        registry: Identifier<'a>,
+    },
+    /// A parser without properties, by its ID in the `minecraft:command_argument_type` registry.
+    Other(i32),
+}
+
+impl<'a> CommandParser<'a> {
+    pub const GAME_PROFILE: Self = CommandParser::Other(7);
+    pub const BLOCK_POS: Self = CommandParser::Other(8);
+    pub const COLUMN_POS: Self = CommandParser::Other(9);
+    pub const VEC3: Self = CommandParser::Other(10);
+    pub const VEC2: Self = CommandParser::Other(11);
+    pub const COMPONENT: Self = CommandParser::Other(17);
+    pub const MESSAGE: Self = CommandParser::Other(19);
+    pub const RESOURCE_LOCATION: Self = CommandParser::Other(35);
+    pub const DIMENSION: Self = CommandParser::Other(40);
+    pub const GAME_MODE: Self = CommandParser::Other(41);
+    pub const UUID: Self = CommandParser::Other(53);
+
+    const BOOL_ID: i32 = 0;
+    const FLOAT_ID: i32 = 1;
+    const DOUBLE_ID: i32 = 2;
+    const INTEGER_ID: i32 = 3;
+    const LONG_ID: i32 = 4;
+    const STRING_ID: i32 = 5;
+    const ENTITY_ID: i32 = 6;
+    const SCORE_HOLDER_ID: i32 = 30;
+    const TIME_ID: i32 = 42;
+    const RESOURCE_OR_TAG_ID: i32 = 43;
+    const RESOURCE_OR_TAG_KEY_ID: i32 = 44;
+    const RESOURCE_ID: i32 = 45;
+    const RESOURCE_KEY_ID: i32 = 46;
+
+    /// The parser's ID in the `minecraft:command_argument_type` registry.
+    pub fn id(&self) -> i32 {
+        match self {
+            CommandParser::Bool => Self::BOOL_ID,
+            CommandParser::Float { .. } => Self::FLOAT_ID,
+            CommandParser::Double { .. } => Self::DOUBLE_ID,
+            CommandParser::Integer { .. } => Self::INTEGER_ID,
+            CommandParser::Long { .. } => Self::LONG_ID,
+            CommandParser::String(_) => Self::STRING_ID,
+            CommandParser::Entity { .. } => Self::ENTITY_ID,
+            CommandParser::ScoreHolder { .. } => Self::SCORE_HOLDER_ID,
+            CommandParser::Time { .. } => Self::TIME_ID,
+            CommandParser::ResourceOrTag { .. } => Self::RESOURCE_OR_TAG_ID,
+            CommandParser::ResourceOrTagKey { .. } => Self::RESOURCE_OR_TAG_KEY_ID,
+            CommandParser::Resource { .. } => Self::RESOURCE_ID,
+            CommandParser::ResourceKey { .. } => Self::RESOURCE_KEY_ID,
+            CommandParser::Other(id) => *id,
+        }
+    }
+}
+
+/// Flags of a numeric parser's properties, telling whether the bounds are present.
+const PARSER_HAS_MIN: u8 = 0x01;
+const PARSER_HAS_MAX: u8 = 0x02;
+
+fn encode_parser_range<T>(
+    buf: &mut dyn BufMut,
+    min: &Option<T>,
+    max: &Option<T>,
+) -> Result<(), EncodeError<Infallible>>
+where
+    T: Encodable<Context = (), Error = Infallible>,
+{
+    let mut flags = 0;
+    if min.is_some() {
+        flags |= PARSER_HAS_MIN;
+    }
+    if max.is_some() {
+        flags |= PARSER_HAS_MAX;
+    }
+
+    flags.encode(buf, ())?;
+    if let Some(min) = min {
+        min.encode(buf, ())?;
+    }
+    if let Some(max) = max {
+        max.encode(buf, ())?;
+    }
+
+    Ok(())
+}
+
+fn decode_parser_range<T>(
+    buf: &mut dyn Buf,
+) -> Result<(Option<T>, Option<T>), DecodeError<Infallible>>
+where
+    T: Decodable<Context = (), Error = Infallible>,
+{
+    let flags = u8::decode(buf, ())?;
+    let min = if flags & PARSER_HAS_MIN != 0 {
+        Some(T::decode(buf, ())?)
+    } else {
+        None
+    };
+    let max = if flags & PARSER_HAS_MAX != 0 {
+        Some(T::decode(buf, ())?)
+    } else {
+        None
+    };
+
+    Ok((min, max))
+}
+
+impl Encodable for CommandParser<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        buf::put_varint(buf, self.id());
+
+        match self {
+            CommandParser::Bool | CommandParser::Other(_) => {}
+            CommandParser::Float { min, max } => encode_parser_range(buf, min, max)?,
+            CommandParser::Double { min, max } => encode_parser_range(buf, min, max)?,
+            CommandParser::Integer { min, max } => encode_parser_range(buf, min, max)?,
+            CommandParser::Long { min, max } => encode_parser_range(buf, min, max)?,
+            CommandParser::String(kind) => kind.encode(buf, ())?,
+            CommandParser::Entity {
+                single,
+                players_only,
+            } => {
+                let mut flags = 0u8;
+                if *single {
+                    flags |= 0x01;
+                }
+                if *players_only {
+                    flags |= 0x02;
+                }
+                flags.encode(buf, ())?;
+            }
+            CommandParser::ScoreHolder { allow_multiple } => {
+                u8::from(*allow_multiple).encode(buf, ())?;
+            }
+            CommandParser::Time { min } => min.encode(buf, ())?,
+            CommandParser::ResourceOrTag { registry }
+            | CommandParser::ResourceOrTagKey { registry }
+            | CommandParser::Resource { registry }
+            | CommandParser::ResourceKey { registry } => {
+                registry.encode(buf, IdentifierProtocolContext::SingleString)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for CommandParser<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(match buf::get_varint(buf)? {
+            Self::BOOL_ID => CommandParser::Bool,
+            Self::FLOAT_ID => {
+                let (min, max) = decode_parser_range(buf)?;
+                CommandParser::Float { min, max }
+            }
+            Self::DOUBLE_ID => {
+                let (min, max) = decode_parser_range(buf)?;
+                CommandParser::Double { min, max }
+            }
+            Self::INTEGER_ID => {
+                let (min, max) = decode_parser_range(buf)?;
+                CommandParser::Integer { min, max }
+            }
+            Self::LONG_ID => {
+                let (min, max) = decode_parser_range(buf)?;
+                CommandParser::Long { min, max }
+            }
+            Self::STRING_ID => CommandParser::String(StringParserKind::decode(buf, ())?),
+            Self::ENTITY_ID => {
+                let flags = u8::decode(buf, ())?;
+                CommandParser::Entity {
+                    single: flags & 0x01 != 0,
+                    players_only: flags & 0x02 != 0,
+                }
+            }
+            Self::SCORE_HOLDER_ID => CommandParser::ScoreHolder {
+                allow_multiple: u8::decode(buf, ())? & 0x01 != 0,
+            },
+            Self::TIME_ID => CommandParser::Time {
+                min: i32::decode(buf, ())?,
+            },
+            Self::RESOURCE_OR_TAG_ID => CommandParser::ResourceOrTag {
+                registry: Identifier::decode(buf, IdentifierProtocolContext::SingleString)?,
+            },
+            Self::RESOURCE_OR_TAG_KEY_ID => CommandParser::ResourceOrTagKey {
+                registry: Identifier::decode(buf, IdentifierProtocolContext::SingleString)?,
+            },
+            Self::RESOURCE_ID => CommandParser::Resource {
+                registry: Identifier::decode(buf, IdentifierProtocolContext::SingleString)?,
+            },
+            Self::RESOURCE_KEY_ID => CommandParser::ResourceKey {
+                registry: Identifier::decode(buf, IdentifierProtocolContext::SingleString)?,
+            },
+            id => CommandParser::Other(id),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(i32)]
+#[protocol(varint)]
+pub enum StringParserKind {
+    /// A single word.
+    SingleWord = 0,
+    /// A single word, or a phrase in quotes.
+    QuotablePhrase = 1,
+    /// The rest of the input.
+    GreedyPhrase = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(i32)]
+#[protocol(varint)]
+pub enum ChatSuggestionsAction {
+    Add = 0,
+    Remove = 1,
+    Set = 2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Protocol)]
+pub struct DamageSourcePosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Protocol)]
+pub struct ExplosionRecord {
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct ChunkBlockEntity<'a> {
+    /// `(block_x & 15) << 4 | (block_z & 15)`.
+    pub packed_xz: u8,
+    pub y: i16,
+    #[protocol(varint)]
+    pub block_entity_type: i32,
+    pub data: NetworkNbt<'a>,
+}
+
+/// Light data, shared by [`ChunkDataAndUpdateLightPacket`] and [`UpdateLightPacket`].
+///
+/// Each mask is a BitSet with one bit per section, including one section below and one above the world.
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct LightData<'a> {
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub sky_light_mask: Cow<'a, [i64]>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub block_light_mask: Cow<'a, [i64]>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub empty_sky_light_mask: Cow<'a, [i64]>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub empty_block_light_mask: Cow<'a, [i64]>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub sky_light_arrays: Cow<'a, [LightArray<'a>]>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub block_light_arrays: Cow<'a, [LightArray<'a>]>,
+}
+
+/// Light levels of a section, half a byte per block (2048 bytes).
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct LightArray<'a> {
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub data: Cow<'a, [u8]>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct DeathLocation<'a> {
+    #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+    pub dimension_name: Identifier<'a>,
+    pub location: BlockPosition,
+}
+
+/// A previously seen message, referenced in [`PlayerChatMessagePacket`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PreviousMessage<'a> {
+    /// Message ID in the client's message cache.
+    Id(i32),
+    /// Full message signature (256 bytes).
+    Signature(Cow<'a, [u8]>),
+}
+
+impl Encodable for PreviousMessage<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        match self {
+            PreviousMessage::Id(id) => buf::put_varint(buf, id + 1),
+            PreviousMessage::Signature(signature) => {
+                buf::put_varint(buf, 0);
+                buf.put_slice(signature);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for PreviousMessage<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(match buf::get_varint(buf)? {
+            0 => PreviousMessage::Signature(Cow::decode(
+                buf,
+                ArrayProtocolContext::FixedLength(256),
+            )?),
+            id => PreviousMessage::Id(id - 1),
+        })
+    }
+}
+
+/// Chat message filtering, referenced in [`PlayerChatMessagePacket`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FilterMask<'a> {
+    PassThrough,
+    FullyFiltered,
+    /// A BitSet, with the filtered characters set.
+    PartiallyFiltered(Cow<'a, [i64]>),
+}
+
+impl Encodable for FilterMask<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        match self {
+            FilterMask::PassThrough => buf::put_varint(buf, 0),
+            FilterMask::FullyFiltered => buf::put_varint(buf, 1),
+            FilterMask::PartiallyFiltered(mask) => {
+                buf::put_varint(buf, 2);
+                mask.encode(buf, ArrayProtocolContext::LengthPrefixed)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for FilterMask<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(match buf::get_varint(buf)? {
+            0 => FilterMask::PassThrough,
+            1 => FilterMask::FullyFiltered,
+            2 => FilterMask::PartiallyFiltered(Cow::decode(
+                buf,
+                ArrayProtocolContext::LengthPrefixed,
+            )?),
+            value => return Err(DecodeError::Enum(GetEnumError::InvalidValue(value))),
+        })
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PlayerInfoActions: u8 {
+        const ADD_PLAYER = 0x01;
+        const INITIALIZE_CHAT = 0x02;
+        const UPDATE_GAME_MODE = 0x04;
+        const UPDATE_LISTED = 0x08;
+        const UPDATE_LATENCY = 0x10;
+        const UPDATE_DISPLAY_NAME = 0x20;
+    }
+}
+
+bitflags! {
+    /// Which fields of [`SynchronizePlayerPositionPacket`] are relative to the current position/rotation.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TeleportFlags: u8 {
+        const X = 0x01;
+        const Y = 0x02;
+        const Z = 0x04;
+        const Y_ROT = 0x08;
+        const X_ROT = 0x10;
+    }
+}
+
+bitflags_protocol!(PlayerInfoActions, TeleportFlags);
+
+/// The contents of [`PlayerInfoUpdatePacket`].
+///
+/// Only the fields of each [`PlayerInfoEntry`] corresponding to `actions` are sent, the rest are ignored
+/// (and set to their default value when decoding).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlayerInfoUpdate<'a> {
+    pub actions: PlayerInfoActions,
+    pub entries: Cow<'a, [PlayerInfoEntry<'a>]>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PlayerInfoEntry<'a> {
+    pub uuid: Uuid,
+    /// [`PlayerInfoActions::ADD_PLAYER`]
+    pub name: Cow<'a, str>,
+    /// [`PlayerInfoActions::ADD_PLAYER`]
+    pub properties: Cow<'a, [ClientLoginSuccessProperty<'a>]>,
+    /// [`PlayerInfoActions::INITIALIZE_CHAT`]
+    pub chat_session: Option<ChatSessionData<'a>>,
+    /// [`PlayerInfoActions::UPDATE_GAME_MODE`]
+    pub game_mode: GameMode,
+    /// [`PlayerInfoActions::UPDATE_LISTED`]
+    pub listed: bool,
+    /// [`PlayerInfoActions::UPDATE_LATENCY`]
+    pub latency: i32,
+    /// [`PlayerInfoActions::UPDATE_DISPLAY_NAME`]
+    pub display_name: Option<NetworkNbt<'a>>,
+}
+
+impl<'a> PlayerInfoEntry<'a> {
+    /// Makes an entry with every field except `uuid` set to its default value.
+    pub fn new(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            name: Cow::Borrowed(""),
+            properties: Cow::Borrowed(&[]),
+            chat_session: None,
+            game_mode: GameMode::Survival,
+            listed: false,
+            latency: 0,
+            display_name: None,
+        }
+    }
+}
+
+impl Encodable for PlayerInfoUpdate<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        self.actions.encode(buf, ())?;
+        buf::put_varint(buf, self.entries.len().try_into().unwrap());
+
+        for entry in self.entries.iter() {
+            entry.uuid.encode(buf, ())?;
+            if self.actions.contains(PlayerInfoActions::ADD_PLAYER) {
+                entry.name.encode(buf, ())?;
+                entry
+                    .properties
+                    .encode(buf, ArrayProtocolContext::LengthPrefixed)?;
+            }
+            if self.actions.contains(PlayerInfoActions::INITIALIZE_CHAT) {
+                entry
+                    .chat_session
+                    .encode(buf, (OptionProtocolContext::BoolPrefixed, ()))?;
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_GAME_MODE) {
+                buf::put_varint(buf, u8::from(entry.game_mode).into());
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_LISTED) {
+                entry.listed.encode(buf, ())?;
+            }
+            if self.actions.contains(PlayerInfoActions::UPDATE_LATENCY) {
+                buf::put_varint(buf, entry.latency);
+            }
+            if self
+                .actions
+                .contains(PlayerInfoActions::UPDATE_DISPLAY_NAME)
+            {
+                entry
+                    .display_name
+                    .encode(buf, (OptionProtocolContext::BoolPrefixed, ()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for PlayerInfoUpdate<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let actions = PlayerInfoActions::decode(buf, ())?;
+        let len: usize = buf::get_varint(buf)?.try_into().unwrap();
+        let mut entries = Vec::with_capacity(len);
+
+        for _ in 0..len {
+            let mut entry = PlayerInfoEntry::new(Uuid::decode(buf, ())?);
+            if actions.contains(PlayerInfoActions::ADD_PLAYER) {
+                entry.name = Cow::decode(buf, ())?;
+                entry.properties = Cow::decode(buf, ArrayProtocolContext::LengthPrefixed)?;
+            }
+            if actions.contains(PlayerInfoActions::INITIALIZE_CHAT) {
+                entry.chat_session =
+                    Option::decode(buf, (OptionProtocolContext::BoolPrefixed, ()))?;
+            }
+            if actions.contains(PlayerInfoActions::UPDATE_GAME_MODE) {
+                let value = buf::get_varint(buf)?;
+                entry.game_mode = u8::try_from(value)
+                    .ok()
+                    .and_then(|value| GameMode::try_from(value).ok())
+                    .ok_or(DecodeError::Enum(GetEnumError::InvalidValue(value)))?;
+            }
+            if actions.contains(PlayerInfoActions::UPDATE_LISTED) {
+                entry.listed = bool::decode(buf, ())?;
+            }
+            if actions.contains(PlayerInfoActions::UPDATE_LATENCY) {
+                entry.latency = buf::get_varint(buf)?;
+            }
+            if actions.contains(PlayerInfoActions::UPDATE_DISPLAY_NAME) {
+                entry.display_name =
+                    Option::decode(buf, (OptionProtocolContext::BoolPrefixed, ()))?;
+            }
+            entries.push(entry);
+        }
+
+        Ok(PlayerInfoUpdate {
+            actions,
+            entries: Cow::Owned(entries),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Protocol)]
+pub struct LookAtEntity {
+    #[protocol(varint)]
+    pub entity_id: i32,
+    #[protocol(varint)]
+    pub entity_feet_or_eyes: i32,
+}
 
-    None2 {} = 0x00
+/// A sound event, either by its registry ID or defined inline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoundEvent<'a> {
+    Id(i32),
+    Inline {
+        sound_name: Identifier<'a>,
+        fixed_range: Option<f32>,
+    },
 }
 
-#[derive(DelegateDebug, Clone, Eq, PartialEq, From)]
+impl Encodable for SoundEvent<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        match self {
+            SoundEvent::Id(id) => buf::put_varint(buf, id + 1),
+            SoundEvent::Inline {
+                sound_name,
+                fixed_range,
+            } => {
+                buf::put_varint(buf, 0);
+                sound_name.encode(buf, IdentifierProtocolContext::SingleString)?;
+                fixed_range.encode(buf, (OptionProtocolContext::BoolPrefixed, ()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for SoundEvent<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(match buf::get_varint(buf)? {
+            0 => SoundEvent::Inline {
+                sound_name: Identifier::decode(buf, IdentifierProtocolContext::SingleString)?,
+                fixed_range: Option::decode(buf, (OptionProtocolContext::BoolPrefixed, ()))?,
+            },
+            id => SoundEvent::Id(id - 1),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Protocol)]
+pub struct AttributeProperty<'a> {
+    #[protocol(varint)]
+    pub id: i32,
+    pub value: f64,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub modifiers: Cow<'a, [AttributeModifier<'a>]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Protocol)]
+pub struct AttributeModifier<'a> {
+    #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+    pub id: Identifier<'a>,
+    pub amount: f64,
+    pub operation: i8,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct RegistryTags<'a> {
+    #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+    pub registry: Identifier<'a>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub tags: Cow<'a, [TagEntries<'a>]>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct TagEntries<'a> {
+    #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+    pub name: Identifier<'a>,
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub entries: Cow<'a, [VarInt]>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct CustomReportDetail<'a> {
+    pub title: Cow<'a, str>,
+    pub description: Cow<'a, str>,
+}
+
+#[derive(DelegateDebug, Clone, PartialEq, From)]
 pub enum ClientPacket<'a> {
     Status(ClientStatusPacket),
     Login(ClientLoginPacket<'a>),
     Configuration(ClientConfigurationPacket<'a>),
-    Play(ClientPlayPacket),
+    Play(ClientPlayPacket<'a>),
 }
 
 impl<'a> Encodable for ClientPacket<'a> {
@@ -243,3 +1859,342 @@ pub struct StatusResponsePlayersSample {
 pub struct StatusResponseDescription {
     pub text: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use protocol::text::TextComponent;
+
+    use super::*;
+    use crate::PacketDirection;
+
+    fn assert_round_trip(packet: ClientPlayPacket<'_>) {
+        let mut buf = BytesMut::new();
+        packet.encode(&mut buf, ()).unwrap();
+        let mut bytes = buf.freeze();
+
+        let decoded = ClientPacket::decode(
+            &mut bytes,
+            PacketDecodeContext {
+                connection_state: ConnectionState::Play,
+                packet_id: packet.get_id(),
+                direction: PacketDirection::Client,
+            },
+        )
+        .unwrap();
+
+        assert!(!bytes.has_remaining(), "packet was not fully decoded");
+        assert_eq!(decoded, ClientPacket::Play(packet));
+    }
+
+    #[test]
+    fn login() {
+        assert_round_trip(
+            PlayLoginPacket {
+                entity_id: 1,
+                is_hardcore: false,
+                dimension_names: vec!["minecraft:overworld".into()].into(),
+                max_players: 20,
+                view_distance: 10,
+                simulation_distance: 10,
+                reduced_debug_info: false,
+                enable_respawn_screen: true,
+                do_limited_crafting: false,
+                dimension_type: 0,
+                dimension_name: Identifier::from_string("minecraft:overworld").unwrap(),
+                hashed_seed: 0x1234_5678_9ABC_DEF0,
+                game_mode: GameMode::Creative,
+                previous_game_mode: -1,
+                is_debug: false,
+                is_flat: true,
+                death_location: Some(DeathLocation {
+                    dimension_name: Identifier::from_string("minecraft:the_nether").unwrap(),
+                    location: BlockPosition::new(-12, 64, 300),
+                }),
+                portal_cooldown: 0,
+                enforces_secure_chat: false,
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert_round_trip(
+            CommandsPacket {
+                nodes: vec![
+                    CommandNode {
+                        node_type: CommandNodeType::Root,
+                        executable: false,
+                        children: vec![VarInt(1), VarInt(3)].into(),
+                        redirect: None,
+                    },
+                    CommandNode {
+                        node_type: CommandNodeType::Literal { name: "tp".into() },
+                        executable: false,
+                        children: vec![VarInt(2)].into(),
+                        redirect: None,
+                    },
+                    CommandNode {
+                        node_type: CommandNodeType::Argument {
+                            name: "location".into(),
+                            parser: CommandParser::VEC3,
+                            suggestions: None,
+                        },
+                        executable: true,
+                        children: vec![].into(),
+                        redirect: None,
+                    },
+                    CommandNode {
+                        node_type: CommandNodeType::Literal {
+                            name: "teleport".into(),
+                        },
+                        executable: false,
+                        children: vec![].into(),
+                        redirect: Some(1),
+                    },
+                ]
+                .into(),
+                root_index: 0,
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn command_parsers() {
+        let parsers = [
+            CommandParser::Bool,
+            CommandParser::Integer {
+                min: Some(0),
+                max: None,
+            },
+            CommandParser::Double {
+                min: Some(-1.5),
+                max: Some(1.5),
+            },
+            CommandParser::String(StringParserKind::GreedyPhrase),
+            CommandParser::Entity {
+                single: true,
+                players_only: true,
+            },
+            CommandParser::Resource {
+                registry: Identifier::from_string("minecraft:enchantment").unwrap(),
+            },
+            CommandParser::GAME_MODE,
+        ];
+
+        for parser in parsers {
+            assert_round_trip(
+                CommandsPacket {
+                    nodes: vec![CommandNode {
+                        node_type: CommandNodeType::Argument {
+                            name: "argument".into(),
+                            parser,
+                            suggestions: Some(
+                                Identifier::from_string("minecraft:ask_server").unwrap(),
+                            ),
+                        },
+                        executable: true,
+                        children: vec![].into(),
+                        redirect: None,
+                    }]
+                    .into(),
+                    root_index: 0,
+                }
+                .into(),
+            );
+        }
+    }
+
+    #[test]
+    fn synchronize_player_position() {
+        assert_round_trip(
+            SynchronizePlayerPositionPacket {
+                x: 0.5,
+                y: 64.0,
+                z: -0.5,
+                yaw: 90.0,
+                pitch: -12.5,
+                flags: TeleportFlags::Y_ROT | TeleportFlags::X_ROT,
+                teleport_id: 42,
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn player_info_update() {
+        let mut entry = PlayerInfoEntry::new(Uuid::from_u128(0xDEADBEEF));
+        entry.name = "Notch".into();
+        entry.properties = vec![ClientLoginSuccessProperty {
+            name: "textures".into(),
+            value: "e30=".into(),
+            signature: None,
+        }]
+        .into();
+        entry.chat_session = Some(ChatSessionData {
+            session_id: Uuid::from_u128(1),
+            expires_at: 1_700_000_000_000,
+            public_key: vec![1, 2, 3].into(),
+            key_signature: vec![4, 5, 6].into(),
+        });
+        entry.game_mode = GameMode::Adventure;
+        entry.listed = true;
+        entry.latency = 37;
+        entry.display_name = Some(TextComponent::from("Notch").to_network_nbt());
+
+        assert_round_trip(
+            PlayerInfoUpdatePacket {
+                update: PlayerInfoUpdate {
+                    actions: PlayerInfoActions::all(),
+                    entries: vec![entry.clone()].into(),
+                },
+            }
+            .into(),
+        );
+
+        let mut partial_entry = PlayerInfoEntry::new(entry.uuid);
+        partial_entry.latency = entry.latency;
+        assert_round_trip(
+            PlayerInfoUpdatePacket {
+                update: PlayerInfoUpdate {
+                    actions: PlayerInfoActions::UPDATE_LATENCY,
+                    entries: vec![partial_entry].into(),
+                },
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn player_chat_message() {
+        assert_round_trip(
+            PlayerChatMessagePacket {
+                sender: Uuid::from_u128(7),
+                index: 3,
+                message_signature: Some(vec![0xAB; 256].into()),
+                message: "hello".into(),
+                timestamp: 1_700_000_000_000,
+                salt: -5,
+                previous_messages: vec![
+                    PreviousMessage::Id(4),
+                    PreviousMessage::Signature(vec![0xCD; 256].into()),
+                ]
+                .into(),
+                unsigned_content: None,
+                filter: FilterMask::PartiallyFiltered(vec![0b101].into()),
+                chat_type: 1,
+                sender_name: TextComponent::from("Steve").to_network_nbt(),
+                target_name: None,
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn sound_effect() {
+        for sound in [
+            SoundEvent::Id(12),
+            SoundEvent::Inline {
+                sound_name: Identifier::from_string("minecraft:block.note_block.harp").unwrap(),
+                fixed_range: Some(16.0),
+            },
+        ] {
+            assert_round_trip(
+                SoundEffectPacket {
+                    sound,
+                    sound_category: 0,
+                    effect_position_x: 8,
+                    effect_position_y: 512,
+                    effect_position_z: -8,
+                    volume: 1.0,
+                    pitch: 0.5,
+                    seed: 99,
+                }
+                .into(),
+            );
+        }
+    }
+
+    #[test]
+    fn chunk_data_and_update_light() {
+        assert_round_trip(
+            ChunkDataAndUpdateLightPacket {
+                chunk_x: -3,
+                chunk_z: 7,
+                heightmaps: NetworkNbt(vec![0x0A, 0x00].into()),
+                data: vec![0; 32].into(),
+                block_entities: vec![ChunkBlockEntity {
+                    packed_xz: 0x3F,
+                    y: -60,
+                    block_entity_type: 7,
+                    data: NetworkNbt::empty(),
+                }]
+                .into(),
+                light: LightData {
+                    sky_light_mask: vec![0b11].into(),
+                    block_light_mask: vec![0].into(),
+                    empty_sky_light_mask: vec![0].into(),
+                    empty_block_light_mask: vec![0].into(),
+                    sky_light_arrays: vec![
+                        LightArray {
+                            data: vec![0xFF; 2048].into(),
+                        };
+                        2
+                    ]
+                    .into(),
+                    block_light_arrays: Vec::new().into(),
+                },
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn misc() {
+        assert_round_trip(BundleDelimiterPacket {}.into());
+        assert_round_trip(
+            SetContainerContentPacket {
+                window_id: 0,
+                state_id: 1,
+                slot_data: vec![
+                    Slot::EMPTY,
+                    Slot {
+                        item_count: 64,
+                        item_id: 1,
+                    },
+                ]
+                .into(),
+                carried_item: Slot::EMPTY,
+            }
+            .into(),
+        );
+        assert_round_trip(
+            RemoveEntitiesPacket {
+                entity_ids: vec![VarInt(1), VarInt(300), VarInt(-1)].into(),
+            }
+            .into(),
+        );
+        assert_round_trip(
+            SystemChatMessagePacket {
+                content: TextComponent::from("Server restarting").to_network_nbt(),
+                overlay: false,
+            }
+            .into(),
+        );
+        assert_round_trip(
+            InitializeWorldBorderPacket {
+                x: 0.0,
+                z: 0.0,
+                old_diameter: 59999968.0,
+                new_diameter: 59999968.0,
+                speed: VarLong(0),
+                portal_teleport_boundary: 29999984,
+                warning_blocks: 5,
+                warning_time: 15,
+            }
+            .into(),
+        );
+    }
+}
@@ -1,18 +1,20 @@
 use std::{borrow::Cow, convert::Infallible};
 
-use bytes::Buf;
+use bitflags::bitflags;
+use bytes::{Buf, BufMut};
 use client::ClientPacket;
 use delegate_display::DelegateDebug;
 use derive_more::derive::From;
 use packet_derive::Packet;
 use protocol::{
-    buf::{self, IdentifierProtocolContext},
+    buf::{self, ArrayProtocolContext, IdentifierProtocolContext},
     identifier::Identifier,
-    ConnectionState, Decodable, DecodeError, Encodable,
+    ConnectionState, Decodable, DecodeError, Encodable, EncodeError,
 };
 use protocol_derive::Protocol;
 use server::ServerPacket;
 use thiserror::Error;
+use uuid::Uuid;
 
 pub mod client;
 pub mod server;
@@ -86,17 +88,25 @@ pub enum PacketDecodeError {
     InvalidPacketId(i32),
 }
 
+/// Checks whether `buf` starts with a complete packet frame, consuming the length prefix and packet ID if it does.
 pub fn check_packet<B, E>(buf: &mut B) -> Result<PacketCheckOutcome, DecodeError<E>>
 where
     B: Buf,
 {
     if let Some(len) = buf::try_get_varint_with_at_most(buf, 3)? {
-        if buf.remaining() < len.try_into().unwrap() {
+        let len: usize = len
+            .try_into()
+            .map_err(|_| DecodeError::Specific("negative packet length"))?;
+        if buf.remaining() < len {
             Ok(PacketCheckOutcome::Incomplete)
         } else {
+            let remaining_before_id = buf.remaining();
             let packet_id = buf::get_varint(buf)?;
+            let id_len = remaining_before_id - buf.remaining();
             Ok(PacketCheckOutcome::Ok {
-                len: len.try_into().unwrap(),
+                len: len.checked_sub(id_len).ok_or(DecodeError::Specific(
+                    "packet length is shorter than packet ID",
+                ))?,
                 packet_id,
             })
         }
@@ -107,7 +117,11 @@ where
 
 #[derive(Debug)]
 pub enum PacketCheckOutcome {
-    Ok { len: u16, packet_id: i32 },
+    /// `len` is the length of the packet data, after the packet ID.
+    Ok {
+        len: usize,
+        packet_id: i32,
+    },
     Incomplete,
 }
 
@@ -115,12 +129,12 @@ pub enum PacketCheckOutcome {
 macro_rules! packets {
     (
         $enum_name:ident $(<$($enum_gen:lifetime),*>)?
-        $($name:ident $(<$($gen:lifetime),*>)? { $($(#[$meta:meta])? $field:ident : $ftype:ty),* $(,)? } = $discrim:expr)*
+        $($name:ident $(<$($gen:lifetime),*>)? { $($(#[$meta:meta])* $field:ident : $ftype:ty),* $(,)? } = $discrim:expr)*
     ) => {
         $(
-            #[derive(Debug, Clone, Eq, PartialEq, protocol_derive::Protocol)]
+            #[derive(Debug, Clone, PartialEq, protocol_derive::Protocol)]
             pub struct $name $(<$($gen),*>)? {
-                $($(#[$meta])? pub $field: $ftype),*
+                $($(#[$meta])* pub $field: $ftype),*
             }
 
             impl $(<$($gen),*>)? packet::Packet for $name $(<$($gen),*>)? {
@@ -130,7 +144,7 @@ macro_rules! packets {
             }
         )*
 
-        #[derive(DelegateDebug, Clone, Eq, PartialEq, derive_more::From, packet_derive::Packet)]
+        #[derive(DelegateDebug, Clone, PartialEq, derive_more::From, packet_derive::Packet)]
         #[repr(i32)]
         pub enum $enum_name $(<$($enum_gen),*>)? {
             $(
@@ -146,3 +160,139 @@ pub struct KnownPack<'a> {
     pub identifier: Identifier<'a>,
     pub version: Cow<'a, str>,
 }
+
+/// Player chat session data, sent by the client in [`server::PlayerSessionPacket`]
+/// and relayed to other clients in [`client::PlayerInfoUpdatePacket`].
+#[derive(Debug, Clone, PartialEq, Eq, Protocol)]
+pub struct ChatSessionData<'a> {
+    pub session_id: Uuid,
+    /// Expiry time of the public key, in epoch milliseconds.
+    pub expires_at: i64,
+    /// The public key, encoded in X.509 SubjectPublicKeyInfo (DER) format.
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub public_key: Cow<'a, [u8]>,
+    /// The public key signature, signed by Mojang.
+    #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+    pub key_signature: Cow<'a, [u8]>,
+}
+
+/// An item stack.
+///
+/// TODO: Data components are not supported yet, decoding a slot that has any fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Slot {
+    pub item_count: i32,
+    pub item_id: i32,
+}
+
+impl Slot {
+    pub const EMPTY: Slot = Slot {
+        item_count: 0,
+        item_id: 0,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        self.item_count <= 0
+    }
+}
+
+impl Encodable for Slot {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        if self.is_empty() {
+            buf::put_varint(buf, 0);
+        } else {
+            buf::put_varint(buf, self.item_count);
+            buf::put_varint(buf, self.item_id);
+            // Number of data components to add and to remove.
+            buf::put_varint(buf, 0);
+            buf::put_varint(buf, 0);
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for Slot {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let item_count = buf::get_varint(buf)?;
+        if item_count <= 0 {
+            return Ok(Slot::EMPTY);
+        }
+
+        let item_id = buf::get_varint(buf)?;
+        let components_to_add = buf::get_varint(buf)?;
+        let components_to_remove = buf::get_varint(buf)?;
+        if components_to_add != 0 || components_to_remove != 0 {
+            return Err(DecodeError::Specific(
+                "item stack data components are not supported yet",
+            ));
+        }
+
+        Ok(Slot {
+            item_count,
+            item_id,
+        })
+    }
+}
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PlayerAbilityFlags: u8 {
+        const INVULNERABLE = 0x01;
+        const FLYING = 0x02;
+        const ALLOW_FLYING = 0x04;
+        const CREATIVE_MODE = 0x08;
+    }
+}
+
+/// Implements [`Encodable`] and [`Decodable`] for `u8` bitflags, ignoring unknown bits.
+#[macro_export]
+macro_rules! bitflags_protocol {
+    ( $($ty:ty),* $(,)? ) => {
+        $(
+            impl protocol::Encodable for $ty {
+                type Context = ();
+                type Error = core::convert::Infallible;
+
+                fn encode(
+                    &self,
+                    buf: &mut dyn bytes::BufMut,
+                    _ctx: Self::Context,
+                ) -> Result<(), protocol::EncodeError<Self::Error>> {
+                    buf.put_u8(self.bits());
+                    Ok(())
+                }
+            }
+
+            impl protocol::Decodable for $ty {
+                type Context = ();
+                type Error = core::convert::Infallible;
+
+                fn decode(
+                    buf: &mut dyn bytes::Buf,
+                    _ctx: Self::Context,
+                ) -> Result<Self, protocol::DecodeError<Self::Error>>
+                where
+                    Self: Sized,
+                {
+                    Ok(<$ty>::from_bits_truncate(buf.get_u8()))
+                }
+            }
+        )*
+    };
+}
+
+bitflags_protocol!(PlayerAbilityFlags);
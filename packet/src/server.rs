@@ -6,15 +6,22 @@ use std::convert::Infallible;
 use bytes::{Buf, BufMut};
 use delegate_display::DelegateDebug;
 use derive_more::derive::From;
-use protocol::buf::{ArrayProtocolContext, IdentifierProtocolContext};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use protocol::buf::{
+    self, ArrayProtocolContext, GetEnumError, IdentifierProtocolContext, OptionProtocolContext,
+};
 use protocol::{
-    identifier::Identifier, ChatMode, ConnectionState, Decodable, DisplayedSkinParts, Encodable,
-    Hand,
+    identifier::Identifier, BlockPosition, ChatMode, ConnectionState, Decodable, Difficulty,
+    DisplayedSkinParts, Encodable, Hand, InteractionHand, VarInt, VarLong,
 };
 use protocol::{ClientInformation, DecodeError, EncodeError};
+use protocol_derive::Protocol;
 use uuid::Uuid;
 
-use crate::{packets, KnownPack, Packet, PacketDecodeContext, PacketDecodeError};
+use crate::{
+    packets, ChatSessionData, KnownPack, Packet, PacketDecodeContext, PacketDecodeError,
+    PlayerAbilityFlags, Slot,
+};
 
 use crate as packet;
 
@@ -79,6 +86,7 @@ packets! {
         #[protocol(ctx = ArrayProtocolContext::Remaining)]
         data: Cow<'a, [u8]>,
     } = 0x02
+    AcknowledgeFinishConfigurationPacket {} = 0x03
     ServerboundKnownPacksPacket<'a> {
         #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
         known_packs: Cow<'a, [KnownPack<'a>]>,
@@ -101,18 +109,445 @@ impl<'a> Into<ClientInformation> for ClientInformationPacket<'a> {
 }
 
 packets! {
-    ServerPlayPacket
+    ServerPlayPacket<'a>
 
-    None1 {} = 0x00
+    ConfirmTeleportationPacket {
+        #[protocol(varint)]
+        teleport_id: i32,
+    } = 0x00
+    QueryBlockEntityTagPacket {
+        #[protocol(varint)]
+        transaction_id: i32,
+        location: BlockPosition,
+    } = 0x01
+    ServerboundChangeDifficultyPacket { new_difficulty: Difficulty } = 0x02
+    AcknowledgeMessagePacket {
+        #[protocol(varint)]
+        message_count: i32,
+    } = 0x03
+    ChatCommandPacket<'a> { command: Cow<'a, str> } = 0x04
+    SignedChatCommandPacket<'a> {
+        command: Cow<'a, str>,
+        timestamp: i64,
+        salt: i64,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        argument_signatures: Cow<'a, [ArgumentSignature<'a>]>,
+        #[protocol(varint)]
+        message_count: i32,
+        /// Fixed BitSet of 20 bits.
+        #[protocol(ctx = ArrayProtocolContext::FixedLength(3))]
+        acknowledged: Cow<'a, [u8]>,
+    } = 0x05
+    ChatMessagePacket<'a> {
+        message: Cow<'a, str>,
+        timestamp: i64,
+        salt: i64,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ArrayProtocolContext::FixedLength(256)))]
+        signature: Option<Cow<'a, [u8]>>,
+        #[protocol(varint)]
+        message_count: i32,
+        /// Fixed BitSet of 20 bits.
+        #[protocol(ctx = ArrayProtocolContext::FixedLength(3))]
+        acknowledged: Cow<'a, [u8]>,
+    } = 0x06
+    PlayerSessionPacket<'a> { session: ChatSessionData<'a> } = 0x07
+    ChunkBatchReceivedPacket { chunks_per_tick: f32 } = 0x08
+    ClientCommandPacket { action: ClientCommandAction } = 0x09
+    PlayClientInformationPacket<'a> {
+        locale: Cow<'a, str>,
+        view_distance: u8,
+        chat_mode: ChatMode,
+        chat_colors: bool,
+        displayed_skin_parts: DisplayedSkinParts,
+        main_hand: Hand,
+        enable_text_filtering: bool,
+        allow_server_listings: bool,
+    } = 0x0A
+    CommandSuggestionsRequestPacket<'a> {
+        #[protocol(varint)]
+        transaction_id: i32,
+        text: Cow<'a, str>,
+    } = 0x0B
+    AcknowledgeConfigurationPacket {} = 0x0C
+    ClickContainerButtonPacket {
+        window_id: i8,
+        button_id: i8,
+    } = 0x0D
+    ClickContainerPacket<'a> {
+        window_id: u8,
+        #[protocol(varint)]
+        state_id: i32,
+        slot: i16,
+        button: i8,
+        #[protocol(varint)]
+        mode: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        changed_slots: Cow<'a, [ChangedSlot]>,
+        carried_item: Slot,
+    } = 0x0E
+    ServerboundCloseContainerPacket { window_id: u8 } = 0x0F
+    ChangeContainerSlotStatePacket {
+        #[protocol(varint)]
+        slot_id: i32,
+        #[protocol(varint)]
+        window_id: i32,
+        state: bool,
+    } = 0x10
+    PlayCookieResponsePacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        key: Identifier<'a>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ArrayProtocolContext::LengthPrefixed))]
+        payload: Option<Cow<'a, [u8]>>,
+    } = 0x11
+    PlayServerboundPluginMessagePacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        channel_identifier: Identifier<'a>,
+        #[protocol(ctx = ArrayProtocolContext::Remaining)]
+        data: Cow<'a, [u8]>,
+    } = 0x12
+    DebugSampleSubscriptionPacket {
+        #[protocol(varint)]
+        sample_type: i32,
+    } = 0x13
+    EditBookPacket<'a> {
+        #[protocol(varint)]
+        slot: i32,
+        #[protocol(ctx = ArrayProtocolContext::LengthPrefixed)]
+        entries: Cow<'a, [Cow<'a, str>]>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        title: Option<Cow<'a, str>>,
+    } = 0x14
+    QueryEntityTagPacket {
+        #[protocol(varint)]
+        transaction_id: i32,
+        #[protocol(varint)]
+        entity_id: i32,
+    } = 0x15
+    InteractPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        action: InteractAction,
+        sneaking: bool,
+    } = 0x16
+    JigsawGeneratePacket {
+        location: BlockPosition,
+        #[protocol(varint)]
+        levels: i32,
+        keep_jigsaws: bool,
+    } = 0x17
+    PlayServerboundKeepAlivePacket { keep_alive_id: i64 } = 0x18
+    LockDifficultyPacket { locked: bool } = 0x19
+    SetPlayerPositionPacket {
+        x: f64,
+        feet_y: f64,
+        z: f64,
+        on_ground: bool,
+    } = 0x1A
+    SetPlayerPositionAndRotationPacket {
+        x: f64,
+        feet_y: f64,
+        z: f64,
+        yaw: f32,
+        pitch: f32,
+        on_ground: bool,
+    } = 0x1B
+    SetPlayerRotationPacket {
+        yaw: f32,
+        pitch: f32,
+        on_ground: bool,
+    } = 0x1C
+    SetPlayerOnGroundPacket { on_ground: bool } = 0x1D
+    ServerboundMoveVehiclePacket {
+        x: f64,
+        y: f64,
+        z: f64,
+        yaw: f32,
+        pitch: f32,
+    } = 0x1E
+    PaddleBoatPacket {
+        left_paddle_turning: bool,
+        right_paddle_turning: bool,
+    } = 0x1F
+    PickItemPacket {
+        #[protocol(varint)]
+        slot_to_use: i32,
+    } = 0x20
+    PlayPingRequestPacket { payload: i64 } = 0x21
+    PlaceRecipePacket<'a> {
+        window_id: i8,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        recipe: Identifier<'a>,
+        make_all: bool,
+    } = 0x22
+    ServerboundPlayerAbilitiesPacket { flags: PlayerAbilityFlags } = 0x23
+    PlayerActionPacket {
+        status: PlayerActionStatus,
+        location: BlockPosition,
+        face: i8,
+        #[protocol(varint)]
+        sequence: i32,
+    } = 0x24
+    PlayerCommandPacket {
+        #[protocol(varint)]
+        entity_id: i32,
+        #[protocol(varint)]
+        action_id: i32,
+        #[protocol(varint)]
+        jump_boost: i32,
+    } = 0x25
+    PlayerInputPacket {
+        sideways: f32,
+        forward: f32,
+        flags: u8,
+    } = 0x26
+    PlayPongPacket { id: i32 } = 0x27
+    ChangeRecipeBookSettingsPacket {
+        #[protocol(varint)]
+        book_id: i32,
+        book_open: bool,
+        filter_active: bool,
+    } = 0x28
+    SetSeenRecipePacket<'a> {
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        recipe_id: Identifier<'a>,
+    } = 0x29
+    RenameItemPacket<'a> { item_name: Cow<'a, str> } = 0x2A
+    PlayResourcePackResponsePacket {
+        uuid: Uuid,
+        #[protocol(varint)]
+        result: i32,
+    } = 0x2B
+    SeenAdvancementsPacket<'a> {
+        #[protocol(varint)]
+        action: i32,
+        /// Only present if `action` is 0 (opened tab).
+        #[protocol(ctx = (OptionProtocolContext::Remaining, IdentifierProtocolContext::SingleString))]
+        tab_id: Option<Identifier<'a>>,
+    } = 0x2C
+    SelectTradePacket {
+        #[protocol(varint)]
+        selected_slot: i32,
+    } = 0x2D
+    SetBeaconEffectPacket {
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        primary_effect: Option<VarInt>,
+        #[protocol(ctx = (OptionProtocolContext::BoolPrefixed, ()))]
+        secondary_effect: Option<VarInt>,
+    } = 0x2E
+    ServerboundSetHeldItemPacket { slot: i16 } = 0x2F
+    ProgramCommandBlockPacket<'a> {
+        location: BlockPosition,
+        command: Cow<'a, str>,
+        #[protocol(varint)]
+        mode: i32,
+        flags: i8,
+    } = 0x30
+    ProgramCommandBlockMinecartPacket<'a> {
+        #[protocol(varint)]
+        entity_id: i32,
+        command: Cow<'a, str>,
+        track_output: bool,
+    } = 0x31
+    SetCreativeModeSlotPacket {
+        slot: i16,
+        clicked_item: Slot,
+    } = 0x32
+    ProgramJigsawBlockPacket<'a> {
+        location: BlockPosition,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        name: Identifier<'a>,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        target: Identifier<'a>,
+        #[protocol(ctx = IdentifierProtocolContext::SingleString)]
+        pool: Identifier<'a>,
+        final_state: Cow<'a, str>,
+        joint_type: Cow<'a, str>,
+        #[protocol(varint)]
+        selection_priority: i32,
+        #[protocol(varint)]
+        placement_priority: i32,
+    } = 0x33
+    ProgramStructureBlockPacket<'a> {
+        location: BlockPosition,
+        #[protocol(varint)]
+        action: i32,
+        #[protocol(varint)]
+        mode: i32,
+        name: Cow<'a, str>,
+        offset_x: i8,
+        offset_y: i8,
+        offset_z: i8,
+        size_x: i8,
+        size_y: i8,
+        size_z: i8,
+        #[protocol(varint)]
+        mirror: i32,
+        #[protocol(varint)]
+        rotation: i32,
+        metadata: Cow<'a, str>,
+        integrity: f32,
+        seed: VarLong,
+        flags: i8,
+    } = 0x34
+    UpdateSignPacket<'a> {
+        location: BlockPosition,
+        is_front_text: bool,
+        line_1: Cow<'a, str>,
+        line_2: Cow<'a, str>,
+        line_3: Cow<'a, str>,
+        line_4: Cow<'a, str>,
+    } = 0x35
+    SwingArmPacket { hand: InteractionHand } = 0x36
+    TeleportToEntityPacket { target_player: Uuid } = 0x37
+    UseItemOnPacket {
+        hand: InteractionHand,
+        location: BlockPosition,
+        #[protocol(varint)]
+        face: i32,
+        cursor_position_x: f32,
+        cursor_position_y: f32,
+        cursor_position_z: f32,
+        inside_block: bool,
+        #[protocol(varint)]
+        sequence: i32,
+    } = 0x38
+    UseItemPacket {
+        hand: InteractionHand,
+        #[protocol(varint)]
+        sequence: i32,
+        yaw: f32,
+        pitch: f32,
+    } = 0x39
 }
 
-#[derive(DelegateDebug, Clone, Eq, PartialEq, From)]
+impl<'a> Into<ClientInformation> for PlayClientInformationPacket<'a> {
+    fn into(self) -> ClientInformation {
+        ClientInformation {
+            locale: self.locale.to_string(),
+            view_distance: self.view_distance,
+            chat_mode: self.chat_mode,
+            chat_colors: self.chat_colors,
+            displayed_skin_parts: self.displayed_skin_parts,
+            main_hand: self.main_hand,
+            enable_text_filtering: self.enable_text_filtering,
+            allow_server_listings: self.allow_server_listings,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Protocol)]
+pub struct ArgumentSignature<'a> {
+    pub argument_name: Cow<'a, str>,
+    #[protocol(ctx = ArrayProtocolContext::FixedLength(256))]
+    pub signature: Cow<'a, [u8]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(i32)]
+#[protocol(varint)]
+pub enum ClientCommandAction {
+    PerformRespawn = 0,
+    RequestStats = 1,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Protocol)]
+pub struct ChangedSlot {
+    pub slot_number: i16,
+    pub slot_data: Slot,
+}
+
+/// The interaction of an [`InteractPacket`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InteractAction {
+    Interact {
+        hand: InteractionHand,
+    },
+    Attack,
+    InteractAt {
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        hand: InteractionHand,
+    },
+}
+
+impl Encodable for InteractAction {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        match self {
+            InteractAction::Interact { hand } => {
+                buf::put_varint(buf, 0);
+                hand.encode(buf, ())?;
+            }
+            InteractAction::Attack => buf::put_varint(buf, 1),
+            InteractAction::InteractAt {
+                target_x,
+                target_y,
+                target_z,
+                hand,
+            } => {
+                buf::put_varint(buf, 2);
+                buf.put_f32(*target_x);
+                buf.put_f32(*target_y);
+                buf.put_f32(*target_z);
+                hand.encode(buf, ())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decodable for InteractAction {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(match buf::get_varint(buf)? {
+            0 => InteractAction::Interact {
+                hand: InteractionHand::decode(buf, ())?,
+            },
+            1 => InteractAction::Attack,
+            2 => InteractAction::InteractAt {
+                target_x: buf.get_f32(),
+                target_y: buf.get_f32(),
+                target_z: buf.get_f32(),
+                hand: InteractionHand::decode(buf, ())?,
+            },
+            value => return Err(DecodeError::Enum(GetEnumError::InvalidValue(value))),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(i32)]
+#[protocol(varint)]
+pub enum PlayerActionStatus {
+    StartedDigging = 0,
+    CancelledDigging = 1,
+    FinishedDigging = 2,
+    DropItemStack = 3,
+    DropItem = 4,
+    ShootArrowOrFinishEating = 5,
+    SwapItemInHand = 6,
+}
+
+#[derive(DelegateDebug, Clone, PartialEq, From)]
 pub enum ServerPacket<'a> {
     Handshaking(ServerHandshakingPacket<'a>),
     Status(ServerStatusPacket),
     Login(ServerLoginPacket<'a>),
     Configuration(ServerConfigurationPacket<'a>),
-    Play(ServerPlayPacket),
+    Play(ServerPlayPacket<'a>),
 }
 
 impl<'a> Encodable for ServerPacket<'a> {
@@ -167,3 +602,166 @@ impl<'a> Packet for ServerPacket<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+
+    use super::*;
+    use crate::PacketDirection;
+
+    fn assert_round_trip(packet: ServerPlayPacket<'_>) {
+        let mut buf = BytesMut::new();
+        packet.encode(&mut buf, ()).unwrap();
+        let mut bytes = buf.freeze();
+
+        let decoded = ServerPacket::decode(
+            &mut bytes,
+            PacketDecodeContext {
+                connection_state: ConnectionState::Play,
+                packet_id: packet.get_id(),
+                direction: PacketDirection::Server,
+            },
+        )
+        .unwrap();
+
+        assert!(!bytes.has_remaining(), "packet was not fully decoded");
+        assert_eq!(decoded, ServerPacket::Play(packet));
+    }
+
+    #[test]
+    fn chat() {
+        assert_round_trip(
+            ChatMessagePacket {
+                message: "hello world".into(),
+                timestamp: 1_700_000_000_000,
+                salt: 1234,
+                signature: Some(vec![0x11; 256].into()),
+                message_count: 2,
+                acknowledged: vec![0b11, 0, 0].into(),
+            }
+            .into(),
+        );
+        assert_round_trip(
+            ChatMessagePacket {
+                message: "unsigned".into(),
+                timestamp: 0,
+                salt: 0,
+                signature: None,
+                message_count: 0,
+                acknowledged: vec![0, 0, 0].into(),
+            }
+            .into(),
+        );
+        assert_round_trip(
+            SignedChatCommandPacket {
+                command: "msg Steve hi".into(),
+                timestamp: 1,
+                salt: 2,
+                argument_signatures: vec![ArgumentSignature {
+                    argument_name: "message".into(),
+                    signature: vec![0x22; 256].into(),
+                }]
+                .into(),
+                message_count: 0,
+                acknowledged: vec![0, 0, 0].into(),
+            }
+            .into(),
+        );
+    }
+
+    #[test]
+    fn movement() {
+        assert_round_trip(
+            SetPlayerPositionAndRotationPacket {
+                x: 12.5,
+                feet_y: 70.0,
+                z: -3.25,
+                yaw: 180.0,
+                pitch: 45.0,
+                on_ground: true,
+            }
+            .into(),
+        );
+        assert_round_trip(ConfirmTeleportationPacket { teleport_id: 1 }.into());
+        assert_round_trip(SetPlayerOnGroundPacket { on_ground: false }.into());
+    }
+
+    #[test]
+    fn interact() {
+        for action in [
+            InteractAction::Interact {
+                hand: InteractionHand::OffHand,
+            },
+            InteractAction::Attack,
+            InteractAction::InteractAt {
+                target_x: 0.5,
+                target_y: 1.0,
+                target_z: 0.25,
+                hand: InteractionHand::MainHand,
+            },
+        ] {
+            assert_round_trip(
+                InteractPacket {
+                    entity_id: 5,
+                    action,
+                    sneaking: true,
+                }
+                .into(),
+            );
+        }
+    }
+
+    #[test]
+    fn misc() {
+        assert_round_trip(
+            ClickContainerPacket {
+                window_id: 1,
+                state_id: 3,
+                slot: 36,
+                button: 0,
+                mode: 0,
+                changed_slots: vec![ChangedSlot {
+                    slot_number: 36,
+                    slot_data: Slot::EMPTY,
+                }]
+                .into(),
+                carried_item: Slot {
+                    item_count: 1,
+                    item_id: 800,
+                },
+            }
+            .into(),
+        );
+        assert_round_trip(
+            SeenAdvancementsPacket {
+                action: 0,
+                tab_id: Some(Identifier::from_string("minecraft:story/root").unwrap()),
+            }
+            .into(),
+        );
+        assert_round_trip(
+            SeenAdvancementsPacket {
+                action: 1,
+                tab_id: None,
+            }
+            .into(),
+        );
+        assert_round_trip(
+            PlayerActionPacket {
+                status: PlayerActionStatus::FinishedDigging,
+                location: BlockPosition::new(1, -2, 3),
+                face: 1,
+                sequence: 9,
+            }
+            .into(),
+        );
+        assert_round_trip(
+            SetBeaconEffectPacket {
+                primary_effect: Some(VarInt(1)),
+                secondary_effect: None,
+            }
+            .into(),
+        );
+    }
+}
@@ -206,7 +206,11 @@ fn protocol_derive_inner(input: TokenStream) -> TokenStream {
 
                         (
                             quote! { buf.#put_type((*self).into()); },
-                            quote! { Ok(Self::try_from(buf.#get_type())?) },
+                            quote! {
+                                Self::try_from(buf.#get_type()).map_err(|e| {
+                                    protocol::DecodeError::Enum(protocol::buf::GetEnumError::InvalidValue(e.number as i32))
+                                })
+                            },
                         )
                     };
 
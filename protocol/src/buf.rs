@@ -142,6 +142,83 @@ basic_encode_decode! {
     i32 => BufMut::put_i32, Buf::get_i32;
     i64 => BufMut::put_i64, Buf::get_i64;
     i128 => BufMut::put_i128, Buf::get_i128;
+
+    f32 => BufMut::put_f32, Buf::get_f32;
+    f64 => BufMut::put_f64, Buf::get_f64;
+}
+
+/// A VarInt, for when one is needed as a standalone type (e.g. inside arrays).
+///
+/// Use `#[protocol(varint)]` on `i32` fields instead where possible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct VarInt(pub i32);
+
+impl Encodable for VarInt {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        put_varint(buf, self.0);
+        Ok(())
+    }
+}
+
+impl Decodable for VarInt {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(VarInt(get_varint(buf)?))
+    }
+}
+
+impl From<i32> for VarInt {
+    fn from(value: i32) -> Self {
+        VarInt(value)
+    }
+}
+
+/// A VarLong, the 64-bit counterpart of [`VarInt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct VarLong(pub i64);
+
+impl Encodable for VarLong {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        put_varlong(buf, self.0);
+        Ok(())
+    }
+}
+
+impl Decodable for VarLong {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(VarLong(get_varlong(buf)?))
+    }
+}
+
+impl From<i64> for VarLong {
+    fn from(value: i64) -> Self {
+        VarLong(value)
+    }
 }
 
 impl Encodable for bool {
@@ -475,6 +552,28 @@ pub fn get_varint_with_at_most<B: Buf + ?Sized>(
     Ok(value)
 }
 
+pub fn get_varlong<B: Buf + ?Sized>(buf: &mut B) -> Result<i64, GetVarIntError> {
+    let mut value = 0;
+    let mut position = 0;
+
+    loop {
+        let byte = buf.get_u8();
+        value |= ((byte & VARINT_SEGMENT_BITS) as i64) << position;
+
+        if byte & VARINT_CONTINUE_BIT == 0 {
+            break;
+        }
+
+        position += 7;
+
+        if position >= 64 {
+            return Err(GetVarIntError::TooBig);
+        }
+    }
+
+    Ok(value)
+}
+
 pub fn try_get_varint_with_at_most<B: Buf + ?Sized>(
     buf: &mut B,
     bytes: usize,
@@ -563,6 +662,19 @@ pub fn put_varint<B: BufMut + ?Sized>(buf: &mut B, mut varint: i32) {
     }
 }
 
+pub fn put_varlong<B: BufMut + ?Sized>(buf: &mut B, mut varlong: i64) {
+    loop {
+        if varlong & !(i64::from(VARINT_SEGMENT_BITS)) == 0 {
+            buf.put_u8((varlong & 0xFF) as u8);
+            return;
+        }
+
+        buf.put_u8((((varlong & 0xFF) as u8) & VARINT_SEGMENT_BITS) | VARINT_CONTINUE_BIT);
+
+        varlong = ((varlong as u64) >> 7) as i64;
+    }
+}
+
 pub fn put_enum<B: BufMut + ?Sized>(buf: &mut B, value: impl Into<i32>) {
     put_varint(buf, value.into());
 }
@@ -664,4 +776,39 @@ mod tests {
             buf.clear();
         }
     }
+
+    #[test]
+    fn varlong() {
+        let mut buf = BytesMut::new();
+
+        let tests: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (1, &[0x01]),
+            (127, &[0x7F]),
+            (128, &[0x80, 0x01]),
+            (2147483647, &[0xFF, 0xFF, 0xFF, 0xFF, 0x07]),
+            (
+                9223372036854775807,
+                &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F],
+            ),
+            (
+                -1,
+                &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01],
+            ),
+            (
+                -9223372036854775808,
+                &[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01],
+            ),
+        ];
+
+        for &(varlong, bytes) in tests {
+            put_varlong(&mut buf, varlong);
+            assert_eq!(&buf[0..bytes.len()], bytes);
+            assert_eq!(
+                get_varlong(&mut (&buf[0..]).copy_to_bytes(bytes.len())).unwrap(),
+                varlong
+            );
+            buf.clear();
+        }
+    }
 }
@@ -9,10 +9,12 @@ use protocol_derive::Protocol;
 
 pub mod buf;
 pub mod identifier;
+pub mod network_nbt;
 pub mod text;
 
-pub use buf::{Decodable, DecodeError, Encodable, EncodeError};
+pub use buf::{Decodable, DecodeError, Encodable, EncodeError, VarInt, VarLong};
 pub use identifier::*;
+pub use network_nbt::NetworkNbt;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, BorrowCow};
 
@@ -46,6 +48,89 @@ pub enum Hand {
     Right = 1,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(i32)]
+#[protocol(varint)]
+pub enum InteractionHand {
+    MainHand = 0,
+    OffHand = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(u8)]
+pub enum GameMode {
+    Survival = 0,
+    Creative = 1,
+    Adventure = 2,
+    Spectator = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive, Protocol)]
+#[repr(u8)]
+pub enum Difficulty {
+    Peaceful = 0,
+    Easy = 1,
+    Normal = 2,
+    Hard = 3,
+}
+
+/// A block position, encoded as a single packed [`i64`].
+///
+/// `XXXXXXXXXXXXXXXXXXXXXXXXXXZZZZZZZZZZZZZZZZZZZZZZZZZZYYYYYYYYYYYY`, with 26 bits for X and Z and 12 bits for Y.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockPosition {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl BlockPosition {
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub const fn to_packed(self) -> i64 {
+        (((self.x as i64) & 0x3FFFFFF) << 38)
+            | (((self.z as i64) & 0x3FFFFFF) << 12)
+            | ((self.y as i64) & 0xFFF)
+    }
+
+    pub const fn from_packed(packed: i64) -> Self {
+        Self {
+            x: (packed >> 38) as i32,
+            y: (packed << 52 >> 52) as i32,
+            z: (packed << 26 >> 38) as i32,
+        }
+    }
+}
+
+impl Encodable for BlockPosition {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn bytes::BufMut,
+        _ctx: (),
+    ) -> Result<(), EncodeError<Self::Error>> {
+        buf.put_i64(self.to_packed());
+
+        Ok(())
+    }
+}
+
+impl Decodable for BlockPosition {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn bytes::Buf, _ctx: ()) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        Ok(BlockPosition::from_packed(buf.get_i64()))
+    }
+}
+
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct DisplayedSkinParts: u8 {
@@ -119,3 +204,28 @@ pub struct Score<'a> {
     #[serde_as(as = "BorrowCow")]
     objective: Cow<'a, str>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_position_packing() {
+        let positions = [
+            BlockPosition::new(0, 0, 0),
+            BlockPosition::new(18357644, 831, -20882616),
+            BlockPosition::new(-1, -64, -1),
+            BlockPosition::new(33554431, 2047, -33554432),
+        ];
+
+        for position in positions {
+            assert_eq!(BlockPosition::from_packed(position.to_packed()), position);
+        }
+
+        // Example from the protocol documentation.
+        assert_eq!(
+            BlockPosition::new(18357644, 831, -20882616).to_packed() as u64,
+            0b0100011000000111011000110010110000010101101101001000001100111111
+        );
+    }
+}
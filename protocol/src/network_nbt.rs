@@ -0,0 +1,194 @@
+//! Raw network NBT, as sent inside packets.
+
+use std::{borrow::Cow, convert::Infallible};
+
+use bytes::{Buf, BufMut};
+use serde::Serialize;
+
+use crate::{Decodable, DecodeError, Encodable, EncodeError};
+
+const TAG_END: u8 = 0;
+const TAG_COMPOUND: u8 = 10;
+
+/// Network NBT data, kept as raw bytes.
+///
+/// Network NBT is regular NBT, except that the root tag has no name (and no name length).
+/// The data is only validated enough to know where it ends; parse it with [`nbt::NbtParser`]
+/// (with `is_network_nbt` set) to actually read it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkNbt<'a>(pub Cow<'a, [u8]>);
+
+impl<'a> NetworkNbt<'a> {
+    /// An empty network NBT value, consisting of just an end tag.
+    pub const fn empty() -> NetworkNbt<'static> {
+        NetworkNbt(Cow::Borrowed(&[TAG_END]))
+    }
+
+    /// Serializes `value` (which must serialize into a compound) into network NBT.
+    pub fn from_serializable<T>(value: &T) -> Result<NetworkNbt<'static>, nbt::serde::Error>
+    where
+        T: Serialize,
+    {
+        let mut bytes = nbt::serde::to_bytes(value)?;
+
+        // The serializer writes a named root compound (with an empty name), strip the name length.
+        if bytes.first() == Some(&TAG_COMPOUND) {
+            bytes.drain(1..3);
+        }
+
+        Ok(NetworkNbt(Cow::Owned(bytes)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.as_ref() == [TAG_END]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Encodable for NetworkNbt<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn encode(
+        &self,
+        buf: &mut dyn BufMut,
+        _ctx: Self::Context,
+    ) -> Result<(), EncodeError<Self::Error>> {
+        buf.put_slice(&self.0);
+        Ok(())
+    }
+}
+
+impl Decodable for NetworkNbt<'_> {
+    type Context = ();
+    type Error = Infallible;
+
+    fn decode(buf: &mut dyn Buf, _ctx: Self::Context) -> Result<Self, DecodeError<Self::Error>>
+    where
+        Self: Sized,
+    {
+        let len = network_nbt_len(buf.chunk())
+            .ok_or(DecodeError::Specific("invalid or incomplete network NBT"))?;
+        Ok(NetworkNbt(Cow::Owned(buf.copy_to_bytes(len).to_vec())))
+    }
+}
+
+/// Returns the length of the network NBT value at the start of `source`, or [`None`] if it is invalid or incomplete.
+pub fn network_nbt_len(source: &[u8]) -> Option<usize> {
+    let mut rest = source;
+    let tag = take(&mut rest, 1)?[0];
+    if tag != TAG_END {
+        skip_payload(&mut rest, tag, 0)?;
+    }
+    Some(source.len() - rest.len())
+}
+
+/// Maximum depth of nested compounds and lists, same as vanilla.
+const MAX_DEPTH: usize = 512;
+
+fn take<'s>(source: &mut &'s [u8], len: usize) -> Option<&'s [u8]> {
+    if source.len() < len {
+        return None;
+    }
+    let (taken, rest) = source.split_at(len);
+    *source = rest;
+    Some(taken)
+}
+
+fn take_len(source: &mut &[u8], len_size: usize) -> Option<usize> {
+    let bytes = take(source, len_size)?;
+    Some(match len_size {
+        2 => u16::from_be_bytes([bytes[0], bytes[1]]) as usize,
+        _ => {
+            let len = i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            len.max(0) as usize
+        }
+    })
+}
+
+fn skip_payload(source: &mut &[u8], tag: u8, depth: usize) -> Option<()> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    match tag {
+        // Byte, Short, Int, Long, Float, Double
+        1 => take(source, 1).map(|_| ()),
+        2 => take(source, 2).map(|_| ()),
+        3 | 5 => take(source, 4).map(|_| ()),
+        4 | 6 => take(source, 8).map(|_| ()),
+        // ByteArray
+        7 => {
+            let len = take_len(source, 4)?;
+            take(source, len).map(|_| ())
+        }
+        // String
+        8 => {
+            let len = take_len(source, 2)?;
+            take(source, len).map(|_| ())
+        }
+        // List
+        9 => {
+            let list_tag = take(source, 1)?[0];
+            let len = take_len(source, 4)?;
+            for _ in 0..len {
+                skip_payload(source, list_tag, depth + 1)?;
+            }
+            Some(())
+        }
+        // Compound
+        10 => loop {
+            let tag = take(source, 1)?[0];
+            if tag == TAG_END {
+                return Some(());
+            }
+            let name_len = take_len(source, 2)?;
+            take(source, name_len)?;
+            skip_payload(source, tag, depth + 1)?;
+        },
+        // IntArray
+        11 => {
+            let len = take_len(source, 4)?;
+            take(source, len.checked_mul(4)?).map(|_| ())
+        }
+        // LongArray
+        12 => {
+            let len = take_len(source, 4)?;
+            take(source, len.checked_mul(8)?).map(|_| ())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length() {
+        // Unnamed root compound with a single `"a": 1b` byte tag.
+        let compound = [0x0A, 0x01, 0x00, 0x01, b'a', 0x01, 0x00];
+        assert_eq!(network_nbt_len(&compound), Some(compound.len()));
+
+        let mut with_trailing = compound.to_vec();
+        with_trailing.extend_from_slice(&[0xDE, 0xAD]);
+        assert_eq!(network_nbt_len(&with_trailing), Some(compound.len()));
+
+        assert_eq!(network_nbt_len(&[TAG_END]), Some(1));
+        assert_eq!(network_nbt_len(&compound[..4]), None);
+    }
+
+    #[test]
+    fn from_serializable() {
+        #[derive(Serialize)]
+        struct Test {
+            a: i8,
+        }
+
+        let nbt = NetworkNbt::from_serializable(&Test { a: 1 }).unwrap();
+        assert_eq!(nbt.as_bytes(), [0x0A, 0x01, 0x00, 0x01, b'a', 0x01, 0x00]);
+    }
+}
@@ -6,7 +6,7 @@ use serde_with::{serde_as, skip_serializing_none, BoolFromInt, BorrowCow, Displa
 use std::borrow::Cow;
 use uuid::Uuid;
 
-use crate::{Identifier, Score};
+use crate::{Identifier, NetworkNbt, Score};
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, IntoOwned, ToOwned)]
 #[serde(remote = "Self")] // https://github.com/jonasbb/serde_with/issues/702
@@ -20,6 +20,121 @@ pub struct TextComponent<'a> {
     style: TextStyling<'a>,
 }
 
+impl<'a> TextComponent<'a> {
+    /// Makes a plain text component.
+    pub fn text(text: impl Into<Cow<'a, str>>) -> Self {
+        TextComponent {
+            content: TextContent::Text(TextContentText { text: text.into() }),
+            extra: Vec::new(),
+            style: Default::default(),
+        }
+    }
+
+    /// Makes a translatable text component, with `with` as the translation arguments.
+    pub fn translatable(
+        translate: impl Into<Cow<'a, str>>,
+        with: impl IntoIterator<Item = TextComponent<'a>>,
+    ) -> Self {
+        TextComponent {
+            content: TextContent::Translatable(TextContentTranslatable {
+                translate: translate.into(),
+                with: with.into_iter().map(Box::new).collect(),
+            }),
+            extra: Vec::new(),
+            style: Default::default(),
+        }
+    }
+
+    /// Appends a child component.
+    pub fn append(mut self, component: TextComponent<'a>) -> Self {
+        self.extra.push(Box::new(component));
+        self
+    }
+
+    pub fn color(mut self, color: TextColor) -> Self {
+        self.style.color = Some(color);
+        self
+    }
+
+    /// Encodes this text component as network NBT, as used in packets since 1.20.3.
+    pub fn to_network_nbt(&self) -> NetworkNbt<'static> {
+        NetworkNbt::from_serializable(self).expect("text components should always serialize to NBT")
+    }
+
+    /// Renders this text component as unstyled text, e.g. for the console.
+    ///
+    /// `translate` looks up the format string of translation keys, like `"<%s> %s"`. Keys it
+    /// doesn't know are rendered as is. Score, selector and NBT contents aren't resolved.
+    pub fn to_plain_text(&self, translate: &dyn Fn(&str) -> Option<&'static str>) -> String {
+        let mut text = String::new();
+        self.write_plain_text(&mut text, translate);
+        text
+    }
+
+    fn write_plain_text(&self, out: &mut String, translate: &dyn Fn(&str) -> Option<&'static str>) {
+        match &self.content {
+            TextContent::Text(content) => out.push_str(&content.text),
+            TextContent::Translatable(content) => match translate(&content.translate) {
+                Some(format) => write_translation(format, &content.with, out, translate),
+                None => out.push_str(&content.translate),
+            },
+            TextContent::Keybind(content) => out.push_str(&content.keybind),
+            TextContent::Score(_) | TextContent::Selector(_) | TextContent::Nbt(_) => {}
+        }
+        for extra in &self.extra {
+            extra.write_plain_text(out, translate);
+        }
+    }
+}
+
+/// Fills in a translation format string, which uses `%s`, `%1$s` and `%%` like vanilla.
+fn write_translation(
+    format: &str,
+    args: &[Box<TextComponent<'_>>],
+    out: &mut String,
+    translate: &dyn Fn(&str) -> Option<&'static str>,
+) {
+    let mut next_arg = 0;
+    let mut rest = format;
+    while let Some(percent) = rest.find('%') {
+        out.push_str(&rest[..percent]);
+        rest = &rest[percent + 1..];
+
+        if let Some(after) = rest.strip_prefix('%') {
+            out.push('%');
+            rest = after;
+            continue;
+        }
+
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let index = match rest[digits..].strip_prefix("$s") {
+            Some(after) if digits > 0 => {
+                let index = rest[..digits]
+                    .parse::<usize>()
+                    .unwrap_or(0)
+                    .saturating_sub(1);
+                rest = after;
+                index
+            }
+            _ => match rest.strip_prefix('s') {
+                Some(after) => {
+                    rest = after;
+                    next_arg += 1;
+                    next_arg - 1
+                }
+                None => {
+                    out.push('%');
+                    continue;
+                }
+            },
+        };
+        if let Some(arg) = args.get(index) {
+            arg.write_plain_text(out, translate);
+        }
+    }
+    out.push_str(rest);
+}
+
 impl<'a> From<&'a str> for TextComponent<'a> {
     fn from(value: &'a str) -> TextComponent<'a> {
         TextComponent {
@@ -635,4 +750,46 @@ mod test {
     fn nbt_same_as_json() {
         assert_eq!(nbt_text_component(), json_text_component());
     }
+
+    #[test]
+    fn builders_same_as_json() {
+        let built = TextComponent::translatable(
+            "chat.type.text",
+            [TextComponent::text("Steve"), TextComponent::text("hi")],
+        );
+        let json: TextComponent<'_> = serde_json::from_str(
+            r#"{"translate": "chat.type.text", "with": [{"text": "Steve"}, {"text": "hi"}]}"#,
+        )
+        .expect("JSON text component should be deserializable");
+        assert_eq!(built, json);
+    }
+
+    #[test]
+    fn plain_text() {
+        let translate = |key: &str| match key {
+            "chat.type.text" => Some("<%s> %s"),
+            "reversed" => Some("%2$s %1$s, 100%%"),
+            _ => None,
+        };
+
+        let chat = TextComponent::translatable(
+            "chat.type.text",
+            [TextComponent::text("Steve"), TextComponent::text("hi")],
+        )
+        .append(TextComponent::text("!"));
+        assert_eq!(chat.to_plain_text(&translate), "<Steve> hi!");
+
+        let reversed = TextComponent::translatable(
+            "reversed",
+            [TextComponent::text("a"), TextComponent::text("b")],
+        );
+        assert_eq!(reversed.to_plain_text(&translate), "b a, 100%");
+
+        let unknown = TextComponent::translatable("unknown.key", []);
+        assert_eq!(unknown.to_plain_text(&translate), "unknown.key");
+
+        assert!(json_text_component()
+            .to_plain_text(&translate)
+            .contains("joined the lobby!"));
+    }
 }
@@ -1 +1,105 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::OnceLock,
+};
 
+use getset::Getters;
+use protocol::NetworkNbt;
+use serde::Deserialize;
+use serde_json::Value;
+
+const REGISTRIES_JSON: &str = include_str!("assets/registries.json");
+const EN_US_JSON: &str = include_str!("assets/en_us.json");
+
+/// The synchronized registries, sent to the client during configuration.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct Registries {
+    registries: BTreeMap<String, Registry>,
+}
+
+impl Registries {
+    /// Returns the registries bundled with the server.
+    pub fn get() -> &'static Registries {
+        static REGISTRIES: OnceLock<Registries> = OnceLock::new();
+        REGISTRIES.get_or_init(|| {
+            serde_json::from_str(REGISTRIES_JSON).expect("bundled registries should be valid")
+        })
+    }
+
+    pub fn registry(&self, id: &str) -> Option<&Registry> {
+        self.registries.get(id)
+    }
+
+    /// Iterates over all registries, by ID.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Registry)> {
+        self.registries
+            .iter()
+            .map(|(id, registry)| (id.as_str(), registry))
+    }
+}
+
+#[derive(Debug, Deserialize, Getters)]
+#[serde(transparent)]
+pub struct Registry {
+    /// The registry entries, in network ID order.
+    #[getset(get = "pub")]
+    entries: BTreeMap<String, Value>,
+}
+
+impl Registry {
+    /// Returns the network ID of the entry `id`, which is its index in the registry.
+    pub fn id_of(&self, id: &str) -> Option<i32> {
+        self.entries
+            .keys()
+            .position(|entry_id| entry_id == id)
+            .map(|index| index as i32)
+    }
+
+    /// Returns the data of the entry `id`, as network NBT.
+    pub fn entry_nbt(&self, id: &str) -> Option<NetworkNbt<'static>> {
+        self.entries.get(id).map(|value| {
+            NetworkNbt::from_serializable(value).expect("registry entries should be compounds")
+        })
+    }
+}
+
+/// Returns the English format string of the translation key `key`, for the keys the server uses.
+///
+/// Clients translate text components themselves; this is only needed to show them as plain
+/// text, e.g. on the console.
+pub fn translation(key: &str) -> Option<&'static str> {
+    static TRANSLATIONS: OnceLock<HashMap<String, String>> = OnceLock::new();
+    TRANSLATIONS
+        .get_or_init(|| {
+            serde_json::from_str(EN_US_JSON).expect("bundled translations should be valid")
+        })
+        .get(key)
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registries() {
+        let registries = Registries::get();
+        let dimension_types = registries.registry("minecraft:dimension_type").unwrap();
+        assert_eq!(dimension_types.id_of("minecraft:overworld"), Some(0));
+        assert_eq!(dimension_types.id_of("minecraft:the_nether"), Some(3));
+        assert!(dimension_types.id_of("minecraft:nonexistent").is_none());
+
+        for (_, registry) in registries.iter() {
+            for id in registry.entries().keys() {
+                assert!(!registry.entry_nbt(id).unwrap().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn translations() {
+        assert_eq!(translation("chat.type.text"), Some("<%s> %s"));
+        assert!(translation("nonexistent.key").is_none());
+    }
+}
@@ -0,0 +1,723 @@
+//! Who may join and who is an operator: the whitelist, ban lists and operator list, stored in
+//! the same JSON files as vanilla.
+//!
+//! Since players aren't authenticated, entries match players by UUID or by name.
+
+use std::{
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, FixedOffset, Local, SubsecRound};
+use protocol::text::TextComponent;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::player::{GameProfile, MAX_PERMISSION_LEVEL};
+
+pub const WHITELIST_FILE: &str = "whitelist.json";
+pub const OPS_FILE: &str = "ops.json";
+pub const BANNED_PLAYERS_FILE: &str = "banned-players.json";
+pub const BANNED_IPS_FILE: &str = "banned-ips.json";
+
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+/// Format of ban dates, e.g. `2024-08-08 15:30:00 +0200`.
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+/// Expiry of bans that never expire.
+const FOREVER: &str = "forever";
+/// Source of bans whose source is missing.
+const UNKNOWN_SOURCE: &str = "(Unknown)";
+
+/// A player on the whitelist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileEntry {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+impl ProfileEntry {
+    pub fn new(profile: &GameProfile) -> Self {
+        Self {
+            uuid: profile.uuid,
+            name: profile.username.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    /// Permission level (1-4).
+    #[serde(default = "default_op_level")]
+    pub level: u8,
+    /// Whether the operator may join when the server is full.
+    #[serde(default)]
+    pub bypasses_player_limit: bool,
+}
+
+/// The level of operators without one, which is the level `/op` grants.
+fn default_op_level() -> u8 {
+    MAX_PERMISSION_LEVEL
+}
+
+/// Why and until when someone is banned.
+///
+/// Like vanilla, missing fields default to a ban created now by an unknown source, which never
+/// expires and has the default reason.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanDetails {
+    #[serde(with = "date", default = "now")]
+    pub created: DateTime<FixedOffset>,
+    /// Who banned them, e.g. an operator's name or `Server`.
+    #[serde(default = "unknown_source")]
+    pub source: String,
+    /// When the ban ends, or `None` if it is permanent.
+    #[serde(with = "expiry", default)]
+    pub expires: Option<DateTime<FixedOffset>>,
+    #[serde(default = "default_ban_reason")]
+    pub reason: String,
+}
+
+/// The current time, in whole seconds like the saved dates.
+fn now() -> DateTime<FixedOffset> {
+    Local::now().fixed_offset().trunc_subsecs(0)
+}
+
+fn unknown_source() -> String {
+    UNKNOWN_SOURCE.to_owned()
+}
+
+fn default_ban_reason() -> String {
+    DEFAULT_BAN_REASON.to_owned()
+}
+
+impl BanDetails {
+    /// Makes a ban starting now.
+    pub fn new(
+        source: impl Into<String>,
+        reason: Option<String>,
+        expires: Option<DateTime<FixedOffset>>,
+    ) -> Self {
+        Self {
+            created: now(),
+            source: source.into(),
+            expires,
+            reason: reason.unwrap_or_else(default_ban_reason),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= now())
+    }
+
+    /// The disconnect reason for a banned player, like vanilla's.
+    fn to_text_component(&self, kind: &str) -> TextComponent<'static> {
+        let mut reason = TextComponent::translatable(
+            format!("multiplayer.disconnect.{kind}.reason"),
+            [TextComponent::text(self.reason.clone())],
+        );
+        if let Some(expires) = self.expires {
+            reason = reason.append(TextComponent::translatable(
+                format!("multiplayer.disconnect.{kind}.expiration"),
+                [TextComponent::text(expires.format(DATE_FORMAT).to_string())],
+            ));
+        }
+        reason
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerBan {
+    pub uuid: Uuid,
+    pub name: String,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpBan {
+    pub ip: IpAddr,
+    #[serde(flatten)]
+    pub details: BanDetails,
+}
+
+/// Entries that identify a player.
+trait ProfileMatch {
+    fn uuid(&self) -> Uuid;
+    fn name(&self) -> &str;
+
+    fn matches(&self, profile: &GameProfile) -> bool {
+        self.uuid() == profile.uuid || self.matches_name(&profile.username)
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name)
+    }
+}
+
+macro_rules! impl_profile_match {
+    ($($entry:ty),*) => {
+        $(
+            impl ProfileMatch for $entry {
+                fn uuid(&self) -> Uuid {
+                    self.uuid
+                }
+
+                fn name(&self) -> &str {
+                    &self.name
+                }
+            }
+        )*
+    };
+}
+
+impl_profile_match!(ProfileEntry, OpEntry, PlayerBan);
+
+/// A list stored as a JSON array, which is saved whenever it changes.
+#[derive(Debug)]
+struct JsonList<T> {
+    path: PathBuf,
+    entries: Vec<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> JsonList<T> {
+    /// Loads the list at `path`, creating an empty one if it doesn't exist.
+    fn load(path: PathBuf) -> Result<Self, AccessError> {
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|source| AccessError::Parse {
+                path: path.clone(),
+                source,
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(source) => return Err(AccessError::Io { path, source }),
+        };
+
+        let list = Self { path, entries };
+        if !list.path.exists() {
+            list.save()?;
+        }
+        Ok(list)
+    }
+
+    fn save(&self) -> Result<(), AccessError> {
+        let json = serde_json::to_string_pretty(&self.entries).expect("lists should serialize");
+        std::fs::write(&self.path, json).map_err(|source| AccessError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// Removes the entries matching `predicate`, returning whether there were any.
+    fn remove(&mut self, predicate: impl Fn(&T) -> bool) -> Result<bool, AccessError> {
+        let len = self.entries.len();
+        self.entries.retain(|entry| !predicate(entry));
+        if self.entries.len() == len {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+}
+
+/// The whitelist, ban lists and operators of a server.
+#[derive(Debug)]
+pub struct AccessControl {
+    whitelist: Mutex<JsonList<ProfileEntry>>,
+    ops: Mutex<JsonList<OpEntry>>,
+    banned_players: Mutex<JsonList<PlayerBan>>,
+    banned_ips: Mutex<JsonList<IpBan>>,
+}
+
+impl AccessControl {
+    /// Loads the lists from `directory`, creating the missing ones.
+    pub fn load(directory: &Path) -> Result<Self, AccessError> {
+        Ok(Self {
+            whitelist: Mutex::new(JsonList::load(directory.join(WHITELIST_FILE))?),
+            ops: Mutex::new(JsonList::load(directory.join(OPS_FILE))?),
+            banned_players: Mutex::new(JsonList::load(directory.join(BANNED_PLAYERS_FILE))?),
+            banned_ips: Mutex::new(JsonList::load(directory.join(BANNED_IPS_FILE))?),
+        })
+    }
+
+    /// Reads all lists from disk again, e.g. after they were edited by hand.
+    pub fn reload(&self) -> Result<(), AccessError> {
+        fn reload<T: Serialize + DeserializeOwned>(
+            list: &Mutex<JsonList<T>>,
+        ) -> Result<(), AccessError> {
+            let mut list = list.lock().unwrap();
+            *list = JsonList::load(list.path.clone())?;
+            Ok(())
+        }
+
+        reload(&self.whitelist)?;
+        reload(&self.ops)?;
+        reload(&self.banned_players)?;
+        reload(&self.banned_ips)
+    }
+
+    /// Checks whether a player may join, returning the disconnect reason if not.
+    pub fn check_login(
+        &self,
+        profile: &GameProfile,
+        ip: IpAddr,
+        whitelist_enabled: bool,
+    ) -> Result<(), Box<TextComponent<'static>>> {
+        if let Some(ban) = self.player_ban(profile) {
+            return Err(Box::new(ban.details.to_text_component("banned")));
+        }
+        if whitelist_enabled && !self.is_whitelisted(profile) && self.op(profile).is_none() {
+            return Err(Box::new(TextComponent::translatable(
+                "multiplayer.disconnect.not_whitelisted",
+                [],
+            )));
+        }
+        if let Some(ban) = self.ip_ban(ip) {
+            return Err(Box::new(ban.details.to_text_component("banned_ip")));
+        }
+        Ok(())
+    }
+
+    pub fn is_whitelisted(&self, profile: &GameProfile) -> bool {
+        let whitelist = self.whitelist.lock().unwrap();
+        whitelist.entries.iter().any(|entry| entry.matches(profile))
+    }
+
+    pub fn whitelist(&self) -> Vec<ProfileEntry> {
+        self.whitelist.lock().unwrap().entries.clone()
+    }
+
+    /// Adds a player to the whitelist, returning whether it wasn't already on it.
+    pub fn add_to_whitelist(&self, profile: &GameProfile) -> Result<bool, AccessError> {
+        let mut whitelist = self.whitelist.lock().unwrap();
+        if whitelist.entries.iter().any(|entry| entry.matches(profile)) {
+            return Ok(false);
+        }
+        whitelist.entries.push(ProfileEntry::new(profile));
+        whitelist.save()?;
+        Ok(true)
+    }
+
+    /// Removes the player named `name` from the whitelist, returning whether it was on it.
+    pub fn remove_from_whitelist(&self, name: &str) -> Result<bool, AccessError> {
+        let mut whitelist = self.whitelist.lock().unwrap();
+        whitelist.remove(|entry| entry.matches_name(name))
+    }
+
+    pub fn op(&self, profile: &GameProfile) -> Option<OpEntry> {
+        let ops = self.ops.lock().unwrap();
+        ops.entries
+            .iter()
+            .find(|entry| entry.matches(profile))
+            .cloned()
+    }
+
+    /// The permission level of a player, which is 0 unless it is an operator.
+    pub fn permission_level(&self, profile: &GameProfile) -> u8 {
+        self.op(profile).map_or(0, |op| op.level)
+    }
+
+    pub fn ops(&self) -> Vec<OpEntry> {
+        self.ops.lock().unwrap().entries.clone()
+    }
+
+    /// Makes a player an operator with permission level `level`, returning whether it wasn't
+    /// one with that level already.
+    pub fn add_op(&self, profile: &GameProfile, level: u8) -> Result<bool, AccessError> {
+        let level = level.min(MAX_PERMISSION_LEVEL);
+        let mut ops = self.ops.lock().unwrap();
+        match ops.entries.iter_mut().find(|entry| entry.matches(profile)) {
+            Some(entry) if entry.level == level => return Ok(false),
+            Some(entry) => entry.level = level,
+            None => ops.entries.push(OpEntry {
+                uuid: profile.uuid,
+                name: profile.username.clone(),
+                level,
+                bypasses_player_limit: false,
+            }),
+        }
+        ops.save()?;
+        Ok(true)
+    }
+
+    /// Removes the operator named `name`, returning whether it was one.
+    pub fn remove_op(&self, name: &str) -> Result<bool, AccessError> {
+        let mut ops = self.ops.lock().unwrap();
+        ops.remove(|entry| entry.matches_name(name))
+    }
+
+    /// Returns the ban of a player, if it is banned.
+    ///
+    /// Expired bans are removed.
+    pub fn player_ban(&self, profile: &GameProfile) -> Option<PlayerBan> {
+        let mut banned_players = self.banned_players.lock().unwrap();
+        remove_expired(&mut banned_players, |ban| &ban.details);
+        banned_players
+            .entries
+            .iter()
+            .find(|ban| ban.matches(profile))
+            .cloned()
+    }
+
+    pub fn banned_players(&self) -> Vec<PlayerBan> {
+        let mut banned_players = self.banned_players.lock().unwrap();
+        remove_expired(&mut banned_players, |ban| &ban.details);
+        banned_players.entries.clone()
+    }
+
+    /// Bans a player, returning whether it wasn't banned already.
+    pub fn ban(&self, profile: &GameProfile, details: BanDetails) -> Result<bool, AccessError> {
+        let mut banned_players = self.banned_players.lock().unwrap();
+        remove_expired(&mut banned_players, |ban| &ban.details);
+        if banned_players
+            .entries
+            .iter()
+            .any(|ban| ban.matches(profile))
+        {
+            return Ok(false);
+        }
+        banned_players.entries.push(PlayerBan {
+            uuid: profile.uuid,
+            name: profile.username.clone(),
+            details,
+        });
+        banned_players.save()?;
+        Ok(true)
+    }
+
+    /// Unbans the player named `name`, returning whether it was banned.
+    pub fn pardon(&self, name: &str) -> Result<bool, AccessError> {
+        let mut banned_players = self.banned_players.lock().unwrap();
+        banned_players.remove(|ban| ban.matches_name(name))
+    }
+
+    /// Returns the ban of an IP address, if it is banned.
+    ///
+    /// Expired bans are removed.
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<IpBan> {
+        let mut banned_ips = self.banned_ips.lock().unwrap();
+        remove_expired(&mut banned_ips, |ban| &ban.details);
+        banned_ips.entries.iter().find(|ban| ban.ip == ip).cloned()
+    }
+
+    pub fn banned_ips(&self) -> Vec<IpBan> {
+        let mut banned_ips = self.banned_ips.lock().unwrap();
+        remove_expired(&mut banned_ips, |ban| &ban.details);
+        banned_ips.entries.clone()
+    }
+
+    /// Bans an IP address, returning whether it wasn't banned already.
+    pub fn ban_ip(&self, ip: IpAddr, details: BanDetails) -> Result<bool, AccessError> {
+        let mut banned_ips = self.banned_ips.lock().unwrap();
+        remove_expired(&mut banned_ips, |ban| &ban.details);
+        if banned_ips.entries.iter().any(|ban| ban.ip == ip) {
+            return Ok(false);
+        }
+        banned_ips.entries.push(IpBan { ip, details });
+        banned_ips.save()?;
+        Ok(true)
+    }
+
+    /// Unbans an IP address, returning whether it was banned.
+    pub fn pardon_ip(&self, ip: IpAddr) -> Result<bool, AccessError> {
+        let mut banned_ips = self.banned_ips.lock().unwrap();
+        banned_ips.remove(|ban| ban.ip == ip)
+    }
+}
+
+/// Removes expired bans, logging rather than failing if the list can't be saved.
+fn remove_expired<T: Serialize + DeserializeOwned>(
+    list: &mut JsonList<T>,
+    details: impl Fn(&T) -> &BanDetails,
+) {
+    if let Err(err) = list.remove(|ban| details(ban).is_expired()) {
+        tracing::warn!("Unable to remove expired bans: {}.", err);
+    }
+}
+
+mod date {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::DATE_FORMAT;
+
+    pub fn serialize<S: Serializer>(
+        date: &DateTime<FixedOffset>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format(DATE_FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<FixedOffset>, D::Error> {
+        let date = String::deserialize(deserializer)?;
+        DateTime::parse_from_str(&date, DATE_FORMAT).map_err(D::Error::custom)
+    }
+}
+
+mod expiry {
+    use chrono::{DateTime, FixedOffset};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::{DATE_FORMAT, FOREVER};
+
+    pub fn serialize<S: Serializer>(
+        expires: &Option<DateTime<FixedOffset>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match expires {
+            Some(expires) => super::date::serialize(expires, serializer),
+            None => serializer.serialize_str(FOREVER),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+        let expires = String::deserialize(deserializer)?;
+        if expires == FOREVER {
+            return Ok(None);
+        }
+        DateTime::parse_from_str(&expires, DATE_FORMAT)
+            .map(Some)
+            .map_err(D::Error::custom)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AccessError {
+    #[error("unable to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid list {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+    const JEB: &str = "853c80ef-3c37-49fd-aa49-938b674adae6";
+
+    // Lists as written by a vanilla server.
+    const WHITELIST: &str = r#"[
+  {
+    "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+    "name": "Notch"
+  }
+]"#;
+    const OPS: &str = r#"[
+  {
+    "uuid": "853c80ef-3c37-49fd-aa49-938b674adae6",
+    "name": "jeb_",
+    "level": 3,
+    "bypassesPlayerLimit": true
+  }
+]"#;
+    const BANNED_PLAYERS: &str = r#"[
+  {
+    "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+    "name": "Notch",
+    "created": "2024-08-08 15:30:00 +0200",
+    "source": "jeb_",
+    "expires": "forever",
+    "reason": "Banned by an operator."
+  }
+]"#;
+    const BANNED_IPS: &str = r#"[
+  {
+    "ip": "192.168.0.42",
+    "created": "2024-08-08 15:30:00 +0200",
+    "source": "Server",
+    "expires": "2999-01-01 00:00:00 +0000",
+    "reason": "Griefing"
+  }
+]"#;
+
+    fn profile(uuid: &str, name: &str) -> GameProfile {
+        GameProfile {
+            uuid: uuid.parse().unwrap(),
+            username: name.to_owned(),
+            properties: Vec::new(),
+        }
+    }
+
+    fn date(date: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_str(date, DATE_FORMAT).unwrap()
+    }
+
+    fn write_lists(directory: &Path) {
+        std::fs::write(directory.join(WHITELIST_FILE), WHITELIST).unwrap();
+        std::fs::write(directory.join(OPS_FILE), OPS).unwrap();
+        std::fs::write(directory.join(BANNED_PLAYERS_FILE), BANNED_PLAYERS).unwrap();
+        std::fs::write(directory.join(BANNED_IPS_FILE), BANNED_IPS).unwrap();
+    }
+
+    #[test]
+    fn loads_vanilla_lists() {
+        let directory = tempfile::tempdir().unwrap();
+        write_lists(directory.path());
+        let access = AccessControl::load(directory.path()).unwrap();
+
+        let notch = profile(NOTCH, "Notch");
+        let jeb = profile(JEB, "jeb_");
+        assert!(access.is_whitelisted(&notch));
+        assert!(!access.is_whitelisted(&jeb));
+        assert_eq!(
+            access.op(&jeb),
+            Some(OpEntry {
+                uuid: jeb.uuid,
+                name: "jeb_".to_owned(),
+                level: 3,
+                bypasses_player_limit: true,
+            })
+        );
+        assert_eq!(access.permission_level(&notch), 0);
+
+        let ban = access.player_ban(&notch).unwrap();
+        assert_eq!(
+            ban.details,
+            BanDetails {
+                created: date("2024-08-08 15:30:00 +0200"),
+                source: "jeb_".to_owned(),
+                expires: None,
+                reason: DEFAULT_BAN_REASON.to_owned(),
+            }
+        );
+        assert!(access.player_ban(&jeb).is_none());
+
+        let ban = access.ip_ban("192.168.0.42".parse().unwrap()).unwrap();
+        assert_eq!(ban.details.source, "Server");
+        assert_eq!(ban.details.expires, Some(date("2999-01-01 00:00:00 +0000")));
+        assert_eq!(ban.details.reason, "Griefing");
+        assert!(access.ip_ban("127.0.0.1".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn defaults_missing_fields() {
+        let op: OpEntry =
+            serde_json::from_str(&format!(r#"{{"uuid":"{JEB}","name":"jeb_"}}"#)).unwrap();
+        assert_eq!(op.level, MAX_PERMISSION_LEVEL);
+        assert!(!op.bypasses_player_limit);
+
+        let before = now();
+        let ban: PlayerBan =
+            serde_json::from_str(&format!(r#"{{"uuid":"{NOTCH}","name":"Notch"}}"#)).unwrap();
+        assert!(ban.details.created >= before - chrono::Duration::seconds(1));
+        assert_eq!(ban.details.source, UNKNOWN_SOURCE);
+        assert_eq!(ban.details.expires, None);
+        assert_eq!(ban.details.reason, DEFAULT_BAN_REASON);
+
+        let ban: IpBan = serde_json::from_str(r#"{"ip":"::1","reason":"Spam"}"#).unwrap();
+        assert_eq!(ban.ip, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(ban.details.reason, "Spam");
+    }
+
+    #[test]
+    fn saves_like_vanilla() {
+        let directory = tempfile::tempdir().unwrap();
+        write_lists(directory.path());
+        let access = AccessControl::load(directory.path()).unwrap();
+
+        // Changing a list saves it, keeping the entries that were loaded.
+        let alex = GameProfile::offline("Alex");
+        assert!(access.add_to_whitelist(&alex).unwrap());
+        assert!(!access.add_to_whitelist(&alex).unwrap());
+        assert!(access.add_op(&alex, 2).unwrap());
+        assert!(access
+            .ban(&alex, BanDetails::new("Server", None, None))
+            .unwrap());
+        assert!(access
+            .ban_ip(
+                "10.0.0.1".parse().unwrap(),
+                BanDetails::new("Server", None, None)
+            )
+            .unwrap());
+
+        let read = |file| -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(directory.path().join(file)).unwrap())
+                .unwrap()
+        };
+        let whitelist = read(WHITELIST_FILE);
+        assert_eq!(
+            whitelist[0],
+            serde_json::json!({"uuid": NOTCH, "name": "Notch"})
+        );
+        assert_eq!(
+            whitelist[1],
+            serde_json::json!({"uuid": alex.uuid, "name": "Alex"})
+        );
+        let ops = read(OPS_FILE);
+        assert_eq!(
+            ops[1],
+            serde_json::json!({
+                "uuid": alex.uuid,
+                "name": "Alex",
+                "level": 2,
+                "bypassesPlayerLimit": false,
+            })
+        );
+        let banned_players = read(BANNED_PLAYERS_FILE);
+        let original: serde_json::Value = serde_json::from_str(BANNED_PLAYERS).unwrap();
+        assert_eq!(banned_players[0], original[0]);
+        assert_eq!(banned_players[1]["source"], "Server");
+        assert_eq!(banned_players[1]["expires"], FOREVER);
+        assert_eq!(banned_players[1]["reason"], DEFAULT_BAN_REASON);
+        let banned_ips = read(BANNED_IPS_FILE);
+        let original: serde_json::Value = serde_json::from_str(BANNED_IPS).unwrap();
+        assert_eq!(banned_ips[0], original[0]);
+        assert_eq!(banned_ips[1]["ip"], "10.0.0.1");
+
+        // The saved lists load again unchanged.
+        let reloaded = AccessControl::load(directory.path()).unwrap();
+        assert_eq!(reloaded.whitelist(), access.whitelist());
+        assert_eq!(reloaded.ops(), access.ops());
+        assert_eq!(reloaded.banned_players(), access.banned_players());
+        assert_eq!(reloaded.banned_ips(), access.banned_ips());
+
+        assert!(access.remove_from_whitelist("alex").unwrap());
+        assert!(access.pardon("ALEX").unwrap());
+        assert!(!access.pardon("Alex").unwrap());
+        let reloaded = AccessControl::load(directory.path()).unwrap();
+        assert_eq!(reloaded.whitelist().len(), 1);
+        assert_eq!(reloaded.banned_players().len(), 1);
+    }
+
+    #[test]
+    fn creates_missing_lists() {
+        let directory = tempfile::tempdir().unwrap();
+        let access = AccessControl::load(directory.path()).unwrap();
+        assert!(access.whitelist().is_empty());
+        for file in [
+            WHITELIST_FILE,
+            OPS_FILE,
+            BANNED_PLAYERS_FILE,
+            BANNED_IPS_FILE,
+        ] {
+            let text = std::fs::read_to_string(directory.path().join(file)).unwrap();
+            assert_eq!(text, "[]");
+        }
+    }
+
+    #[test]
+    fn removes_expired_bans() {
+        let directory = tempfile::tempdir().unwrap();
+        let access = AccessControl::load(directory.path()).unwrap();
+        let notch = profile(NOTCH, "Notch");
+        let expired = BanDetails::new("Server", None, Some(date("2000-01-01 00:00:00 +0000")));
+        assert!(access.ban(&notch, expired).unwrap());
+        assert!(access.player_ban(&notch).is_none());
+        assert!(access.banned_players().is_empty());
+    }
+}
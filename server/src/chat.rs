@@ -0,0 +1,379 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use packet::{client::*, ChatSessionData};
+use protocol::text::TextComponent;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::player_list::{PlayerEntry, PlayerList};
+
+/// Length of a message signature, in bytes.
+pub const MESSAGE_SIGNATURE_LEN: usize = 256;
+/// Maximum length of a chat message, in characters.
+pub const MAX_MESSAGE_LEN: usize = 256;
+/// Number of last seen messages a client can acknowledge.
+const LAST_SEEN_WINDOW: usize = 20;
+/// Number of signatures a client caches, which can be referenced by ID instead of being sent in full.
+const SIGNATURE_CACHE_SIZE: usize = 128;
+/// Maximum number of messages a client may not have acknowledged yet.
+const MAX_PENDING_MESSAGES: usize = 4096;
+const MAX_PUBLIC_KEY_LEN: usize = 512;
+const MAX_KEY_SIGNATURE_LEN: usize = 4096;
+
+/// A message signature, cheap to clone.
+pub type MessageSignature = Arc<[u8]>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ChatSettings {
+    /// Whether players must sign their chat messages, which is also advertised to clients.
+    ///
+    /// Always disabled for now: players aren't authenticated yet, so neither their keys nor their
+    /// signatures are verified, and clients must not be told that chat is secure.
+    pub enforce_secure_chat: bool,
+}
+
+/// A player's chat session, which they sign their messages with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatSession {
+    pub session_id: Uuid,
+    /// Expiry time of the public key, in epoch milliseconds.
+    pub expires_at: i64,
+    pub public_key: Vec<u8>,
+    pub key_signature: Vec<u8>,
+}
+
+impl ChatSession {
+    /// Validates the session data sent by the client.
+    ///
+    /// TODO: The key signature is not verified against Mojang's public key,
+    /// as players aren't authenticated yet.
+    pub fn from_packet(data: &ChatSessionData<'_>) -> Result<Self, ChatError> {
+        if data.expires_at < now_millis() {
+            return Err(ChatError::ExpiredPublicKey);
+        }
+        if data.public_key.is_empty() || data.public_key.len() > MAX_PUBLIC_KEY_LEN {
+            return Err(ChatError::InvalidPublicKey);
+        }
+        if data.key_signature.is_empty() || data.key_signature.len() > MAX_KEY_SIGNATURE_LEN {
+            return Err(ChatError::InvalidPublicKey);
+        }
+
+        Ok(Self {
+            session_id: data.session_id,
+            expires_at: data.expires_at,
+            public_key: data.public_key.to_vec(),
+            key_signature: data.key_signature.to_vec(),
+        })
+    }
+
+    pub fn to_packet(&self) -> ChatSessionData<'_> {
+        ChatSessionData {
+            session_id: self.session_id,
+            expires_at: self.expires_at,
+            public_key: self.public_key.as_slice().into(),
+            key_signature: self.key_signature.as_slice().into(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at < now_millis()
+    }
+}
+
+/// Tracks the signed messages sent to a client.
+///
+/// The client acknowledges them as "last seen" when sending its own messages.
+#[derive(Debug)]
+pub struct LastSeenTracker {
+    /// The acknowledgement window, followed by pending messages. `None` if a message was not signed.
+    entries: VecDeque<Option<MessageSignature>>,
+}
+
+impl LastSeenTracker {
+    pub fn new() -> Self {
+        Self {
+            entries: std::iter::repeat(None).take(LAST_SEEN_WINDOW).collect(),
+        }
+    }
+
+    /// Tracks a message sent to the client.
+    pub fn add_pending(&mut self, signature: Option<MessageSignature>) -> Result<(), ChatError> {
+        if self.entries.len() >= LAST_SEEN_WINDOW + MAX_PENDING_MESSAGES {
+            return Err(ChatError::TooManyPendingMessages);
+        }
+
+        self.entries.push_back(signature);
+        Ok(())
+    }
+
+    /// Handles the client acknowledging `offset` more messages, shifting the window.
+    pub fn apply_offset(&mut self, offset: i32) -> Result<(), ChatError> {
+        let max_offset = self.entries.len() - LAST_SEEN_WINDOW;
+        match usize::try_from(offset) {
+            Ok(offset) if offset <= max_offset => {
+                self.entries.drain(..offset);
+                Ok(())
+            }
+            _ => Err(ChatError::InvalidAcknowledgement),
+        }
+    }
+
+    /// Handles a last seen update from a chat message or command.
+    ///
+    /// Returns the signatures of the messages the client has last seen.
+    pub fn apply_update(
+        &mut self,
+        offset: i32,
+        acknowledged: &[u8],
+    ) -> Result<Vec<MessageSignature>, ChatError> {
+        self.apply_offset(offset)?;
+
+        let mut last_seen = Vec::new();
+        for (index, entry) in self.entries.iter().take(LAST_SEEN_WINDOW).enumerate() {
+            let is_acknowledged = acknowledged
+                .get(index / 8)
+                .is_some_and(|byte| byte & (1 << (index % 8)) != 0);
+            if !is_acknowledged {
+                continue;
+            }
+
+            match entry {
+                Some(signature) => last_seen.push(Arc::clone(signature)),
+                None => return Err(ChatError::InvalidAcknowledgement),
+            }
+        }
+
+        Ok(last_seen)
+    }
+}
+
+impl Default for LastSeenTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Mirrors the client's cache of message signatures, so previous messages can be referenced by ID.
+///
+/// Only needed to relay signed player chat, which requires verified signatures.
+#[derive(Debug, Default)]
+pub struct MessageSignatureCache {
+    /// Most recent first.
+    entries: VecDeque<MessageSignature>,
+}
+
+impl MessageSignatureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packs a signature as an ID into the cache if it is cached, or the full signature if not.
+    pub fn pack<'a>(&self, signature: &'a MessageSignature) -> PreviousMessage<'a> {
+        match self.entries.iter().position(|entry| entry == signature) {
+            Some(id) => PreviousMessage::Id(id as i32),
+            None => PreviousMessage::Signature(signature.as_ref().into()),
+        }
+    }
+
+    /// Updates the cache the same way the client does after receiving a signed message.
+    pub fn push(&mut self, last_seen: &[MessageSignature], signature: &MessageSignature) {
+        let mut entries: VecDeque<MessageSignature> = last_seen
+            .iter()
+            .chain([signature])
+            .map(Arc::clone)
+            .collect();
+        for entry in self.entries.drain(..) {
+            if entries.len() >= SIGNATURE_CACHE_SIZE {
+                break;
+            }
+            if !entries.contains(&entry) {
+                entries.push_back(entry);
+            }
+        }
+        entries.truncate(SIGNATURE_CACHE_SIZE);
+        self.entries = entries;
+    }
+}
+
+/// Chat state of a player as a message recipient, shared with the other players that send it messages.
+#[derive(Debug, Default)]
+pub struct ChatRecipient {
+    pub last_seen: LastSeenTracker,
+}
+
+/// Broadcasts a chat message from `sender` to all players in `players`.
+///
+/// Messages are sent as system chat using the `minecraft:chat` chat type's format, even if they are
+/// signed: neither chat session keys nor message signatures are verified yet, so relaying them as
+/// signed player chat would tell clients that unverified messages are secure.
+pub fn broadcast_chat_message(players: &PlayerList, sender: &PlayerEntry, message: &str) {
+    let content = TextComponent::translatable(
+        "chat.type.text",
+        [
+            TextComponent::text(sender.profile.username.as_str()),
+            TextComponent::text(message),
+        ],
+    );
+    players.broadcast_system_message(&content);
+}
+
+/// Checks that a chat message is allowed to be sent.
+pub fn validate_message(message: &str) -> Result<(), ChatError> {
+    if message.chars().count() > MAX_MESSAGE_LEN {
+        return Err(ChatError::MessageTooLong);
+    }
+    if message
+        .chars()
+        .any(|c| c == '\u{a7}' || c == '\u{7f}' || c < ' ')
+    {
+        return Err(ChatError::IllegalCharacters);
+    }
+
+    Ok(())
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as i64)
+}
+
+#[derive(Error, Debug)]
+pub enum ChatError {
+    #[error("chat message is too long")]
+    MessageTooLong,
+    #[error("illegal characters in chat message")]
+    IllegalCharacters,
+    #[error("chat message is out of order")]
+    OutOfOrder,
+    #[error("chat message is missing a signature, but secure chat is enforced")]
+    MissingSignature,
+    #[error("signed chat message without a chat session")]
+    MissingSession,
+    #[error("chat session public key has expired")]
+    ExpiredPublicKey,
+    #[error("invalid chat session public key")]
+    InvalidPublicKey,
+    #[error("invalid last seen message acknowledgement")]
+    InvalidAcknowledgement,
+    #[error("too many unacknowledged chat messages")]
+    TooManyPendingMessages,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(byte: u8) -> MessageSignature {
+        vec![byte; MESSAGE_SIGNATURE_LEN].into()
+    }
+
+    #[test]
+    fn acknowledges_pending_messages() {
+        let mut tracker = LastSeenTracker::new();
+        for byte in 0..3 {
+            tracker.add_pending(Some(signature(byte))).unwrap();
+        }
+
+        // The 3 new messages are at the end of the window, at indices 17 to 19.
+        let last_seen = tracker.apply_update(3, &[0, 0, 0b1110]).unwrap();
+        assert_eq!(last_seen, [signature(0), signature(1), signature(2)]);
+        // They are still in the window, without any more pending messages.
+        let last_seen = tracker.apply_update(0, &[0, 0, 0b1000]).unwrap();
+        assert_eq!(last_seen, [signature(2)]);
+        assert!(tracker.apply_offset(1).is_err());
+    }
+
+    #[test]
+    fn invalid_acknowledgements() {
+        let mut tracker = LastSeenTracker::new();
+        tracker.add_pending(None).unwrap();
+        tracker.add_pending(Some(signature(1))).unwrap();
+
+        assert!(matches!(
+            tracker.apply_offset(3),
+            Err(ChatError::InvalidAcknowledgement)
+        ));
+        assert!(matches!(
+            tracker.apply_offset(-1),
+            Err(ChatError::InvalidAcknowledgement)
+        ));
+        // The unsigned message can't be acknowledged.
+        assert!(matches!(
+            tracker.apply_update(2, &[0, 0, 0b0100]),
+            Err(ChatError::InvalidAcknowledgement)
+        ));
+    }
+
+    #[test]
+    fn limits_pending_messages() {
+        let mut tracker = LastSeenTracker::new();
+        for _ in 0..MAX_PENDING_MESSAGES {
+            tracker.add_pending(None).unwrap();
+        }
+        assert!(matches!(
+            tracker.add_pending(None),
+            Err(ChatError::TooManyPendingMessages)
+        ));
+
+        tracker.apply_offset(1).unwrap();
+        tracker.add_pending(None).unwrap();
+    }
+
+    #[test]
+    fn packs_cached_signatures() {
+        let mut cache = MessageSignatureCache::new();
+        let (first, second, third) = (signature(1), signature(2), signature(3));
+        assert_eq!(
+            cache.pack(&first),
+            PreviousMessage::Signature(first.as_ref().into())
+        );
+
+        cache.push(&[Arc::clone(&first)], &second);
+        assert_eq!(cache.pack(&first), PreviousMessage::Id(0));
+        assert_eq!(cache.pack(&second), PreviousMessage::Id(1));
+
+        // Last seen messages and the new one move to the front, without duplicates.
+        cache.push(&[Arc::clone(&second)], &third);
+        assert_eq!(cache.pack(&second), PreviousMessage::Id(0));
+        assert_eq!(cache.pack(&third), PreviousMessage::Id(1));
+        assert_eq!(cache.pack(&first), PreviousMessage::Id(2));
+    }
+
+    #[test]
+    fn evicts_oldest_signatures() {
+        let mut cache = MessageSignatureCache::new();
+        let signatures: Vec<_> = (0..=SIGNATURE_CACHE_SIZE as u8).map(signature).collect();
+        for signature in &signatures {
+            cache.push(&[], signature);
+        }
+
+        assert_eq!(
+            cache.pack(&signatures[0]),
+            PreviousMessage::Signature(signatures[0].as_ref().into())
+        );
+        assert_eq!(cache.pack(&signatures[1]), PreviousMessage::Id(127));
+        assert_eq!(
+            cache.pack(&signatures[SIGNATURE_CACHE_SIZE]),
+            PreviousMessage::Id(0)
+        );
+    }
+
+    #[test]
+    fn validates_messages() {
+        assert!(validate_message("Hello, world!").is_ok());
+        assert!(validate_message(&"a".repeat(MAX_MESSAGE_LEN)).is_ok());
+        assert!(matches!(
+            validate_message(&"a".repeat(MAX_MESSAGE_LEN + 1)),
+            Err(ChatError::MessageTooLong)
+        ));
+        assert!(matches!(
+            validate_message("\u{a7}cred"),
+            Err(ChatError::IllegalCharacters)
+        ));
+    }
+}
@@ -0,0 +1,420 @@
+//! Tracking of the chunks a player can see, streaming them to the client as it moves.
+//!
+//! Chunks are sent in batches, at most once per tick, at the rate the client reports it can
+//! handle in its batch acknowledgements, like vanilla. The light of the chunks the client has is
+//! sent again when it changes.
+
+use std::collections::{HashSet, VecDeque};
+
+use packet::client::{
+    ChunkBatchFinishedPacket, ChunkBatchStartPacket, SetCenterChunkPacket, UnloadChunkPacket,
+};
+
+use crate::{
+    connection::{PacketSendError, PacketSender},
+    world::{self, Chunk, ChunkPos, Ticket, TicketKind, World},
+};
+
+/// Smallest view distance, in chunks, same as vanilla.
+pub const MIN_VIEW_DISTANCE: i32 = 2;
+
+/// Chunks per tick sent until the client reports its rate, same as vanilla.
+const INITIAL_CHUNKS_PER_TICK: f32 = 9.0;
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+/// Batches that may be sent without being acknowledged, once the client acknowledged the first.
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+/// The chunks around a player that its client has, or should get.
+#[derive(Debug)]
+pub struct ChunkView {
+    center: ChunkPos,
+    /// Radius of the square of chunks around the center, in chunks.
+    view_distance: i32,
+    /// Chunks sent to the client, which it has to be told to forget once out of range.
+    sent: HashSet<ChunkPos>,
+    /// Chunks in range that haven't been sent yet, in spiral order around the center, as they
+    /// are loaded.
+    pending: VecDeque<ChunkPos>,
+    /// Rate the client reported it can receive chunks at.
+    chunks_per_tick: f32,
+    /// Chunks that may be sent in the next batch, growing by `chunks_per_tick` every tick.
+    batch_quota: f32,
+    unacknowledged_batches: u32,
+    max_unacknowledged_batches: u32,
+    /// Light version of the world up to which light changes were sent.
+    light_version: u64,
+}
+
+impl ChunkView {
+    /// Makes a view with no chunks sent yet.
+    pub fn new(center: ChunkPos, view_distance: i32) -> Self {
+        Self {
+            center,
+            view_distance,
+            sent: HashSet::new(),
+            pending: world::spiral(center, view_distance).collect(),
+            chunks_per_tick: INITIAL_CHUNKS_PER_TICK,
+            batch_quota: 0.0,
+            unacknowledged_batches: 0,
+            // Only one batch is sent until the client reports its rate.
+            max_unacknowledged_batches: 1,
+            light_version: 0,
+        }
+    }
+
+    /// The chunk the player is in.
+    pub fn center(&self) -> ChunkPos {
+        self.center
+    }
+
+    pub fn view_distance(&self) -> i32 {
+        self.view_distance
+    }
+
+    /// The ticket keeping the chunks in range loaded.
+    pub fn ticket(&self) -> Ticket {
+        Ticket::new(TicketKind::Player, self.center, self.view_distance as u32)
+    }
+
+    /// Whether `pos` is within the view distance of the center.
+    pub fn is_in_range(&self, pos: ChunkPos) -> bool {
+        (pos.x - self.center.x).abs() <= self.view_distance
+            && (pos.z - self.center.z).abs() <= self.view_distance
+    }
+
+    /// Whether the chunk at `pos` was sent to the client.
+    pub fn is_sent(&self, pos: ChunkPos) -> bool {
+        self.sent.contains(&pos)
+    }
+
+    /// Number of chunks in range that haven't been sent yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Moves the view, telling the client the new center if it changed and to forget the chunks
+    /// that are now out of range. The chunks that came into range are sent by later batches.
+    pub fn update(
+        &mut self,
+        sender: &PacketSender,
+        center: ChunkPos,
+        view_distance: i32,
+    ) -> Result<(), PacketSendError> {
+        if center == self.center && view_distance == self.view_distance {
+            return Ok(());
+        }
+        if center != self.center {
+            sender.send_packet(&SetCenterChunkPacket {
+                chunk_x: center.x,
+                chunk_z: center.z,
+            })?;
+        }
+        self.center = center;
+        self.view_distance = view_distance;
+
+        let mut forgotten: Vec<ChunkPos> = self
+            .sent
+            .iter()
+            .copied()
+            .filter(|&pos| !self.is_in_range(pos))
+            .collect();
+        forgotten.sort_unstable_by_key(|pos| (pos.x, pos.z));
+        for pos in forgotten {
+            self.sent.remove(&pos);
+            sender.send_packet(&UnloadChunkPacket {
+                chunk_z: pos.z,
+                chunk_x: pos.x,
+            })?;
+        }
+
+        self.pending = world::spiral(center, view_distance)
+            .filter(|pos| !self.sent.contains(pos))
+            .collect();
+        Ok(())
+    }
+
+    /// Sends the next batch of pending chunks if the client is ready for it. Chunks that aren't
+    /// loaded yet are skipped until they are. Called once per tick, returns the number of chunks
+    /// sent.
+    pub fn send_batch(
+        &mut self,
+        sender: &PacketSender,
+        world: &World,
+    ) -> Result<usize, PacketSendError> {
+        if self.unacknowledged_batches >= self.max_unacknowledged_batches {
+            return Ok(0);
+        }
+        // Unused quota only accumulates up to one tick's worth, or one chunk at low rates.
+        self.batch_quota =
+            (self.batch_quota + self.chunks_per_tick).min(self.chunks_per_tick.max(1.0));
+        if self.batch_quota < 1.0 || self.pending.is_empty() {
+            return Ok(0);
+        }
+
+        let count = self.batch_quota as usize;
+        let mut batch = Vec::with_capacity(count);
+        {
+            let chunks = world.chunks();
+            self.pending.retain(|pos| {
+                let chunk = chunks.get(pos).filter(|_| batch.len() < count);
+                if let Some(chunk) = chunk {
+                    batch.push((*pos, chunk.to_packet()));
+                }
+                chunk.is_none()
+            });
+        }
+        if batch.is_empty() {
+            return Ok(0);
+        }
+
+        sender.send_packet(&ChunkBatchStartPacket {})?;
+        for (pos, packet) in &batch {
+            sender.send_packet(packet)?;
+            self.sent.insert(*pos);
+        }
+        sender.send_packet(&ChunkBatchFinishedPacket {
+            batch_size: batch.len() as i32,
+        })?;
+
+        self.unacknowledged_batches += 1;
+        self.batch_quota -= batch.len() as f32;
+        Ok(batch.len())
+    }
+
+    /// Sends the light of the chunks the client has whose light changed since the last time.
+    /// Called once per tick.
+    pub fn send_light_updates(
+        &mut self,
+        sender: &PacketSender,
+        world: &World,
+    ) -> Result<(), PacketSendError> {
+        let (version, changed) = world.chunk_manager().light_changes(self.light_version);
+        self.light_version = version;
+        let packets: Vec<_> = {
+            let chunks = world.chunks();
+            changed
+                .iter()
+                .filter(|pos| self.sent.contains(pos))
+                .filter_map(|pos| chunks.get(pos))
+                .map(Chunk::light_packet)
+                .collect()
+        };
+        for packet in &packets {
+            sender.send_packet(packet)?;
+        }
+        Ok(())
+    }
+
+    /// Handles the client acknowledging a batch, reporting the rate it can receive chunks at.
+    pub fn batch_received(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged_batches = self.unacknowledged_batches.saturating_sub(1);
+        self.chunks_per_tick = if chunks_per_tick.is_nan() {
+            MIN_CHUNKS_PER_TICK
+        } else {
+            chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK)
+        };
+        if self.unacknowledged_batches == 0 {
+            self.batch_quota = 1.0;
+        }
+        self.max_unacknowledged_batches = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use packet::client::ClientPlayPacket;
+
+    use super::*;
+    use crate::{
+        connection::tests::SentPackets,
+        world::{generator::VoidGenerator, ChunkStatus},
+    };
+
+    const ORIGIN: ChunkPos = ChunkPos::new(0, 0);
+
+    /// Makes a world with the chunks within `radius` of the origin loaded.
+    async fn world(radius: u32) -> World {
+        let world = World::overworld(Box::new(VoidGenerator::new()));
+        let ticket = Ticket::new(TicketKind::Forced, ORIGIN, radius);
+        world.chunk_manager().add_ticket(ticket);
+        for pos in world::spiral(ORIGIN, radius as i32) {
+            assert!(world.chunk_manager().wait_for(pos, ChunkStatus::Full).await);
+        }
+        world
+    }
+
+    /// The positions of the chunks in `packets`, and the size of the batch they were sent in.
+    fn batch(packets: &[ClientPlayPacket]) -> (Vec<ChunkPos>, usize) {
+        let [ClientPlayPacket::ChunkBatchStartPacket(_), chunks @ .., ClientPlayPacket::ChunkBatchFinishedPacket(finished)] =
+            packets
+        else {
+            panic!("expected a batch, got {packets:?}");
+        };
+        let chunks = chunks
+            .iter()
+            .map(|packet| match packet {
+                ClientPlayPacket::ChunkDataAndUpdateLightPacket(packet) => {
+                    ChunkPos::new(packet.chunk_x, packet.chunk_z)
+                }
+                _ => panic!("expected chunk data, got {packet:?}"),
+            })
+            .collect();
+        (chunks, finished.batch_size as usize)
+    }
+
+    #[test]
+    fn pending_in_spiral_order() {
+        let view = ChunkView::new(ChunkPos::new(3, -1), 1);
+        assert_eq!(
+            Vec::from(view.pending.clone()),
+            [
+                (3, -1),
+                (4, -1),
+                (4, 0),
+                (3, 0),
+                (2, 0),
+                (2, -1),
+                (2, -2),
+                (3, -2),
+                (4, -2)
+            ]
+            .map(|(x, z)| ChunkPos::new(x, z))
+        );
+        assert_eq!(ChunkView::new(ORIGIN, 10).pending(), 21 * 21);
+    }
+
+    #[tokio::test]
+    async fn sends_loaded_chunks_in_order() {
+        let world = world(1).await;
+        let (sender, mut sent) = SentPackets::new();
+        let mut view = ChunkView::new(ORIGIN, 2);
+
+        // Chunks that aren't loaded are skipped until they are.
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 9);
+        let (chunks, size) = batch(&sent.take());
+        assert_eq!(chunks, world::spiral(ORIGIN, 1).collect::<Vec<_>>());
+        assert_eq!(size, 9);
+        assert!(chunks.iter().all(|&pos| view.is_sent(pos)));
+        assert_eq!(view.pending(), 16);
+    }
+
+    #[tokio::test]
+    async fn waits_for_acknowledgements() {
+        let world = world(2).await;
+        let (sender, mut sent) = SentPackets::new();
+        let mut view = ChunkView::new(ORIGIN, 2);
+
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 9);
+        // Only one batch is sent until the client reports its rate.
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 0);
+        sent.take();
+        assert!(sent.take().is_empty());
+
+        view.batch_received(1.0);
+        for _ in 0..MAX_UNACKNOWLEDGED_BATCHES {
+            assert_eq!(view.send_batch(&sender, &world).unwrap(), 1);
+            assert_eq!(batch(&sent.take()).1, 1);
+        }
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 0);
+        assert!(sent.take().is_empty());
+
+        view.batch_received(1.0);
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 1);
+        assert_eq!(view.pending(), 25 - 9 - 11);
+    }
+
+    #[tokio::test]
+    async fn spreads_slow_batches() {
+        let world = world(2).await;
+        let (sender, mut sent) = SentPackets::new();
+        let mut view = ChunkView::new(ORIGIN, 2);
+        view.send_batch(&sender, &world).unwrap();
+        view.batch_received(0.5);
+        sent.take();
+
+        // Clients that can't take a chunk per tick get one every few ticks.
+        let counts: Vec<_> = (0..5)
+            .map(|_| view.send_batch(&sender, &world).unwrap())
+            .collect();
+        assert_eq!(counts, [1, 0, 1, 0, 1]);
+
+        // Quota doesn't pile up while the client catches up.
+        view.batch_received(4.0);
+        view.batch_received(4.0);
+        view.batch_received(4.0);
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 4);
+        assert_eq!(view.send_batch(&sender, &world).unwrap(), 4);
+    }
+
+    #[test]
+    fn clamps_reported_rates() {
+        let mut view = ChunkView::new(ORIGIN, 2);
+        view.unacknowledged_batches = 2;
+        view.batch_received(1000.0);
+        assert_eq!(view.chunks_per_tick, MAX_CHUNKS_PER_TICK);
+        assert_eq!(view.unacknowledged_batches, 1);
+        assert_eq!(view.max_unacknowledged_batches, MAX_UNACKNOWLEDGED_BATCHES);
+
+        view.batch_received(-1.0);
+        assert_eq!(view.chunks_per_tick, MIN_CHUNKS_PER_TICK);
+        view.batch_received(f32::NAN);
+        assert_eq!(view.chunks_per_tick, MIN_CHUNKS_PER_TICK);
+        // Acknowledging more batches than were sent doesn't wrap.
+        assert_eq!(view.unacknowledged_batches, 0);
+        assert_eq!(view.batch_quota, 1.0);
+    }
+
+    #[tokio::test]
+    async fn forgets_chunks_out_of_range() {
+        let world = world(2).await;
+        let (sender, mut sent) = SentPackets::new();
+        let mut view = ChunkView::new(ORIGIN, 2);
+        view.batch_received(MAX_CHUNKS_PER_TICK);
+        while view.pending() > 0 {
+            view.send_batch(&sender, &world).unwrap();
+            view.batch_received(MAX_CHUNKS_PER_TICK);
+        }
+        sent.take();
+
+        // Nothing changes without moving.
+        view.update(&sender, ORIGIN, 2).unwrap();
+        assert!(sent.take().is_empty());
+
+        view.update(&sender, ChunkPos::new(1, 0), 2).unwrap();
+        let packets = sent.take();
+        assert!(matches!(
+            &packets[0],
+            ClientPlayPacket::SetCenterChunkPacket(packet)
+                if packet.chunk_x == 1 && packet.chunk_z == 0
+        ));
+        let forgotten: Vec<_> = packets[1..]
+            .iter()
+            .map(|packet| match packet {
+                ClientPlayPacket::UnloadChunkPacket(packet) => {
+                    ChunkPos::new(packet.chunk_x, packet.chunk_z)
+                }
+                _ => panic!("expected an unload, got {packet:?}"),
+            })
+            .collect();
+        assert_eq!(
+            forgotten,
+            (-2..=2).map(|z| ChunkPos::new(-2, z)).collect::<Vec<_>>()
+        );
+        assert!(!view.is_sent(ChunkPos::new(-2, 0)));
+        // Only the chunks that came into range are sent, in spiral order.
+        assert_eq!(
+            Vec::from(view.pending.clone()),
+            [(3, -1), (3, 0), (3, 1), (3, 2), (3, -2)].map(|(x, z)| ChunkPos::new(x, z))
+        );
+
+        // Shrinking the view forgets chunks without moving the center.
+        view.update(&sender, ChunkPos::new(1, 0), 1).unwrap();
+        let packets = sent.take();
+        assert_eq!(packets.len(), 25 - 5 - 9);
+        assert!(packets
+            .iter()
+            .all(|packet| matches!(packet, ClientPlayPacket::UnloadChunkPacket(_))));
+        assert_eq!(view.pending(), 0);
+    }
+}
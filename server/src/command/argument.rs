@@ -0,0 +1,864 @@
+use packet::client::{CommandParser, StringParserKind};
+use protocol::{identifier::Identifier, BlockPosition, GameMode};
+use rand::seq::IteratorRandom;
+use uuid::Uuid;
+
+use crate::{
+    context::ServerContext,
+    player_list::PlayerEntry,
+    world::{Position, Rotation},
+};
+
+use super::{
+    reader::{StringReader, ARGUMENT_SEPARATOR},
+    CommandError, CommandSender, CommandSyntaxError, Suggestion, SyntaxErrorKind,
+};
+
+/// Maximum length of a player name.
+const MAX_PLAYER_NAME_LEN: usize = 16;
+
+/// How an argument is parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentType {
+    Bool,
+    Integer {
+        min: i32,
+        max: i32,
+    },
+    Double {
+        min: f64,
+        max: f64,
+    },
+    /// A single word.
+    Word,
+    /// A single word, or a phrase in quotes.
+    String,
+    /// The rest of the input.
+    GreedyString,
+    /// A player name, UUID or selector like `@p`.
+    Entity {
+        single: bool,
+        players_only: bool,
+    },
+    /// Block coordinates, possibly relative like `~ ~1 ~`.
+    BlockPos,
+    /// Coordinates, possibly relative like `~ ~1 ~`.
+    Vec3,
+    Identifier,
+    /// The rest of the input, as a chat message.
+    Message,
+    GameMode,
+}
+
+impl ArgumentType {
+    pub const fn integer() -> Self {
+        ArgumentType::Integer {
+            min: i32::MIN,
+            max: i32::MAX,
+        }
+    }
+
+    pub const fn double() -> Self {
+        ArgumentType::Double {
+            min: f64::MIN,
+            max: f64::MAX,
+        }
+    }
+
+    pub const fn player() -> Self {
+        ArgumentType::Entity {
+            single: true,
+            players_only: true,
+        }
+    }
+
+    pub const fn players() -> Self {
+        ArgumentType::Entity {
+            single: false,
+            players_only: true,
+        }
+    }
+
+    pub fn parse(
+        &self,
+        reader: &mut StringReader<'_>,
+    ) -> Result<ArgumentValue, CommandSyntaxError> {
+        let start = reader.cursor();
+        Ok(match self {
+            ArgumentType::Bool => ArgumentValue::Bool(reader.read_bool()?),
+            ArgumentType::Integer { min, max } => {
+                let value = reader.read_int()?;
+                if value < *min {
+                    return Err(reader.error_at(
+                        SyntaxErrorKind::IntegerTooLow {
+                            min: *min,
+                            found: value,
+                        },
+                        start,
+                    ));
+                }
+                if value > *max {
+                    return Err(reader.error_at(
+                        SyntaxErrorKind::IntegerTooHigh {
+                            max: *max,
+                            found: value,
+                        },
+                        start,
+                    ));
+                }
+                ArgumentValue::Integer(value)
+            }
+            ArgumentType::Double { min, max } => {
+                let value = reader.read_double()?;
+                if value < *min {
+                    return Err(reader.error_at(
+                        SyntaxErrorKind::DoubleTooLow {
+                            min: *min,
+                            found: value,
+                        },
+                        start,
+                    ));
+                }
+                if value > *max {
+                    return Err(reader.error_at(
+                        SyntaxErrorKind::DoubleTooHigh {
+                            max: *max,
+                            found: value,
+                        },
+                        start,
+                    ));
+                }
+                ArgumentValue::Double(value)
+            }
+            ArgumentType::Word => ArgumentValue::String(reader.read_unquoted_string().to_owned()),
+            ArgumentType::String => ArgumentValue::String(reader.read_string()?),
+            ArgumentType::GreedyString => ArgumentValue::String(reader.read_remaining().to_owned()),
+            ArgumentType::Entity {
+                single,
+                players_only,
+            } => {
+                let selector = EntitySelector::parse(reader)?;
+                if *players_only && selector.includes_entities() {
+                    return Err(reader.error_at(SyntaxErrorKind::PlayersOnly, start));
+                }
+                if *single && !selector.is_single() {
+                    let kind = if *players_only {
+                        SyntaxErrorKind::TooManyPlayers
+                    } else {
+                        SyntaxErrorKind::TooManyEntities
+                    };
+                    return Err(reader.error_at(kind, start));
+                }
+                ArgumentValue::Entity(selector)
+            }
+            ArgumentType::BlockPos => ArgumentValue::Coordinates(Coordinates::parse(reader, true)?),
+            ArgumentType::Vec3 => ArgumentValue::Coordinates(Coordinates::parse(reader, false)?),
+            ArgumentType::Identifier => {
+                let identifier = reader.read_word();
+                match Identifier::try_from(identifier.to_owned()) {
+                    Ok(identifier) => ArgumentValue::Identifier(identifier),
+                    Err(_) => {
+                        return Err(reader.error_at(
+                            SyntaxErrorKind::InvalidIdentifier(identifier.to_owned()),
+                            start,
+                        ))
+                    }
+                }
+            }
+            ArgumentType::Message => ArgumentValue::String(reader.read_remaining().to_owned()),
+            ArgumentType::GameMode => {
+                let name = reader.read_unquoted_string();
+                match game_mode_from_name(name) {
+                    Some(game_mode) => ArgumentValue::GameMode(game_mode),
+                    None => {
+                        return Err(reader
+                            .error_at(SyntaxErrorKind::InvalidGameMode(name.to_owned()), start))
+                    }
+                }
+            }
+        })
+    }
+
+    /// Suggests completions for `remaining`, the partially typed argument.
+    pub fn suggest(
+        &self,
+        server: &ServerContext,
+        sender: &dyn CommandSender,
+        remaining: &str,
+    ) -> Vec<Suggestion> {
+        let candidates: Vec<String> = match self {
+            ArgumentType::Bool => vec!["true".to_owned(), "false".to_owned()],
+            ArgumentType::Entity { players_only, .. } => {
+                let mut candidates: Vec<String> = server
+                    .world
+                    .players()
+                    .entries()
+                    .into_iter()
+                    .map(|entry| entry.profile.username)
+                    .collect();
+                candidates.extend(["@p", "@a", "@r", "@s"].map(str::to_owned));
+                if !players_only {
+                    candidates.push("@e".to_owned());
+                }
+                candidates
+            }
+            ArgumentType::BlockPos => match sender.position() {
+                Some(position) => {
+                    let block = position.block_position();
+                    vec![
+                        "~ ~ ~".to_owned(),
+                        format!("{} {} {}", block.x, block.y, block.z),
+                    ]
+                }
+                None => vec!["~ ~ ~".to_owned()],
+            },
+            ArgumentType::Vec3 => match sender.position() {
+                Some(position) => vec![
+                    "~ ~ ~".to_owned(),
+                    format!("{:.2} {:.2} {:.2}", position.x, position.y, position.z),
+                ],
+                None => vec!["~ ~ ~".to_owned()],
+            },
+            ArgumentType::GameMode => GAME_MODES
+                .iter()
+                .map(|(name, _)| (*name).to_owned())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|candidate| starts_with_ignore_case(candidate, remaining))
+            .map(Suggestion::new)
+            .collect()
+    }
+
+    /// The parser sent to clients in the command graph.
+    pub fn to_parser(&self) -> CommandParser<'static> {
+        match self {
+            ArgumentType::Bool => CommandParser::Bool,
+            ArgumentType::Integer { min, max } => CommandParser::Integer {
+                min: (*min != i32::MIN).then_some(*min),
+                max: (*max != i32::MAX).then_some(*max),
+            },
+            ArgumentType::Double { min, max } => CommandParser::Double {
+                min: (*min != f64::MIN).then_some(*min),
+                max: (*max != f64::MAX).then_some(*max),
+            },
+            ArgumentType::Word => CommandParser::String(StringParserKind::SingleWord),
+            ArgumentType::String => CommandParser::String(StringParserKind::QuotablePhrase),
+            ArgumentType::GreedyString => CommandParser::String(StringParserKind::GreedyPhrase),
+            ArgumentType::Entity {
+                single,
+                players_only,
+            } => CommandParser::Entity {
+                single: *single,
+                players_only: *players_only,
+            },
+            ArgumentType::BlockPos => CommandParser::BLOCK_POS,
+            ArgumentType::Vec3 => CommandParser::VEC3,
+            ArgumentType::Identifier => CommandParser::RESOURCE_LOCATION,
+            ArgumentType::Message => CommandParser::MESSAGE,
+            ArgumentType::GameMode => CommandParser::GAME_MODE,
+        }
+    }
+}
+
+/// A parsed argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    Bool(bool),
+    Integer(i32),
+    Double(f64),
+    String(String),
+    Entity(EntitySelector),
+    Coordinates(Coordinates),
+    Identifier(Identifier<'static>),
+    GameMode(GameMode),
+}
+
+const GAME_MODES: [(&str, GameMode); 4] = [
+    ("survival", GameMode::Survival),
+    ("creative", GameMode::Creative),
+    ("adventure", GameMode::Adventure),
+    ("spectator", GameMode::Spectator),
+];
+
+pub fn game_mode_from_name(name: &str) -> Option<GameMode> {
+    GAME_MODES
+        .iter()
+        .find(|(mode_name, _)| *mode_name == name)
+        .map(|(_, game_mode)| *game_mode)
+}
+
+pub fn game_mode_name(game_mode: GameMode) -> &'static str {
+    GAME_MODES
+        .iter()
+        .find(|(_, mode)| *mode == game_mode)
+        .map(|(name, _)| *name)
+        .unwrap()
+}
+
+pub(super) fn starts_with_ignore_case(candidate: &str, prefix: &str) -> bool {
+    candidate
+        .get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+/// A world coordinate, which may be relative to the sender's position (`~`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldCoordinate {
+    pub value: f64,
+    pub relative: bool,
+}
+
+impl WorldCoordinate {
+    pub fn resolve(&self, origin: f64) -> f64 {
+        if self.relative {
+            origin + self.value
+        } else {
+            self.value
+        }
+    }
+}
+
+/// Parsed coordinates, as used by block position and position arguments.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinates {
+    /// World coordinates, e.g. `1 ~2 3`.
+    World([WorldCoordinate; 3]),
+    /// Local coordinates (left, up, forwards) relative to the sender's rotation, e.g. `^ ^ ^5`.
+    Local([f64; 3]),
+}
+
+impl Coordinates {
+    fn parse(reader: &mut StringReader<'_>, block: bool) -> Result<Self, CommandSyntaxError> {
+        let start = reader.cursor();
+        if reader.peek() == Some('^') {
+            let mut values = [0.0; 3];
+            for (i, value) in values.iter_mut().enumerate() {
+                if i > 0 {
+                    Self::expect_separator(reader, start)?;
+                }
+                if reader.peek() != Some('^') {
+                    return Err(reader.error_at(SyntaxErrorKind::MixedCoordinates, start));
+                }
+                reader.skip();
+                *value = Self::read_offset(reader)?;
+            }
+            return Ok(Coordinates::Local(values));
+        }
+
+        let mut coordinates = [WorldCoordinate {
+            value: 0.0,
+            relative: false,
+        }; 3];
+        for (i, coordinate) in coordinates.iter_mut().enumerate() {
+            if i > 0 {
+                Self::expect_separator(reader, start)?;
+            }
+            match reader.peek() {
+                Some('^') => return Err(reader.error_at(SyntaxErrorKind::MixedCoordinates, start)),
+                Some('~') => {
+                    reader.skip();
+                    coordinate.relative = true;
+                    coordinate.value = Self::read_offset(reader)?;
+                }
+                _ if block => coordinate.value = reader.read_int()? as f64,
+                _ => {
+                    let value_start = reader.cursor();
+                    coordinate.value = reader.read_double()?;
+                    // Vanilla centers integer X and Z coordinates on the block.
+                    let is_integer = !reader.input()[value_start..reader.cursor()].contains('.');
+                    if is_integer && i != 1 {
+                        coordinate.value += 0.5;
+                    }
+                }
+            }
+        }
+
+        Ok(Coordinates::World(coordinates))
+    }
+
+    fn expect_separator(
+        reader: &mut StringReader<'_>,
+        start: usize,
+    ) -> Result<(), CommandSyntaxError> {
+        if reader.peek() != Some(ARGUMENT_SEPARATOR) {
+            return Err(reader.error_at(SyntaxErrorKind::IncompleteCoordinates, start));
+        }
+        reader.skip();
+        Ok(())
+    }
+
+    /// Reads the optional offset after `~` or `^`.
+    fn read_offset(reader: &mut StringReader<'_>) -> Result<f64, CommandSyntaxError> {
+        match reader.peek() {
+            None | Some(ARGUMENT_SEPARATOR) => Ok(0.0),
+            Some(_) => reader.read_double(),
+        }
+    }
+
+    /// Resolves the coordinates relative to `origin`, facing `rotation`.
+    pub fn resolve(&self, origin: Position, rotation: Rotation) -> Position {
+        match self {
+            Coordinates::World([x, y, z]) => Position::new(
+                x.resolve(origin.x),
+                y.resolve(origin.y),
+                z.resolve(origin.z),
+            ),
+            Coordinates::Local([left, up, forwards]) => {
+                let (yaw, pitch) = (
+                    (rotation.yaw as f64 + 90.0).to_radians(),
+                    (-rotation.pitch as f64).to_radians(),
+                );
+                let (up_yaw, up_pitch) = (yaw, (-rotation.pitch as f64 + 90.0).to_radians());
+                let forwards_axis = [
+                    yaw.cos() * pitch.cos(),
+                    pitch.sin(),
+                    yaw.sin() * pitch.cos(),
+                ];
+                let up_axis = [
+                    up_yaw.cos() * up_pitch.cos(),
+                    up_pitch.sin(),
+                    up_yaw.sin() * up_pitch.cos(),
+                ];
+                // Cross product of forwards and up, negated.
+                let left_axis = [
+                    -(forwards_axis[1] * up_axis[2] - forwards_axis[2] * up_axis[1]),
+                    -(forwards_axis[2] * up_axis[0] - forwards_axis[0] * up_axis[2]),
+                    -(forwards_axis[0] * up_axis[1] - forwards_axis[1] * up_axis[0]),
+                ];
+                let axis =
+                    |i: usize| forwards_axis[i] * forwards + up_axis[i] * up + left_axis[i] * left;
+                Position::new(origin.x + axis(0), origin.y + axis(1), origin.z + axis(2))
+            }
+        }
+    }
+
+    /// Resolves the coordinates relative to `origin` as a block position.
+    pub fn resolve_block(&self, origin: Position, rotation: Rotation) -> BlockPosition {
+        self.resolve(origin, rotation).block_position()
+    }
+}
+
+/// Selects entities by name, UUID or selector.
+///
+/// TODO: Only players exist, and selector arguments (`@e[...]`) are not supported yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntitySelector {
+    Name(String),
+    Uuid(Uuid),
+    /// `@p`, the nearest player.
+    NearestPlayer,
+    /// `@r`, a random player.
+    RandomPlayer,
+    /// `@a`, all players.
+    AllPlayers,
+    /// `@e`, all entities.
+    AllEntities,
+    /// `@s`, the sender itself.
+    Sender,
+}
+
+impl EntitySelector {
+    fn parse(reader: &mut StringReader<'_>) -> Result<Self, CommandSyntaxError> {
+        let start = reader.cursor();
+        if reader.peek() == Some('@') {
+            reader.skip();
+            let selector = match reader.peek() {
+                Some('p') => EntitySelector::NearestPlayer,
+                Some('r') => EntitySelector::RandomPlayer,
+                Some('a') => EntitySelector::AllPlayers,
+                Some('e') => EntitySelector::AllEntities,
+                Some('s') => EntitySelector::Sender,
+                _ => {
+                    let selector = reader.read_word().to_owned();
+                    return Err(reader.error_at(
+                        SyntaxErrorKind::UnknownSelectorType(format!("@{selector}")),
+                        start,
+                    ));
+                }
+            };
+            reader.skip();
+            if reader.peek() == Some('[') {
+                return Err(reader.error(SyntaxErrorKind::SelectorArgumentsNotSupported));
+            }
+            return Ok(selector);
+        }
+
+        let name = reader.read_word();
+        if let Ok(uuid) = Uuid::parse_str(name) {
+            return Ok(EntitySelector::Uuid(uuid));
+        }
+        let is_valid_name = !name.is_empty()
+            && name.len() <= MAX_PLAYER_NAME_LEN
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_valid_name {
+            return Err(reader.error_at(SyntaxErrorKind::InvalidEntity, start));
+        }
+
+        Ok(EntitySelector::Name(name.to_owned()))
+    }
+
+    /// Whether the selector can select entities other than players.
+    pub fn includes_entities(&self) -> bool {
+        matches!(self, EntitySelector::AllEntities)
+    }
+
+    /// Whether the selector can only select at most one entity.
+    pub fn is_single(&self) -> bool {
+        !matches!(
+            self,
+            EntitySelector::AllPlayers | EntitySelector::AllEntities
+        )
+    }
+
+    /// Finds the players selected by the selector.
+    pub fn resolve_players(
+        &self,
+        server: &ServerContext,
+        sender: &dyn CommandSender,
+    ) -> Result<Vec<PlayerEntry>, CommandError> {
+        let players = server.world.players().entries();
+        let selected: Vec<_> = match self {
+            EntitySelector::Name(name) => players
+                .into_iter()
+                .filter(|entry| entry.profile.username.eq_ignore_ascii_case(name))
+                .collect(),
+            EntitySelector::Uuid(uuid) => players
+                .into_iter()
+                .filter(|entry| entry.profile.uuid == *uuid)
+                .collect(),
+            EntitySelector::NearestPlayer => {
+                let origin = sender.position().unwrap_or_default();
+                let distance_squared = |entry: &PlayerEntry| {
+                    (entry.position.x - origin.x).powi(2)
+                        + (entry.position.y - origin.y).powi(2)
+                        + (entry.position.z - origin.z).powi(2)
+                };
+                players
+                    .into_iter()
+                    .min_by(|a, b| distance_squared(a).total_cmp(&distance_squared(b)))
+                    .into_iter()
+                    .collect()
+            }
+            EntitySelector::RandomPlayer => players
+                .into_iter()
+                .choose(&mut rand::thread_rng())
+                .into_iter()
+                .collect(),
+            EntitySelector::AllPlayers | EntitySelector::AllEntities => players,
+            EntitySelector::Sender => match sender.entity_id() {
+                Some(entity_id) => players
+                    .into_iter()
+                    .filter(|entry| entry.entity_id == entity_id)
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+
+        if selected.is_empty() {
+            return Err(CommandError::NoPlayersFound);
+        }
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(
+        argument_type: &ArgumentType,
+        input: &str,
+    ) -> Result<ArgumentValue, CommandSyntaxError> {
+        let mut reader = StringReader::new(input);
+        let value = argument_type.parse(&mut reader)?;
+        assert!(!reader.can_read(), "{input} should be read entirely");
+        Ok(value)
+    }
+
+    fn parse_error(argument_type: &ArgumentType, input: &str) -> (SyntaxErrorKind, usize) {
+        let error = argument_type
+            .parse(&mut StringReader::new(input))
+            .unwrap_err();
+        (error.kind, error.cursor)
+    }
+
+    fn world(coordinates: [(f64, bool); 3]) -> ArgumentValue {
+        ArgumentValue::Coordinates(Coordinates::World(
+            coordinates.map(|(value, relative)| WorldCoordinate { value, relative }),
+        ))
+    }
+
+    #[test]
+    fn numbers() {
+        let percentage = ArgumentType::Integer { min: 0, max: 100 };
+        assert_eq!(parse(&percentage, "42"), Ok(ArgumentValue::Integer(42)));
+        assert_eq!(
+            parse_error(&percentage, "101"),
+            (
+                SyntaxErrorKind::IntegerTooHigh {
+                    max: 100,
+                    found: 101
+                },
+                0
+            )
+        );
+        assert_eq!(
+            parse_error(&percentage, "-1"),
+            (SyntaxErrorKind::IntegerTooLow { min: 0, found: -1 }, 0)
+        );
+
+        let scale = ArgumentType::Double { min: 0.5, max: 2.0 };
+        assert_eq!(parse(&scale, "1.25"), Ok(ArgumentValue::Double(1.25)));
+        assert_eq!(
+            parse_error(&scale, "0.25"),
+            (
+                SyntaxErrorKind::DoubleTooLow {
+                    min: 0.5,
+                    found: 0.25
+                },
+                0
+            )
+        );
+        assert_eq!(
+            parse(&ArgumentType::double(), "-3"),
+            Ok(ArgumentValue::Double(-3.0))
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let mut reader = StringReader::new("hello world");
+        assert_eq!(
+            ArgumentType::Word.parse(&mut reader),
+            Ok(ArgumentValue::String("hello".to_owned()))
+        );
+        assert_eq!(
+            parse(&ArgumentType::String, r#""hello world""#),
+            Ok(ArgumentValue::String("hello world".to_owned()))
+        );
+        assert_eq!(
+            parse(&ArgumentType::GreedyString, "hello \"world\""),
+            Ok(ArgumentValue::String("hello \"world\"".to_owned()))
+        );
+        assert_eq!(
+            parse(&ArgumentType::Message, "hi all"),
+            Ok(ArgumentValue::String("hi all".to_owned()))
+        );
+    }
+
+    #[test]
+    fn entities() {
+        let player = ArgumentType::player();
+        assert_eq!(
+            parse(&player, "Notch"),
+            Ok(ArgumentValue::Entity(EntitySelector::Name(
+                "Notch".to_owned()
+            )))
+        );
+        assert_eq!(
+            parse(&player, "069a79f4-44e9-4726-a5be-fca90e38aaf5"),
+            Ok(ArgumentValue::Entity(EntitySelector::Uuid(
+                "069a79f4-44e9-4726-a5be-fca90e38aaf5".parse().unwrap()
+            )))
+        );
+        assert_eq!(
+            parse(&player, "@s"),
+            Ok(ArgumentValue::Entity(EntitySelector::Sender))
+        );
+        assert_eq!(
+            parse(&ArgumentType::players(), "@a"),
+            Ok(ArgumentValue::Entity(EntitySelector::AllPlayers))
+        );
+
+        assert_eq!(
+            parse_error(&player, "@a"),
+            (SyntaxErrorKind::TooManyPlayers, 0)
+        );
+        assert_eq!(
+            parse_error(&player, "@e"),
+            (SyntaxErrorKind::PlayersOnly, 0)
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::players(), "@e"),
+            (SyntaxErrorKind::PlayersOnly, 0)
+        );
+        let entities = ArgumentType::Entity {
+            single: false,
+            players_only: false,
+        };
+        assert_eq!(
+            parse(&entities, "@e"),
+            Ok(ArgumentValue::Entity(EntitySelector::AllEntities))
+        );
+        let entity = ArgumentType::Entity {
+            single: true,
+            players_only: false,
+        };
+        assert_eq!(
+            parse_error(&entity, "@e"),
+            (SyntaxErrorKind::TooManyEntities, 0)
+        );
+
+        assert_eq!(
+            parse_error(&player, "@x"),
+            (SyntaxErrorKind::UnknownSelectorType("@x".to_owned()), 0)
+        );
+        assert_eq!(
+            parse_error(&player, "@p[distance=..5]"),
+            (SyntaxErrorKind::SelectorArgumentsNotSupported, 2)
+        );
+        assert_eq!(
+            parse_error(&player, "Not-a-name"),
+            (SyntaxErrorKind::InvalidEntity, 0)
+        );
+        assert_eq!(
+            parse_error(&player, "SeventeenLetters_"),
+            (SyntaxErrorKind::InvalidEntity, 0)
+        );
+    }
+
+    #[test]
+    fn coordinates() {
+        assert_eq!(
+            parse(&ArgumentType::Vec3, "~ ~1.5 ~-2"),
+            Ok(world([(0.0, true), (1.5, true), (-2.0, true)]))
+        );
+        // Integer X and Z coordinates are centered on the block.
+        assert_eq!(
+            parse(&ArgumentType::Vec3, "1 64 -3.25"),
+            Ok(world([(1.5, false), (64.0, false), (-3.25, false)]))
+        );
+        assert_eq!(
+            parse(&ArgumentType::BlockPos, "1 ~ -3"),
+            Ok(world([(1.0, false), (0.0, true), (-3.0, false)]))
+        );
+        assert_eq!(
+            parse(&ArgumentType::Vec3, "^ ^1 ^-2"),
+            Ok(ArgumentValue::Coordinates(Coordinates::Local([
+                0.0, 1.0, -2.0
+            ])))
+        );
+
+        assert_eq!(
+            parse_error(&ArgumentType::BlockPos, "1 2"),
+            (SyntaxErrorKind::IncompleteCoordinates, 0)
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::Vec3, "~ ^ ~"),
+            (SyntaxErrorKind::MixedCoordinates, 0)
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::Vec3, "^ ~ ^"),
+            (SyntaxErrorKind::MixedCoordinates, 0)
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::BlockPos, "1 2.5 3"),
+            (SyntaxErrorKind::InvalidInteger("2.5".to_owned()), 2)
+        );
+    }
+
+    #[test]
+    fn resolves_coordinates() {
+        let origin = Position::new(10.0, 64.0, -5.0);
+        let ArgumentValue::Coordinates(relative) = parse(&ArgumentType::Vec3, "~1 ~ 3").unwrap()
+        else {
+            unreachable!()
+        };
+        assert_eq!(
+            relative.resolve(origin, Rotation::new(0.0, 0.0)),
+            Position::new(11.0, 64.0, 3.5)
+        );
+        assert_eq!(
+            relative.resolve_block(origin, Rotation::new(0.0, 0.0)),
+            BlockPosition::new(11, 64, 3)
+        );
+
+        // Facing south (+Z), left is east (+X).
+        let local = Coordinates::Local([1.0, 2.0, 3.0]);
+        let position = local.resolve(origin, Rotation::new(0.0, 0.0));
+        assert!((position.x - 11.0).abs() < 1e-9);
+        assert!((position.y - 66.0).abs() < 1e-9);
+        assert!((position.z - -2.0).abs() < 1e-9);
+        // Facing west (-X), forwards is -X.
+        let forwards = Coordinates::Local([0.0, 0.0, 1.0]);
+        let position = forwards.resolve(origin, Rotation::new(90.0, 0.0));
+        assert!((position.x - 9.0).abs() < 1e-9);
+        assert!((position.z - -5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn identifiers_and_game_modes() {
+        assert_eq!(
+            parse(&ArgumentType::Identifier, "minecraft:stone"),
+            Ok(ArgumentValue::Identifier(
+                Identifier::from_string("minecraft:stone").unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::Identifier, "Not:Valid"),
+            (
+                SyntaxErrorKind::InvalidIdentifier("Not:Valid".to_owned()),
+                0
+            )
+        );
+
+        assert_eq!(
+            parse(&ArgumentType::GameMode, "creative"),
+            Ok(ArgumentValue::GameMode(GameMode::Creative))
+        );
+        assert_eq!(
+            parse_error(&ArgumentType::GameMode, "hardcore"),
+            (SyntaxErrorKind::InvalidGameMode("hardcore".to_owned()), 0)
+        );
+        for (name, game_mode) in GAME_MODES {
+            assert_eq!(game_mode_from_name(name), Some(game_mode));
+            assert_eq!(game_mode_name(game_mode), name);
+        }
+    }
+
+    #[test]
+    fn parsers() {
+        assert_eq!(
+            ArgumentType::integer().to_parser(),
+            CommandParser::Integer {
+                min: None,
+                max: None
+            }
+        );
+        assert_eq!(
+            ArgumentType::Integer { min: 1, max: 64 }.to_parser(),
+            CommandParser::Integer {
+                min: Some(1),
+                max: Some(64)
+            }
+        );
+        assert_eq!(
+            ArgumentType::Double {
+                min: 0.0,
+                max: f64::MAX
+            }
+            .to_parser(),
+            CommandParser::Double {
+                min: Some(0.0),
+                max: None
+            }
+        );
+        assert_eq!(
+            ArgumentType::players().to_parser(),
+            CommandParser::Entity {
+                single: false,
+                players_only: true
+            }
+        );
+        assert_eq!(
+            ArgumentType::GreedyString.to_parser(),
+            CommandParser::String(StringParserKind::GreedyPhrase)
+        );
+        assert_eq!(ArgumentType::Vec3.to_parser(), CommandParser::VEC3);
+        assert_eq!(ArgumentType::Message.to_parser(), CommandParser::MESSAGE);
+    }
+}
@@ -0,0 +1,265 @@
+//! Commands every server has.
+
+use protocol::text::{TextColor, TextComponent};
+
+use crate::world::Position;
+
+use super::{argument, literal, ArgumentType, CommandContext, CommandDispatcher, CommandError};
+
+/// Permission level required for commands that affect other players, same as vanilla.
+const GAME_MASTER_LEVEL: u8 = 2;
+/// Permission level required for commands that manage the server, same as vanilla.
+const OWNER_LEVEL: u8 = 4;
+
+/// Registers the built-in commands.
+pub fn register(dispatcher: &mut CommandDispatcher) {
+    dispatcher.register(literal("help").executes(|ctx| help(ctx, None)).then(
+        argument("command", ArgumentType::GreedyString).executes(|ctx| {
+            let command = ctx.string("command").unwrap_or_default().to_owned();
+            help(ctx, Some(&command))
+        }),
+    ));
+
+    dispatcher.register(literal("list").executes(list));
+
+    dispatcher.register(literal("stop").requires_level(OWNER_LEVEL).executes(stop));
+    dispatcher.register(
+        literal("reload")
+            .requires_level(OWNER_LEVEL)
+            .executes(reload),
+    );
+    dispatcher.register(
+        literal("save-all")
+            .requires_level(OWNER_LEVEL)
+            .executes(save_all),
+    );
+
+    dispatcher.register(
+        literal("say")
+            .requires_level(GAME_MASTER_LEVEL)
+            .then(argument("message", ArgumentType::Message).executes(say)),
+    );
+
+    let teleport = dispatcher.register(
+        literal("teleport")
+            .requires_level(GAME_MASTER_LEVEL)
+            .then(argument("location", ArgumentType::Vec3).executes(teleport_to_location))
+            .then(argument("destination", ArgumentType::player()).executes(teleport_to_player)),
+    );
+    dispatcher.register(
+        literal("tp")
+            .requires_level(GAME_MASTER_LEVEL)
+            .redirect(teleport),
+    );
+}
+
+fn help(ctx: &mut CommandContext<'_>, command: Option<&str>) -> Result<i32, CommandError> {
+    let usage: Vec<_> = ctx
+        .server
+        .commands
+        .usage(&*ctx.sender)
+        .into_iter()
+        .filter(|usage| command.map_or(true, |command| usage.split(' ').next() == Some(command)))
+        .collect();
+    if usage.is_empty() {
+        return Err(CommandError::Failed(
+            "Unknown command or insufficient permissions".to_owned(),
+        ));
+    }
+
+    for usage in &usage {
+        ctx.send_message(&TextComponent::text(format!("/{usage}")));
+    }
+    Ok(usage.len() as i32)
+}
+
+fn list(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let players = ctx.server.world.players().entries();
+    let names: Vec<_> = players
+        .iter()
+        .map(|entry| entry.profile.username.as_str())
+        .collect();
+
+    ctx.send_message(&TextComponent::text(format!(
+        "There are {} players online: {}",
+        names.len(),
+        names.join(", ")
+    )));
+    Ok(players.len() as i32)
+}
+
+fn stop(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    ctx.send_message(&TextComponent::translatable("commands.stop.stopping", []));
+    ctx.server.request_shutdown();
+    Ok(1)
+}
+
+fn reload(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let reload = ctx.server.config.reload().map_err(|err| {
+        CommandError::Failed(format!("Unable to reload the configuration: {err}"))
+    })?;
+
+    if let Some(permissions) = ctx.server.commands.permission_provider() {
+        permissions.reload().map_err(|err| {
+            CommandError::Failed(format!("Unable to reload the permissions: {err}"))
+        })?;
+        // Permissions decide which commands players see.
+        for entry in ctx.server.world.players().entries() {
+            entry.send_packet(&ctx.server.commands.commands_packet(&entry));
+        }
+    }
+
+    ctx.send_message(&TextComponent::text("Reloaded the configuration"));
+    if !reload.pending_restart.is_empty() {
+        let pending = format!(
+            "Changes to {} take effect after a restart",
+            reload.pending_restart.join(", ")
+        );
+        ctx.send_message(&TextComponent::text(pending).color(TextColor::Yellow));
+    }
+    Ok(1)
+}
+
+fn save_all(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    ctx.send_message(&TextComponent::translatable("commands.save.saving", []));
+    ctx.server
+        .world
+        .save_chunks()
+        .map_err(|err| CommandError::Failed(format!("Unable to save the world: {err}")))?;
+    ctx.send_message(&TextComponent::translatable("commands.save.success", []));
+    Ok(1)
+}
+
+fn say(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let message = ctx.string("message").unwrap_or_default();
+    let announcement = TextComponent::translatable(
+        "chat.type.announcement",
+        [
+            TextComponent::text(ctx.sender.name()),
+            TextComponent::text(message),
+        ],
+    );
+
+    tracing::info!("[{}] {}", ctx.sender.name(), message);
+    ctx.server
+        .world
+        .players()
+        .broadcast_system_message(&announcement);
+    Ok(1)
+}
+
+fn teleport_to_location(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let (origin, rotation) = ctx.origin();
+    let location = ctx
+        .coordinates("location")
+        .ok_or(CommandError::Failed("Missing location".to_owned()))?
+        .resolve(origin, rotation);
+    teleport(ctx, location)
+}
+
+fn teleport_to_player(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let destination = ctx
+        .players("destination")?
+        .into_iter()
+        .next()
+        .ok_or(CommandError::NoPlayersFound)?;
+    teleport(ctx, destination.position)
+}
+
+/// Teleports the sender to `position`.
+fn teleport(ctx: &mut CommandContext<'_>, position: Position) -> Result<i32, CommandError> {
+    let server = ctx.server;
+    let player = ctx.player()?;
+    player
+        .teleport(position, player.rotation())
+        .and_then(|()| player.update_chunk_view(server))
+        .map_err(|err| CommandError::Failed(err.to_string()))?;
+    server.world.players().update_movement(
+        player.entity_id(),
+        position,
+        player.rotation(),
+        player.on_ground(),
+    );
+
+    let coordinate = |value: f64| TextComponent::text(format!("{value:.2}"));
+    let feedback = TextComponent::translatable(
+        "commands.teleport.success.location.single",
+        [
+            TextComponent::text(player.username().to_owned()),
+            coordinate(position.x),
+            coordinate(position.y),
+            coordinate(position.z),
+        ],
+    )
+    .color(TextColor::Gray);
+    ctx.send_message(&feedback);
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{
+        tests::{test_server, TestSender},
+        SyntaxErrorKind,
+    };
+
+    use super::*;
+
+    #[test]
+    fn help_lists_usable_commands() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+
+        let mut sender = TestSender::new(0);
+        let result = server.commands.execute(&server, &mut sender, "help");
+        assert_eq!(result, Ok(3));
+        assert_eq!(sender.messages, ["/help", "/help <command>", "/list"]);
+
+        let mut sender = TestSender::new(OWNER_LEVEL);
+        let result = server.commands.execute(&server, &mut sender, "help tp");
+        assert_eq!(result, Ok(1));
+        assert_eq!(sender.messages, ["/tp -> teleport"]);
+        let result = server
+            .commands
+            .execute(&server, &mut sender, "help unknown");
+        assert!(matches!(result, Err(CommandError::Failed(_))));
+    }
+
+    #[test]
+    fn list_and_stop() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let mut sender = TestSender::new(OWNER_LEVEL);
+
+        let result = server.commands.execute(&server, &mut sender, "list");
+        assert_eq!(result, Ok(0));
+        assert_eq!(sender.messages, ["There are 0 players online: "]);
+
+        assert!(!server.is_shutdown_requested());
+        assert_eq!(server.commands.execute(&server, &mut sender, "stop"), Ok(1));
+        assert!(server.is_shutdown_requested());
+    }
+
+    #[test]
+    fn teleport_requires_player() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let mut sender = TestSender::new(GAME_MASTER_LEVEL);
+
+        assert_eq!(
+            server.commands.execute(&server, &mut sender, "tp 0 64 0"),
+            Err(CommandError::RequiresPlayer)
+        );
+        assert_eq!(
+            server
+                .commands
+                .execute(&server, &mut sender, "teleport Notch"),
+            Err(CommandError::NoPlayersFound)
+        );
+        let result = server.commands.execute(&server, &mut sender, "tp ~ ~");
+        assert!(matches!(
+            result,
+            Err(CommandError::Syntax(error)) if error.kind == SyntaxErrorKind::IncompleteCoordinates
+        ));
+    }
+}
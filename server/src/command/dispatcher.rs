@@ -0,0 +1,682 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use packet::client::{CommandNodeType, CommandsPacket};
+use protocol::{identifier::Identifier, VarInt};
+
+use crate::{
+    context::ServerContext,
+    permission::{PermissionProvider, COMMAND_PERMISSION_PREFIX},
+};
+
+use super::{
+    argument::starts_with_ignore_case,
+    node::{CommandNode, NodeKind},
+    reader::{StringReader, ARGUMENT_SEPARATOR},
+    ArgumentValue, CommandBuilder, CommandContext, CommandError, CommandSender, CommandSyntaxError,
+    NodeId, Suggestion, Suggestions, SyntaxErrorKind,
+};
+
+const ROOT: NodeId = NodeId(0);
+
+/// Suggestion provider telling clients to request suggestions from the server.
+const ASK_SERVER_SUGGESTIONS: &str = "minecraft:ask_server";
+
+/// A command successfully parsed up to a node.
+struct ParsedCommand {
+    node: NodeId,
+    arguments: HashMap<String, ArgumentValue>,
+}
+
+/// Parses and executes commands, and serializes them for clients.
+#[derive(Debug)]
+pub struct CommandDispatcher {
+    /// All nodes of the command graph, the first one being the root.
+    nodes: Vec<CommandNode>,
+    /// Decides node permissions, or `None` to only check requirements.
+    permissions: Option<Arc<dyn PermissionProvider>>,
+}
+
+impl CommandDispatcher {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![CommandNode::new(NodeKind::Root)],
+            permissions: None,
+        }
+    }
+
+    pub fn permission_provider(&self) -> Option<&Arc<dyn PermissionProvider>> {
+        self.permissions.as_ref()
+    }
+
+    pub fn set_permission_provider(&mut self, permissions: Arc<dyn PermissionProvider>) {
+        self.permissions = Some(permissions);
+    }
+
+    /// Whether `sender` has `permission`, or `None` if the permission provider doesn't decide
+    /// it, e.g. because the sender isn't a player.
+    pub fn has_permission(&self, sender: &dyn CommandSender, permission: &str) -> Option<bool> {
+        let profile = sender.profile()?;
+        self.permissions.as_ref()?.permission(profile, permission)
+    }
+
+    /// Whether `sender` may use `node`, checking its permission and then its requirement.
+    fn can_use(&self, node: &CommandNode, sender: &dyn CommandSender) -> bool {
+        node.permission
+            .as_deref()
+            .and_then(|permission| self.has_permission(sender, permission))
+            .unwrap_or_else(|| node.can_use(sender))
+    }
+
+    pub fn root(&self) -> NodeId {
+        ROOT
+    }
+
+    pub fn node(&self, id: NodeId) -> &CommandNode {
+        &self.nodes[id.0]
+    }
+
+    /// Registers a command, merging it with already registered nodes of the same name.
+    ///
+    /// Returns the command's node, e.g. to [redirect](CommandBuilder::redirect) aliases to.
+    pub fn register(&mut self, command: CommandBuilder) -> NodeId {
+        self.insert(ROOT, command)
+    }
+
+    fn insert(&mut self, parent: NodeId, builder: CommandBuilder) -> NodeId {
+        let (mut node, children) = builder.into_parts();
+        if let (ROOT, NodeKind::Literal(name), None) = (parent, &node.kind, &node.permission) {
+            node.permission = Some(format!("{COMMAND_PERMISSION_PREFIX}{name}"));
+        }
+
+        let existing = self.nodes[parent.0].children.iter().copied().find(|&id| {
+            let kind = &self.nodes[id.0].kind;
+            std::mem::discriminant(kind) == std::mem::discriminant(&node.kind)
+                && kind.name() == node.kind.name()
+        });
+        let id = match existing {
+            Some(id) => {
+                let existing = &mut self.nodes[id.0];
+                existing.kind = node.kind;
+                if node.executor.is_some() {
+                    existing.executor = node.executor;
+                }
+                if node.requirement.is_some() {
+                    existing.requirement = node.requirement;
+                }
+                if node.permission.is_some() {
+                    existing.permission = node.permission;
+                }
+                if node.redirect.is_some() {
+                    existing.redirect = node.redirect;
+                }
+                id
+            }
+            None => {
+                let id = NodeId(self.nodes.len());
+                self.nodes.push(node);
+                self.nodes[parent.0].children.push(id);
+                id
+            }
+        };
+
+        for child in children {
+            self.insert(id, child);
+        }
+
+        id
+    }
+
+    /// Parses and executes `input`, a command without the leading slash.
+    pub fn execute(
+        &self,
+        server: &ServerContext,
+        sender: &mut dyn CommandSender,
+        input: &str,
+    ) -> Result<i32, CommandError> {
+        let mut reader = StringReader::new(input);
+        let parsed = self.parse_children(ROOT, &mut reader, &*sender, HashMap::new())?;
+
+        let Some(executor) = &self.node(parsed.node).executor else {
+            return Err(reader
+                .error_at(SyntaxErrorKind::UnknownCommand, input.len())
+                .into());
+        };
+
+        let mut context = CommandContext::new(server, sender, input, parsed.arguments);
+        executor(&mut context)
+    }
+
+    /// Parses the rest of `reader` with the children of `node`, returning the last parsed node.
+    fn parse_children(
+        &self,
+        node: NodeId,
+        reader: &mut StringReader<'_>,
+        sender: &dyn CommandSender,
+        arguments: HashMap<String, ArgumentValue>,
+    ) -> Result<ParsedCommand, CommandSyntaxError> {
+        let start = reader.cursor();
+        let mut error: Option<CommandSyntaxError> = None;
+
+        for child_id in self.relevant_children(node, reader) {
+            let child = self.node(child_id);
+            if !self.can_use(child, sender) {
+                continue;
+            }
+
+            let mut child_reader = reader.clone();
+            let mut child_arguments = arguments.clone();
+            let result =
+                parse_node(child, &mut child_reader, &mut child_arguments).and_then(|_| {
+                    if !child_reader.can_read() {
+                        return Ok(ParsedCommand {
+                            node: child_id,
+                            arguments: child_arguments,
+                        });
+                    }
+
+                    child_reader.skip();
+                    let next = child.redirect.unwrap_or(child_id);
+                    self.parse_children(next, &mut child_reader, sender, child_arguments)
+                });
+
+            match result {
+                Ok(parsed) => {
+                    *reader = child_reader;
+                    return Ok(parsed);
+                }
+                // Report the error from the child that got the furthest.
+                Err(err)
+                    if error
+                        .as_ref()
+                        .map_or(true, |error| err.cursor > error.cursor) =>
+                {
+                    error = Some(err);
+                }
+                Err(_) => {}
+            }
+        }
+
+        Err(error.unwrap_or_else(|| {
+            let kind = if node == ROOT {
+                SyntaxErrorKind::UnknownCommand
+            } else {
+                SyntaxErrorKind::UnknownArgument
+            };
+            reader.error_at(kind, start)
+        }))
+    }
+
+    /// The children of `node` worth trying: the literal matching the next word if there is one,
+    /// otherwise the arguments.
+    fn relevant_children(&self, node: NodeId, reader: &StringReader<'_>) -> Vec<NodeId> {
+        let children = &self.node(node).children;
+        let word = reader.clone().read_word();
+
+        let literal = children
+            .iter()
+            .copied()
+            .find(|&id| matches!(&self.node(id).kind, NodeKind::Literal(name) if name == word));
+        match literal {
+            Some(literal) => vec![literal],
+            None => children
+                .iter()
+                .copied()
+                .filter(|&id| matches!(self.node(id).kind, NodeKind::Argument { .. }))
+                .collect(),
+        }
+    }
+
+    /// Suggests completions for the end of `input`, a command without the leading slash.
+    pub fn suggest(
+        &self,
+        server: &ServerContext,
+        sender: &dyn CommandSender,
+        input: &str,
+    ) -> Suggestions {
+        let mut suggestions = Suggestions::default();
+        self.collect_suggestions(
+            ROOT,
+            &StringReader::new(input),
+            server,
+            sender,
+            &mut suggestions,
+        );
+
+        suggestions.matches.sort_by(|a, b| a.text.cmp(&b.text));
+        suggestions.matches.dedup_by(|a, b| a.text == b.text);
+        suggestions
+    }
+
+    fn collect_suggestions(
+        &self,
+        node: NodeId,
+        reader: &StringReader<'_>,
+        server: &ServerContext,
+        sender: &dyn CommandSender,
+        suggestions: &mut Suggestions,
+    ) {
+        let start = reader.cursor();
+        let remaining = reader.remaining();
+
+        for &child_id in &self.node(node).children {
+            let child = self.node(child_id);
+            if !self.can_use(child, sender) {
+                continue;
+            }
+
+            // Fully typed nodes are skipped, suggesting their children instead.
+            let mut child_reader = reader.clone();
+            if parse_node(child, &mut child_reader, &mut HashMap::new()).is_ok()
+                && child_reader.peek() == Some(ARGUMENT_SEPARATOR)
+            {
+                child_reader.skip();
+                let next = child.redirect.unwrap_or(child_id);
+                self.collect_suggestions(next, &child_reader, server, sender, suggestions);
+                continue;
+            }
+
+            let matches = match &child.kind {
+                NodeKind::Root => Vec::new(),
+                NodeKind::Literal(name) if starts_with_ignore_case(name, remaining) => {
+                    vec![Suggestion::new(name.clone())]
+                }
+                NodeKind::Literal(_) => Vec::new(),
+                NodeKind::Argument {
+                    suggestions: Some(provider),
+                    ..
+                } => provider(server, sender, remaining),
+                NodeKind::Argument { argument_type, .. } => {
+                    argument_type.suggest(server, sender, remaining)
+                }
+            };
+
+            if matches.is_empty() {
+                continue;
+            }
+            // Keep the suggestions for the furthest argument only.
+            if suggestions.matches.is_empty() || start > suggestions.start {
+                suggestions.start = start;
+                suggestions.matches = matches;
+            } else if start == suggestions.start {
+                suggestions.matches.extend(matches);
+            }
+        }
+    }
+
+    /// Builds the command graph sent to clients, with the nodes `sender` may use.
+    pub fn commands_packet(&self, sender: &dyn CommandSender) -> CommandsPacket<'static> {
+        // Number the usable nodes breadth-first.
+        let mut indices = HashMap::from([(ROOT, 0)]);
+        let mut order = vec![ROOT];
+        let mut queue = VecDeque::from([ROOT]);
+        while let Some(id) = queue.pop_front() {
+            let node = self.node(id);
+            for &child in node.children.iter().chain(&node.redirect) {
+                if indices.contains_key(&child) || !self.can_use(self.node(child), sender) {
+                    continue;
+                }
+                indices.insert(child, order.len() as i32);
+                order.push(child);
+                queue.push_back(child);
+            }
+        }
+
+        let nodes: Vec<_> = order
+            .iter()
+            .map(|&id| {
+                let node = self.node(id);
+                let node_type = match &node.kind {
+                    NodeKind::Root => CommandNodeType::Root,
+                    NodeKind::Literal(name) => CommandNodeType::Literal {
+                        name: name.clone().into(),
+                    },
+                    NodeKind::Argument {
+                        name,
+                        argument_type,
+                        suggestions,
+                    } => CommandNodeType::Argument {
+                        name: name.clone().into(),
+                        parser: argument_type.to_parser(),
+                        suggestions: suggestions
+                            .as_ref()
+                            .map(|_| Identifier::from_string(ASK_SERVER_SUGGESTIONS).unwrap()),
+                    },
+                };
+
+                packet::client::CommandNode {
+                    node_type,
+                    executable: node.executor.is_some(),
+                    children: node
+                        .children
+                        .iter()
+                        .filter_map(|child| indices.get(child))
+                        .map(|&index| VarInt(index))
+                        .collect::<Vec<_>>()
+                        .into(),
+                    redirect: node
+                        .redirect
+                        .and_then(|redirect| indices.get(&redirect).copied()),
+                }
+            })
+            .collect();
+
+        CommandsPacket {
+            nodes: nodes.into(),
+            root_index: 0,
+        }
+    }
+
+    /// Lists the usage of all commands `sender` may use, e.g. `tp <location>`.
+    pub fn usage(&self, sender: &dyn CommandSender) -> Vec<String> {
+        let mut usage = Vec::new();
+        for &child in &self.node(ROOT).children {
+            self.collect_usage(child, String::new(), sender, &mut usage);
+        }
+        usage
+    }
+
+    fn collect_usage(
+        &self,
+        id: NodeId,
+        prefix: String,
+        sender: &dyn CommandSender,
+        usage: &mut Vec<String>,
+    ) {
+        let node = self.node(id);
+        if !self.can_use(node, sender) {
+            return;
+        }
+
+        let usage_part = match &node.kind {
+            NodeKind::Root => return,
+            NodeKind::Literal(name) => name.clone(),
+            NodeKind::Argument { name, .. } => format!("<{name}>"),
+        };
+        let prefix = prefix + &usage_part;
+
+        if let Some(redirect) = node.redirect {
+            let target = match &self.node(redirect).kind {
+                NodeKind::Literal(name) => name.as_str(),
+                _ => "...",
+            };
+            usage.push(format!("{prefix} -> {target}"));
+            return;
+        }
+
+        if node.executor.is_some() {
+            usage.push(prefix.clone());
+        }
+        for &child in &node.children {
+            self.collect_usage(child, format!("{prefix} "), sender, usage);
+        }
+    }
+}
+
+impl Default for CommandDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a single node, checking that it is followed by a separator or the end of the input.
+fn parse_node(
+    node: &CommandNode,
+    reader: &mut StringReader<'_>,
+    arguments: &mut HashMap<String, ArgumentValue>,
+) -> Result<(), CommandSyntaxError> {
+    let start = reader.cursor();
+    match &node.kind {
+        NodeKind::Root => {}
+        NodeKind::Literal(name) => {
+            if reader.read_word() != name {
+                return Err(reader.error_at(SyntaxErrorKind::UnknownArgument, start));
+            }
+        }
+        NodeKind::Argument {
+            name,
+            argument_type,
+            ..
+        } => {
+            let value = argument_type.parse(reader)?;
+            arguments.insert(name.clone(), value);
+        }
+    }
+
+    if reader.can_read() && reader.peek() != Some(ARGUMENT_SEPARATOR) {
+        return Err(reader.error(SyntaxErrorKind::ExpectedArgumentSeparator));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use packet::client::CommandParser;
+
+    use crate::command::{
+        argument, literal,
+        tests::{test_server, TestSender},
+        ArgumentType, Suggestion,
+    };
+
+    use super::*;
+
+    /// A dispatcher with a `give <target> <count>` command for level 2, aliased as `g`, and a
+    /// `ping [<times>]` command for everyone.
+    fn dispatcher() -> CommandDispatcher {
+        let mut dispatcher = CommandDispatcher::new();
+        let give = dispatcher.register(
+            literal("give").requires_level(2).then(
+                argument("target", ArgumentType::player()).then(
+                    argument("count", ArgumentType::Integer { min: 1, max: 64 })
+                        .executes(|ctx| Ok(ctx.integer("count").unwrap())),
+                ),
+            ),
+        );
+        dispatcher.register(literal("g").requires_level(2).redirect(give));
+        dispatcher.register(
+            literal("ping").executes(|_| Ok(0)).then(
+                argument("times", ArgumentType::Word)
+                    .suggests(|_, _, _| vec![Suggestion::new("1"), Suggestion::new("10")])
+                    .executes(|ctx| Ok(ctx.string("times").unwrap().len() as i32)),
+            ),
+        );
+        dispatcher
+    }
+
+    fn execute(
+        dispatcher: &CommandDispatcher,
+        level: u8,
+        input: &str,
+    ) -> Result<i32, CommandError> {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        dispatcher.execute(&server, &mut TestSender::new(level), input)
+    }
+
+    fn syntax_error(result: Result<i32, CommandError>) -> (SyntaxErrorKind, usize) {
+        match result {
+            Err(CommandError::Syntax(error)) => (error.kind, error.cursor),
+            result => panic!("expected a syntax error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn executes() {
+        let dispatcher = dispatcher();
+        assert_eq!(execute(&dispatcher, 2, "give Notch 5"), Ok(5));
+        assert_eq!(execute(&dispatcher, 0, "ping"), Ok(0));
+        assert_eq!(execute(&dispatcher, 0, "ping abc"), Ok(3));
+        // Aliases continue at the children of the command they redirect to.
+        assert_eq!(execute(&dispatcher, 2, "g Notch 7"), Ok(7));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let dispatcher = dispatcher();
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "gift")),
+            (SyntaxErrorKind::UnknownCommand, 0)
+        );
+        // Commands that aren't complete fail at the end.
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "give Notch")),
+            (SyntaxErrorKind::UnknownCommand, 10)
+        );
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "give Notch 65")),
+            (SyntaxErrorKind::IntegerTooHigh { max: 64, found: 65 }, 11)
+        );
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "give @e 1")),
+            (SyntaxErrorKind::PlayersOnly, 5)
+        );
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "give Notch 1 2")),
+            (SyntaxErrorKind::UnknownArgument, 13)
+        );
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 2, "give Notch 1x")),
+            (SyntaxErrorKind::ExpectedArgumentSeparator, 12)
+        );
+    }
+
+    #[test]
+    fn requirements_hide_nodes() {
+        let dispatcher = dispatcher();
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 1, "give Notch 5")),
+            (SyntaxErrorKind::UnknownCommand, 0)
+        );
+        assert_eq!(
+            syntax_error(execute(&dispatcher, 1, "g Notch 5")),
+            (SyntaxErrorKind::UnknownCommand, 0)
+        );
+        assert_eq!(
+            dispatcher.usage(&TestSender::new(1)),
+            ["ping", "ping <times>"]
+        );
+        assert_eq!(
+            dispatcher.usage(&TestSender::new(2)),
+            ["give <target> <count>", "g -> give", "ping", "ping <times>"]
+        );
+    }
+
+    #[test]
+    fn merges_registered_nodes() {
+        let mut dispatcher = dispatcher();
+        dispatcher.register(literal("ping").then(literal("all").executes(|_| Ok(-1))));
+        assert_eq!(execute(&dispatcher, 0, "ping"), Ok(0));
+        assert_eq!(execute(&dispatcher, 0, "ping all"), Ok(-1));
+        // The literal is preferred over the argument.
+        assert_eq!(execute(&dispatcher, 0, "ping alls"), Ok(4));
+    }
+
+    #[test]
+    fn suggestions() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let dispatcher = dispatcher();
+        let sender = TestSender::new(2);
+        let suggest = |input| {
+            let suggestions = dispatcher.suggest(&server, &sender, input);
+            let texts: Vec<_> = suggestions
+                .matches
+                .into_iter()
+                .map(|suggestion| suggestion.text)
+                .collect();
+            (suggestions.start, texts)
+        };
+
+        assert_eq!(
+            suggest(""),
+            (0, ["g", "give", "ping"].map(String::from).to_vec())
+        );
+        assert_eq!(suggest("gi"), (0, vec!["give".to_owned()]));
+        // Players only arguments don't suggest `@e`.
+        assert_eq!(
+            suggest("give "),
+            (5, ["@a", "@p", "@r", "@s"].map(String::from).to_vec())
+        );
+        assert_eq!(
+            suggest("give @"),
+            (5, ["@a", "@p", "@r", "@s"].map(String::from).to_vec())
+        );
+        assert_eq!(suggest("g @a"), (2, vec!["@a".to_owned()]));
+        assert_eq!(
+            suggest("ping 1"),
+            (5, vec!["1".to_owned(), "10".to_owned()])
+        );
+        assert_eq!(suggest("ping "), (5, vec!["1".to_owned(), "10".to_owned()]));
+        assert_eq!(suggest("give Notch "), (0, Vec::new()));
+        assert_eq!(suggest("nothing"), (0, Vec::new()));
+
+        // Commands the sender can't use aren't suggested.
+        let suggestions = dispatcher.suggest(&server, &TestSender::new(0), "g");
+        assert!(suggestions.matches.is_empty());
+    }
+
+    #[test]
+    fn commands_packet() {
+        let dispatcher = dispatcher();
+        let packet = dispatcher.commands_packet(&TestSender::new(2));
+        let children = |index: usize| -> Vec<i32> {
+            packet.nodes[index]
+                .children
+                .iter()
+                .map(|child| child.0)
+                .collect()
+        };
+
+        // Nodes are numbered breadth-first: give, g, ping, target, times, count.
+        assert_eq!(packet.root_index, 0);
+        assert_eq!(packet.nodes.len(), 7);
+        assert_eq!(children(0), [1, 2, 3]);
+        assert_eq!(
+            packet.nodes[1].node_type,
+            CommandNodeType::Literal {
+                name: "give".into()
+            }
+        );
+        assert_eq!(children(1), [4]);
+        assert_eq!(packet.nodes[2].redirect, Some(1));
+        assert!(packet.nodes[2].children.is_empty());
+        assert!(packet.nodes[3].executable);
+        assert_eq!(
+            packet.nodes[4].node_type,
+            CommandNodeType::Argument {
+                name: "target".into(),
+                parser: CommandParser::Entity {
+                    single: true,
+                    players_only: true
+                },
+                suggestions: None,
+            }
+        );
+        assert!(!packet.nodes[4].executable);
+        assert_eq!(children(4), [6]);
+        assert_eq!(
+            packet.nodes[5].node_type,
+            CommandNodeType::Argument {
+                name: "times".into(),
+                parser: CommandParser::String(packet::client::StringParserKind::SingleWord),
+                suggestions: Some(Identifier::from_string(ASK_SERVER_SUGGESTIONS).unwrap()),
+            }
+        );
+        assert!(packet.nodes[6].executable);
+
+        // Nodes the sender can't use are left out.
+        let packet = dispatcher.commands_packet(&TestSender::new(0));
+        assert_eq!(packet.nodes.len(), 3);
+        assert_eq!(
+            packet.nodes[0]
+                .children
+                .iter()
+                .map(|child| child.0)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+}
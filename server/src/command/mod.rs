@@ -0,0 +1,473 @@
+//! Commands, with a dispatcher modeled on Mojang's Brigadier so that clients can highlight
+//! and complete them from the command graph.
+
+use std::{collections::HashMap, fmt::Display};
+
+use protocol::{
+    identifier::Identifier,
+    text::{TextColor, TextComponent},
+    GameMode,
+};
+use thiserror::Error;
+
+use crate::{
+    context::ServerContext,
+    player::{GameProfile, Player},
+    player_list::PlayerEntry,
+    world::{Position, Rotation},
+};
+
+pub mod argument;
+pub mod builtin;
+mod dispatcher;
+pub mod moderation;
+pub mod node;
+pub mod reader;
+
+pub use argument::{ArgumentType, ArgumentValue, Coordinates, EntitySelector};
+pub use dispatcher::CommandDispatcher;
+pub use node::{argument, literal, CommandBuilder, NodeId};
+
+/// Number of characters before the cursor shown in syntax errors.
+const ERROR_CONTEXT_LEN: usize = 10;
+
+/// Something that runs commands, e.g. a player or the console.
+pub trait CommandSender {
+    fn name(&self) -> &str;
+
+    /// Sends command feedback to the sender.
+    fn send_message(&mut self, message: &TextComponent<'_>);
+
+    /// Permission level (0-4), same as vanilla operator levels.
+    fn permission_level(&self) -> u8;
+
+    /// The profile of the player, which permissions are looked up for.
+    fn profile(&self) -> Option<&GameProfile> {
+        None
+    }
+
+    fn entity_id(&self) -> Option<i32> {
+        None
+    }
+
+    fn position(&self) -> Option<Position> {
+        None
+    }
+
+    fn rotation(&self) -> Option<Rotation> {
+        None
+    }
+
+    /// The sender as a player, for commands that only players can run.
+    fn as_player(&mut self) -> Option<&mut Player> {
+        None
+    }
+}
+
+/// The state a command is executed with.
+pub struct CommandContext<'a> {
+    pub server: &'a ServerContext,
+    pub sender: &'a mut dyn CommandSender,
+    /// The full command, without the leading slash.
+    pub input: &'a str,
+    arguments: HashMap<String, ArgumentValue>,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn new(
+        server: &'a ServerContext,
+        sender: &'a mut dyn CommandSender,
+        input: &'a str,
+        arguments: HashMap<String, ArgumentValue>,
+    ) -> Self {
+        Self {
+            server,
+            sender,
+            input,
+            arguments,
+        }
+    }
+
+    /// Returns the parsed argument named `name`, if the command has one.
+    pub fn argument(&self, name: &str) -> Option<&ArgumentValue> {
+        self.arguments.get(name)
+    }
+
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.argument(name)? {
+            ArgumentValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i32> {
+        match self.argument(name)? {
+            ArgumentValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn double(&self, name: &str) -> Option<f64> {
+        match self.argument(name)? {
+            ArgumentValue::Double(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.argument(name)? {
+            ArgumentValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn entity_selector(&self, name: &str) -> Option<&EntitySelector> {
+        match self.argument(name)? {
+            ArgumentValue::Entity(selector) => Some(selector),
+            _ => None,
+        }
+    }
+
+    pub fn coordinates(&self, name: &str) -> Option<&Coordinates> {
+        match self.argument(name)? {
+            ArgumentValue::Coordinates(coordinates) => Some(coordinates),
+            _ => None,
+        }
+    }
+
+    pub fn identifier(&self, name: &str) -> Option<&Identifier<'static>> {
+        match self.argument(name)? {
+            ArgumentValue::Identifier(identifier) => Some(identifier),
+            _ => None,
+        }
+    }
+
+    pub fn game_mode(&self, name: &str) -> Option<GameMode> {
+        match self.argument(name)? {
+            ArgumentValue::GameMode(game_mode) => Some(*game_mode),
+            _ => None,
+        }
+    }
+
+    /// The position and rotation relative coordinates are resolved against.
+    ///
+    /// Senders that aren't in the world use the world spawn.
+    pub fn origin(&self) -> (Position, Rotation) {
+        let world = &self.server.world;
+        (
+            self.sender
+                .position()
+                .unwrap_or_else(|| Position::from_block(world.spawn_position)),
+            self.sender
+                .rotation()
+                .unwrap_or(Rotation::new(world.spawn_angle, 0.0)),
+        )
+    }
+
+    /// Finds the players selected by the entity argument `name`.
+    pub fn players(&self, name: &str) -> Result<Vec<PlayerEntry>, CommandError> {
+        let selector = self
+            .entity_selector(name)
+            .ok_or_else(|| CommandError::Failed(format!("Missing argument {name}")))?;
+        selector.resolve_players(self.server, &*self.sender)
+    }
+
+    /// Whether the sender has `permission`, or `None` if the permission provider doesn't
+    /// decide it.
+    pub fn has_permission(&self, permission: &str) -> Option<bool> {
+        self.server
+            .commands
+            .has_permission(&*self.sender, permission)
+    }
+
+    pub fn send_message(&mut self, message: &TextComponent<'_>) {
+        self.sender.send_message(message);
+    }
+
+    /// The sender as a player, failing if it isn't one.
+    pub fn player(&mut self) -> Result<&mut Player, CommandError> {
+        self.sender.as_player().ok_or(CommandError::RequiresPlayer)
+    }
+}
+
+/// Executes `input` (a command without the leading slash) as `sender`, sending it any error.
+///
+/// Returns the command's result if it succeeded.
+pub fn run_command(
+    server: &ServerContext,
+    sender: &mut dyn CommandSender,
+    input: &str,
+) -> Option<i32> {
+    tracing::info!("{} issued server command: /{}", sender.name(), input);
+    match server.commands.execute(server, sender, input) {
+        Ok(result) => Some(result),
+        Err(err) => {
+            tracing::debug!("Command /{} failed: {}", input, err);
+            sender.send_message(&err.to_text_component());
+            None
+        }
+    }
+}
+
+/// A command completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub text: String,
+    pub tooltip: Option<String>,
+}
+
+impl Suggestion {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            tooltip: None,
+        }
+    }
+
+    pub fn with_tooltip(text: impl Into<String>, tooltip: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            tooltip: Some(tooltip.into()),
+        }
+    }
+}
+
+/// Completions for the end of a command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Suggestions {
+    /// Byte offset in the input the suggestions replace from, up to the end.
+    pub start: usize,
+    pub matches: Vec<Suggestion>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SyntaxErrorKind {
+    #[error("Unknown or incomplete command, see below for error")]
+    UnknownCommand,
+    #[error("Incorrect argument for command")]
+    UnknownArgument,
+    #[error("Expected whitespace to end one argument, but found trailing data")]
+    ExpectedArgumentSeparator,
+    #[error("Expected integer")]
+    ExpectedInteger,
+    #[error("Invalid integer '{0}'")]
+    InvalidInteger(String),
+    #[error("Expected double")]
+    ExpectedDouble,
+    #[error("Invalid double '{0}'")]
+    InvalidDouble(String),
+    #[error("Expected bool")]
+    ExpectedBool,
+    #[error("Invalid bool, expected true or false but found '{0}'")]
+    InvalidBool(String),
+    #[error("Integer must not be less than {min}, found {found}")]
+    IntegerTooLow { min: i32, found: i32 },
+    #[error("Integer must not be more than {max}, found {found}")]
+    IntegerTooHigh { max: i32, found: i32 },
+    #[error("Double must not be less than {min}, found {found}")]
+    DoubleTooLow { min: f64, found: f64 },
+    #[error("Double must not be more than {max}, found {found}")]
+    DoubleTooHigh { max: f64, found: f64 },
+    #[error("Expected quote to start a string")]
+    ExpectedStartOfQuote,
+    #[error("Unclosed quoted string")]
+    ExpectedEndOfQuote,
+    #[error("Invalid escape sequence '\\{0}' in quoted string")]
+    InvalidEscape(char),
+    #[error("Invalid ID '{0}'")]
+    InvalidIdentifier(String),
+    #[error("Incomplete (expected 3 coordinates)")]
+    IncompleteCoordinates,
+    #[error("Cannot mix world & local coordinates (everything must either use ^ or not)")]
+    MixedCoordinates,
+    #[error("Unknown selector type '{0}'")]
+    UnknownSelectorType(String),
+    #[error("Selector arguments are not supported")]
+    SelectorArgumentsNotSupported,
+    #[error("Invalid name or UUID")]
+    InvalidEntity,
+    #[error(
+        "Only players may be affected by this command, but the provided selector includes entities"
+    )]
+    PlayersOnly,
+    #[error("Only one player is allowed, but the provided selector allows more than one")]
+    TooManyPlayers,
+    #[error("Only one entity is allowed, but the provided selector allows more than one")]
+    TooManyEntities,
+    #[error("Unknown game mode '{0}'")]
+    InvalidGameMode(String),
+}
+
+/// An error parsing a command, at `cursor` in `input`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSyntaxError {
+    pub kind: SyntaxErrorKind,
+    pub input: String,
+    /// Byte offset in `input` the error is at.
+    pub cursor: usize,
+}
+
+impl CommandSyntaxError {
+    /// Splits the input around the cursor, keeping only a few characters before it.
+    fn context(&self) -> (String, &str) {
+        let cursor = self.cursor.min(self.input.len());
+        let (before, after) = self.input.split_at(cursor);
+
+        let context_start = before
+            .char_indices()
+            .rev()
+            .nth(ERROR_CONTEXT_LEN - 1)
+            .map_or(0, |(index, _)| index);
+        let before = if context_start > 0 {
+            format!("...{}", &before[context_start..])
+        } else {
+            before.to_owned()
+        };
+
+        (before, after)
+    }
+}
+
+impl Display for CommandSyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (before, after) = self.context();
+        write!(
+            f,
+            "{} at position {}: {}{}<--[HERE]",
+            self.kind, self.cursor, before, after
+        )
+    }
+}
+
+impl std::error::Error for CommandSyntaxError {}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CommandError {
+    #[error(transparent)]
+    Syntax(#[from] CommandSyntaxError),
+    #[error("A player is required to run this command here")]
+    RequiresPlayer,
+    #[error("No player was found")]
+    NoPlayersFound,
+    /// The command failed, with a message for the sender.
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl CommandError {
+    /// The error as command feedback, formatted like vanilla.
+    pub fn to_text_component(&self) -> TextComponent<'static> {
+        let CommandError::Syntax(error) = self else {
+            return TextComponent::text(self.to_string()).color(TextColor::Red);
+        };
+
+        let (before, after) = error.context();
+        TextComponent::text(error.kind.to_string())
+            .color(TextColor::Red)
+            .append(TextComponent::text("\n"))
+            .append(TextComponent::text(before).color(TextColor::Gray))
+            .append(TextComponent::text(after.to_owned()))
+            .append(TextComponent::translatable("command.context.here", []))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use crate::{
+        access::AccessControl,
+        config::{ConfigOverrides, LiveConfig, ServerConfig},
+        world::{generator::VoidGenerator, World},
+    };
+
+    use super::*;
+
+    /// A sender collecting its feedback as plain text.
+    #[derive(Debug, Default)]
+    pub(crate) struct TestSender {
+        pub level: u8,
+        pub messages: Vec<String>,
+    }
+
+    impl TestSender {
+        pub fn new(level: u8) -> Self {
+            Self {
+                level,
+                messages: Vec::new(),
+            }
+        }
+    }
+
+    impl CommandSender for TestSender {
+        fn name(&self) -> &str {
+            "Tester"
+        }
+
+        fn send_message(&mut self, message: &TextComponent<'_>) {
+            self.messages
+                .push(message.to_plain_text(&server_assets::translation));
+        }
+
+        fn permission_level(&self) -> u8 {
+            self.level
+        }
+    }
+
+    /// Makes a server with an empty void world, keeping its files in `directory`.
+    pub(crate) fn test_server(directory: &Path) -> ServerContext {
+        let world = World::overworld(Box::new(VoidGenerator::new()));
+        let config = LiveConfig::new(
+            directory.join("server.toml"),
+            ConfigOverrides::default(),
+            ServerConfig::default(),
+        );
+        let access = AccessControl::load(directory).unwrap();
+        ServerContext::new(Arc::new(world), config, access)
+    }
+
+    fn syntax_error(kind: SyntaxErrorKind, input: &str, cursor: usize) -> CommandSyntaxError {
+        CommandSyntaxError {
+            kind,
+            input: input.to_owned(),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn syntax_error_context() {
+        let error = syntax_error(SyntaxErrorKind::ExpectedInteger, "give Notch x", 11);
+        assert_eq!(
+            error.to_string(),
+            "Expected integer at position 11: ...ive Notch x<--[HERE]"
+        );
+
+        let error = syntax_error(SyntaxErrorKind::UnknownCommand, "foo", 0);
+        assert_eq!(
+            error.to_string(),
+            "Unknown or incomplete command, see below for error at position 0: foo<--[HERE]"
+        );
+
+        // The context is counted in characters, not bytes.
+        let error = syntax_error(SyntaxErrorKind::UnknownArgument, "say ééééééééé !", 23);
+        assert_eq!(error.context(), ("...ééééééééé ".to_owned(), "!"));
+    }
+
+    #[test]
+    fn error_feedback() {
+        let error =
+            CommandError::from(syntax_error(SyntaxErrorKind::ExpectedBool, "gamerule x", 9));
+        assert_eq!(
+            error
+                .to_text_component()
+                .to_plain_text(&server_assets::translation),
+            "Expected bool\ngamerule x<--[HERE]"
+        );
+        assert_eq!(
+            CommandError::NoPlayersFound
+                .to_text_component()
+                .to_plain_text(&server_assets::translation),
+            "No player was found"
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Commands managing who may join: the whitelist, operators and bans.
+
+use std::net::IpAddr;
+
+use protocol::text::TextComponent;
+
+use crate::{
+    access::{AccessError, BanDetails},
+    context::ServerContext,
+    player::{GameProfile, MAX_PERMISSION_LEVEL},
+};
+
+use super::{
+    argument, argument::starts_with_ignore_case, literal, ArgumentType, CommandContext,
+    CommandDispatcher, CommandError, CommandSender, Suggestion,
+};
+
+/// Permission level required for the moderation commands, same as vanilla.
+const ADMIN_LEVEL: u8 = 3;
+
+/// Registers the moderation commands.
+pub fn register(dispatcher: &mut CommandDispatcher) {
+    dispatcher.register(
+        literal("whitelist")
+            .requires_level(ADMIN_LEVEL)
+            .then(literal("on").executes(|ctx| set_whitelist(ctx, true)))
+            .then(literal("off").executes(|ctx| set_whitelist(ctx, false)))
+            .then(literal("list").executes(whitelist_list))
+            .then(literal("reload").executes(whitelist_reload))
+            .then(
+                literal("add").then(
+                    argument("player", ArgumentType::Word)
+                        .suggests(online_players)
+                        .executes(whitelist_add),
+                ),
+            )
+            .then(
+                literal("remove").then(
+                    argument("player", ArgumentType::Word)
+                        .suggests(whitelisted_players)
+                        .executes(whitelist_remove),
+                ),
+            ),
+    );
+
+    dispatcher.register(
+        literal("op").requires_level(ADMIN_LEVEL).then(
+            argument("player", ArgumentType::Word)
+                .suggests(online_players)
+                .executes(op),
+        ),
+    );
+    dispatcher.register(
+        literal("deop").requires_level(ADMIN_LEVEL).then(
+            argument("player", ArgumentType::Word)
+                .suggests(operators)
+                .executes(deop),
+        ),
+    );
+
+    dispatcher.register(
+        literal("ban").requires_level(ADMIN_LEVEL).then(
+            argument("player", ArgumentType::Word)
+                .suggests(online_players)
+                .executes(ban)
+                .then(argument("reason", ArgumentType::Message).executes(ban)),
+        ),
+    );
+    dispatcher.register(
+        literal("ban-ip").requires_level(ADMIN_LEVEL).then(
+            argument("target", ArgumentType::Word)
+                .suggests(online_players)
+                .executes(ban_ip)
+                .then(argument("reason", ArgumentType::Message).executes(ban_ip)),
+        ),
+    );
+    dispatcher.register(
+        literal("pardon").requires_level(ADMIN_LEVEL).then(
+            argument("player", ArgumentType::Word)
+                .suggests(banned_players)
+                .executes(pardon),
+        ),
+    );
+    dispatcher.register(
+        literal("pardon-ip").requires_level(ADMIN_LEVEL).then(
+            argument("target", ArgumentType::Word)
+                .suggests(banned_ips)
+                .executes(pardon_ip),
+        ),
+    );
+
+    dispatcher.register(
+        literal("banlist")
+            .requires_level(ADMIN_LEVEL)
+            .executes(|ctx| banlist(ctx, true, true))
+            .then(literal("players").executes(|ctx| banlist(ctx, true, false)))
+            .then(literal("ips").executes(|ctx| banlist(ctx, false, true))),
+    );
+}
+
+fn set_whitelist(ctx: &mut CommandContext<'_>, enabled: bool) -> Result<i32, CommandError> {
+    if ctx.server.config.get().whitelist == enabled {
+        let state = if enabled { "on" } else { "off" };
+        return Err(CommandError::Failed(format!(
+            "Whitelist is already turned {state}"
+        )));
+    }
+    ctx.server
+        .config
+        .edit(|config| config.whitelist = enabled)
+        .map_err(|err| CommandError::Failed(format!("Unable to save the configuration: {err}")))?;
+
+    let key = if enabled {
+        "commands.whitelist.enabled"
+    } else {
+        "commands.whitelist.disabled"
+    };
+    ctx.send_message(&TextComponent::translatable(key, []));
+    Ok(1)
+}
+
+fn whitelist_list(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let whitelist = ctx.server.access.whitelist();
+    if whitelist.is_empty() {
+        ctx.send_message(&TextComponent::translatable("commands.whitelist.none", []));
+        return Ok(0);
+    }
+
+    let names: Vec<_> = whitelist.iter().map(|entry| entry.name.as_str()).collect();
+    ctx.send_message(&TextComponent::translatable(
+        "commands.whitelist.list",
+        [
+            TextComponent::text(names.len().to_string()),
+            TextComponent::text(names.join(", ")),
+        ],
+    ));
+    Ok(names.len() as i32)
+}
+
+fn whitelist_reload(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    ctx.server.access.reload().map_err(save_error)?;
+    ctx.send_message(&TextComponent::translatable(
+        "commands.whitelist.reloaded",
+        [],
+    ));
+    Ok(1)
+}
+
+fn whitelist_add(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let access = &ctx.server.access;
+    let profile = resolve_profile(ctx.server, ctx.string("player").unwrap_or_default());
+    if !access.add_to_whitelist(&profile).map_err(save_error)? {
+        return Err(CommandError::Failed(
+            "Player is already whitelisted".to_owned(),
+        ));
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.whitelist.add.success",
+        [TextComponent::text(profile.username)],
+    ));
+    Ok(1)
+}
+
+fn whitelist_remove(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let access = &ctx.server.access;
+    let name = ctx.string("player").unwrap_or_default().to_owned();
+    if !access.remove_from_whitelist(&name).map_err(save_error)? {
+        return Err(CommandError::Failed("Player is not whitelisted".to_owned()));
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.whitelist.remove.success",
+        [TextComponent::text(name)],
+    ));
+    Ok(1)
+}
+
+fn op(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let server = ctx.server;
+    let profile = resolve_profile(server, ctx.string("player").unwrap_or_default());
+    if !server
+        .access
+        .add_op(&profile, MAX_PERMISSION_LEVEL)
+        .map_err(save_error)?
+    {
+        return Err(CommandError::Failed(
+            "The player already is an operator".to_owned(),
+        ));
+    }
+    if let Some(entry) = server.world.players().find_by_name(&profile.username) {
+        entry.update_permission_level(MAX_PERMISSION_LEVEL, &server.commands);
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.op.success",
+        [TextComponent::text(profile.username)],
+    ));
+    Ok(1)
+}
+
+fn deop(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let server = ctx.server;
+    let name = ctx.string("player").unwrap_or_default().to_owned();
+    if !server.access.remove_op(&name).map_err(save_error)? {
+        return Err(CommandError::Failed(
+            "The player is not an operator".to_owned(),
+        ));
+    }
+    if let Some(entry) = server.world.players().find_by_name(&name) {
+        entry.update_permission_level(0, &server.commands);
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.deop.success",
+        [TextComponent::text(name)],
+    ));
+    Ok(1)
+}
+
+fn ban(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let server = ctx.server;
+    let profile = resolve_profile(server, ctx.string("player").unwrap_or_default());
+    let details = ban_details(ctx);
+    let reason = details.reason.clone();
+    if !server.access.ban(&profile, details).map_err(save_error)? {
+        return Err(CommandError::Failed(
+            "The player is already banned".to_owned(),
+        ));
+    }
+    if let Some(entry) = server.world.players().find_by_name(&profile.username) {
+        entry.kick(&TextComponent::translatable(
+            "multiplayer.disconnect.banned",
+            [],
+        ));
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.ban.success",
+        [
+            TextComponent::text(profile.username),
+            TextComponent::text(reason),
+        ],
+    ));
+    Ok(1)
+}
+
+fn ban_ip(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let server = ctx.server;
+    let target = ctx.string("target").unwrap_or_default();
+    // Like vanilla, an online player's name bans their IP address.
+    let ip = match target.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        Err(_) => match server.world.players().find_by_name(target) {
+            Some(entry) => entry.sender.address().ip(),
+            None => {
+                return Err(CommandError::Failed(
+                    "Invalid IP address or unknown player".to_owned(),
+                ))
+            }
+        },
+    };
+    let details = ban_details(ctx);
+    let reason = details.reason.clone();
+    if !server.access.ban_ip(ip, details).map_err(save_error)? {
+        return Err(CommandError::Failed("That IP is already banned".to_owned()));
+    }
+
+    let kick_reason = TextComponent::translatable("multiplayer.disconnect.ip_banned", []);
+    let mut kicked = Vec::new();
+    for entry in server.world.players().entries() {
+        if entry.sender.address().ip() == ip {
+            entry.kick(&kick_reason);
+            kicked.push(entry.profile.username);
+        }
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.banip.success",
+        [
+            TextComponent::text(ip.to_string()),
+            TextComponent::text(reason),
+        ],
+    ));
+    if !kicked.is_empty() {
+        ctx.send_message(&TextComponent::translatable(
+            "commands.banip.info",
+            [
+                TextComponent::text(kicked.len().to_string()),
+                TextComponent::text(kicked.join(", ")),
+            ],
+        ));
+    }
+    Ok(1)
+}
+
+fn pardon(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let name = ctx.string("player").unwrap_or_default().to_owned();
+    if !ctx.server.access.pardon(&name).map_err(save_error)? {
+        return Err(CommandError::Failed(
+            "Nothing changed. The player isn't banned".to_owned(),
+        ));
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.pardon.success",
+        [TextComponent::text(name)],
+    ));
+    Ok(1)
+}
+
+fn pardon_ip(ctx: &mut CommandContext<'_>) -> Result<i32, CommandError> {
+    let ip = ctx
+        .string("target")
+        .unwrap_or_default()
+        .parse::<IpAddr>()
+        .map_err(|_| CommandError::Failed("Invalid IP address".to_owned()))?;
+    if !ctx.server.access.pardon_ip(ip).map_err(save_error)? {
+        return Err(CommandError::Failed(
+            "Nothing changed. That IP isn't banned".to_owned(),
+        ));
+    }
+
+    ctx.send_message(&TextComponent::translatable(
+        "commands.pardonip.success",
+        [TextComponent::text(ip.to_string())],
+    ));
+    Ok(1)
+}
+
+fn banlist(ctx: &mut CommandContext<'_>, players: bool, ips: bool) -> Result<i32, CommandError> {
+    let mut entries = Vec::new();
+    if players {
+        for ban in ctx.server.access.banned_players() {
+            entries.push((ban.name, ban.details));
+        }
+    }
+    if ips {
+        for ban in ctx.server.access.banned_ips() {
+            entries.push((ban.ip.to_string(), ban.details));
+        }
+    }
+
+    if entries.is_empty() {
+        ctx.send_message(&TextComponent::translatable("commands.banlist.none", []));
+        return Ok(0);
+    }
+    ctx.send_message(&TextComponent::translatable(
+        "commands.banlist.list",
+        [TextComponent::text(entries.len().to_string())],
+    ));
+    for (target, details) in &entries {
+        ctx.send_message(&TextComponent::translatable(
+            "commands.banlist.entry",
+            [
+                TextComponent::text(target.clone()),
+                TextComponent::text(details.source.clone()),
+                TextComponent::text(details.reason.clone()),
+            ],
+        ));
+    }
+    Ok(entries.len() as i32)
+}
+
+/// A ban by the sender, with the optional `reason` argument.
+fn ban_details(ctx: &CommandContext<'_>) -> BanDetails {
+    let reason = ctx.string("reason").map(str::to_owned);
+    BanDetails::new(ctx.sender.name(), reason, None)
+}
+
+/// Finds the profile of a player by name, who doesn't need to be online.
+///
+/// Offline players get the UUID they would have in offline mode.
+fn resolve_profile(server: &ServerContext, name: &str) -> GameProfile {
+    match server.world.players().find_by_name(name) {
+        Some(entry) => entry.profile,
+        None => GameProfile::offline(name),
+    }
+}
+
+fn save_error(err: AccessError) -> CommandError {
+    CommandError::Failed(format!("Unable to update the access lists: {err}"))
+}
+
+/// Suggests candidates starting with the argument typed so far.
+fn matching(candidates: impl IntoIterator<Item = String>, remaining: &str) -> Vec<Suggestion> {
+    candidates
+        .into_iter()
+        .filter(|candidate| starts_with_ignore_case(candidate, remaining))
+        .map(Suggestion::new)
+        .collect()
+}
+
+fn online_players(
+    server: &ServerContext,
+    _sender: &dyn CommandSender,
+    remaining: &str,
+) -> Vec<Suggestion> {
+    let players = server.world.players().entries();
+    matching(
+        players.into_iter().map(|entry| entry.profile.username),
+        remaining,
+    )
+}
+
+fn whitelisted_players(
+    server: &ServerContext,
+    _sender: &dyn CommandSender,
+    remaining: &str,
+) -> Vec<Suggestion> {
+    let whitelist = server.access.whitelist();
+    matching(whitelist.into_iter().map(|entry| entry.name), remaining)
+}
+
+fn operators(
+    server: &ServerContext,
+    _sender: &dyn CommandSender,
+    remaining: &str,
+) -> Vec<Suggestion> {
+    matching(server.access.ops().into_iter().map(|op| op.name), remaining)
+}
+
+fn banned_players(
+    server: &ServerContext,
+    _sender: &dyn CommandSender,
+    remaining: &str,
+) -> Vec<Suggestion> {
+    let bans = server.access.banned_players();
+    matching(bans.into_iter().map(|ban| ban.name), remaining)
+}
+
+fn banned_ips(
+    server: &ServerContext,
+    _sender: &dyn CommandSender,
+    remaining: &str,
+) -> Vec<Suggestion> {
+    let bans = server.access.banned_ips();
+    matching(bans.into_iter().map(|ban| ban.ip.to_string()), remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::tests::{test_server, TestSender};
+
+    use super::*;
+
+    /// Runs `input` as an admin, returning the result and the feedback.
+    fn run(server: &ServerContext, input: &str) -> (Result<i32, CommandError>, Vec<String>) {
+        let mut sender = TestSender::new(ADMIN_LEVEL);
+        let result = server.commands.execute(server, &mut sender, input);
+        (result, sender.messages)
+    }
+
+    fn failed(message: &str) -> Result<i32, CommandError> {
+        Err(CommandError::Failed(message.to_owned()))
+    }
+
+    #[test]
+    fn whitelist() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let notch = GameProfile::offline("Notch");
+
+        assert_eq!(run(&server, "whitelist list").0, Ok(0));
+        assert_eq!(
+            run(&server, "whitelist add Notch"),
+            (Ok(1), vec!["Added Notch to the whitelist".to_owned()])
+        );
+        assert!(server.access.is_whitelisted(&notch));
+        assert_eq!(
+            run(&server, "whitelist add Notch").0,
+            failed("Player is already whitelisted")
+        );
+        assert_eq!(
+            run(&server, "whitelist list"),
+            (
+                Ok(1),
+                vec!["There are 1 whitelisted player(s): Notch".to_owned()]
+            )
+        );
+        assert_eq!(run(&server, "whitelist remove notch").0, Ok(1));
+        assert!(!server.access.is_whitelisted(&notch));
+        assert_eq!(
+            run(&server, "whitelist remove Notch").0,
+            failed("Player is not whitelisted")
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let notch = GameProfile::offline("Notch");
+
+        assert_eq!(
+            run(&server, "op Notch"),
+            (Ok(1), vec!["Made Notch a server operator".to_owned()])
+        );
+        assert_eq!(server.access.permission_level(&notch), MAX_PERMISSION_LEVEL);
+        assert_eq!(
+            run(&server, "op Notch").0,
+            failed("The player already is an operator")
+        );
+
+        let sender = TestSender::new(ADMIN_LEVEL);
+        let suggestions = server.commands.suggest(&server, &sender, "deop N");
+        assert_eq!(suggestions.start, 5);
+        assert_eq!(suggestions.matches, [Suggestion::new("Notch")]);
+
+        assert_eq!(run(&server, "deop Notch").0, Ok(1));
+        assert_eq!(server.access.permission_level(&notch), 0);
+        assert_eq!(
+            run(&server, "deop Notch").0,
+            failed("The player is not an operator")
+        );
+    }
+
+    #[test]
+    fn bans() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let notch = GameProfile::offline("Notch");
+
+        assert_eq!(
+            run(&server, "ban Notch Griefing the spawn"),
+            (Ok(1), vec!["Banned Notch: Griefing the spawn".to_owned()])
+        );
+        let ban = server.access.player_ban(&notch).unwrap();
+        assert_eq!(ban.details.source, "Tester");
+        assert_eq!(ban.details.reason, "Griefing the spawn");
+        assert_eq!(
+            run(&server, "ban Notch").0,
+            failed("The player is already banned")
+        );
+
+        assert_eq!(run(&server, "ban-ip 10.0.0.1").0, Ok(1));
+        assert_eq!(
+            run(&server, "ban-ip Nobody").0,
+            failed("Invalid IP address or unknown player")
+        );
+        let (result, messages) = run(&server, "banlist");
+        assert_eq!(result, Ok(2));
+        assert_eq!(
+            messages,
+            [
+                "There are 2 ban(s):",
+                "Notch was banned by Tester: Griefing the spawn",
+                "10.0.0.1 was banned by Tester: Banned by an operator.",
+            ]
+        );
+        assert_eq!(run(&server, "banlist ips").0, Ok(1));
+
+        assert_eq!(run(&server, "pardon Notch").0, Ok(1));
+        assert!(server.access.player_ban(&notch).is_none());
+        assert_eq!(
+            run(&server, "pardon Notch").0,
+            failed("Nothing changed. The player isn't banned")
+        );
+        assert_eq!(
+            run(&server, "pardon-ip 10.0.0").0,
+            failed("Invalid IP address")
+        );
+        assert_eq!(run(&server, "pardon-ip 10.0.0.1").0, Ok(1));
+        assert_eq!(run(&server, "banlist").0, Ok(0));
+    }
+
+    #[test]
+    fn requires_admin() {
+        let directory = tempfile::tempdir().unwrap();
+        let server = test_server(directory.path());
+        let mut sender = TestSender::new(ADMIN_LEVEL - 1);
+        for input in ["whitelist list", "op Notch", "ban Notch", "banlist"] {
+            let result = server.commands.execute(&server, &mut sender, input);
+            assert!(
+                matches!(result, Err(CommandError::Syntax(_))),
+                "{input} should be unknown"
+            );
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::{fmt::Debug, sync::Arc};
+
+use crate::context::ServerContext;
+
+use super::{ArgumentType, CommandContext, CommandError, CommandSender, Suggestion};
+
+/// Runs a command, returning its result (e.g. how many players were affected).
+pub type CommandExecutor =
+    Arc<dyn Fn(&mut CommandContext<'_>) -> Result<i32, CommandError> + Send + Sync>;
+/// Decides whether a sender may use a command node.
+pub type CommandRequirement = Arc<dyn Fn(&dyn CommandSender) -> bool + Send + Sync>;
+/// Suggests completions for a partially typed argument.
+pub type SuggestionProvider =
+    Arc<dyn Fn(&ServerContext, &dyn CommandSender, &str) -> Vec<Suggestion> + Send + Sync>;
+
+/// Index of a node in a [`CommandDispatcher`](super::CommandDispatcher).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(pub(super) usize);
+
+#[derive(Clone)]
+pub enum NodeKind {
+    Root,
+    Literal(String),
+    Argument {
+        name: String,
+        argument_type: ArgumentType,
+        /// Custom suggestions, which clients request from the server.
+        suggestions: Option<SuggestionProvider>,
+    },
+}
+
+impl NodeKind {
+    /// The name of the node, which siblings are merged by.
+    pub fn name(&self) -> &str {
+        match self {
+            NodeKind::Root => "",
+            NodeKind::Literal(name) => name,
+            NodeKind::Argument { name, .. } => name,
+        }
+    }
+}
+
+impl Debug for NodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NodeKind::Root => f.write_str("Root"),
+            NodeKind::Literal(name) => f.debug_tuple("Literal").field(name).finish(),
+            NodeKind::Argument {
+                name,
+                argument_type,
+                suggestions,
+            } => f
+                .debug_struct("Argument")
+                .field("name", name)
+                .field("argument_type", argument_type)
+                .field("suggestions", &suggestions.is_some())
+                .finish(),
+        }
+    }
+}
+
+/// A node of the command graph.
+#[derive(Clone)]
+pub struct CommandNode {
+    pub kind: NodeKind,
+    pub children: Vec<NodeId>,
+    /// Runs the command, if it may end at this node.
+    pub executor: Option<CommandExecutor>,
+    pub requirement: Option<CommandRequirement>,
+    /// Permission deciding whether a sender may use this node, before the requirement.
+    pub permission: Option<String>,
+    /// Node to continue parsing at after this one, instead of the children.
+    pub redirect: Option<NodeId>,
+}
+
+impl CommandNode {
+    pub fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            children: Vec::new(),
+            executor: None,
+            requirement: None,
+            permission: None,
+            redirect: None,
+        }
+    }
+
+    /// Whether `sender` meets the requirement of this node.
+    ///
+    /// The [dispatcher](super::CommandDispatcher) checks the node's permission first.
+    pub fn can_use(&self, sender: &dyn CommandSender) -> bool {
+        self.requirement
+            .as_ref()
+            .map_or(true, |requirement| requirement(sender))
+    }
+}
+
+impl Debug for CommandNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandNode")
+            .field("kind", &self.kind)
+            .field("children", &self.children)
+            .field("executable", &self.executor.is_some())
+            .field("permission", &self.permission)
+            .field("redirect", &self.redirect)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builds a command to [register](super::CommandDispatcher::register), like Brigadier's builders.
+#[derive(Debug, Clone)]
+pub struct CommandBuilder {
+    node: CommandNode,
+    children: Vec<CommandBuilder>,
+}
+
+/// Starts a literal node, which matches `name` exactly.
+pub fn literal(name: impl Into<String>) -> CommandBuilder {
+    CommandBuilder::new(NodeKind::Literal(name.into()))
+}
+
+/// Starts an argument node, which is parsed by `argument_type`.
+pub fn argument(name: impl Into<String>, argument_type: ArgumentType) -> CommandBuilder {
+    CommandBuilder::new(NodeKind::Argument {
+        name: name.into(),
+        argument_type,
+        suggestions: None,
+    })
+}
+
+impl CommandBuilder {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            node: CommandNode::new(kind),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn then(mut self, child: CommandBuilder) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn executes(
+        mut self,
+        executor: impl Fn(&mut CommandContext<'_>) -> Result<i32, CommandError> + Send + Sync + 'static,
+    ) -> Self {
+        self.node.executor = Some(Arc::new(executor));
+        self
+    }
+
+    pub fn requires(
+        mut self,
+        requirement: impl Fn(&dyn CommandSender) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.node.requirement = Some(Arc::new(requirement));
+        self
+    }
+
+    /// Requires the sender to have at least `level` as its permission level.
+    pub fn requires_level(self, level: u8) -> Self {
+        self.requires(move |sender| sender.permission_level() >= level)
+    }
+
+    /// Guards this node with `permission`, which takes precedence over the requirement for
+    /// senders the [permission provider](crate::permission::PermissionProvider) decides it for.
+    ///
+    /// Commands are guarded by `minecraft.command.<name>` unless they set another permission.
+    pub fn permission(mut self, permission: impl Into<String>) -> Self {
+        self.node.permission = Some(permission.into());
+        self
+    }
+
+    /// Sets custom suggestions for an argument node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is not an argument node.
+    pub fn suggests(
+        mut self,
+        provider: impl Fn(&ServerContext, &dyn CommandSender, &str) -> Vec<Suggestion>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        match &mut self.node.kind {
+            NodeKind::Argument { suggestions, .. } => *suggestions = Some(Arc::new(provider)),
+            _ => panic!("only argument nodes can have suggestions"),
+        }
+        self
+    }
+
+    /// Continues parsing at `target` after this node, e.g. for aliases.
+    pub fn redirect(mut self, target: NodeId) -> Self {
+        self.node.redirect = Some(target);
+        self
+    }
+
+    pub(super) fn into_parts(self) -> (CommandNode, Vec<CommandBuilder>) {
+        (self.node, self.children)
+    }
+}
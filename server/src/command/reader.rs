@@ -0,0 +1,287 @@
+use super::{CommandSyntaxError, SyntaxErrorKind};
+
+/// Separates the arguments of a command.
+pub const ARGUMENT_SEPARATOR: char = ' ';
+
+/// Reads a command, keeping track of the cursor for error messages and suggestions.
+///
+/// Cursor positions are byte offsets into the input.
+#[derive(Debug, Clone)]
+pub struct StringReader<'a> {
+    input: &'a str,
+    cursor: usize,
+}
+
+impl<'a> StringReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, cursor: 0 }
+    }
+
+    pub fn input(&self) -> &'a str {
+        self.input
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+    }
+
+    /// The input that has not been read yet.
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.cursor..]
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.cursor < self.input.len()
+    }
+
+    pub fn peek(&self) -> Option<char> {
+        self.remaining().chars().next()
+    }
+
+    /// Skips the next character.
+    pub fn skip(&mut self) {
+        if let Some(c) = self.peek() {
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    pub fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.skip();
+        }
+    }
+
+    /// Makes an error at the current cursor position.
+    pub fn error(&self, kind: SyntaxErrorKind) -> CommandSyntaxError {
+        self.error_at(kind, self.cursor)
+    }
+
+    pub fn error_at(&self, kind: SyntaxErrorKind, cursor: usize) -> CommandSyntaxError {
+        CommandSyntaxError {
+            kind,
+            input: self.input.to_owned(),
+            cursor,
+        }
+    }
+
+    /// Reads characters while `predicate` holds.
+    pub fn read_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start = self.cursor;
+        while self.peek().is_some_and(&predicate) {
+            self.skip();
+        }
+        &self.input[start..self.cursor]
+    }
+
+    /// Reads until the next argument separator.
+    pub fn read_word(&mut self) -> &'a str {
+        self.read_while(|c| c != ARGUMENT_SEPARATOR)
+    }
+
+    /// Reads the rest of the input.
+    pub fn read_remaining(&mut self) -> &'a str {
+        let remaining = self.remaining();
+        self.cursor = self.input.len();
+        remaining
+    }
+
+    pub fn read_unquoted_string(&mut self) -> &'a str {
+        self.read_while(is_allowed_in_unquoted_string)
+    }
+
+    /// Reads a string in double or single quotes, in which `\` escapes the quote and itself.
+    pub fn read_quoted_string(&mut self) -> Result<String, CommandSyntaxError> {
+        let quote = match self.peek() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error(SyntaxErrorKind::ExpectedStartOfQuote)),
+        };
+        self.skip();
+
+        let mut string = String::new();
+        let mut escaped = false;
+        while let Some(c) = self.peek() {
+            self.skip();
+            if escaped {
+                if c != quote && c != '\\' {
+                    self.cursor -= c.len_utf8();
+                    return Err(self.error(SyntaxErrorKind::InvalidEscape(c)));
+                }
+                string.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return Ok(string);
+            } else {
+                string.push(c);
+            }
+        }
+
+        Err(self.error(SyntaxErrorKind::ExpectedEndOfQuote))
+    }
+
+    /// Reads a quoted or unquoted string.
+    pub fn read_string(&mut self) -> Result<String, CommandSyntaxError> {
+        match self.peek() {
+            Some('"' | '\'') => self.read_quoted_string(),
+            _ => Ok(self.read_unquoted_string().to_owned()),
+        }
+    }
+
+    pub fn read_int(&mut self) -> Result<i32, CommandSyntaxError> {
+        let start = self.cursor;
+        let number = self.read_while(is_allowed_in_number);
+        if number.is_empty() {
+            return Err(self.error(SyntaxErrorKind::ExpectedInteger));
+        }
+
+        number
+            .parse()
+            .map_err(|_| self.error_at(SyntaxErrorKind::InvalidInteger(number.to_owned()), start))
+    }
+
+    pub fn read_double(&mut self) -> Result<f64, CommandSyntaxError> {
+        let start = self.cursor;
+        let number = self.read_while(is_allowed_in_number);
+        if number.is_empty() {
+            return Err(self.error(SyntaxErrorKind::ExpectedDouble));
+        }
+
+        number
+            .parse()
+            .map_err(|_| self.error_at(SyntaxErrorKind::InvalidDouble(number.to_owned()), start))
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, CommandSyntaxError> {
+        let start = self.cursor;
+        match self.read_unquoted_string() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            "" => Err(self.error(SyntaxErrorKind::ExpectedBool)),
+            value => Err(self.error_at(SyntaxErrorKind::InvalidBool(value.to_owned()), start)),
+        }
+    }
+}
+
+pub fn is_allowed_in_unquoted_string(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+fn is_allowed_in_number(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, '.' | '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_kind<T: std::fmt::Debug>(
+        result: Result<T, CommandSyntaxError>,
+    ) -> (SyntaxErrorKind, usize) {
+        let error = result.unwrap_err();
+        (error.kind, error.cursor)
+    }
+
+    #[test]
+    fn reads_words() {
+        let mut reader = StringReader::new("tp ~ ~1 ~");
+        assert_eq!(reader.read_word(), "tp");
+        assert_eq!(reader.peek(), Some(ARGUMENT_SEPARATOR));
+        reader.skip();
+        assert_eq!(reader.read_word(), "~");
+        reader.skip();
+        assert_eq!(reader.remaining(), "~1 ~");
+        assert_eq!(reader.read_remaining(), "~1 ~");
+        assert!(!reader.can_read());
+        assert_eq!(reader.read_word(), "");
+    }
+
+    #[test]
+    fn reads_numbers() {
+        let mut reader = StringReader::new("-12 1.5 x 1-2");
+        assert_eq!(reader.read_int(), Ok(-12));
+        reader.skip();
+        assert_eq!(reader.read_double(), Ok(1.5));
+        reader.skip();
+        assert_eq!(
+            error_kind(reader.clone().read_int()),
+            (SyntaxErrorKind::ExpectedInteger, 8)
+        );
+        assert_eq!(
+            error_kind(reader.read_double()),
+            (SyntaxErrorKind::ExpectedDouble, 8)
+        );
+        reader.skip();
+        reader.skip();
+        assert_eq!(
+            error_kind(reader.read_int()),
+            (SyntaxErrorKind::InvalidInteger("1-2".to_owned()), 10)
+        );
+    }
+
+    #[test]
+    fn reads_bools() {
+        let mut reader = StringReader::new("true false yes");
+        assert_eq!(reader.read_bool(), Ok(true));
+        reader.skip();
+        assert_eq!(reader.read_bool(), Ok(false));
+        reader.skip();
+        assert_eq!(
+            error_kind(reader.read_bool()),
+            (SyntaxErrorKind::InvalidBool("yes".to_owned()), 11)
+        );
+        assert_eq!(
+            error_kind(reader.read_bool()),
+            (SyntaxErrorKind::ExpectedBool, 14)
+        );
+    }
+
+    #[test]
+    fn reads_strings() {
+        let mut reader = StringReader::new(r#"word-1.2 "a \"b\" \\ c" 'it''s'"#);
+        assert_eq!(reader.read_string(), Ok("word-1.2".to_owned()));
+        reader.skip();
+        assert_eq!(reader.read_string(), Ok(r#"a "b" \ c"#.to_owned()));
+        reader.skip();
+        assert_eq!(reader.read_string(), Ok("it".to_owned()));
+        assert_eq!(reader.remaining(), "'s'");
+
+        // Unquoted strings stop at characters that aren't allowed in them.
+        let mut reader = StringReader::new("name:value");
+        assert_eq!(reader.read_unquoted_string(), "name");
+        assert_eq!(reader.peek(), Some(':'));
+    }
+
+    #[test]
+    fn invalid_quoted_strings() {
+        assert_eq!(
+            error_kind(StringReader::new("abc").read_quoted_string()),
+            (SyntaxErrorKind::ExpectedStartOfQuote, 0)
+        );
+        assert_eq!(
+            error_kind(StringReader::new(r#""abc"#).read_quoted_string()),
+            (SyntaxErrorKind::ExpectedEndOfQuote, 4)
+        );
+        assert_eq!(
+            error_kind(StringReader::new(r#""a\nb""#).read_quoted_string()),
+            (SyntaxErrorKind::InvalidEscape('n'), 3)
+        );
+    }
+
+    #[test]
+    fn cursor_is_in_bytes() {
+        let mut reader = StringReader::new("\"é\" ü");
+        assert_eq!(reader.read_quoted_string(), Ok("é".to_owned()));
+        assert_eq!(reader.cursor(), 4);
+        reader.skip_whitespace();
+        assert_eq!(reader.peek(), Some('ü'));
+        reader.skip();
+        assert_eq!(reader.cursor(), reader.input().len());
+        reader.skip();
+        assert_eq!(reader.cursor(), reader.input().len());
+    }
+}
@@ -0,0 +1,748 @@
+//! The server configuration, stored as TOML and importable from vanilla's `server.properties`.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anvil::Compression;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::{
+    movement::MovementSettings,
+    world::generator::{FlatGenerator, LevelType},
+};
+
+/// Allowed view and simulation distances (in chunks), same as vanilla.
+pub const DISTANCE_RANGE: std::ops::RangeInclusive<i32> = 3..=32;
+
+const DEFAULT_PORT: u16 = 25565;
+const DEFAULT_RCON_PORT: u16 = 25575;
+const DEFAULT_METRICS_PORT: u16 = 9225;
+
+/// How often the configuration file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address the server listens on.
+    pub address: SocketAddr,
+    /// The message of the day, shown in the server list.
+    pub motd: String,
+    pub max_players: i32,
+    /// Whether only players on the whitelist may join.
+    pub whitelist: bool,
+    /// Whether players are authenticated with Mojang. Not supported yet.
+    pub online_mode: bool,
+    /// Whether players must sign their chat messages. Not supported yet.
+    pub enforce_secure_chat: bool,
+    /// Minimum size (in bytes) of packets to compress, or -1 to disable compression.
+    ///
+    /// Compression is not supported yet.
+    pub compression_threshold: i32,
+    /// View distance (in chunks) of players.
+    pub view_distance: i32,
+    /// Distance (in chunks) around players in which the world is ticked.
+    pub simulation_distance: i32,
+    /// Directory of the world, with vanilla's layout.
+    pub level_name: String,
+    /// Seed of the world generation, a number or any text. A random seed is chosen if empty.
+    pub level_seed: String,
+    /// Generator of the world: `normal`, `flat` or `void`.
+    pub level_type: String,
+    /// Layers of flat worlds, in the format of vanilla's presets, e.g.
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`. The classic
+    /// flat world if empty.
+    pub generator_settings: String,
+    /// Compression of saved chunks: `deflate`, `lz4` or `none`.
+    pub region_file_compression: String,
+    pub rcon: RconConfig,
+    pub query: QueryConfig,
+    pub throttle: ThrottleConfig,
+    pub metrics: MetricsConfig,
+    pub movement: MovementSettings,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), DEFAULT_PORT),
+            motd: "Blazing fast server".to_owned(),
+            max_players: 20,
+            whitelist: false,
+            online_mode: false,
+            enforce_secure_chat: false,
+            compression_threshold: -1,
+            view_distance: 10,
+            simulation_distance: 10,
+            level_name: "world".to_owned(),
+            level_seed: String::new(),
+            level_type: "normal".to_owned(),
+            generator_settings: String::new(),
+            region_file_compression: "deflate".to_owned(),
+            rcon: RconConfig::default(),
+            query: QueryConfig::default(),
+            throttle: ThrottleConfig::default(),
+            metrics: MetricsConfig::default(),
+            movement: MovementSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RconConfig {
+    pub enabled: bool,
+    pub address: SocketAddr,
+    /// Password RCON clients log in with, which must be set to enable RCON.
+    pub password: String,
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), DEFAULT_RCON_PORT),
+            password: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueryConfig {
+    pub enabled: bool,
+    /// UDP address to answer queries on, usually the same as the server's.
+    pub address: SocketAddr,
+}
+
+impl Default for QueryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), DEFAULT_PORT),
+        }
+    }
+}
+
+/// Limits protecting the server from clients opening too many connections or sending too much
+/// data. A limit of 0 disables it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThrottleConfig {
+    /// Maximum number of open connections, including ones still logging in.
+    pub max_connections: u32,
+    /// Maximum number of connections an IP address may open per minute.
+    pub connections_per_minute: u32,
+    /// Maximum number of packets a client may send per second before it is kicked.
+    pub packets_per_second: u32,
+    /// Maximum number of bytes a client may send per second before it is kicked.
+    pub bytes_per_second: u32,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 256,
+            connections_per_minute: 30,
+            packets_per_second: 500,
+            bytes_per_second: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// Address to serve Prometheus metrics on over HTTP, at `/metrics`.
+    pub address: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: SocketAddr::new(Ipv4Addr::LOCALHOST.into(), DEFAULT_METRICS_PORT),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the configuration from the TOML file at `path`.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        toml::from_str(&text).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Reads the configuration from `path`, writing the default configuration there first if
+    /// the file doesn't exist.
+    pub fn load_or_create(path: &Path) -> Result<Self, ConfigError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let config = Self::default();
+        config.save(path)?;
+        tracing::info!("Created default configuration in {}.", path.display());
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let text = toml::to_string_pretty(self)?;
+        std::fs::write(path, text).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Imports a vanilla `server.properties` file, using the defaults for missing properties.
+    pub fn import_properties(path: &Path) -> Result<Self, ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::from_properties(&text)
+    }
+
+    /// Parses the contents of a vanilla `server.properties` file.
+    pub fn from_properties(text: &str) -> Result<Self, ConfigError> {
+        let properties = parse_properties(text);
+        let get = |key: &'static str| properties.get(key).map(String::as_str);
+        let port = |key: &'static str, default: u16| match get(key) {
+            Some(port) => parse(key, port),
+            None => Ok(default),
+        };
+
+        let mut config = Self::default();
+        // An empty server IP means all interfaces.
+        let ip = match get("server-ip") {
+            Some(ip) if !ip.trim().is_empty() => parse::<IpAddr>("server-ip", ip)?,
+            _ => Ipv4Addr::UNSPECIFIED.into(),
+        };
+        config.address = SocketAddr::new(ip, port("server-port", DEFAULT_PORT)?);
+
+        if let Some(motd) = get("motd") {
+            config.motd = motd.to_owned();
+        }
+        if let Some(max_players) = get("max-players") {
+            config.max_players = parse("max-players", max_players)?;
+        }
+        if let Some(whitelist) = get("white-list") {
+            config.whitelist = parse("white-list", whitelist)?;
+        }
+        if let Some(online_mode) = get("online-mode") {
+            config.online_mode = parse("online-mode", online_mode)?;
+        }
+        if let Some(enforce_secure_chat) = get("enforce-secure-chat") {
+            config.enforce_secure_chat = parse("enforce-secure-chat", enforce_secure_chat)?;
+        }
+        if let Some(threshold) = get("network-compression-threshold") {
+            config.compression_threshold = parse("network-compression-threshold", threshold)?;
+        }
+        if let Some(view_distance) = get("view-distance") {
+            config.view_distance = parse("view-distance", view_distance)?;
+        }
+        if let Some(simulation_distance) = get("simulation-distance") {
+            config.simulation_distance = parse("simulation-distance", simulation_distance)?;
+        }
+        if let Some(level_name) = get("level-name") {
+            config.level_name = level_name.to_owned();
+        }
+        if let Some(seed) = get("level-seed") {
+            config.level_seed = seed.to_owned();
+        }
+        if let Some(level_type) = get("level-type") {
+            config.level_type = level_type.to_owned();
+        }
+        // Vanilla's settings are JSON, which isn't supported.
+        if let Some(settings) = get("generator-settings").filter(|text| !text.starts_with('{')) {
+            config.generator_settings = settings.to_owned();
+        }
+        if let Some(compression) = get("region-file-compression") {
+            config.region_file_compression = compression.to_owned();
+        }
+
+        // RCON and query listen on the same IP as the server.
+        if let Some(enabled) = get("enable-rcon") {
+            config.rcon.enabled = parse("enable-rcon", enabled)?;
+        }
+        config.rcon.address = SocketAddr::new(ip, port("rcon.port", DEFAULT_RCON_PORT)?);
+        if let Some(password) = get("rcon.password") {
+            config.rcon.password = password.to_owned();
+        }
+
+        if let Some(enabled) = get("enable-query") {
+            config.query.enabled = parse("enable-query", enabled)?;
+        }
+        config.query.address = SocketAddr::new(ip, port("query.port", DEFAULT_PORT)?);
+
+        if let Some(rate_limit) = get("rate-limit") {
+            config.throttle.packets_per_second = parse("rate-limit", rate_limit)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Checks that all values are in range, so that the server can start with them.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, message: String| Err(ConfigError::Invalid { field, message });
+
+        if self.max_players < 0 {
+            return invalid(
+                "max_players",
+                format!("must not be negative, got {}", self.max_players),
+            );
+        }
+        if self.compression_threshold < -1 {
+            return invalid(
+                "compression_threshold",
+                format!("must be -1 or more, got {}", self.compression_threshold),
+            );
+        }
+        for (field, distance) in [
+            ("view_distance", self.view_distance),
+            ("simulation_distance", self.simulation_distance),
+        ] {
+            if !DISTANCE_RANGE.contains(&distance) {
+                return invalid(
+                    field,
+                    format!(
+                        "must be between {} and {}, got {}",
+                        DISTANCE_RANGE.start(),
+                        DISTANCE_RANGE.end(),
+                        distance
+                    ),
+                );
+            }
+        }
+        if self.level_name.is_empty() {
+            return invalid("level_name", "must not be empty".to_owned());
+        }
+        match LevelType::from_name(&self.level_type) {
+            None => {
+                return invalid(
+                    "level_type",
+                    format!("must be normal, flat or void, got {}", self.level_type),
+                );
+            }
+            Some(LevelType::Flat) => {
+                if let Err(err) = FlatGenerator::from_layers(&self.generator_settings) {
+                    return invalid("generator_settings", err.to_string());
+                }
+            }
+            Some(_) => {}
+        }
+        if Compression::from_name(&self.region_file_compression).is_none() {
+            return invalid(
+                "region_file_compression",
+                format!(
+                    "must be deflate, lz4 or none, got {}",
+                    self.region_file_compression
+                ),
+            );
+        }
+        if self.rcon.enabled && self.rcon.password.is_empty() {
+            return invalid("rcon.password", "must be set to enable RCON".to_owned());
+        }
+        if self.movement.leniency.is_nan() || self.movement.leniency <= 0.0 {
+            return invalid(
+                "movement.leniency",
+                format!("must be positive, got {}", self.movement.leniency),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(key: &'static str, value: &str) -> Result<T, ConfigError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ConfigError::InvalidProperty {
+            key,
+            value: value.to_owned(),
+        })
+}
+
+/// Configuration values given on the command line, which take precedence over the file.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigOverrides {
+    /// Address to listen on, e.g. 0.0.0.0:25565.
+    #[arg(long)]
+    pub address: Option<SocketAddr>,
+    #[arg(long)]
+    pub motd: Option<String>,
+    #[arg(long)]
+    pub max_players: Option<i32>,
+    #[arg(long)]
+    pub online_mode: Option<bool>,
+    /// Minimum size (in bytes) of packets to compress, or -1 to disable compression.
+    #[arg(long, allow_negative_numbers = true)]
+    pub compression_threshold: Option<i32>,
+    #[arg(long)]
+    pub view_distance: Option<i32>,
+    #[arg(long)]
+    pub simulation_distance: Option<i32>,
+}
+
+impl ConfigOverrides {
+    pub fn apply(&self, config: &mut ServerConfig) {
+        if let Some(address) = self.address {
+            config.address = address;
+        }
+        if let Some(motd) = &self.motd {
+            config.motd = motd.clone();
+        }
+        if let Some(max_players) = self.max_players {
+            config.max_players = max_players;
+        }
+        if let Some(online_mode) = self.online_mode {
+            config.online_mode = online_mode;
+        }
+        if let Some(compression_threshold) = self.compression_threshold {
+            config.compression_threshold = compression_threshold;
+        }
+        if let Some(view_distance) = self.view_distance {
+            config.view_distance = view_distance;
+        }
+        if let Some(simulation_distance) = self.simulation_distance {
+            config.simulation_distance = simulation_distance;
+        }
+    }
+}
+
+/// The configuration of a running server, which is reloaded when its file changes.
+///
+/// Values that are only read at startup, like the address, are reported as pending until the
+/// server restarts. Everything else should be read through [`Self::get`] or
+/// [`Self::subscribe`] to pick up changes.
+#[derive(Debug)]
+pub struct LiveConfig {
+    path: PathBuf,
+    overrides: ConfigOverrides,
+    /// The configuration the server started with.
+    startup: Arc<ServerConfig>,
+    current: watch::Sender<Arc<ServerConfig>>,
+}
+
+impl LiveConfig {
+    /// Wraps `config`, loaded from `path` with `overrides` applied.
+    pub fn new(path: PathBuf, overrides: ConfigOverrides, config: ServerConfig) -> Self {
+        let config = Arc::new(config);
+        Self {
+            path,
+            overrides,
+            startup: Arc::clone(&config),
+            current: watch::Sender::new(config),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current configuration.
+    pub fn get(&self) -> Arc<ServerConfig> {
+        Arc::clone(&self.current.borrow())
+    }
+
+    /// The configuration the server started with, for values that need a restart to change.
+    pub fn startup(&self) -> &ServerConfig {
+        &self.startup
+    }
+
+    /// Returns a receiver that is notified whenever the configuration changes.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<ServerConfig>> {
+        self.current.subscribe()
+    }
+
+    /// Reads the configuration file again, keeping the current configuration if it is invalid.
+    pub fn reload(&self) -> Result<ConfigReload, ConfigError> {
+        let mut config = ServerConfig::load(&self.path)?;
+        self.overrides.apply(&mut config);
+        config.validate()?;
+
+        let pending_restart = restart_required_changes(&self.startup, &config);
+        let changed = self.current.send_if_modified(|current| {
+            if **current == config {
+                return false;
+            }
+            *current = Arc::new(config);
+            true
+        });
+
+        Ok(ConfigReload {
+            changed,
+            pending_restart,
+        })
+    }
+
+    /// Changes the configuration file with `edit`, then reloads it.
+    ///
+    /// The command line overrides stay in effect, but aren't saved to the file.
+    pub fn edit(&self, edit: impl FnOnce(&mut ServerConfig)) -> Result<ConfigReload, ConfigError> {
+        let mut config = ServerConfig::load(&self.path)?;
+        edit(&mut config);
+        config.validate()?;
+        config.save(&self.path)?;
+        self.reload()
+    }
+
+    /// Reloads the configuration whenever its file is modified.
+    pub async fn watch_file(&self) -> ! {
+        let modified = || {
+            std::fs::metadata(&self.path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        };
+        let mut last_modified: Option<SystemTime> = modified();
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            let modified = modified();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match self.reload() {
+                Ok(reload) => reload.log(),
+                Err(err) => tracing::warn!("Unable to reload the configuration: {}.", err),
+            }
+        }
+    }
+}
+
+/// The outcome of [reloading](LiveConfig::reload) the configuration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigReload {
+    /// Whether any value changed.
+    pub changed: bool,
+    /// Values that differ from the startup configuration, but only take effect after a restart.
+    pub pending_restart: Vec<&'static str>,
+}
+
+impl ConfigReload {
+    pub fn log(&self) {
+        if self.changed {
+            tracing::info!("Reloaded the configuration.");
+        }
+        if !self.pending_restart.is_empty() {
+            tracing::warn!(
+                "Changes to {} take effect after a restart.",
+                self.pending_restart.join(", ")
+            );
+        }
+    }
+}
+
+/// Lists the values that differ between `old` and `new`, but are only read at startup.
+fn restart_required_changes(old: &ServerConfig, new: &ServerConfig) -> Vec<&'static str> {
+    [
+        ("address", old.address != new.address),
+        ("online_mode", old.online_mode != new.online_mode),
+        (
+            "enforce_secure_chat",
+            old.enforce_secure_chat != new.enforce_secure_chat,
+        ),
+        (
+            "compression_threshold",
+            old.compression_threshold != new.compression_threshold,
+        ),
+        ("view_distance", old.view_distance != new.view_distance),
+        (
+            "simulation_distance",
+            old.simulation_distance != new.simulation_distance,
+        ),
+        ("level_name", old.level_name != new.level_name),
+        ("level_seed", old.level_seed != new.level_seed),
+        ("level_type", old.level_type != new.level_type),
+        (
+            "generator_settings",
+            old.generator_settings != new.generator_settings,
+        ),
+        (
+            "region_file_compression",
+            old.region_file_compression != new.region_file_compression,
+        ),
+        ("rcon", old.rcon != new.rcon),
+        ("query", old.query != new.query),
+        ("metrics", old.metrics != new.metrics),
+        ("movement", old.movement != new.movement),
+    ]
+    .into_iter()
+    .filter_map(|(field, changed)| changed.then_some(field))
+    .collect()
+}
+
+/// Parses Java properties, as written by vanilla: `key=value` lines, with `#` and `!` comments
+/// and backslash escapes.
+fn parse_properties(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim_start)
+        .filter(|line| !line.is_empty() && !line.starts_with(['#', '!']))
+        .map(|line| {
+            let (key, value) = split_property(line);
+            (unescape(key.trim_end()), unescape(value.trim_start()))
+        })
+        .collect()
+}
+
+/// Splits a property line at the first unescaped `=` or `:`.
+fn split_property(line: &str) -> (&str, &str) {
+    let mut escaped = false;
+    for (index, char) in line.char_indices() {
+        match char {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '=' | ':' => return (&line[..index], &line[index + 1..]),
+            _ => {}
+        }
+    }
+    (line, "")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            unescaped.push(char);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                if let Some(char) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    unescaped.push(char);
+                }
+            }
+            Some(char) => unescaped.push(char),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("unable to access {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("invalid configuration file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("unable to serialize configuration: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    #[error("invalid value {value:?} for property {key}")]
+    InvalidProperty { key: &'static str, value: String },
+    #[error("invalid {field}: {message}")]
+    Invalid {
+        field: &'static str,
+        message: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_vanilla_properties() {
+        let properties = r#"#Minecraft server properties
+#Sun Oct 18 12:00:00 UTC 2026
+accepts-transfers=false
+allow-flight=false
+enable-query=true
+enable-rcon=true
+enforce-secure-chat=true
+generator-settings={}
+level-name=my world
+level-seed=-42
+level-type=minecraft\:flat
+max-players=50
+motd=Caf\u00E9 \= fun\: \\o/
+network-compression-threshold=256
+online-mode=false
+query.port=25566
+rate-limit=0
+rcon.password=p\=ss\:w\u00F6rd
+rcon.port=25576
+region-file-compression=lz4
+server-ip=
+server-port=25570
+simulation-distance=8
+  ! not a property
+view-distance = 12
+white-list=true
+"#;
+        let config = ServerConfig::from_properties(properties).unwrap();
+        let any = IpAddr::from(Ipv4Addr::UNSPECIFIED);
+        let expected = ServerConfig {
+            address: SocketAddr::new(any, 25570),
+            motd: "Café = fun: \\o/".to_owned(),
+            max_players: 50,
+            whitelist: true,
+            online_mode: false,
+            enforce_secure_chat: true,
+            compression_threshold: 256,
+            view_distance: 12,
+            simulation_distance: 8,
+            level_name: "my world".to_owned(),
+            level_seed: "-42".to_owned(),
+            level_type: "minecraft:flat".to_owned(),
+            // Vanilla's JSON settings are left out.
+            generator_settings: String::new(),
+            region_file_compression: "lz4".to_owned(),
+            rcon: RconConfig {
+                enabled: true,
+                address: SocketAddr::new(any, 25576),
+                password: "p=ss:wörd".to_owned(),
+            },
+            query: QueryConfig {
+                enabled: true,
+                address: SocketAddr::new(any, 25566),
+            },
+            throttle: ThrottleConfig {
+                packets_per_second: 0,
+                ..ThrottleConfig::default()
+            },
+            ..ServerConfig::default()
+        };
+        assert_eq!(config, expected);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn invalid_properties() {
+        assert!(matches!(
+            ServerConfig::from_properties("max-players=many"),
+            Err(ConfigError::InvalidProperty {
+                key: "max-players",
+                ..
+            })
+        ));
+    }
+}
@@ -1,38 +1,56 @@
 use std::convert::Infallible;
 use std::io::Cursor;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use futures::FutureExt;
+use packet::client::{ConfigurationDisconnectPacket, LoginDisconnectPacket, PlayDisconnectPacket};
 use packet::Packet;
 use packet::PacketDecodeError;
 use packet::PacketDirection;
 use packet::{server::ServerPacket, PacketCheckOutcome, PacketDecodeContext};
 use protocol::buf;
+use protocol::text::TextComponent;
 use protocol::DecodeError;
 use protocol::EncodeError;
 use protocol::{ClientInformation, ConnectionState, Decodable};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream, ToSocketAddrs},
     task::JoinHandle,
 };
+use tracing::Instrument;
 
+use crate::context::ServerContext;
+use crate::metrics::{metrics, HANDLE_PACKET_SPAN};
 use crate::packet_handler::default_packet_handler;
 use crate::packet_handler::PacketHandlerManagerHandle;
 use crate::packet_handler::{PacketHandleError, PacketHandlerManager};
+use crate::player::{change_ticket, GameProfile, Player};
+use crate::throttle::{ConnectionLimit, ConnectionThrottle, RateCounter};
 
 pub const TARGET_PROTOCOL_VERSION: i32 = 767;
 
+/// How long to wait before accepting connections again after failing to, e.g. because the
+/// process ran out of file descriptors.
+pub(crate) const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// Window the packets and bytes a client sends are counted in.
+const TRAFFIC_WINDOW: Duration = Duration::from_secs(1);
+
 pub struct ConnectionManager {
     tcp_listener: TcpListener,
     packet_handler_manager: Arc<Mutex<PacketHandlerManager<'static>>>,
+    connections: ConnectionLimit,
+    server: Arc<ServerContext>,
 }
 
 impl ConnectionManager {
-    pub async fn new<A>(address: A) -> std::io::Result<Self>
+    pub async fn new<A>(address: A, server: Arc<ServerContext>) -> std::io::Result<Self>
     where
         A: ToSocketAddrs,
     {
@@ -45,85 +63,233 @@ impl ConnectionManager {
         Ok(Self {
             tcp_listener: TcpListener::bind(address).await?,
             packet_handler_manager: Arc::new(Mutex::new(packet_handler_manager)),
+            connections: ConnectionLimit::default(),
+            server,
         })
     }
 
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp_listener.local_addr()
+    }
+
+    /// Number of connections open, including ones still logging in.
+    pub fn open_connections(&self) -> u32 {
+        self.connections.open()
+    }
+
     pub async fn listen(&self) -> ! {
+        let mut throttle = ConnectionThrottle::default();
+
         loop {
-            let (socket, addr) = self.tcp_listener.accept().await.unwrap();
+            let (socket, addr) = match self.tcp_listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::error!("Unable to accept connection: {}.", err);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+
+            // Refused connections are closed right away by dropping the socket.
+            let config = self.server.config.get();
+            let limits = &config.throttle;
+            if !throttle.allow(addr.ip(), limits.connections_per_minute) {
+                tracing::info!("Refused connection from {}: connecting too often.", addr);
+                continue;
+            }
+            let Some(slot) = self.connections.try_acquire(limits.max_connections) else {
+                tracing::warn!("Refused connection from {}: too many connections.", addr);
+                continue;
+            };
+
             tracing::info!("Got socket (address {}), establishing connection...", addr);
-            let connection = Connection::new(socket);
-            connection
+            let connection = Connection::new(socket, addr, Arc::clone(&self.server));
+            let handle = connection
                 .start_process(PacketHandlerManagerHandle::new(Arc::clone(
                     &self.packet_handler_manager,
                 )))
                 .await;
+            tokio::spawn(async move {
+                let result = handle.await;
+                drop(slot);
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(err)) => tracing::debug!("Connection to {} failed: {}.", addr, err),
+                    Err(err) => tracing::error!("Connection to {} panicked: {}.", addr, err),
+                }
+            });
         }
     }
 }
 
 pub struct Connection {
     stream: TcpStream,
+    address: SocketAddr,
     buffer: BytesMut,
+    outbound: PacketSender,
+    /// Taken when the connection starts processing.
+    outbound_rx: Option<mpsc::UnboundedReceiver<Outbound>>,
+    /// Packets received in the current window, to kick clients flooding the server.
+    packet_rate: RateCounter,
+    /// Bytes received in the current window.
+    byte_rate: RateCounter,
     pub(crate) state: ConnectionState,
     pub(crate) can_request_status: bool,
     pub(crate) client_information: Option<ClientInformation>,
+    /// Set once the client starts logging in.
+    pub(crate) game_profile: Option<GameProfile>,
+    /// Set once the client finishes configuration.
+    pub(crate) player: Option<Player>,
+    pub(crate) server: Arc<ServerContext>,
 }
 
 impl Connection {
-    pub fn new(stream: TcpStream) -> Self {
+    pub fn new(stream: TcpStream, address: SocketAddr, server: Arc<ServerContext>) -> Self {
+        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+        metrics().connection_entered(ConnectionState::Handshaking);
+
         Self {
             stream,
-            buffer: BytesMut::zeroed(4096),
+            address,
+            buffer: BytesMut::with_capacity(4096),
+            outbound: PacketSender {
+                tx: outbound_tx,
+                address,
+            },
+            outbound_rx: Some(outbound_rx),
+            packet_rate: RateCounter::new(TRAFFIC_WINDOW),
+            byte_rate: RateCounter::new(TRAFFIC_WINDOW),
             state: ConnectionState::Handshaking,
             can_request_status: false,
             client_information: None,
+            game_profile: None,
+            player: None,
+            server,
         }
     }
 
+    /// The address of the client.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub(crate) fn set_state(&mut self, state: ConnectionState) {
+        metrics().connection_left(self.state);
+        metrics().connection_entered(state);
+        self.state = state;
+    }
+
+    /// Returns a [`PacketSender`] for queueing packets to this connection.
+    pub fn sender(&self) -> PacketSender {
+        self.outbound.clone()
+    }
+
     pub async fn start_process(
         mut self,
         mut packet_handler_manager_handle: PacketHandlerManagerHandle<'static>,
     ) -> JoinHandle<ConnectionResult<()>> {
         tokio::spawn(async move {
-            loop {
-                tracing::trace!("Waiting for packet...");
-                match self.stream.read(&mut self.buffer).await {
-                    Ok(0) => {
+            let mut outbound_rx = self
+                .outbound_rx
+                .take()
+                .expect("connection should only be started once");
+            let result = self
+                .process(&mut packet_handler_manager_handle, &mut outbound_rx)
+                .await;
+            if let Err(ConnectionError::RateLimited(limit)) = &result {
+                tracing::info!(
+                    "Kicked {} for exceeding the {} rate limit.",
+                    self.address,
+                    limit
+                );
+                let reason = TextComponent::translatable("disconnect.exceeded_packet_rate", []);
+                if let Err(err) = self.disconnect(&reason).await {
+                    tracing::trace!("Unable to send disconnect reason: {}.", err);
+                }
+            }
+            self.disconnected();
+            result
+        })
+    }
+
+    async fn process(
+        &mut self,
+        packet_handler_manager_handle: &mut PacketHandlerManagerHandle<'static>,
+        outbound_rx: &mut mpsc::UnboundedReceiver<Outbound>,
+    ) -> ConnectionResult<()> {
+        // Players are ticked by their connection, when the tick thread starts a tick.
+        let mut ticks = self.server.scheduler.ticks();
+        loop {
+            tracing::trace!("Waiting for packet...");
+            tokio::select! {
+                packet = self.read_packet() => {
+                    let Some(packet) = packet? else {
                         tracing::trace!("Remote has closed.");
                         return Ok(());
+                    };
+                    let span = tracing::trace_span!(HANDLE_PACKET_SPAN, state = ?self.state);
+                    packet_handler_manager_handle
+                        .handle_packet(packet, self)
+                        .instrument(span)
+                        .await?;
+                }
+                Some(outbound) = outbound_rx.recv() => match outbound {
+                    Outbound::Frame { packet_id, frame } => {
+                        self.stream.write_all(&frame).await?;
+                        self.packet_sent(packet_id, frame.len());
                     }
-                    Ok(n) => {
-                        tracing::trace!("Received {} bytes, attempting to read packet...", n);
-                        if let Some(packet) = self.read_packet().await? {
-                            packet_handler_manager_handle
-                                .handle_packet(packet, &mut self)
-                                .await?;
-                        }
+                    Outbound::Close => {
+                        tracing::trace!("Closing connection.");
+                        return Ok(());
                     }
-                    Err(err) => {
-                        tracing::warn!("Unexpected socket error: {}.", err);
-                        return Err(err.into());
+                },
+                Ok(()) = ticks.changed() => {
+                    if let Some(player) = &mut self.player {
+                        player
+                            .tick(&self.server.world)
+                            .map_err(PacketHandleError::from)?;
                     }
                 }
             }
-        })
+        }
     }
 
+    /// Cleans up after the connection is closed.
+    fn disconnected(&mut self) {
+        metrics().connection_left(self.state);
+        if let Some(player) = self.player.take() {
+            tracing::info!("{} left the game.", player.username());
+            self.server.world.players().remove(player.entity_id());
+            let ticket = player.chunk_view().ticket();
+            change_ticket(&self.server.scheduler, None, Some(ticket));
+        }
+    }
+
+    /// Reads the next packet, failing if the client exceeds the rate limits.
     pub async fn read_packet(&mut self) -> ConnectionResult<Option<ServerPacket<'static>>> {
         loop {
             if let Some(packet) = self.parse_packet()? {
                 tracing::trace!("Got packet {:?}.", packet);
+                let limit = self.server.config.get().throttle.packets_per_second;
+                if !self.packet_rate.record(1, limit) {
+                    return Err(ConnectionError::RateLimited("packet"));
+                }
                 return Ok(Some(packet));
             }
 
-            if self.stream.read_buf(&mut self.buffer).await? == 0 {
+            let len = self.stream.read_buf(&mut self.buffer).await?;
+            metrics().bytes_received.inc_by(len as u64);
+            if len == 0 {
                 if self.buffer.is_empty() {
                     return Ok(None);
                 } else {
                     return Err(ConnectionError::ResetByPeer);
                 }
             }
+            let limit = self.server.config.get().throttle.bytes_per_second;
+            if !self.byte_rate.record(len as u32, limit) {
+                return Err(ConnectionError::RateLimited("byte"));
+            }
         }
     }
 
@@ -132,47 +298,146 @@ impl Connection {
 
         match packet::check_packet(&mut buf) {
             Ok(PacketCheckOutcome::Ok { len, packet_id }) => {
-                let full_len = buf.position() as usize;
+                let full_len = buf.position() as usize + len;
                 let packet = ServerPacket::decode(
-                    &mut buf.copy_to_bytes(len.try_into().unwrap()),
+                    &mut buf.copy_to_bytes(len),
                     PacketDecodeContext {
                         connection_state: self.state,
                         packet_id,
                         direction: PacketDirection::Server,
                     },
-                )?;
+                )
+                .inspect_err(|err| metrics().decode_error(err))?;
                 self.buffer.advance(full_len);
+                metrics().packet_received(self.state, packet_id);
                 Ok(Some(packet))
             }
             Ok(PacketCheckOutcome::Incomplete) => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => {
+                metrics().decode_error(&e);
+                Err(e.into())
+            }
         }
     }
 
+    /// Sends the client the reason it is disconnected for, in the packet of the current state.
+    ///
+    /// Clients that haven't started logging in have no way to show a reason, so they are sent
+    /// nothing.
+    pub async fn disconnect(&mut self, reason: &TextComponent<'_>) -> SendPacketResult<()> {
+        match self.state {
+            ConnectionState::Handshaking | ConnectionState::Status => Ok(()),
+            ConnectionState::Login => {
+                let reason = serde_json::to_string(reason).expect("text components are valid JSON");
+                self.send_packet(&LoginDisconnectPacket {
+                    reason: reason.into(),
+                })
+                .await
+            }
+            ConnectionState::Configuration => {
+                self.send_packet(&ConfigurationDisconnectPacket {
+                    reason: reason.to_network_nbt(),
+                })
+                .await
+            }
+            ConnectionState::Play => {
+                self.send_packet(&PlayDisconnectPacket {
+                    reason: reason.to_network_nbt(),
+                })
+                .await
+            }
+        }
+    }
+
+    /// Sends a packet directly, bypassing the queue of packets sent through [`PacketSender`]s.
     pub async fn send_packet<P: Packet + std::fmt::Debug>(
         &mut self,
         packet: &P,
     ) -> SendPacketResult<()> {
-        let mut encoded_packet = BytesMut::with_capacity(4096);
-        packet.encode(&mut encoded_packet, ())?;
-        let mut packet_id_buf = BytesMut::with_capacity(5);
-        buf::put_varint(&mut packet_id_buf, packet.get_id());
-        let mut len_buf = BytesMut::with_capacity(5);
-        buf::put_varint(
-            &mut len_buf,
-            (encoded_packet.len() + packet_id_buf.len())
-                .try_into()
-                .unwrap(),
-        );
+        let frame = encode_packet(packet)?;
 
         tracing::trace!("Sending packet {:?}...", packet);
 
-        self.stream.write_all_buf(&mut len_buf).await?;
-        self.stream.write_all_buf(&mut packet_id_buf).await?;
-        self.stream.write_all(&encoded_packet).await?;
+        self.stream.write_all(&frame).await?;
+        self.packet_sent(packet.get_id(), frame.len());
 
         Ok(())
     }
+
+    fn packet_sent(&self, packet_id: i32, len: usize) {
+        metrics().packet_sent(self.state, packet_id);
+        metrics().bytes_sent.inc_by(len as u64);
+    }
+}
+
+/// What is queued to be written to a [`Connection`].
+#[derive(Debug)]
+enum Outbound {
+    Frame {
+        packet_id: i32,
+        frame: Bytes,
+    },
+    /// Closes the connection once the frames queued before are written.
+    Close,
+}
+
+/// A handle for queueing packets to a [`Connection`], which can be cloned and sent to other tasks.
+#[derive(Debug, Clone)]
+pub struct PacketSender {
+    tx: mpsc::UnboundedSender<Outbound>,
+    /// The address of the client.
+    address: SocketAddr,
+}
+
+impl PacketSender {
+    /// The address of the client, e.g. for IP bans.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn send_packet<P: Packet + std::fmt::Debug>(&self, packet: &P) -> SendPacketResult<()> {
+        let frame = encode_packet(packet)?;
+
+        tracing::trace!("Queueing packet {:?}...", packet);
+
+        self.tx
+            .send(Outbound::Frame {
+                packet_id: packet.get_id(),
+                frame,
+            })
+            .map_err(|_| PacketSendError::Closed)
+    }
+
+    /// Closes the connection after the packets queued so far are sent, e.g. after a disconnect
+    /// packet.
+    pub fn close(&self) {
+        let _ = self.tx.send(Outbound::Close);
+    }
+
+    /// Whether the connection this sender belongs to is closed.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// Encodes a packet into a frame, prefixed with its length and packet ID.
+fn encode_packet<P: Packet>(packet: &P) -> Result<Bytes, EncodeError<Infallible>> {
+    let mut encoded_packet = BytesMut::with_capacity(4096);
+    packet.encode(&mut encoded_packet, ())?;
+    let mut packet_id_buf = BytesMut::with_capacity(5);
+    buf::put_varint(&mut packet_id_buf, packet.get_id());
+
+    let mut frame = BytesMut::with_capacity(5 + packet_id_buf.len() + encoded_packet.len());
+    buf::put_varint(
+        &mut frame,
+        (encoded_packet.len() + packet_id_buf.len())
+            .try_into()
+            .unwrap(),
+    );
+    frame.extend_from_slice(&packet_id_buf);
+    frame.extend_from_slice(&encoded_packet);
+
+    Ok(frame.freeze())
 }
 
 pub type ConnectionResult<T> = Result<T, ConnectionError>;
@@ -187,6 +452,8 @@ pub enum ConnectionError {
     Io(#[from] std::io::Error),
     #[error("connection reset by peer")]
     ResetByPeer,
+    #[error("exceeded the {0} rate limit")]
+    RateLimited(&'static str),
 }
 
 pub type SendPacketResult<T> = Result<T, PacketSendError>;
@@ -197,4 +464,46 @@ pub enum PacketSendError {
     PacketEncode(#[from] EncodeError<Infallible>),
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    #[error("connection is closed")]
+    Closed,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use packet::client::ClientPlayPacket;
+
+    use super::*;
+
+    /// The packets queued by a [`PacketSender`] without a connection.
+    pub(crate) struct SentPackets(mpsc::UnboundedReceiver<Outbound>);
+
+    impl SentPackets {
+        /// Makes a sender whose packets are kept to be taken.
+        pub(crate) fn new() -> (PacketSender, Self) {
+            let (tx, rx) = mpsc::unbounded_channel();
+            let sender = PacketSender {
+                tx,
+                address: SocketAddr::from(([127, 0, 0, 1], 25565)),
+            };
+            (sender, Self(rx))
+        }
+
+        /// Takes the packets queued since the last time, decoded as play packets.
+        pub(crate) fn take(&mut self) -> Vec<ClientPlayPacket<'static>> {
+            let mut packets = Vec::new();
+            while let Ok(Outbound::Frame { packet_id, frame }) = self.0.try_recv() {
+                let mut frame = frame;
+                buf::get_varint(&mut frame).unwrap();
+                buf::get_varint(&mut frame).unwrap();
+                let ctx = PacketDecodeContext {
+                    connection_state: ConnectionState::Play,
+                    packet_id,
+                    direction: PacketDirection::Client,
+                };
+                packets.push(ClientPlayPacket::decode(&mut frame, ctx).unwrap());
+                assert!(!frame.has_remaining(), "{packet_id:#x} has trailing data");
+            }
+            packets
+        }
+    }
 }
@@ -0,0 +1,203 @@
+//! The interactive server console, which runs commands with full permissions.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{Arc, Mutex},
+};
+
+use protocol::text::TextComponent;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    CompletionType, Config, Context, Editor, ExternalPrinter, Helper,
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::{
+    command::{self, CommandSender},
+    context::ServerContext,
+    player::MAX_PERMISSION_LEVEL,
+};
+
+const PROMPT: &str = "> ";
+const MAX_HISTORY_SIZE: usize = 1000;
+
+/// The console as a command sender.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConsoleSender;
+
+impl CommandSender for ConsoleSender {
+    fn name(&self) -> &str {
+        "Server"
+    }
+
+    fn send_message(&mut self, message: &TextComponent<'_>) {
+        tracing::info!("{}", message.to_plain_text(&server_assets::translation));
+    }
+
+    fn permission_level(&self) -> u8 {
+        MAX_PERMISSION_LEVEL
+    }
+}
+
+/// Log output that is printed above the console prompt once the console runs, so that it
+/// doesn't mess up the line being typed.
+///
+/// Until then, or if the console can't print, output goes to stdout.
+#[derive(Clone, Default)]
+pub struct ConsoleOutput {
+    printer: Arc<Mutex<Option<Box<dyn ExternalPrinter + Send>>>>,
+}
+
+impl<'a> MakeWriter<'a> for ConsoleOutput {
+    type Writer = ConsoleWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ConsoleWriter {
+            output: self.clone(),
+            buffer: Vec::new(),
+        }
+    }
+}
+
+/// Buffers a log event, printing it when dropped.
+pub struct ConsoleWriter {
+    output: ConsoleOutput,
+    buffer: Vec<u8>,
+}
+
+impl Write for ConsoleWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ConsoleWriter {
+    fn drop(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut printer = self.output.printer.lock().unwrap();
+        if let Some(printer) = printer.as_mut() {
+            let message = String::from_utf8_lossy(&self.buffer).into_owned();
+            if printer.print(message).is_ok() {
+                return;
+            }
+        }
+        let _ = io::stdout().write_all(&self.buffer);
+    }
+}
+
+/// Completes commands with the dispatcher's suggestions.
+struct ConsoleHelper {
+    server: Arc<ServerContext>,
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let input = line.strip_prefix('/').unwrap_or(line);
+        let offset = line.len() - input.len();
+
+        let suggestions = self
+            .server
+            .commands
+            .suggest(&self.server, &ConsoleSender, input);
+        let candidates = suggestions
+            .matches
+            .into_iter()
+            .map(|suggestion| Pair {
+                display: suggestion.text.clone(),
+                replacement: suggestion.text,
+            })
+            .collect();
+        Ok((offset + suggestions.start, candidates))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+/// Reads commands from stdin, with line editing, history and tab completion.
+pub struct Console {
+    server: Arc<ServerContext>,
+    editor: Editor<ConsoleHelper, DefaultHistory>,
+}
+
+impl Console {
+    /// Sets up the console, printing `output` above its prompt from now on.
+    pub fn new(server: Arc<ServerContext>, output: &ConsoleOutput) -> rustyline::Result<Self> {
+        let config = Config::builder()
+            .max_history_size(MAX_HISTORY_SIZE)?
+            .auto_add_history(true)
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(ConsoleHelper {
+            server: Arc::clone(&server),
+        }));
+
+        match editor.create_external_printer() {
+            Ok(printer) => *output.printer.lock().unwrap() = Some(Box::new(printer)),
+            Err(err) => tracing::debug!("Unable to print above the console prompt: {}.", err),
+        }
+
+        Ok(Self { server, editor })
+    }
+
+    /// Runs commands until the server stops or stdin is closed, blocking the current thread.
+    ///
+    /// Ctrl-C or Ctrl-D at the prompt stop the server.
+    pub fn run(mut self) {
+        while !self.server.is_shutdown_requested() {
+            let line = match self.editor.readline(PROMPT) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    self.server.request_shutdown();
+                    break;
+                }
+                Err(ReadlineError::Eof) => {
+                    // Without a terminal, e.g. when running as a service, stdin may just be empty.
+                    if io::stdin().is_terminal() {
+                        self.server.request_shutdown();
+                    }
+                    break;
+                }
+                Err(err) => {
+                    tracing::error!("Unable to read from the console: {}.", err);
+                    break;
+                }
+            };
+
+            let line = line.trim();
+            let input = line.strip_prefix('/').unwrap_or(line);
+            if !input.is_empty() {
+                command::run_command(&self.server, &mut ConsoleSender, input);
+            }
+        }
+    }
+}
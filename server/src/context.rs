@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::{
+    access::AccessControl,
+    chat::ChatSettings,
+    command::{builtin, moderation, CommandDispatcher},
+    config::LiveConfig,
+    movement::MovementSettings,
+    tick::{Scheduler, TickStats},
+    world::World,
+};
+
+/// Server state shared by all connections.
+#[derive(Debug)]
+pub struct ServerContext {
+    pub world: Arc<World>,
+    pub config: LiveConfig,
+    pub access: AccessControl,
+    pub movement: MovementSettings,
+    pub chat: ChatSettings,
+    pub commands: CommandDispatcher,
+    /// Runs tasks on the tick thread.
+    pub scheduler: Scheduler,
+    pub tick_stats: TickStats,
+    /// Set once the server should stop.
+    shutdown: watch::Sender<bool>,
+}
+
+impl ServerContext {
+    pub fn new(world: Arc<World>, config: LiveConfig, access: AccessControl) -> Self {
+        let mut commands = CommandDispatcher::new();
+        builtin::register(&mut commands);
+        moderation::register(&mut commands);
+
+        Self {
+            world,
+            config,
+            access,
+            movement: MovementSettings::default(),
+            chat: ChatSettings::default(),
+            commands,
+            scheduler: Scheduler::new(),
+            tick_stats: TickStats::default(),
+            shutdown: watch::Sender::new(false),
+        }
+    }
+
+    /// Asks the server to stop, e.g. from the `stop` command.
+    pub fn request_shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutdown_requested(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Waits until the server is asked to stop.
+    pub async fn shutdown_requested(&self) {
+        let mut shutdown = self.shutdown.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = shutdown.wait_for(|&shutdown| shutdown).await;
+    }
+}
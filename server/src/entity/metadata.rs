@@ -0,0 +1,311 @@
+//! Entity metadata, the indexed values vanilla keeps in its "synched entity data" and clients
+//! use to render entities.
+//!
+//! Each entry has an index, fixed per entity type, and a value of one of the serializers below.
+//! Entries are encoded one after the other as the index, the serializer ID and the value, and
+//! the list ends with an index of `0xFF`.
+
+use std::collections::BTreeMap;
+
+use bytes::BufMut;
+use packet::Slot;
+use protocol::{
+    buf::{self, Encodable},
+    BlockPosition, NetworkNbt,
+};
+use uuid::Uuid;
+
+use crate::world::BlockState;
+
+/// Index ending the list of entries.
+const END_INDEX: u8 = 0xFF;
+
+/// Flags of all entities, see [`flags`].
+pub const FLAGS: u8 = 0;
+/// Ticks of air left, defaulting to 300.
+pub const AIR_TICKS: u8 = 1;
+pub const CUSTOM_NAME: u8 = 2;
+pub const CUSTOM_NAME_VISIBLE: u8 = 3;
+pub const SILENT: u8 = 4;
+pub const NO_GRAVITY: u8 = 5;
+pub const POSE: u8 = 6;
+pub const TICKS_FROZEN: u8 = 7;
+/// Item stack of item entities.
+pub const ITEM: u8 = 8;
+/// Block position falling blocks started falling from.
+pub const FALLING_BLOCK_SPAWN_POSITION: u8 = 8;
+
+/// Bits of the [`FLAGS`] entry.
+pub mod flags {
+    pub const ON_FIRE: i8 = 0x01;
+    pub const CROUCHING: i8 = 0x02;
+    pub const SPRINTING: i8 = 0x08;
+    pub const SWIMMING: i8 = 0x10;
+    pub const INVISIBLE: i8 = 0x20;
+    pub const GLOWING: i8 = 0x40;
+    pub const FLYING_WITH_ELYTRA: i8 = -0x80;
+}
+
+/// Poses of entities, for the [`POSE`] entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pose {
+    #[default]
+    Standing,
+    FallFlying,
+    Sleeping,
+    Swimming,
+    SpinAttack,
+    Sneaking,
+    LongJumping,
+    Dying,
+    Croaking,
+    UsingTongue,
+    Sitting,
+    Roaring,
+    Sniffing,
+    Emerging,
+    Digging,
+    Sliding,
+    Shooting,
+    Inhaling,
+}
+
+/// The value of a metadata entry.
+///
+/// Values whose serializers aren't used by the server yet, e.g. particles or villager data,
+/// are missing.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Byte(i8),
+    VarInt(i32),
+    VarLong(i64),
+    Float(f32),
+    String(String),
+    TextComponent(NetworkNbt<'static>),
+    OptionalTextComponent(Option<NetworkNbt<'static>>),
+    Slot(Slot),
+    Boolean(bool),
+    Rotations(f32, f32, f32),
+    BlockPosition(BlockPosition),
+    OptionalBlockPosition(Option<BlockPosition>),
+    /// Direction as its 3D data value: down, up, north, south, west, east.
+    Direction(i32),
+    OptionalUuid(Option<Uuid>),
+    BlockState(BlockState),
+    OptionalBlockState(Option<BlockState>),
+    Pose(Pose),
+    Vector3(f32, f32, f32),
+    Quaternion(f32, f32, f32, f32),
+}
+
+impl MetadataValue {
+    /// ID of the serializer of this value.
+    fn serializer_id(&self) -> i32 {
+        match self {
+            Self::Byte(_) => 0,
+            Self::VarInt(_) => 1,
+            Self::VarLong(_) => 2,
+            Self::Float(_) => 3,
+            Self::String(_) => 4,
+            Self::TextComponent(_) => 5,
+            Self::OptionalTextComponent(_) => 6,
+            Self::Slot(_) => 7,
+            Self::Boolean(_) => 8,
+            Self::Rotations(..) => 9,
+            Self::BlockPosition(_) => 10,
+            Self::OptionalBlockPosition(_) => 11,
+            Self::Direction(_) => 12,
+            Self::OptionalUuid(_) => 13,
+            Self::BlockState(_) => 14,
+            Self::OptionalBlockState(_) => 15,
+            Self::Pose(_) => 21,
+            Self::Vector3(..) => 29,
+            Self::Quaternion(..) => 30,
+        }
+    }
+
+    fn encode(&self, buf: &mut dyn BufMut) {
+        match self {
+            Self::Byte(value) => buf.put_i8(*value),
+            Self::VarInt(value) | Self::Direction(value) => buf::put_varint(buf, *value),
+            Self::VarLong(value) => buf::put_varlong(buf, *value),
+            Self::Float(value) => buf.put_f32(*value),
+            Self::String(value) => buf::put_string(buf, value),
+            Self::TextComponent(value) => buf.put_slice(value.as_bytes()),
+            Self::OptionalTextComponent(value) => {
+                buf::put_bool(buf, value.is_some());
+                if let Some(value) = value {
+                    buf.put_slice(value.as_bytes());
+                }
+            }
+            Self::Slot(slot) => slot.encode(buf, ()).expect("slots should always encode"),
+            Self::Boolean(value) => buf::put_bool(buf, *value),
+            Self::Rotations(x, y, z) | Self::Vector3(x, y, z) => {
+                buf.put_f32(*x);
+                buf.put_f32(*y);
+                buf.put_f32(*z);
+            }
+            Self::BlockPosition(position) => buf.put_i64(position.to_packed()),
+            Self::OptionalBlockPosition(position) => {
+                buf::put_bool(buf, position.is_some());
+                if let Some(position) = position {
+                    buf.put_i64(position.to_packed());
+                }
+            }
+            Self::OptionalUuid(uuid) => {
+                buf::put_bool(buf, uuid.is_some());
+                if let Some(uuid) = uuid {
+                    buf::put_uuid(buf, uuid);
+                }
+            }
+            Self::BlockState(block) => buf::put_varint(buf, block.id() as i32),
+            // 0 is air, which stands for no block.
+            Self::OptionalBlockState(block) => {
+                buf::put_varint(buf, block.map_or(0, |block| block.id() as i32))
+            }
+            Self::Pose(pose) => buf::put_varint(buf, *pose as i32),
+            Self::Quaternion(x, y, z, w) => {
+                buf.put_f32(*x);
+                buf.put_f32(*y);
+                buf.put_f32(*z);
+                buf.put_f32(*w);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MetadataEntry {
+    value: MetadataValue,
+    /// Whether the value changed since it was last sent.
+    dirty: bool,
+}
+
+/// The metadata entries of an entity, remembering which ones changed so that only those are
+/// sent to clients.
+///
+/// Entries that were never set are left to clients' defaults.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EntityMetadata {
+    entries: BTreeMap<u8, MetadataEntry>,
+}
+
+impl EntityMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: u8) -> Option<&MetadataValue> {
+        self.entries.get(&index).map(|entry| &entry.value)
+    }
+
+    /// Sets the entry at `index`, to be sent to clients if its value changed.
+    ///
+    /// # Panics
+    /// Panics if `index` is `0xFF`, which ends the list of entries.
+    pub fn set(&mut self, index: u8, value: MetadataValue) {
+        assert_ne!(index, END_INDEX, "metadata index 0xFF ends the entries");
+        if self.get(index) == Some(&value) {
+            return;
+        }
+        self.entries
+            .insert(index, MetadataEntry { value, dirty: true });
+    }
+
+    /// Sets or clears `flag` in the [`FLAGS`] entry.
+    pub fn set_flag(&mut self, flag: i8, enabled: bool) {
+        let flags = match self.get(FLAGS) {
+            Some(&MetadataValue::Byte(flags)) => flags,
+            _ => 0,
+        };
+        let flags = if enabled { flags | flag } else { flags & !flag };
+        self.set(FLAGS, MetadataValue::Byte(flags));
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.entries.values().any(|entry| entry.dirty)
+    }
+
+    /// Encodes all entries, as sent to players that start seeing the entity.
+    pub fn encode_all(&self) -> Vec<u8> {
+        encode_entries(self.entries.iter())
+    }
+
+    /// Encodes the entries that changed since the last call, then marks them as sent. Returns
+    /// `None` if none changed.
+    pub fn take_dirty(&mut self) -> Option<Vec<u8>> {
+        if !self.is_dirty() {
+            return None;
+        }
+        let encoded = encode_entries(self.entries.iter().filter(|(_, entry)| entry.dirty));
+        for entry in self.entries.values_mut() {
+            entry.dirty = false;
+        }
+        Some(encoded)
+    }
+}
+
+fn encode_entries<'a>(entries: impl Iterator<Item = (&'a u8, &'a MetadataEntry)>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (&index, entry) in entries {
+        buf.put_u8(index);
+        buf::put_varint(&mut buf, entry.value.serializer_id());
+        entry.value.encode(&mut buf);
+    }
+    buf.put_u8(END_INDEX);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_changed_entries() {
+        let mut metadata = EntityMetadata::new();
+        assert!(metadata.is_empty());
+        assert_eq!(metadata.take_dirty(), None);
+
+        metadata.set(AIR_TICKS, MetadataValue::VarInt(300));
+        metadata.set(POSE, MetadataValue::Pose(Pose::Sneaking));
+        assert!(metadata.is_dirty());
+        assert_eq!(
+            metadata.take_dirty(),
+            Some(vec![AIR_TICKS, 1, 0xAC, 0x02, POSE, 21, 5, 0xFF])
+        );
+        assert!(!metadata.is_dirty());
+
+        // Setting the same value again doesn't send it.
+        metadata.set(AIR_TICKS, MetadataValue::VarInt(300));
+        assert_eq!(metadata.take_dirty(), None);
+        metadata.set(AIR_TICKS, MetadataValue::VarInt(1));
+        assert_eq!(metadata.take_dirty(), Some(vec![AIR_TICKS, 1, 1, 0xFF]));
+
+        // Players that start seeing the entity get every entry.
+        assert_eq!(metadata.encode_all(), [AIR_TICKS, 1, 1, POSE, 21, 5, 0xFF]);
+    }
+
+    #[test]
+    fn sets_flags() {
+        let mut metadata = EntityMetadata::new();
+        metadata.set_flag(flags::SPRINTING, true);
+        metadata.set_flag(flags::FLYING_WITH_ELYTRA, true);
+        assert_eq!(metadata.get(FLAGS), Some(&MetadataValue::Byte(-0x78)));
+        metadata.take_dirty();
+
+        metadata.set_flag(flags::GLOWING, false);
+        assert!(!metadata.is_dirty());
+        metadata.set_flag(flags::SPRINTING, false);
+        assert_eq!(metadata.take_dirty(), Some(vec![FLAGS, 0, 0x80, 0xFF]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_end_index() {
+        EntityMetadata::new().set(END_INDEX, MetadataValue::Boolean(true));
+    }
+}
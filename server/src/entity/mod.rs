@@ -0,0 +1,327 @@
+//! Entities other than players, e.g. items or falling blocks, and syncing them to the players
+//! that see them.
+//!
+//! Players are entities too, but are kept in the [`PlayerList`](crate::player_list::PlayerList)
+//! and seen by every player in their world.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use packet::Slot;
+use protocol::BlockPosition;
+use uuid::Uuid;
+
+use crate::{
+    physics::{self, BoundingBox, Physics},
+    player::next_entity_id,
+    world::{BlockState, ChunkPos, Position, Rotation, World},
+};
+
+pub mod metadata;
+mod tracker;
+
+pub use metadata::{EntityMetadata, MetadataValue, Pose};
+
+use tracker::TrackedEntity;
+
+/// A kind of entity, with the properties vanilla gives it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EntityType {
+    pub name: &'static str,
+    /// ID in the `minecraft:entity_type` registry.
+    pub id: i32,
+    pub width: f64,
+    pub height: f64,
+    /// Distance (in chunks) from which players see entities of this type, unless their view
+    /// distance is shorter.
+    pub tracking_range: i32,
+    /// Ticks between two movement updates sent to players.
+    pub update_interval: u64,
+    pub physics: Physics,
+}
+
+impl EntityType {
+    pub const ARROW: Self = Self::new(
+        "minecraft:arrow",
+        4,
+        0.5,
+        0.5,
+        4,
+        20,
+        Physics::new(0.05, 0.99, 0.0),
+    );
+    pub const EXPERIENCE_ORB: Self = Self::new(
+        "minecraft:experience_orb",
+        38,
+        0.5,
+        0.5,
+        6,
+        20,
+        Physics::new(0.03, 0.98, 0.0),
+    );
+    pub const FALLING_BLOCK: Self = Self::new(
+        "minecraft:falling_block",
+        40,
+        0.98,
+        0.98,
+        10,
+        20,
+        Physics::new(0.04, 0.98, 0.0),
+    );
+    pub const ITEM: Self = Self::new(
+        "minecraft:item",
+        58,
+        0.25,
+        0.25,
+        6,
+        20,
+        Physics::new(0.04, 0.98, 0.0),
+    );
+    pub const SNOWBALL: Self = Self::new(
+        "minecraft:snowball",
+        97,
+        0.25,
+        0.25,
+        4,
+        10,
+        Physics::new(0.03, 0.99, 0.0),
+    );
+    pub const TNT: Self = Self::new(
+        "minecraft:tnt",
+        106,
+        0.98,
+        0.98,
+        10,
+        10,
+        Physics::new(0.04, 0.98, 0.0),
+    );
+    pub const PLAYER: Self = Self::new(
+        "minecraft:player",
+        128,
+        0.6,
+        1.8,
+        32,
+        2,
+        Physics::new(0.08, 0.98, 0.6),
+    );
+
+    const fn new(
+        name: &'static str,
+        id: i32,
+        width: f64,
+        height: f64,
+        tracking_range: i32,
+        update_interval: u64,
+        physics: Physics,
+    ) -> Self {
+        Self {
+            name,
+            id,
+            width,
+            height,
+            tracking_range,
+            update_interval,
+            physics,
+        }
+    }
+
+    /// The bounding box of an entity of this type at `position`.
+    pub fn bounding_box(&self, position: Position) -> BoundingBox {
+        let half_width = self.width / 2.0;
+        BoundingBox::new(
+            Position::new(position.x - half_width, position.y, position.z - half_width),
+            Position::new(
+                position.x + half_width,
+                position.y + self.height,
+                position.z + half_width,
+            ),
+        )
+    }
+}
+
+/// A velocity, in blocks per tick.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Velocity {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Velocity {
+    /// Largest speed along an axis that can be sent to clients.
+    const MAX_PROTOCOL_SPEED: f64 = 3.9;
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The velocity as sent to clients, in 1/8000 of a block per tick along each axis.
+    pub fn to_protocol(&self) -> [i16; 3] {
+        [self.x, self.y, self.z].map(|speed| {
+            (speed.clamp(-Self::MAX_PROTOCOL_SPEED, Self::MAX_PROTOCOL_SPEED) * 8000.0) as i16
+        })
+    }
+}
+
+/// An entity, as simulated by the server.
+#[derive(Debug, Clone)]
+pub struct Entity {
+    id: i32,
+    uuid: Uuid,
+    kind: EntityType,
+    pub position: Position,
+    pub rotation: Rotation,
+    pub head_yaw: f32,
+    pub velocity: Velocity,
+    pub on_ground: bool,
+    /// Type-specific value sent when spawning the entity, e.g. the block of falling blocks.
+    pub data: i32,
+    pub metadata: EntityMetadata,
+}
+
+impl Entity {
+    /// Makes an entity of type `kind` at `position`, with a new ID and a random UUID.
+    pub fn new(kind: EntityType, position: Position) -> Self {
+        Self {
+            id: next_entity_id(),
+            uuid: Uuid::new_v4(),
+            kind,
+            position,
+            rotation: Rotation::default(),
+            head_yaw: 0.0,
+            velocity: Velocity::default(),
+            on_ground: false,
+            data: 0,
+            metadata: EntityMetadata::new(),
+        }
+    }
+
+    /// Makes an item entity holding `item` at `position`.
+    pub fn item(position: Position, item: Slot) -> Self {
+        let mut entity = Self::new(EntityType::ITEM, position);
+        entity
+            .metadata
+            .set(metadata::ITEM, MetadataValue::Slot(item));
+        entity
+    }
+
+    /// Makes a falling block of `block`, starting to fall from `position`.
+    pub fn falling_block(position: BlockPosition, block: BlockState) -> Self {
+        let mut entity = Self::new(EntityType::FALLING_BLOCK, Position::from_block(position));
+        entity.data = block.id() as i32;
+        entity.metadata.set(
+            metadata::FALLING_BLOCK_SPAWN_POSITION,
+            MetadataValue::BlockPosition(position),
+        );
+        entity
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    pub fn kind(&self) -> EntityType {
+        self.kind
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.kind.bounding_box(self.position)
+    }
+}
+
+/// The entities of a world, by ID, along with the players that see each of them.
+#[derive(Debug, Default)]
+pub struct EntityStore {
+    entities: Mutex<HashMap<i32, TrackedEntity>>,
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds an entity, returning its ID. Players in range start seeing it on the next tick.
+    pub fn spawn(&self, entity: Entity) -> i32 {
+        let id = entity.id;
+        self.entities().insert(id, TrackedEntity::new(entity));
+        id
+    }
+
+    /// Removes an entity, despawning it for the players that see it.
+    pub fn remove(&self, id: i32) -> Option<Entity> {
+        let tracked = self.entities().remove(&id)?;
+        tracked.despawn();
+        Some(tracked.entity)
+    }
+
+    /// Returns a snapshot of an entity.
+    pub fn get(&self, id: i32) -> Option<Entity> {
+        self.entities()
+            .get(&id)
+            .map(|tracked| tracked.entity.clone())
+    }
+
+    /// Returns the IDs of all entities.
+    pub fn ids(&self) -> Vec<i32> {
+        self.entities().keys().copied().collect()
+    }
+
+    /// Changes an entity with `update`, returning its result, or `None` if there is no entity
+    /// with that ID. Changes are sent to players on the next tick.
+    pub fn update<T>(&self, id: i32, update: impl FnOnce(&mut Entity) -> T) -> Option<T> {
+        self.entities()
+            .get_mut(&id)
+            .map(|tracked| update(&mut tracked.entity))
+    }
+
+    /// Moves the entities in loaded chunks, removes those that fell out of the world, then sends
+    /// the changes to the players that see them, spawning and despawning them for players that
+    /// came in or went out of range.
+    pub fn tick(&self, world: &World) {
+        let void_y = world.min_y() as f64 - physics::VOID_DEPTH;
+        let mut entities = self.entities();
+        entities.retain(|_, tracked| {
+            let position = tracked.entity.position;
+            let chunk = ChunkPos::new(position.chunk_x(), position.chunk_z());
+            if world.is_chunk_loaded(chunk) {
+                physics::tick_entity(world, &mut tracked.entity);
+            }
+            if tracked.entity.position.y >= void_y {
+                return true;
+            }
+            tracked.despawn();
+            false
+        });
+
+        let players = world
+            .players()
+            .entries()
+            .into_iter()
+            .map(|player| (player.entity_id, player))
+            .collect();
+        let tick = world.game_time() as u64;
+        let mut despawned = HashMap::new();
+        for tracked in entities.values_mut() {
+            tracked.sync(&players, world.view_distance, tick, &mut despawned);
+        }
+        tracker::send_despawned(&players, despawned);
+    }
+
+    fn entities(&self) -> MutexGuard<'_, HashMap<i32, TrackedEntity>> {
+        self.entities.lock().unwrap()
+    }
+}
@@ -0,0 +1,495 @@
+//! Viewer tracking: which players see an entity, and sending them what changed about it.
+
+use std::{collections::HashMap, fmt::Debug};
+
+use packet::{client::*, Packet};
+use protocol::VarInt;
+
+use super::Entity;
+use crate::{
+    connection::PacketSender,
+    player_list::PlayerEntry,
+    world::{movement_delta, Position, Rotation, SECTION_SIZE},
+};
+
+/// The state of an entity as last sent to its viewers, which changes are sent relative to.
+#[derive(Debug, Clone, PartialEq)]
+struct SentState {
+    /// Position in 1/4096 of a block, like clients decode it.
+    position: [i64; 3],
+    yaw: u8,
+    pitch: u8,
+    head_yaw: u8,
+    on_ground: bool,
+    velocity: [i16; 3],
+}
+
+impl SentState {
+    fn of(entity: &Entity) -> Self {
+        Self {
+            position: entity.position.to_protocol(),
+            yaw: entity.rotation.yaw_angle(),
+            pitch: entity.rotation.pitch_angle(),
+            head_yaw: head_yaw_angle(entity),
+            on_ground: entity.on_ground,
+            velocity: entity.velocity.to_protocol(),
+        }
+    }
+
+    fn position(&self) -> Position {
+        Position::from_protocol(self.position)
+    }
+}
+
+/// An entity along with the players that see it.
+#[derive(Debug)]
+pub(super) struct TrackedEntity {
+    pub entity: Entity,
+    /// Players that were sent the entity, by entity ID.
+    viewers: HashMap<i32, PacketSender>,
+    sent: SentState,
+}
+
+impl TrackedEntity {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            sent: SentState::of(&entity),
+            entity,
+            viewers: HashMap::new(),
+        }
+    }
+
+    /// Despawns the entity for its viewers.
+    pub fn despawn(&self) {
+        self.broadcast(&RemoveEntitiesPacket {
+            entity_ids: vec![VarInt(self.entity.id)].into(),
+        });
+    }
+
+    /// Sends the changes to the entity to its viewers, then spawns it for the `players` that
+    /// came in range. Players that went out of range are added to `despawned`, to send them
+    /// which entities to remove all at once.
+    pub fn sync(
+        &mut self,
+        players: &HashMap<i32, PlayerEntry>,
+        view_distance: i32,
+        tick: u64,
+        despawned: &mut HashMap<i32, Vec<VarInt>>,
+    ) {
+        let range = (self.entity.kind.tracking_range.min(view_distance) * SECTION_SIZE) as f64;
+        let position = self.entity.position;
+        let in_range = |player: &PlayerEntry| {
+            (player.position.x - position.x).abs() <= range
+                && (player.position.z - position.z).abs() <= range
+        };
+
+        // Players that left the world don't need to be told.
+        self.viewers.retain(|&viewer, _| {
+            let Some(player) = players.get(&viewer) else {
+                return false;
+            };
+            let keep = in_range(player);
+            if !keep {
+                despawned
+                    .entry(viewer)
+                    .or_default()
+                    .push(VarInt(self.entity.id));
+            }
+            keep
+        });
+
+        if tick % self.entity.kind.update_interval == 0 {
+            self.send_movement();
+        }
+        if let Some(metadata) = self.entity.metadata.take_dirty() {
+            self.broadcast(&SetEntityMetadataPacket {
+                entity_id: self.entity.id,
+                metadata: metadata.into(),
+            });
+        }
+
+        let new_viewers: Vec<_> = players
+            .values()
+            .filter(|player| !self.viewers.contains_key(&player.entity_id) && in_range(player))
+            .collect();
+        for player in new_viewers {
+            self.spawn_for(player);
+            self.viewers.insert(player.entity_id, player.sender.clone());
+        }
+    }
+
+    /// Sends how the entity moved and turned since the last update, as deltas when it moved
+    /// little enough, or as a teleport otherwise.
+    fn send_movement(&mut self) {
+        let entity_id = self.entity.id;
+        let current = SentState::of(&self.entity);
+        let sent = &self.sent;
+
+        let delta = movement_delta(sent.position, current.position);
+        let moved = current.position != sent.position || current.on_ground != sent.on_ground;
+        let rotated = (current.yaw, current.pitch) != (sent.yaw, sent.pitch);
+        let head_rotated = current.head_yaw != sent.head_yaw;
+        let accelerated = current.velocity != sent.velocity;
+        let [delta_x, delta_y, delta_z] = delta.unwrap_or_default();
+        let (yaw, pitch, on_ground) = (current.yaw, current.pitch, current.on_ground);
+
+        if delta.is_none() {
+            let position = current.position();
+            self.broadcast(&TeleportEntityPacket {
+                entity_id,
+                x: position.x,
+                y: position.y,
+                z: position.z,
+                yaw,
+                pitch,
+                on_ground,
+            });
+        } else if moved && rotated {
+            self.broadcast(&UpdateEntityPositionAndRotationPacket {
+                entity_id,
+                delta_x,
+                delta_y,
+                delta_z,
+                yaw,
+                pitch,
+                on_ground,
+            });
+        } else if moved {
+            self.broadcast(&UpdateEntityPositionPacket {
+                entity_id,
+                delta_x,
+                delta_y,
+                delta_z,
+                on_ground,
+            });
+        } else if rotated {
+            self.broadcast(&UpdateEntityRotationPacket {
+                entity_id,
+                yaw,
+                pitch,
+                on_ground,
+            });
+        }
+
+        if head_rotated {
+            self.broadcast(&SetHeadRotationPacket {
+                entity_id,
+                head_yaw: current.head_yaw,
+            });
+        }
+        if accelerated {
+            let [velocity_x, velocity_y, velocity_z] = current.velocity;
+            self.broadcast(&SetEntityVelocityPacket {
+                entity_id,
+                velocity_x,
+                velocity_y,
+                velocity_z,
+            });
+        }
+
+        self.sent = current;
+    }
+
+    /// Spawns the entity for `player`, as last sent to the other viewers so that later deltas
+    /// apply to it the same way.
+    fn spawn_for(&self, player: &PlayerEntry) {
+        let position = self.sent.position();
+        let [velocity_x, velocity_y, velocity_z] = self.sent.velocity;
+        player.send_packet(&SpawnEntityPacket {
+            entity_id: self.entity.id,
+            entity_uuid: self.entity.uuid,
+            entity_type: self.entity.kind.id,
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            pitch: self.sent.pitch,
+            yaw: self.sent.yaw,
+            head_yaw: self.sent.head_yaw,
+            data: self.entity.data,
+            velocity_x,
+            velocity_y,
+            velocity_z,
+        });
+        if !self.entity.metadata.is_empty() {
+            player.send_packet(&SetEntityMetadataPacket {
+                entity_id: self.entity.id,
+                metadata: self.entity.metadata.encode_all().into(),
+            });
+        }
+    }
+
+    fn broadcast<P: Packet + Debug>(&self, packet: &P) {
+        for sender in self.viewers.values() {
+            send_packet(sender, packet);
+        }
+    }
+}
+
+/// Sends the players which entities they stopped seeing, by the players' entity IDs.
+pub(super) fn send_despawned(
+    players: &HashMap<i32, PlayerEntry>,
+    despawned: HashMap<i32, Vec<VarInt>>,
+) {
+    for (viewer, entity_ids) in despawned {
+        if let Some(player) = players.get(&viewer) {
+            player.send_packet(&RemoveEntitiesPacket {
+                entity_ids: entity_ids.into(),
+            });
+        }
+    }
+}
+
+/// Sends a packet to a viewer, ignoring errors if its connection is closed.
+fn send_packet<P: Packet + Debug>(sender: &PacketSender, packet: &P) {
+    if let Err(err) = sender.send_packet(packet) {
+        tracing::trace!(
+            "Unable to send entity packet to {}: {}.",
+            sender.address(),
+            err
+        );
+    }
+}
+
+fn head_yaw_angle(entity: &Entity) -> u8 {
+    Rotation::new(entity.head_yaw, 0.0).yaw_angle()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU8, Arc, Mutex};
+
+    use packet::client::ClientPlayPacket;
+    use protocol::GameMode;
+
+    use super::*;
+    use crate::{
+        connection::tests::SentPackets,
+        entity::{metadata, EntityType, MetadataValue, Velocity},
+        player::GameProfile,
+    };
+
+    const VIEW_DISTANCE: i32 = 10;
+
+    /// Players by entity ID, with the packets they are sent.
+    struct Viewers {
+        players: HashMap<i32, PlayerEntry>,
+        sent: HashMap<i32, SentPackets>,
+    }
+
+    impl Viewers {
+        fn new(positions: &[(i32, Position)]) -> Self {
+            let mut viewers = Self {
+                players: HashMap::new(),
+                sent: HashMap::new(),
+            };
+            for &(entity_id, position) in positions {
+                viewers.add(entity_id, position);
+            }
+            viewers
+        }
+
+        fn add(&mut self, entity_id: i32, position: Position) {
+            let (sender, sent) = SentPackets::new();
+            let player = PlayerEntry {
+                entity_id,
+                profile: GameProfile::offline(format!("Player{entity_id}")),
+                game_mode: GameMode::Survival,
+                position,
+                rotation: Rotation::default(),
+                on_ground: true,
+                chat_session: None,
+                chat: Arc::new(Mutex::default()),
+                permission_level: Arc::new(AtomicU8::new(0)),
+                sender,
+            };
+            self.players.insert(entity_id, player);
+            self.sent.insert(entity_id, sent);
+        }
+
+        /// Syncs `tracked` on `tick`, returning the despawned entities by viewer.
+        fn sync(&self, tracked: &mut TrackedEntity, tick: u64) -> HashMap<i32, Vec<VarInt>> {
+            let mut despawned = HashMap::new();
+            tracked.sync(&self.players, VIEW_DISTANCE, tick, &mut despawned);
+            despawned
+        }
+
+        fn take(&mut self, entity_id: i32) -> Vec<ClientPlayPacket<'static>> {
+            self.sent.get_mut(&entity_id).unwrap().take()
+        }
+    }
+
+    /// A snowball, seen from up to 64 blocks away and updated every 10 ticks.
+    fn snowball() -> TrackedEntity {
+        TrackedEntity::new(Entity::new(
+            EntityType::SNOWBALL,
+            Position::new(0.5, 64.0, 0.5),
+        ))
+    }
+
+    #[test]
+    fn spawns_for_players_in_range() {
+        let mut viewers = Viewers::new(&[
+            (1, Position::new(60.0, 64.0, -60.0)),
+            (2, Position::new(100.0, 64.0, 0.0)),
+        ]);
+        let mut item = TrackedEntity::new(Entity::item(
+            Position::new(0.5, 64.0, 0.5),
+            packet::Slot {
+                item_count: 1,
+                item_id: 1,
+            },
+        ));
+        assert!(viewers.sync(&mut item, 0).is_empty());
+
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::SpawnEntityPacket(spawn), ClientPlayPacket::SetEntityMetadataPacket(metadata)] =
+            &packets[..]
+        else {
+            panic!("expected a spawn, got {packets:?}");
+        };
+        assert_eq!(spawn.entity_id, item.entity.id());
+        assert_eq!(spawn.entity_type, EntityType::ITEM.id);
+        assert_eq!((spawn.x, spawn.y, spawn.z), (0.5, 64.0, 0.5));
+        assert_eq!(*metadata.metadata, item.entity.metadata.encode_all());
+        assert!(viewers.take(2).is_empty());
+
+        // Players are only spawned the entity once, and told to remove it out of range.
+        assert!(viewers.sync(&mut item, 0).is_empty());
+        assert!(viewers.take(1).is_empty());
+        viewers.players.get_mut(&1).unwrap().position = Position::new(0.0, 64.0, 100.0);
+        let despawned = viewers.sync(&mut item, 0);
+        assert_eq!(despawned[&1], [VarInt(item.entity.id())]);
+        assert!(viewers.take(1).is_empty());
+
+        // Players that left aren't told.
+        viewers.players.get_mut(&1).unwrap().position = Position::new(0.0, 64.0, 0.0);
+        viewers.sync(&mut item, 0);
+        viewers.take(1);
+        viewers.players.remove(&1);
+        assert!(viewers.sync(&mut item, 0).is_empty());
+    }
+
+    #[test]
+    fn sends_deltas_or_teleports() {
+        let mut viewers = Viewers::new(&[(1, Position::new(0.0, 64.0, 0.0))]);
+        let mut snowball = snowball();
+        viewers.sync(&mut snowball, 0);
+        viewers.take(1);
+
+        snowball.entity.position = Position::new(1.5, 63.0, 0.5);
+        viewers.sync(&mut snowball, 10);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::UpdateEntityPositionPacket(update)] = &packets[..] else {
+            panic!("expected a delta, got {packets:?}");
+        };
+        assert_eq!(
+            (update.delta_x, update.delta_y, update.delta_z),
+            (4096, -4096, 0)
+        );
+
+        snowball.entity.position = Position::new(1.5, 63.0, 2.0);
+        snowball.entity.rotation = Rotation::new(90.0, 0.0);
+        viewers.sync(&mut snowball, 20);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::UpdateEntityPositionAndRotationPacket(update)] = &packets[..] else {
+            panic!("expected a delta and rotation, got {packets:?}");
+        };
+        assert_eq!((update.delta_z, update.yaw), (6144, 64));
+
+        snowball.entity.rotation = Rotation::new(90.0, 45.0);
+        snowball.entity.head_yaw = 180.0;
+        viewers.sync(&mut snowball, 30);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::UpdateEntityRotationPacket(rotation), ClientPlayPacket::SetHeadRotationPacket(head)] =
+            &packets[..]
+        else {
+            panic!("expected rotations, got {packets:?}");
+        };
+        assert_eq!((rotation.yaw, rotation.pitch, head.head_yaw), (64, 32, 128));
+
+        // Moving more than 8 blocks takes a teleport.
+        snowball.entity.position = Position::new(10.0, 63.0, 2.0);
+        snowball.entity.velocity = Velocity::new(0.5, 0.0, 0.0);
+        viewers.sync(&mut snowball, 40);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::TeleportEntityPacket(teleport), ClientPlayPacket::SetEntityVelocityPacket(velocity)] =
+            &packets[..]
+        else {
+            panic!("expected a teleport, got {packets:?}");
+        };
+        assert_eq!((teleport.x, teleport.y, teleport.z), (10.0, 63.0, 2.0));
+        assert_eq!(velocity.velocity_x, 4000);
+
+        // Nothing is sent without changes.
+        viewers.sync(&mut snowball, 50);
+        assert!(viewers.take(1).is_empty());
+    }
+
+    #[test]
+    fn sends_movement_every_interval() {
+        let mut viewers = Viewers::new(&[(1, Position::new(0.0, 64.0, 0.0))]);
+        let mut snowball = snowball();
+        viewers.sync(&mut snowball, 0);
+        viewers.take(1);
+
+        // Movement between updates adds up, to keep small steps from being rounded away.
+        for tick in 1..10 {
+            snowball.entity.position.x += 0.0001;
+            viewers.sync(&mut snowball, tick);
+            assert!(viewers.take(1).is_empty());
+        }
+        viewers.sync(&mut snowball, 10);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::UpdateEntityPositionPacket(update)] = &packets[..] else {
+            panic!("expected a delta, got {packets:?}");
+        };
+        assert_eq!(update.delta_x, 4);
+
+        // Players that start seeing the entity get it as last sent, which later deltas apply to.
+        snowball.entity.position.x += 0.5;
+        viewers.add(2, Position::new(0.0, 64.0, 0.0));
+        viewers.sync(&mut snowball, 11);
+        let packets = viewers.take(2);
+        let [ClientPlayPacket::SpawnEntityPacket(spawn)] = &packets[..] else {
+            panic!("expected a spawn, got {packets:?}");
+        };
+        assert_eq!(spawn.x, 2052.0 / 4096.0);
+        viewers.sync(&mut snowball, 20);
+        for viewer in [1, 2] {
+            let packets = viewers.take(viewer);
+            let [ClientPlayPacket::UpdateEntityPositionPacket(update)] = &packets[..] else {
+                panic!("expected a delta, got {packets:?}");
+            };
+            assert_eq!(update.delta_x, 2048);
+        }
+    }
+
+    #[test]
+    fn sends_changed_metadata() {
+        let mut viewers = Viewers::new(&[(1, Position::new(0.0, 64.0, 0.0))]);
+        let mut snowball = snowball();
+        viewers.sync(&mut snowball, 1);
+        // Entities without metadata are only spawned.
+        assert_eq!(viewers.take(1).len(), 1);
+
+        snowball
+            .entity
+            .metadata
+            .set(metadata::NO_GRAVITY, MetadataValue::Boolean(true));
+        snowball
+            .entity
+            .metadata
+            .set_flag(metadata::flags::GLOWING, true);
+        viewers.sync(&mut snowball, 2);
+        snowball
+            .entity
+            .metadata
+            .set(metadata::NO_GRAVITY, MetadataValue::Boolean(true));
+        viewers.sync(&mut snowball, 3);
+        let packets = viewers.take(1);
+        let [ClientPlayPacket::SetEntityMetadataPacket(update)] = &packets[..] else {
+            panic!("expected metadata, got {packets:?}");
+        };
+        assert_eq!(*update.metadata, [0, 0, 0x40, 5, 8, 1, 0xFF]);
+    }
+}
@@ -0,0 +1,64 @@
+use packet::Slot;
+
+/// Number of slots in the player inventory window.
+///
+/// Slot 0 is the crafting output, 1-4 the crafting grid, 5-8 armor, 9-35 the main inventory,
+/// 36-44 the hotbar and 45 the offhand.
+pub const PLAYER_INVENTORY_SIZE: usize = 46;
+pub const HOTBAR_START: usize = 36;
+pub const HOTBAR_SIZE: usize = 9;
+pub const OFFHAND_SLOT: usize = 45;
+
+#[derive(Debug, Clone)]
+pub struct PlayerInventory {
+    slots: [Slot; PLAYER_INVENTORY_SIZE],
+    /// Incremented on every change, sent with inventory updates so the client can detect desyncs.
+    state_id: i32,
+}
+
+impl PlayerInventory {
+    pub fn new() -> Self {
+        Self {
+            slots: [Slot::EMPTY; PLAYER_INVENTORY_SIZE],
+            state_id: 0,
+        }
+    }
+
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    pub fn slot(&self, index: usize) -> Option<&Slot> {
+        self.slots.get(index)
+    }
+
+    /// Sets the slot at `index`. Returns `false` if `index` is out of bounds.
+    pub fn set_slot(&mut self, index: usize, slot: Slot) -> bool {
+        let Some(target) = self.slots.get_mut(index) else {
+            return false;
+        };
+
+        *target = slot;
+        self.state_id = self.state_id.wrapping_add(1);
+        true
+    }
+
+    /// Returns the item in hotbar slot `held_slot` (0-8).
+    pub fn hotbar_item(&self, held_slot: u8) -> &Slot {
+        &self.slots[HOTBAR_START + (held_slot as usize).min(HOTBAR_SIZE - 1)]
+    }
+
+    pub fn offhand_item(&self) -> &Slot {
+        &self.slots[OFFHAND_SLOT]
+    }
+
+    pub fn state_id(&self) -> i32 {
+        self.state_id
+    }
+}
+
+impl Default for PlayerInventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,24 +1,181 @@
+use std::{path::Path, sync::Arc};
+
+use access::AccessControl;
+use anvil::Compression;
+use config::LiveConfig;
 use connection::ConnectionManager;
+use context::ServerContext;
+use metrics::MetricsListener;
+use permission::{FilePermissions, PERMISSIONS_FILE};
+use query::QueryListener;
+use rcon::{RconError, RconListener};
+use tick::TickLoop;
 use tokio::net::ToSocketAddrs;
+use world::{
+    generator::{self, LevelType},
+    storage, ChunkStorage, World,
+};
 
+pub mod access;
+pub mod chat;
+pub mod chunk_view;
+pub mod command;
+pub mod config;
 pub mod connection;
+pub mod console;
+pub mod context;
+pub mod entity;
+pub mod inventory;
+pub mod metrics;
+pub mod movement;
 pub mod packet_handler;
+pub mod permission;
+pub mod physics;
+pub mod player;
+pub mod player_list;
+pub mod query;
+pub mod rcon;
+pub mod status;
+pub mod throttle;
+pub mod tick;
+pub mod world;
 
 pub struct MinecraftServer {
     connection_manager: ConnectionManager,
+    context: Arc<ServerContext>,
+    rcon: Option<Arc<RconListener>>,
+    query: Option<Arc<QueryListener>>,
+    metrics: Option<Arc<MetricsListener>>,
 }
 
 impl MinecraftServer {
-    pub async fn new<A>(address: A) -> std::io::Result<Self>
+    /// Sets up the server from `config`, which should be
+    /// [validated](config::ServerConfig::validate).
+    ///
+    /// RCON, query and metrics are not enabled by this, see [`Self::enable_rcon`],
+    /// [`Self::enable_query`] and [`Self::enable_metrics`].
+    pub async fn new(config: LiveConfig) -> std::io::Result<Self> {
+        let startup = config.startup();
+        if startup.online_mode {
+            tracing::warn!("Online mode is not supported yet, players won't be authenticated.");
+        }
+        if startup.enforce_secure_chat {
+            tracing::warn!("Secure chat is not supported yet, chat signatures won't be enforced.");
+        }
+        if startup.compression_threshold >= 0 {
+            tracing::warn!("Compression is not supported yet, packets won't be compressed.");
+        }
+
+        let level_type = LevelType::from_name(&startup.level_type)
+            .expect("the configuration should be validated");
+        let seed = generator::parse_seed(&startup.level_seed);
+        let generator = level_type
+            .generator(&startup.generator_settings, seed)
+            .map_err(std::io::Error::other)?;
+        let mut world = World::overworld(generator);
+        world.hashed_seed = generator::hash_seed(seed);
+        world.is_flat = level_type == LevelType::Flat;
+        world.view_distance = startup.view_distance;
+        world.simulation_distance = startup.simulation_distance;
+        let compression = Compression::from_name(&startup.region_file_compression)
+            .expect("the configuration should be validated");
+        let regions =
+            storage::region_directory(Path::new(&startup.level_name), &world.name.to_string());
+        world.set_storage(ChunkStorage::new(regions, compression));
+        world.load_spawn_chunks();
+        let address = startup.address;
+
+        // Like vanilla, the lists are in the working directory.
+        let access = AccessControl::load(Path::new(".")).map_err(std::io::Error::other)?;
+
+        let permissions =
+            FilePermissions::load(Path::new(PERMISSIONS_FILE)).map_err(std::io::Error::other)?;
+
+        let mut context = ServerContext::new(Arc::new(world), config, access);
+        context.movement = context.config.startup().movement.clone();
+        context
+            .commands
+            .set_permission_provider(Arc::new(permissions));
+        let context = Arc::new(context);
+
+        Ok(MinecraftServer {
+            connection_manager: ConnectionManager::new(address, Arc::clone(&context)).await?,
+            context,
+            rcon: None,
+            query: None,
+            metrics: None,
+        })
+    }
+
+    /// The state shared with all connections, e.g. to run commands from outside the game.
+    pub fn context(&self) -> &Arc<ServerContext> {
+        &self.context
+    }
+
+    /// Accepts RCON connections on `address` once the server starts, running commands like the
+    /// console does.
+    pub async fn enable_rcon<A>(&mut self, address: A, password: &str) -> Result<(), RconError>
     where
         A: ToSocketAddrs,
     {
-        Ok(MinecraftServer {
-            connection_manager: ConnectionManager::new(address).await?,
-        })
+        let rcon = RconListener::new(address, password, Arc::clone(&self.context)).await?;
+        tracing::info!("RCON running on {}.", rcon.local_addr()?);
+        self.rcon = Some(Arc::new(rcon));
+        Ok(())
+    }
+
+    /// Answers UDP query requests on `address` once the server starts.
+    pub async fn enable_query<A>(&mut self, address: A) -> std::io::Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let game_address = self.connection_manager.local_addr()?;
+        let query = QueryListener::new(address, game_address, Arc::clone(&self.context)).await?;
+        tracing::info!("Query running on {}.", query.local_addr()?);
+        self.query = Some(Arc::new(query));
+        Ok(())
+    }
+
+    /// Serves Prometheus metrics over HTTP on `address` once the server starts.
+    pub async fn enable_metrics<A>(&mut self, address: A) -> std::io::Result<()>
+    where
+        A: ToSocketAddrs,
+    {
+        let metrics = MetricsListener::new(address, Arc::clone(&self.context)).await?;
+        tracing::info!(
+            "Metrics available on http://{}/metrics.",
+            metrics.local_addr()?
+        );
+        self.metrics = Some(Arc::new(metrics));
+        Ok(())
     }
 
     pub async fn start(&self) -> ! {
+        if let Err(err) = TickLoop::new(Arc::clone(&self.context)).spawn() {
+            tracing::error!("Unable to start the tick loop: {}.", err);
+        }
+        let context = Arc::clone(&self.context);
+        tokio::spawn(async move {
+            context.config.watch_file().await;
+        });
+        if let Some(rcon) = &self.rcon {
+            let rcon = Arc::clone(rcon);
+            tokio::spawn(async move {
+                rcon.listen().await;
+            });
+        }
+        if let Some(query) = &self.query {
+            let query = Arc::clone(query);
+            tokio::spawn(async move {
+                query.listen().await;
+            });
+        }
+        if let Some(metrics) = &self.metrics {
+            let metrics = Arc::clone(metrics);
+            tokio::spawn(async move {
+                metrics.listen().await;
+            });
+        }
         self.connection_manager.listen().await
     }
 }
@@ -1,29 +1,131 @@
-use std::error::Error;
+use std::{error::Error, path::PathBuf, sync::Arc};
 
-use server::MinecraftServer;
+use clap::Parser;
+use server::{
+    config::{ConfigError, ConfigOverrides, LiveConfig, ServerConfig},
+    console::{Console, ConsoleOutput},
+    metrics, MinecraftServer,
+};
 use tokio::signal;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// A Minecraft server.
+///
+/// Options given here override the configuration file.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Configuration file, created with the default configuration if missing.
+    #[arg(long, value_name = "PATH", default_value = "server.toml")]
+    config: PathBuf,
+    /// Imports a vanilla server.properties file, replacing the configuration file.
+    #[arg(long, value_name = "PATH")]
+    import_properties: Option<PathBuf>,
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+}
+
+impl Cli {
+    fn load_config(&self) -> Result<ServerConfig, ConfigError> {
+        let mut config = match &self.import_properties {
+            Some(path) => {
+                let config = ServerConfig::import_properties(path)?;
+                config.save(&self.config)?;
+                tracing::info!(
+                    "Imported {} into {}.",
+                    path.display(),
+                    self.config.display()
+                );
+                config
+            }
+            None => ServerConfig::load_or_create(&self.config)?,
+        };
+        // Like vanilla, an empty seed means a random one. It is saved, as there is nowhere else
+        // to keep it, so that chunks generated later fit with the others.
+        if config.level_seed.trim().is_empty() {
+            config.level_seed = rand::random::<i64>().to_string();
+            config.save(&self.config)?;
+        }
+
+        self.overrides.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
+    let cli = Cli::parse();
+
+    let console_output = ConsoleOutput::default();
+    // The log filter only applies to logs, so that it doesn't disable the spans metrics are
+    // measured from.
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(console_output.clone())
+                .with_filter(EnvFilter::from_default_env()),
+        )
+        .with(metrics::layer())
         .init();
 
-    tracing::info!("Starting server...");
+    let config = match cli.load_config() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("Unable to load the configuration: {}.", err);
+            std::process::exit(1);
+        }
+    };
 
-    let minecraft_server = MinecraftServer::new("127.0.0.1:25565").await?;
+    tracing::info!("Starting server on {}...", config.address);
+
+    let live_config = LiveConfig::new(cli.config, cli.overrides, config.clone());
+    let mut minecraft_server = MinecraftServer::new(live_config).await?;
+    if config.rcon.enabled {
+        minecraft_server
+            .enable_rcon(config.rcon.address, &config.rcon.password)
+            .await?;
+    }
+    if config.query.enabled {
+        minecraft_server.enable_query(config.query.address).await?;
+    }
+    if config.metrics.enabled {
+        minecraft_server
+            .enable_metrics(config.metrics.address)
+            .await?;
+    }
+    let context = Arc::clone(minecraft_server.context());
+
+    match Console::new(Arc::clone(&context), &console_output) {
+        // The console blocks on stdin, so it gets its own thread, which doesn't keep the
+        // process alive on shutdown.
+        Ok(console) => {
+            std::thread::Builder::new()
+                .name("console".to_owned())
+                .spawn(move || console.run())?;
+        }
+        Err(err) => {
+            tracing::error!("Unable to start the console: {}.", err);
+        }
+    }
 
     tokio::spawn(async move {
         minecraft_server.start().await;
     });
 
-    match signal::ctrl_c().await {
-        Ok(()) => {}
-        Err(err) => {
-            tracing::error!("Unable to listen for shutdown signal: {}.", err);
+    tokio::select! {
+        result = signal::ctrl_c() => {
+            if let Err(err) = result {
+                tracing::error!("Unable to listen for shutdown signal: {}.", err);
+            }
         }
+        () = context.shutdown_requested() => {}
     }
 
+    tracing::info!("Stopping server...");
+    match context.world.save_chunks() {
+        Ok(saved) => tracing::info!("Saved {} chunks.", saved),
+        Err(err) => tracing::error!("Unable to save the world: {}.", err),
+    }
     Ok(())
 }
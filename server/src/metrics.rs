@@ -0,0 +1,468 @@
+//! Metrics in the Prometheus text format, served over HTTP for monitoring.
+//!
+//! Durations are measured from `tracing` spans, see [`layer`], so that the same instrumentation
+//! shows up in logs and metrics.
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use packet::PacketDecodeError;
+use prometheus::{
+    Encoder, Gauge, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+use protocol::{ConnectionState, DecodeError};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+use tracing::{
+    span::{Attributes, Id},
+    Subscriber,
+};
+use tracing_subscriber::{filter::filter_fn, layer::Context, registry::LookupSpan, Layer};
+
+use crate::{connection::ACCEPT_ERROR_DELAY, context::ServerContext};
+
+/// Name of the span around handling a packet, whose duration is the handler latency.
+pub const HANDLE_PACKET_SPAN: &str = "handle_packet";
+/// Name of the span around a server tick.
+pub const TICK_SPAN: &str = "tick";
+
+/// Maximum size of an HTTP request head, which is plenty for scrapers.
+const MAX_REQUEST_LEN: usize = 8192;
+/// How long scrapers have to send their request before the connection is closed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Metrics of the server, registered to be exported together.
+pub struct Metrics {
+    registry: Registry,
+    /// Open connections by state.
+    pub connections: IntGaugeVec,
+    /// Packets received by state and packet ID.
+    pub packets_received: IntCounterVec,
+    /// Packets sent by state and packet ID.
+    pub packets_sent: IntCounterVec,
+    pub bytes_received: IntCounter,
+    pub bytes_sent: IntCounter,
+    /// Packets that couldn't be decoded, by kind of error.
+    pub decode_errors: IntCounterVec,
+    /// Time (in seconds) spent handling a packet.
+    pub handler_latency: Histogram,
+    /// Time (in seconds) a tick takes.
+    pub tick_duration: Histogram,
+    /// Ticks per second, averaged over the last ticks.
+    pub tps: Gauge,
+    /// Milliseconds per tick, averaged over the last ticks.
+    pub mspt: Gauge,
+    pub players: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("mcserver".to_owned()), None)
+            .expect("metric prefix should be valid");
+        let metrics = Self {
+            connections: IntGaugeVec::new(
+                Opts::new("connections", "Open connections by state."),
+                &["state"],
+            )
+            .unwrap(),
+            packets_received: IntCounterVec::new(
+                Opts::new(
+                    "packets_received_total",
+                    "Packets received by state and ID.",
+                ),
+                &["state", "id"],
+            )
+            .unwrap(),
+            packets_sent: IntCounterVec::new(
+                Opts::new("packets_sent_total", "Packets sent by state and ID."),
+                &["state", "id"],
+            )
+            .unwrap(),
+            bytes_received: IntCounter::new("bytes_received_total", "Bytes received.").unwrap(),
+            bytes_sent: IntCounter::new("bytes_sent_total", "Bytes sent.").unwrap(),
+            decode_errors: IntCounterVec::new(
+                Opts::new("decode_errors_total", "Packets that couldn't be decoded."),
+                &["kind"],
+            )
+            .unwrap(),
+            handler_latency: Histogram::with_opts(HistogramOpts::new(
+                "packet_handler_seconds",
+                "Time spent handling a packet.",
+            ))
+            .unwrap(),
+            tick_duration: Histogram::with_opts(HistogramOpts::new(
+                "tick_seconds",
+                "Time a tick takes.",
+            ))
+            .unwrap(),
+            tps: Gauge::new("ticks_per_second", "Ticks per second, recently.").unwrap(),
+            mspt: Gauge::new("milliseconds_per_tick", "Time a tick takes, recently.").unwrap(),
+            players: IntGauge::new("players", "Players online.").unwrap(),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 11] = [
+            Box::new(metrics.connections.clone()),
+            Box::new(metrics.packets_received.clone()),
+            Box::new(metrics.packets_sent.clone()),
+            Box::new(metrics.bytes_received.clone()),
+            Box::new(metrics.bytes_sent.clone()),
+            Box::new(metrics.decode_errors.clone()),
+            Box::new(metrics.handler_latency.clone()),
+            Box::new(metrics.tick_duration.clone()),
+            Box::new(metrics.tps.clone()),
+            Box::new(metrics.mspt.clone()),
+            Box::new(metrics.players.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metrics should be registered once");
+        }
+        metrics
+    }
+
+    pub fn packet_received(&self, state: ConnectionState, packet_id: i32) {
+        self.packets_received
+            .with_label_values(&[state_label(state), &format!("{packet_id:#04x}")])
+            .inc();
+    }
+
+    pub fn packet_sent(&self, state: ConnectionState, packet_id: i32) {
+        self.packets_sent
+            .with_label_values(&[state_label(state), &format!("{packet_id:#04x}")])
+            .inc();
+    }
+
+    pub fn decode_error(&self, error: &DecodeError<PacketDecodeError>) {
+        let kind = match error {
+            DecodeError::Specific(_) => "specific",
+            DecodeError::VarInt(_) => "varint",
+            DecodeError::Enum(_) => "enum",
+            DecodeError::String(_) => "string",
+            DecodeError::Identifier(_) => "identifier",
+            DecodeError::Json(_) => "json",
+            DecodeError::Other(PacketDecodeError::InvalidPacketId(_)) => "invalid_packet_id",
+        };
+        self.decode_errors.with_label_values(&[kind]).inc();
+    }
+
+    /// Counts a connection as being in `state`.
+    pub fn connection_entered(&self, state: ConnectionState) {
+        self.connections
+            .with_label_values(&[state_label(state)])
+            .inc();
+    }
+
+    /// Stops counting a connection as being in `state`, e.g. when it is closed.
+    pub fn connection_left(&self, state: ConnectionState) {
+        self.connections
+            .with_label_values(&[state_label(state)])
+            .dec();
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("metrics should encode");
+        buf
+    }
+}
+
+/// The metrics of this process.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+fn state_label(state: ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Handshaking => "handshaking",
+        ConnectionState::Status => "status",
+        ConnectionState::Login => "login",
+        ConnectionState::Configuration => "configuration",
+        ConnectionState::Play => "play",
+    }
+}
+
+/// A `tracing` layer recording the durations of [`HANDLE_PACKET_SPAN`] and [`TICK_SPAN`] spans.
+///
+/// It only enables those spans, regardless of the log filter.
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    MetricsLayer.with_filter(filter_fn(|metadata| {
+        metadata.is_span() && [HANDLE_PACKET_SPAN, TICK_SPAN].contains(&metadata.name())
+    }))
+}
+
+struct MetricsLayer;
+
+/// When a span was created, stored in its extensions.
+struct SpanStart(Instant);
+
+impl<S> Layer<S> for MetricsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(&SpanStart(start)) = span.extensions().get::<SpanStart>() else {
+            return;
+        };
+        let histogram = match span.name() {
+            HANDLE_PACKET_SPAN => &metrics().handler_latency,
+            TICK_SPAN => &metrics().tick_duration,
+            _ => return,
+        };
+        histogram.observe(start.elapsed().as_secs_f64());
+    }
+}
+
+/// Serves the metrics over HTTP at `/metrics`, for Prometheus to scrape.
+pub struct MetricsListener {
+    tcp_listener: TcpListener,
+    server: Arc<ServerContext>,
+}
+
+impl MetricsListener {
+    pub async fn new<A>(address: A, server: Arc<ServerContext>) -> std::io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        Ok(Self {
+            tcp_listener: TcpListener::bind(address).await?,
+            server,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp_listener.local_addr()
+    }
+
+    pub async fn listen(&self) -> ! {
+        loop {
+            let (stream, addr) = match self.tcp_listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::debug!("Unable to accept metrics connection: {}.", err);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+
+            // Gauges that are cheaper to read when scraped than to keep up to date.
+            metrics()
+                .players
+                .set(self.server.world.players().len() as i64);
+            metrics().tps.set(self.server.tick_stats.tps());
+            metrics().mspt.set(self.server.tick_stats.mspt());
+
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream).await {
+                    tracing::debug!("Unable to answer metrics request from {}: {}.", addr, err);
+                }
+            });
+        }
+    }
+}
+
+/// Answers a single HTTP request, then closes the connection.
+async fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    // Idle connections aren't kept open.
+    let Ok(request) = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await else {
+        return Ok(());
+    };
+    let Some(request) = request? else {
+        return Ok(());
+    };
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next();
+    // Scrapers may add parameters, which are ignored.
+    let path = request_line
+        .next()
+        .map(|target| target.split_once('?').map_or(target, |(path, _)| path));
+    let (status, content_type, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            TextEncoder::new().format_type().to_owned(),
+            metrics().encode(),
+        ),
+        (Some("GET"), _) => (
+            "404 Not Found",
+            "text/plain".to_owned(),
+            b"Not found\n".to_vec(),
+        ),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain".to_owned(),
+            b"Method not allowed\n".to_vec(),
+        ),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await
+}
+
+/// Reads the head of an HTTP request, or `None` if the connection is closed or the head is too
+/// long.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<Vec<u8>>> {
+    let mut request = Vec::new();
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() >= MAX_REQUEST_LEN || stream.read_buf(&mut request).await? == 0 {
+            return Ok(None);
+        }
+    }
+    Ok(Some(request))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends `request` to [`respond`], returning the response.
+    async fn request(request: &[u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        let responder = tokio::spawn(respond(server));
+
+        client.write_all(request).await.unwrap();
+        let mut response = String::new();
+        // Connections closed without reading all of the request are reset, which ends the
+        // response just the same.
+        let _ = client.read_to_string(&mut response).await;
+        responder.await.unwrap().unwrap();
+        response
+    }
+
+    fn status_line(response: &str) -> &str {
+        response.lines().next().unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        metrics().bytes_sent.inc_by(3);
+        let response = request(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+        assert!(body.contains("# TYPE mcserver_bytes_sent_total counter"));
+
+        // Parameters don't change the path.
+        let response = request(b"GET /metrics?name[]=tps HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    }
+
+    #[tokio::test]
+    async fn rejects_other_requests() {
+        let response = request(b"GET / HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+        assert!(response.ends_with("\r\n\r\nNot found\n"));
+        let response = request(b"GET /metrics/other HTTP/1.1\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+
+        let response = request(b"POST /metrics HTTP/1.1\r\nContent-Length: 0\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 405 Method Not Allowed");
+        let response = request(b"\r\n\r\n").await;
+        assert_eq!(status_line(&response), "HTTP/1.1 405 Method Not Allowed");
+    }
+
+    #[tokio::test]
+    async fn closes_long_requests() {
+        let mut long = b"GET /metrics HTTP/1.1\r\nX-Padding: ".to_vec();
+        long.resize(2 * MAX_REQUEST_LEN, b'a');
+        long.extend_from_slice(b"\r\n\r\n");
+        assert_eq!(request(&long).await, "");
+
+        // Requests that aren't finished when the connection is closed aren't answered.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        client.shutdown().await.unwrap();
+        respond(server).await.unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closes_idle_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        client
+            .write_all(b"GET /metrics HTTP/1.1\r\n")
+            .await
+            .unwrap();
+
+        let start = tokio::time::Instant::now();
+        respond(server).await.unwrap();
+        assert!(start.elapsed() >= REQUEST_TIMEOUT);
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        assert!(response.is_empty());
+    }
+
+    #[test]
+    fn encodes_metrics() {
+        let metrics = Metrics::new();
+        metrics.connection_entered(ConnectionState::Play);
+        metrics.connection_entered(ConnectionState::Play);
+        metrics.connection_left(ConnectionState::Play);
+        metrics.packet_received(ConnectionState::Status, 0x01);
+        metrics.players.set(5);
+        metrics.decode_error(&DecodeError::Specific("test"));
+
+        let text = String::from_utf8(metrics.encode()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        for line in [
+            "mcserver_connections{state=\"play\"} 1",
+            "mcserver_packets_received_total{id=\"0x01\",state=\"status\"} 1",
+            "mcserver_players 5",
+            "mcserver_decode_errors_total{kind=\"specific\"} 1",
+            "mcserver_bytes_received_total 0",
+            "# HELP mcserver_ticks_per_second Ticks per second, recently.",
+            "# TYPE mcserver_tick_seconds histogram",
+        ] {
+            assert!(lines.contains(&line), "missing {line} in:\n{text}");
+        }
+    }
+}
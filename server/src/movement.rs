@@ -0,0 +1,393 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    entity::EntityType,
+    physics,
+    world::{BlockState, Position, World},
+};
+
+/// Shrinks bounding boxes slightly, so that standing right on or next to a block isn't colliding.
+const COLLISION_EPSILON: f64 = 1.0e-5;
+/// Coordinates beyond this are rejected, same as vanilla.
+const MAX_COORDINATE: f64 = 3.0e7;
+const MAX_Y_COORDINATE: f64 = 2.0e7;
+/// How high players rise above the ground when jumping, with vanilla's jump velocity, gravity
+/// and drag.
+const MAX_JUMP_HEIGHT: f64 = 1.2523;
+/// How far below a player's feet a block may be for the player to stand on it, allowing for
+/// latency.
+const SUPPORT_DEPTH: f64 = 0.5;
+
+/// Settings for server-side movement validation, the `[movement]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MovementSettings {
+    /// Whether to validate movement at all. If disabled, the client is trusted.
+    pub validate: bool,
+    /// Multiplier applied to the distance limits.
+    ///
+    /// Raise it if legitimate players (e.g. on laggy connections) get teleported back.
+    pub leniency: f64,
+    /// Maximum squared distance a player may move in one movement packet.
+    pub max_distance_squared: f64,
+    /// Maximum squared distance a player that can fly may move in one movement packet.
+    pub max_flying_distance_squared: f64,
+    /// Whether to reject movement into solid blocks.
+    pub check_collision: bool,
+    /// Whether to reject players that can't fly rising higher than a jump above where they last
+    /// stood.
+    pub check_floating: bool,
+}
+
+impl Default for MovementSettings {
+    fn default() -> Self {
+        // Vanilla limits.
+        Self {
+            validate: true,
+            leniency: 1.0,
+            max_distance_squared: 100.0,
+            max_flying_distance_squared: 300.0,
+            check_collision: true,
+            check_floating: true,
+        }
+    }
+}
+
+/// The result of handling a movement packet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementOutcome {
+    Accepted {
+        chunk_changed: bool,
+    },
+    /// The movement was ignored because a teleport is pending.
+    Ignored,
+    /// The movement was rejected, and the player was teleported back.
+    Rejected(MovementRejection),
+}
+
+/// Why a player's movement was rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MovementRejection {
+    /// The position is not finite, or outside of the world.
+    InvalidPosition,
+    /// The player moved further than allowed.
+    TooFast { distance_squared: f64 },
+    /// The player moved into a solid block.
+    Collision,
+    /// The player rose higher than a jump while in the air.
+    Floating { height: f64 },
+}
+
+impl Display for MovementRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovementRejection::InvalidPosition => f.write_str("invalid position"),
+            MovementRejection::TooFast { distance_squared } => {
+                write!(
+                    f,
+                    "moved too quickly ({:.2} blocks)",
+                    distance_squared.sqrt()
+                )
+            }
+            MovementRejection::Collision => f.write_str("moved into a block"),
+            MovementRejection::Floating { height } => {
+                write!(f, "floated {height:.2} blocks above the ground")
+            }
+        }
+    }
+}
+
+/// Validates a player's move from `from` to `to`.
+///
+/// `ground_y` is the Y coordinate the player was last [supported](is_supported) at.
+pub fn validate_movement(
+    settings: &MovementSettings,
+    world: &World,
+    from: Position,
+    to: Position,
+    ground_y: f64,
+    can_fly: bool,
+) -> Result<(), MovementRejection> {
+    if !is_valid_position(to) {
+        return Err(MovementRejection::InvalidPosition);
+    }
+
+    if !settings.validate {
+        return Ok(());
+    }
+
+    let distance_squared =
+        (to.x - from.x).powi(2) + (to.y - from.y).powi(2) + (to.z - from.z).powi(2);
+    let max_distance_squared = if can_fly {
+        settings.max_flying_distance_squared
+    } else {
+        settings.max_distance_squared
+    };
+    if distance_squared > max_distance_squared * settings.leniency {
+        return Err(MovementRejection::TooFast { distance_squared });
+    }
+
+    // Players already stuck in a block may move out of it.
+    if settings.check_collision && collides(world, to) && !collides(world, from) {
+        return Err(MovementRejection::Collision);
+    }
+
+    // Players may rise in the air while jumping, but not above the top of the jump.
+    let height = to.y - ground_y;
+    if settings.check_floating
+        && !can_fly
+        && to.y > from.y
+        && height > MAX_JUMP_HEIGHT * settings.leniency
+        && !is_supported(world, to, false)
+    {
+        return Err(MovementRejection::Floating { height });
+    }
+
+    Ok(())
+}
+
+/// Whether a player at `position` is held up, by standing on a block if it claims to be
+/// `on_ground`, or by being in a fluid.
+pub fn is_supported(world: &World, position: Position, on_ground: bool) -> bool {
+    let bounding_box = EntityType::PLAYER
+        .bounding_box(position)
+        .deflate(COLLISION_EPSILON);
+    let in_fluid = bounding_box
+        .blocks()
+        .any(|block| world.block(block).is_some_and(BlockState::is_fluid));
+    in_fluid
+        || on_ground
+            && physics::collides(
+                world,
+                &bounding_box.expand_towards(0.0, -SUPPORT_DEPTH, 0.0),
+            )
+}
+
+fn is_valid_position(position: Position) -> bool {
+    position.x.is_finite()
+        && position.y.is_finite()
+        && position.z.is_finite()
+        && position.x.abs() < MAX_COORDINATE
+        && position.y.abs() < MAX_Y_COORDINATE
+        && position.z.abs() < MAX_COORDINATE
+}
+
+/// Whether a player standing at `position` intersects any solid block.
+fn collides(world: &World, position: Position) -> bool {
+    let bounding_box = EntityType::PLAYER
+        .bounding_box(position)
+        .deflate(COLLISION_EPSILON);
+    physics::collides(world, &bounding_box)
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::BlockPosition;
+
+    use crate::world::{generator::FlatGenerator, ChunkPos, ChunkStatus, Ticket, TicketKind};
+
+    use super::*;
+
+    /// Y coordinate of the top of the classic flat world's grass.
+    const GROUND_Y: f64 = -60.0;
+
+    /// A classic flat world with chunk 0, 0 loaded.
+    async fn world() -> World {
+        let world = World::overworld(Box::new(FlatGenerator::default()));
+        let pos = ChunkPos::new(0, 0);
+        let chunks = world.chunk_manager();
+        chunks.add_ticket(Ticket::new(TicketKind::Forced, pos, 0));
+        assert!(chunks.wait_for(pos, ChunkStatus::Full).await);
+        world
+    }
+
+    fn at(y: f64) -> Position {
+        Position::new(8.5, y, 8.5)
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_positions() {
+        let world = world().await;
+        let settings = MovementSettings::default();
+        let from = at(GROUND_Y);
+        for to in [
+            Position::new(f64::NAN, GROUND_Y, 0.0),
+            Position::new(0.0, f64::INFINITY, 0.0),
+            Position::new(MAX_COORDINATE, GROUND_Y, 0.0),
+            Position::new(0.0, -MAX_Y_COORDINATE, 0.0),
+        ] {
+            assert_eq!(
+                validate_movement(&settings, &world, from, to, GROUND_Y, false),
+                Err(MovementRejection::InvalidPosition)
+            );
+        }
+
+        // Even if validation is disabled.
+        let settings = MovementSettings {
+            validate: false,
+            ..MovementSettings::default()
+        };
+        assert_eq!(
+            validate_movement(&settings, &world, from, at(f64::NAN), GROUND_Y, false),
+            Err(MovementRejection::InvalidPosition)
+        );
+        assert_eq!(
+            validate_movement(
+                &settings,
+                &world,
+                from,
+                at(GROUND_Y + 50.0),
+                GROUND_Y,
+                false
+            ),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_distance() {
+        let world = world().await;
+        let settings = MovementSettings::default();
+        let from = Position::new(0.5, GROUND_Y, 0.5);
+        let near = Position::new(9.5, GROUND_Y, 0.5);
+        let far = Position::new(11.5, GROUND_Y, 0.5);
+
+        assert_eq!(
+            validate_movement(&settings, &world, from, near, GROUND_Y, false),
+            Ok(())
+        );
+        assert_eq!(
+            validate_movement(&settings, &world, from, far, GROUND_Y, false),
+            Err(MovementRejection::TooFast {
+                distance_squared: 121.0
+            })
+        );
+        // Players that can fly may move further.
+        assert_eq!(
+            validate_movement(&settings, &world, from, far, GROUND_Y, true),
+            Ok(())
+        );
+
+        let lenient = MovementSettings {
+            leniency: 1.5,
+            ..MovementSettings::default()
+        };
+        assert_eq!(
+            validate_movement(&lenient, &world, from, far, GROUND_Y, false),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_collisions() {
+        let world = world().await;
+        let settings = MovementSettings::default();
+        let from = at(GROUND_Y);
+
+        assert_eq!(
+            validate_movement(&settings, &world, from, at(GROUND_Y - 0.5), GROUND_Y, false),
+            Err(MovementRejection::Collision)
+        );
+        // Players stuck in blocks may move out of them.
+        let stuck = at(GROUND_Y - 1.5);
+        assert_eq!(
+            validate_movement(
+                &settings,
+                &world,
+                stuck,
+                at(GROUND_Y - 1.0),
+                GROUND_Y,
+                false
+            ),
+            Ok(())
+        );
+
+        let settings = MovementSettings {
+            check_collision: false,
+            ..MovementSettings::default()
+        };
+        assert_eq!(
+            validate_movement(&settings, &world, from, at(GROUND_Y - 0.5), GROUND_Y, false),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_floating() {
+        let world = world().await;
+        let settings = MovementSettings::default();
+
+        // Jumping.
+        let top = at(GROUND_Y + 1.25);
+        assert_eq!(
+            validate_movement(&settings, &world, at(GROUND_Y + 1.0), top, GROUND_Y, false),
+            Ok(())
+        );
+        // Rising further while in the air.
+        let above = at(GROUND_Y + 1.5);
+        assert_eq!(
+            validate_movement(&settings, &world, top, above, GROUND_Y, false),
+            Err(MovementRejection::Floating { height: 1.5 })
+        );
+        // Falling, or moving sideways, is fine at any height.
+        let high = at(GROUND_Y + 10.0);
+        assert_eq!(
+            validate_movement(&settings, &world, high, at(GROUND_Y + 9.0), GROUND_Y, false),
+            Ok(())
+        );
+        assert_eq!(
+            validate_movement(
+                &settings,
+                &world,
+                high,
+                Position::new(9.0, GROUND_Y + 10.0, 8.5),
+                GROUND_Y,
+                false
+            ),
+            Ok(())
+        );
+
+        // Players that can fly, or swim up, may rise.
+        assert_eq!(
+            validate_movement(&settings, &world, top, above, GROUND_Y, true),
+            Ok(())
+        );
+        world.set_block(BlockPosition::new(8, -59, 8), BlockState::WATER);
+        assert_eq!(
+            validate_movement(&settings, &world, top, above, GROUND_Y, false),
+            Ok(())
+        );
+
+        let settings = MovementSettings {
+            check_floating: false,
+            ..MovementSettings::default()
+        };
+        assert_eq!(
+            validate_movement(
+                &settings,
+                &world,
+                high,
+                at(GROUND_Y + 11.0),
+                GROUND_Y,
+                false
+            ),
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn supported_players() {
+        let world = world().await;
+        assert!(is_supported(&world, at(GROUND_Y), true));
+        // Latency may make players land a bit above the ground.
+        assert!(is_supported(&world, at(GROUND_Y + 0.25), true));
+        // Players claiming to be on the ground in the air aren't standing on anything.
+        assert!(!is_supported(&world, at(GROUND_Y + 2.0), true));
+        assert!(!is_supported(&world, at(GROUND_Y), false));
+
+        world.set_block(BlockPosition::new(8, -58, 8), BlockState::WATER);
+        assert!(is_supported(&world, at(GROUND_Y + 2.0), false));
+    }
+}
@@ -7,12 +7,18 @@ use std::{
 
 use futures::future::BoxFuture;
 use packet::{client::*, server::*, KnownPack, Packet};
-use protocol::{identifier::Identifier, ConnectionState, EncodeError};
+use protocol::{identifier::Identifier, text::TextComponent, ConnectionState, EncodeError};
+use server_assets::Registries;
 use thiserror::Error;
 use tokio::sync::Mutex;
-use uuid::Uuid;
 
+use crate::chat::ChatError;
+use crate::command;
 use crate::connection::{Connection, PacketSendError, TARGET_PROTOCOL_VERSION};
+use crate::context::ServerContext;
+use crate::player::{GameProfile, Player, SpawnError};
+use crate::status;
+use crate::world::{Position, Rotation};
 
 pub trait PacketHandlerFn<P>:
     for<'a> FnMut(&'a P, &'a mut Connection) -> BoxFuture<'a, Result<(), PacketHandleError>> + Send
@@ -130,12 +136,118 @@ pub enum PacketHandleError {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     PacketSend(#[from] PacketSendError),
+    #[error(transparent)]
+    Spawn(#[from] SpawnError),
+    #[error(transparent)]
+    Chat(#[from] ChatError),
+    #[error("{0} is not allowed to join")]
+    LoginDenied(String),
+    #[error("unexpected packet: {0}")]
+    UnexpectedPacket(&'static str),
     #[error("packet handling was cancelled")]
     Cancelled,
     #[error(transparent)]
     User(#[from] anyhow::Error),
 }
 
+/// Checks whether a player may join, returning the disconnect reason if not.
+fn check_login(
+    connection: &Connection,
+    profile: &GameProfile,
+) -> Result<(), Box<TextComponent<'static>>> {
+    let server = &connection.server;
+    let config = server.config.get();
+    server
+        .access
+        .check_login(profile, connection.address().ip(), config.whitelist)?;
+
+    let bypasses_player_limit = server
+        .access
+        .op(profile)
+        .is_some_and(|op| op.bypasses_player_limit);
+    let online = server.world.players().len();
+    if online >= config.max_players.max(0) as usize && !bypasses_player_limit {
+        return Err(Box::new(TextComponent::translatable(
+            "multiplayer.disconnect.server_full",
+            [],
+        )));
+    }
+    Ok(())
+}
+
+/// The data pack the server's registries come from.
+fn core_pack() -> KnownPack<'static> {
+    KnownPack {
+        identifier: Identifier::from_string("core").unwrap(),
+        version: "1.21".into(),
+    }
+}
+
+/// Sends all registries, omitting entry data if the client already knows it from the core pack.
+async fn send_registry_data(
+    connection: &mut Connection,
+    knows_core_pack: bool,
+) -> Result<(), PacketHandleError> {
+    for (registry_id, registry) in Registries::get().iter() {
+        let registry_entries: Vec<_> = registry
+            .entries()
+            .keys()
+            .map(|id| RegistryEntry {
+                id: Identifier::from_string(id.as_str()).expect("valid registry entry ID"),
+                data: (!knows_core_pack).then(|| registry.entry_nbt(id).unwrap()),
+            })
+            .collect();
+
+        connection
+            .send_packet(&RegistryDataPacket {
+                registry_id: Identifier::from_string(registry_id).expect("valid registry ID"),
+                registry_entries: registry_entries.into(),
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+fn handle_movement(
+    connection: &mut Connection,
+    position: Option<Position>,
+    rotation: Option<Rotation>,
+    on_ground: bool,
+) -> Result<(), PacketHandleError> {
+    let Some(player) = &mut connection.player else {
+        return Ok(());
+    };
+
+    let server = &connection.server;
+    player.handle_movement(server, position, rotation, on_ground)?;
+
+    Ok(())
+}
+
+/// Runs `handle` on the connection's player, disconnecting it if the chat packet is invalid.
+async fn handle_chat(
+    connection: &mut Connection,
+    handle: impl FnOnce(&mut Player, &ServerContext) -> Result<(), ChatError>,
+) -> Result<(), PacketHandleError> {
+    let Some(player) = &mut connection.player else {
+        return Ok(());
+    };
+
+    if let Err(err) = handle(player, &connection.server) {
+        let reason =
+            TextComponent::translatable("multiplayer.disconnect.chat_validation_failed", []);
+        connection
+            .send_packet(&PlayDisconnectPacket {
+                reason: reason.to_network_nbt(),
+            })
+            .await?;
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
 pub async fn default_packet_handler(
     packet: &ServerPacket<'_>,
     connection: &mut Connection,
@@ -162,7 +274,7 @@ pub async fn default_packet_handler(
                 }
 
                 tracing::trace!("Switching to state {:?}.", next_state);
-                connection.state = *next_state;
+                connection.set_state(*next_state);
                 connection.can_request_status = true;
             }
         },
@@ -173,29 +285,9 @@ pub async fn default_packet_handler(
                     return Ok(());
                 }
 
-                let status_response =
-                    ClientStatusPacket::StatusResponsePacket(StatusResponsePacket {
-                        response: StatusResponse {
-                            version: StatusResponseVersion {
-                                name: "1.21.1".into(),
-                                protocol: 767,
-                            },
-                            players: StatusResponsePlayers {
-                                max: 10000000,
-                                online: 1,
-                                sample: vec![StatusResponsePlayersSample {
-                                    name: "hi".into(),
-                                    id: Uuid::new_v4(),
-                                }],
-                            },
-                            description: StatusResponseDescription {
-                                text: "Blazing fast server".into(),
-                            },
-                            favicon: "".into(),
-                            enforces_secure_chat: false,
-                        },
-                    });
-
+                let status_response = StatusResponsePacket {
+                    response: status::status_response(&connection.server),
+                };
                 connection.send_packet(&status_response).await?;
 
                 connection.can_request_status = false;
@@ -213,55 +305,275 @@ pub async fn default_packet_handler(
             }) => {
                 // TODO: client auth, encryption, compression
 
+                let game_profile = GameProfile {
+                    uuid: *player_uuid,
+                    username: player_username.to_string(),
+                    properties: Vec::new(),
+                };
+
+                if let Err(reason) = check_login(connection, &game_profile) {
+                    tracing::info!(
+                        "Disconnecting {} ({}): {}.",
+                        game_profile.username,
+                        connection.address(),
+                        reason.to_plain_text(&server_assets::translation)
+                    );
+                    connection.disconnect(&reason).await?;
+                    return Err(PacketHandleError::LoginDenied(game_profile.username));
+                }
+
                 connection
                     .send_packet(&LoginSuccessPacket {
-                        player_uuid: *player_uuid,
-                        player_username: Cow::Borrowed(player_username),
-                        properties: Vec::new().into(),
+                        player_uuid: game_profile.uuid,
+                        player_username: Cow::Borrowed(&game_profile.username),
+                        properties: game_profile
+                            .properties
+                            .iter()
+                            .map(|property| ClientLoginSuccessProperty {
+                                name: Cow::Borrowed(&property.name),
+                                value: Cow::Borrowed(&property.value),
+                                signature: property.signature.as_deref().map(Cow::Borrowed),
+                            })
+                            .collect::<Vec<_>>()
+                            .into(),
                         strict_error_handling: true,
                     })
                     .await?;
+
+                connection.game_profile = Some(game_profile);
             }
             ServerLoginPacket::LoginAcknowledgedPacket(LoginAcknowledgedPacket {}) => {
                 tracing::trace!("Login was acknowledged by the client.");
 
-                connection.state = ConnectionState::Configuration;
+                connection.set_state(ConnectionState::Configuration);
 
                 connection
                     .send_packet(&ClientboundKnownPacksPacket {
-                        known_packs: (&[KnownPack {
-                            identifier: Identifier::from_string("core").unwrap(),
-                            version: "1.21".into(),
-                        }])
-                            .into(),
+                        known_packs: vec![core_pack()].into(),
                     })
                     .await?;
             }
             _ => todo!(),
         },
-        ServerPacket::Configuration(packet) => match packet {
-            ServerConfigurationPacket::ServerboundPluginMessagePacket(
-                ServerboundPluginMessagePacket {
-                    channel_identifier,
-                    data,
+        ServerPacket::Configuration(packet) => {
+            match packet {
+                ServerConfigurationPacket::ServerboundPluginMessagePacket(
+                    ServerboundPluginMessagePacket {
+                        channel_identifier,
+                        data,
+                    },
+                ) => {
+                    tracing::trace!(
+                        "Received plugin message in channel {}: {:?}",
+                        channel_identifier,
+                        std::str::from_utf8(data)
+                    );
+
+                    // TODO
+                }
+                ServerConfigurationPacket::ClientInformationPacket(packet) => {
+                    connection.client_information = Some(packet.clone().into());
+                }
+                ServerConfigurationPacket::ServerboundKnownPacksPacket(
+                    ServerboundKnownPacksPacket { known_packs },
+                ) => {
+                    let knows_core_pack = known_packs.contains(&core_pack());
+                    send_registry_data(connection, knows_core_pack).await?;
+
+                    // TODO: tags
+
+                    connection
+                        .send_packet(&FinishConfigurationPacket {})
+                        .await?;
+                }
+                ServerConfigurationPacket::AcknowledgeFinishConfigurationPacket(
+                    AcknowledgeFinishConfigurationPacket {},
+                ) => {
+                    tracing::trace!("Configuration finished, switching to play state.");
+                    connection.set_state(ConnectionState::Play);
+
+                    let profile = connection.game_profile.clone().ok_or(
+                        PacketHandleError::UnexpectedPacket(
+                            "finished configuration without logging in",
+                        ),
+                    )?;
+                    let server = &connection.server;
+                    let permission_level = server.access.permission_level(&profile);
+                    let mut player =
+                        Player::new(profile, server.world.default_game_mode, connection.sender());
+                    player.set_permission_level(permission_level);
+                    if let Some(information) = &connection.client_information {
+                        player.set_client_view_distance(information.view_distance);
+                    }
+                    player.spawn_into(server)?;
+                    // From now on, the player is removed from the world when the connection closes.
+                    let player = connection.player.insert(player);
+                    player.send_commands(&server.commands)?;
+                }
+            }
+        }
+        ServerPacket::Play(packet) => match packet {
+            ServerPlayPacket::ConfirmTeleportationPacket(ConfirmTeleportationPacket {
+                teleport_id,
+            }) => {
+                if let Some(player) = &mut connection.player {
+                    if !player.confirm_teleport(*teleport_id) {
+                        tracing::trace!("Ignoring unexpected teleport ID {}.", teleport_id);
+                    }
+                }
+            }
+            ServerPlayPacket::SetPlayerPositionPacket(SetPlayerPositionPacket {
+                x,
+                feet_y,
+                z,
+                on_ground,
+            }) => {
+                handle_movement(
+                    connection,
+                    Some(Position::new(*x, *feet_y, *z)),
+                    None,
+                    *on_ground,
+                )?;
+            }
+            ServerPlayPacket::SetPlayerPositionAndRotationPacket(
+                SetPlayerPositionAndRotationPacket {
+                    x,
+                    feet_y,
+                    z,
+                    yaw,
+                    pitch,
+                    on_ground,
                 },
             ) => {
-                tracing::trace!(
-                    "Received plugin message in channel {}: {:?}",
-                    channel_identifier,
-                    std::str::from_utf8(data)
-                );
+                handle_movement(
+                    connection,
+                    Some(Position::new(*x, *feet_y, *z)),
+                    Some(Rotation::new(*yaw, *pitch)),
+                    *on_ground,
+                )?;
+            }
+            ServerPlayPacket::SetPlayerRotationPacket(SetPlayerRotationPacket {
+                yaw,
+                pitch,
+                on_ground,
+            }) => {
+                handle_movement(
+                    connection,
+                    None,
+                    Some(Rotation::new(*yaw, *pitch)),
+                    *on_ground,
+                )?;
+            }
+            ServerPlayPacket::SetPlayerOnGroundPacket(SetPlayerOnGroundPacket { on_ground }) => {
+                handle_movement(connection, None, None, *on_ground)?;
+            }
+            ServerPlayPacket::ServerboundSetHeldItemPacket(ServerboundSetHeldItemPacket {
+                slot,
+            }) => {
+                if let Some(player) = &mut connection.player {
+                    if !player.set_held_slot(*slot) {
+                        tracing::trace!("Ignoring invalid held slot {}.", slot);
+                    }
+                }
+            }
+            ServerPlayPacket::PlayerSessionPacket(PlayerSessionPacket { session }) => {
+                handle_chat(connection, |player, server| {
+                    player.handle_chat_session(&server.world, session)
+                })
+                .await?;
+            }
+            ServerPlayPacket::ChatMessagePacket(packet) => {
+                handle_chat(connection, |player, server| {
+                    player.handle_chat_message(&server.chat, &server.world, packet)
+                })
+                .await?;
+            }
+            ServerPlayPacket::AcknowledgeMessagePacket(AcknowledgeMessagePacket {
+                message_count,
+            }) => {
+                handle_chat(connection, |player, _| {
+                    player.acknowledge_messages(*message_count)
+                })
+                .await?;
+            }
+            ServerPlayPacket::ChatCommandPacket(ChatCommandPacket { command }) => {
+                if let Some(player) = &mut connection.player {
+                    command::run_command(&connection.server, player, command);
+                }
+            }
+            ServerPlayPacket::SignedChatCommandPacket(SignedChatCommandPacket {
+                command,
+                timestamp,
+                message_count,
+                acknowledged,
+                ..
+            }) => {
+                handle_chat(connection, |player, _| {
+                    player.handle_chat_command(Some(*timestamp), *message_count, acknowledged)
+                })
+                .await?;
+
+                // TODO: Signed arguments (e.g. of /msg) are not relayed as player chat yet.
+                if let Some(player) = &mut connection.player {
+                    command::run_command(&connection.server, player, command);
+                }
+            }
+            ServerPlayPacket::CommandSuggestionsRequestPacket(
+                CommandSuggestionsRequestPacket {
+                    transaction_id,
+                    text,
+                },
+            ) => {
+                let Some(player) = &connection.player else {
+                    return Ok(());
+                };
+
+                // The suggestion range has to account for the leading slash.
+                let input = text.strip_prefix('/').unwrap_or(text);
+                let offset = text.len() - input.len();
+                let server = &connection.server;
+                let suggestions = server.commands.suggest(server, player, input);
 
-                // TODO
+                let (before, replaced) = text.split_at(offset + suggestions.start);
+                let matches: Vec<_> = suggestions
+                    .matches
+                    .iter()
+                    .map(|suggestion| CommandSuggestionMatch {
+                        text: suggestion.text.as_str().into(),
+                        tooltip: suggestion
+                            .tooltip
+                            .as_deref()
+                            .map(|tooltip| TextComponent::text(tooltip).to_network_nbt()),
+                    })
+                    .collect();
+                // Clients count in UTF-16 code units.
+                player
+                    .sender()
+                    .send_packet(&CommandSuggestionsResponsePacket {
+                        transaction_id: *transaction_id,
+                        start: before.encode_utf16().count() as i32,
+                        length: replaced.encode_utf16().count() as i32,
+                        matches: matches.into(),
+                    })?;
             }
-            ServerConfigurationPacket::ClientInformationPacket(packet) => {
+            ServerPlayPacket::PlayClientInformationPacket(packet) => {
                 connection.client_information = Some(packet.clone().into());
+                if let Some(player) = &mut connection.player {
+                    player.set_client_view_distance(packet.view_distance);
+                    player.update_chunk_view(&connection.server)?;
+                }
+            }
+            ServerPlayPacket::ChunkBatchReceivedPacket(ChunkBatchReceivedPacket {
+                chunks_per_tick,
+            }) => {
+                if let Some(player) = &mut connection.player {
+                    player.chunk_batch_received(*chunks_per_tick);
+                }
             }
-            ServerConfigurationPacket::ServerboundKnownPacksPacket(_packet) => {
-                // TODO
+            packet => {
+                tracing::trace!("Unhandled play packet {:?}.", packet);
             }
         },
-        ServerPacket::Play(_) => todo!(),
     }
 
     Ok(())
@@ -0,0 +1,350 @@
+//! Fine-grained permissions beyond operator levels, e.g. to let moderators ban players without
+//! making them operators.
+//!
+//! Permissions are dot-separated nodes like `minecraft.command.ban`. Commands are guarded by
+//! `minecraft.command.<name>`, and fall back to their permission level when a player has
+//! neither been granted nor denied that node.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::player::GameProfile;
+
+pub const PERMISSIONS_FILE: &str = "permissions.toml";
+
+/// The group every player is in.
+pub const DEFAULT_GROUP: &str = "default";
+
+/// Prefix of the permissions guarding commands, followed by the command name.
+pub const COMMAND_PERMISSION_PREFIX: &str = "minecraft.command.";
+
+/// Decides which permissions players have.
+pub trait PermissionProvider: Debug + Send + Sync {
+    /// Whether `player` has `permission`, or `None` if this provider doesn't decide it.
+    fn permission(&self, player: &GameProfile, permission: &str) -> Option<bool>;
+
+    /// Reads the permissions again, e.g. after they were edited by hand.
+    fn reload(&self) -> Result<(), PermissionError> {
+        Ok(())
+    }
+}
+
+/// Permissions of groups and players, as stored in [`PERMISSIONS_FILE`].
+///
+/// Permissions may end with `*` to match all nodes starting with the rest, like
+/// `minecraft.command.*`, and start with `-` to deny rather than grant. A player's own
+/// permissions take precedence over its groups', which take precedence over the groups they
+/// inherit from. Within one list, the most specific match wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Permissions {
+    pub groups: HashMap<String, Group>,
+    /// Players by UUID or name.
+    pub players: HashMap<String, PlayerPermissions>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Group {
+    /// Groups whose permissions this group has too, unless it overrides them.
+    pub inherits: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerPermissions {
+    /// Groups the player is in besides [`DEFAULT_GROUP`], earlier ones taking precedence.
+    pub groups: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl Permissions {
+    /// Checks that all groups players are in or groups inherit from exist.
+    pub fn validate(&self) -> Result<(), PermissionError> {
+        let referenced = self
+            .groups
+            .values()
+            .flat_map(|group| &group.inherits)
+            .chain(self.players.values().flat_map(|player| &player.groups));
+        for group in referenced {
+            if !self.groups.contains_key(group) {
+                return Err(PermissionError::UnknownGroup(group.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `player` has `permission`, or `None` if neither it nor any of its groups grant
+    /// or deny it.
+    pub fn permission(&self, player: &GameProfile, permission: &str) -> Option<bool> {
+        let entry = self.player(player);
+        if let Some(granted) = entry.and_then(|entry| find(&entry.permissions, permission)) {
+            return Some(granted);
+        }
+
+        let mut visited = HashSet::new();
+        entry
+            .map_or(&[][..], |entry| entry.groups.as_slice())
+            .iter()
+            .map(String::as_str)
+            .chain([DEFAULT_GROUP])
+            .find_map(|group| self.group_permission(group, permission, &mut visited))
+    }
+
+    fn player(&self, profile: &GameProfile) -> Option<&PlayerPermissions> {
+        self.players.get(&profile.uuid.to_string()).or_else(|| {
+            self.players
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&profile.username))
+                .map(|(_, player)| player)
+        })
+    }
+
+    /// Looks `permission` up in a group, then in the groups it inherits from, depth-first.
+    fn group_permission<'a>(
+        &'a self,
+        name: &'a str,
+        permission: &str,
+        visited: &mut HashSet<&'a str>,
+    ) -> Option<bool> {
+        // Skips groups inherited more than once, which also breaks cycles.
+        if !visited.insert(name) {
+            return None;
+        }
+        let group = self.groups.get(name)?;
+        find(&group.permissions, permission).or_else(|| {
+            group
+                .inherits
+                .iter()
+                .find_map(|parent| self.group_permission(parent, permission, visited))
+        })
+    }
+}
+
+/// Finds the most specific entry of `entries` matching `permission`, returning whether it
+/// grants rather than denies it.
+fn find(entries: &[String], permission: &str) -> Option<bool> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let (granted, pattern) = match entry.strip_prefix('-') {
+                Some(pattern) => (false, pattern),
+                None => (true, entry.as_str()),
+            };
+            specificity(pattern, permission).map(|specificity| (specificity, granted))
+        })
+        .max_by_key(|&(specificity, _)| specificity)
+        .map(|(_, granted)| granted)
+}
+
+/// How specific `pattern` is if it matches `permission`, exact matches being the most specific
+/// and longer wildcards being more specific than shorter ones.
+fn specificity(pattern: &str, permission: &str) -> Option<usize> {
+    if pattern == permission {
+        return Some(usize::MAX);
+    }
+    let prefix = pattern.strip_suffix('*')?;
+    let is_wildcard = prefix.is_empty() || prefix.ends_with('.');
+    (is_wildcard && permission.starts_with(prefix)).then_some(prefix.len())
+}
+
+/// Permissions read from a TOML file.
+#[derive(Debug)]
+pub struct FilePermissions {
+    path: PathBuf,
+    permissions: RwLock<Permissions>,
+}
+
+impl FilePermissions {
+    /// Loads the permissions at `path`, which grants nothing if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self, PermissionError> {
+        Ok(Self {
+            path: path.to_owned(),
+            permissions: RwLock::new(read(path)?),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a snapshot of the permissions.
+    pub fn get(&self) -> Permissions {
+        self.permissions.read().unwrap().clone()
+    }
+}
+
+impl PermissionProvider for FilePermissions {
+    fn permission(&self, player: &GameProfile, permission: &str) -> Option<bool> {
+        self.permissions
+            .read()
+            .unwrap()
+            .permission(player, permission)
+    }
+
+    fn reload(&self) -> Result<(), PermissionError> {
+        let permissions = read(&self.path)?;
+        *self.permissions.write().unwrap() = permissions;
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<Permissions, PermissionError> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Permissions::default()),
+        Err(source) => {
+            return Err(PermissionError::Io {
+                path: path.to_owned(),
+                source,
+            })
+        }
+    };
+    let permissions: Permissions =
+        toml::from_str(&text).map_err(|source| PermissionError::Parse {
+            path: path.to_owned(),
+            source,
+        })?;
+    permissions.validate()?;
+    Ok(permissions)
+}
+
+#[derive(Error, Debug)]
+pub enum PermissionError {
+    #[error("unable to access {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid permissions file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("unknown group {0}")]
+    UnknownGroup(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Permissions {
+        let permissions: Permissions = toml::from_str(toml).unwrap();
+        permissions.validate().unwrap();
+        permissions
+    }
+
+    #[test]
+    fn most_specific_wins() {
+        let player = GameProfile::offline("Steve");
+        for entries in [r#"["a.*", "-a.b"]"#, r#"["-a.b", "a.*"]"#] {
+            let permissions = parse(&format!("[players.Steve]\npermissions = {entries}"));
+            assert_eq!(permissions.permission(&player, "a.c"), Some(true));
+            assert_eq!(permissions.permission(&player, "a.c.d"), Some(true));
+            assert_eq!(permissions.permission(&player, "a.b"), Some(false));
+            assert_eq!(permissions.permission(&player, "b"), None);
+        }
+
+        let permissions = parse(
+            r#"
+            [players.Steve]
+            permissions = ["-*", "a.b.*", "-a.b.c", "a.b.c.d"]
+            "#,
+        );
+        assert_eq!(permissions.permission(&player, "z"), Some(false));
+        assert_eq!(permissions.permission(&player, "a.b.x"), Some(true));
+        assert_eq!(permissions.permission(&player, "a.b.c"), Some(false));
+        assert_eq!(permissions.permission(&player, "a.b.c.d"), Some(true));
+        // Wildcards only match whole nodes.
+        let permissions = parse("[players.Steve]\npermissions = [\"a.b*\"]");
+        assert_eq!(permissions.permission(&player, "a.bc"), None);
+    }
+
+    #[test]
+    fn player_and_group_precedence() {
+        let permissions = parse(
+            r#"
+            [groups.default]
+            permissions = ["chat", "build"]
+
+            [groups.moderator]
+            inherits = ["default"]
+            permissions = ["-build", "ban"]
+
+            [groups.admin]
+            inherits = ["moderator"]
+            permissions = ["build.*"]
+
+            [players.Steve]
+            groups = ["admin"]
+            permissions = ["-ban"]
+
+            [players.Alex]
+            groups = ["moderator"]
+            "#,
+        );
+        let (steve, alex, herobrine) = (
+            GameProfile::offline("Steve"),
+            GameProfile::offline("Alex"),
+            GameProfile::offline("Herobrine"),
+        );
+
+        // Players' own permissions first.
+        assert_eq!(permissions.permission(&steve, "ban"), Some(false));
+        // Then their groups', before the inherited ones.
+        assert_eq!(permissions.permission(&steve, "build.house"), Some(true));
+        assert_eq!(permissions.permission(&alex, "build"), Some(false));
+        assert_eq!(permissions.permission(&alex, "ban"), Some(true));
+        // Inherited through two groups.
+        assert_eq!(permissions.permission(&steve, "chat"), Some(true));
+        // Players that aren't listed are in the default group only.
+        assert_eq!(permissions.permission(&herobrine, "build"), Some(true));
+        assert_eq!(permissions.permission(&herobrine, "ban"), None);
+    }
+
+    #[test]
+    fn inheritance_cycles() {
+        let permissions = parse(
+            r#"
+            [groups.a]
+            inherits = ["b"]
+            permissions = ["a"]
+
+            [groups.b]
+            inherits = ["a"]
+            permissions = ["b"]
+
+            [players.Steve]
+            groups = ["a"]
+            "#,
+        );
+        let player = GameProfile::offline("Steve");
+        assert_eq!(permissions.permission(&player, "a"), Some(true));
+        assert_eq!(permissions.permission(&player, "b"), Some(true));
+        assert_eq!(permissions.permission(&player, "c"), None);
+    }
+
+    #[test]
+    fn unknown_groups() {
+        let permissions: Permissions = toml::from_str(
+            r#"
+            [players.Steve]
+            groups = ["missing"]
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            permissions.validate(),
+            Err(PermissionError::UnknownGroup(group)) if group == "missing"
+        ));
+    }
+}
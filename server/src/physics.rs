@@ -0,0 +1,449 @@
+//! Physics of entities: gravity, drag, and collision with the collision shapes of blocks.
+//!
+//! Collision works like vanilla's: movement is clipped against the boxes of the blocks around
+//! a bounding box one axis at a time, Y first, then the horizontal axis with the largest
+//! movement. Entities that hit a wall while on the ground may step up onto it.
+
+use protocol::BlockPosition;
+
+use crate::{
+    entity::{metadata, Entity, MetadataValue, Velocity},
+    world::{ChunkPos, Position, World},
+};
+
+/// Bounding boxes are moved at least this far apart from blocks, and only collide with them when
+/// they overlap by more, so that boxes touching a block aren't stuck in it.
+const COLLISION_EPSILON: f64 = 1.0e-7;
+
+/// Horizontal velocity kept every tick by entities on the ground, on top of their drag. This is
+/// the slipperiness of most blocks in vanilla.
+const GROUND_FRICTION: f64 = 0.6;
+
+/// How far below the bottom of the world entities are removed, same as vanilla.
+pub const VOID_DEPTH: f64 = 64.0;
+
+/// How an entity type moves on its own, in blocks per tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Physics {
+    /// Speed lost downwards every tick.
+    pub gravity: f64,
+    /// Fraction of the velocity kept every tick.
+    pub drag: f64,
+    /// Height of the blocks the entity walks onto instead of being stopped by them.
+    pub step_height: f64,
+}
+
+impl Physics {
+    pub const fn new(gravity: f64, drag: f64, step_height: f64) -> Self {
+        Self {
+            gravity,
+            drag,
+            step_height,
+        }
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BoundingBox {
+    pub min: Position,
+    pub max: Position,
+}
+
+impl BoundingBox {
+    /// The box filling a whole block, relative to the block.
+    pub const FULL_BLOCK: Self =
+        Self::new(Position::new(0.0, 0.0, 0.0), Position::new(1.0, 1.0, 1.0));
+
+    pub const fn new(min: Position, max: Position) -> Self {
+        Self { min, max }
+    }
+
+    /// The box moved by `x`, `y` and `z`.
+    pub fn offset(&self, x: f64, y: f64, z: f64) -> Self {
+        Self::new(
+            Position::new(self.min.x + x, self.min.y + y, self.min.z + z),
+            Position::new(self.max.x + x, self.max.y + y, self.max.z + z),
+        )
+    }
+
+    /// The box grown to also cover where it would be moved by `x`, `y` and `z`.
+    pub fn expand_towards(&self, x: f64, y: f64, z: f64) -> Self {
+        Self::new(
+            Position::new(
+                self.min.x + x.min(0.0),
+                self.min.y + y.min(0.0),
+                self.min.z + z.min(0.0),
+            ),
+            Position::new(
+                self.max.x + x.max(0.0),
+                self.max.y + y.max(0.0),
+                self.max.z + z.max(0.0),
+            ),
+        )
+    }
+
+    /// The box shrunk by `amount` on every side.
+    pub fn deflate(&self, amount: f64) -> Self {
+        Self::new(
+            Position::new(
+                self.min.x + amount,
+                self.min.y + amount,
+                self.min.z + amount,
+            ),
+            Position::new(
+                self.max.x - amount,
+                self.max.y - amount,
+                self.max.z - amount,
+            ),
+        )
+    }
+
+    /// Whether the boxes overlap, not only touch.
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        (0..3).all(|axis| self.overlaps_on(other, axis, 0.0))
+    }
+
+    /// The blocks the box is in or touches.
+    pub fn blocks(&self) -> impl Iterator<Item = BlockPosition> {
+        let min = Position::new(
+            self.min.x - COLLISION_EPSILON,
+            self.min.y - COLLISION_EPSILON,
+            self.min.z - COLLISION_EPSILON,
+        )
+        .block_position();
+        let max = Position::new(
+            self.max.x + COLLISION_EPSILON,
+            self.max.y + COLLISION_EPSILON,
+            self.max.z + COLLISION_EPSILON,
+        )
+        .block_position();
+        (min.x..=max.x).flat_map(move |x| {
+            (min.y..=max.y)
+                .flat_map(move |y| (min.z..=max.z).map(move |z| BlockPosition::new(x, y, z)))
+        })
+    }
+
+    /// How far the box can move along `axis` (0 for X, 1 for Y, 2 for Z), up to `delta`,
+    /// before hitting `obstacle`.
+    fn clip(&self, obstacle: &BoundingBox, axis: usize, delta: f64) -> f64 {
+        let others = [(axis + 1) % 3, (axis + 2) % 3];
+        if others
+            .iter()
+            .any(|&other| !self.overlaps_on(obstacle, other, COLLISION_EPSILON))
+        {
+            return delta;
+        }
+        let (min, max) = (coordinate(self.min, axis), coordinate(self.max, axis));
+        let (obstacle_min, obstacle_max) = (
+            coordinate(obstacle.min, axis),
+            coordinate(obstacle.max, axis),
+        );
+        if delta > 0.0 && obstacle_min >= max - COLLISION_EPSILON {
+            delta.min(obstacle_min - max)
+        } else if delta < 0.0 && obstacle_max <= min + COLLISION_EPSILON {
+            delta.max(obstacle_max - min)
+        } else {
+            delta
+        }
+    }
+
+    /// Whether the boxes overlap by more than `epsilon` along `axis`.
+    fn overlaps_on(&self, other: &BoundingBox, axis: usize, epsilon: f64) -> bool {
+        coordinate(self.min, axis) < coordinate(other.max, axis) - epsilon
+            && coordinate(self.max, axis) > coordinate(other.min, axis) + epsilon
+    }
+}
+
+fn coordinate(position: Position, axis: usize) -> f64 {
+    match axis {
+        0 => position.x,
+        1 => position.y,
+        _ => position.z,
+    }
+}
+
+/// The result of moving a bounding box with [`move_box`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collision {
+    /// How far the box actually moved.
+    pub movement: Velocity,
+    /// Whether the box was stopped along X.
+    pub collided_x: bool,
+    /// Whether the box was stopped along Y.
+    pub collided_y: bool,
+    /// Whether the box was stopped along Z.
+    pub collided_z: bool,
+    /// Whether the box landed on a block.
+    pub on_ground: bool,
+}
+
+/// Returns the collision boxes of the blocks in `area`. Blocks that aren't loaded have none.
+pub fn block_boxes(world: &World, area: &BoundingBox) -> Vec<BoundingBox> {
+    let chunks = world.chunks();
+    area.blocks()
+        .filter_map(|position| {
+            let block = chunks.get(&ChunkPos::from_block(position))?.block(position);
+            Some((position, block))
+        })
+        .flat_map(|(position, block)| {
+            block.collision_shape().iter().map(move |shape| {
+                shape.offset(position.x as f64, position.y as f64, position.z as f64)
+            })
+        })
+        .collect()
+}
+
+/// Whether `bounding_box` overlaps the collision shape of any block.
+pub fn collides(world: &World, bounding_box: &BoundingBox) -> bool {
+    block_boxes(world, bounding_box)
+        .iter()
+        .any(|block| block.intersects(bounding_box))
+}
+
+/// Moves `bounding_box` by `movement`, or as far as it can before hitting blocks.
+///
+/// Boxes `on_ground` that hit a wall step up onto it if it's at most `step_height` high.
+pub fn move_box(
+    world: &World,
+    bounding_box: &BoundingBox,
+    movement: Velocity,
+    step_height: f64,
+    on_ground: bool,
+) -> Collision {
+    let area = bounding_box
+        .expand_towards(movement.x, movement.y, movement.z)
+        .expand_towards(0.0, step_height, 0.0);
+    let obstacles = block_boxes(world, &area);
+    move_among(&obstacles, bounding_box, movement, step_height, on_ground)
+}
+
+/// Moves `bounding_box` like [`move_box`], with `obstacles` as the boxes of the blocks around.
+fn move_among(
+    obstacles: &[BoundingBox],
+    bounding_box: &BoundingBox,
+    movement: Velocity,
+    step_height: f64,
+    on_ground: bool,
+) -> Collision {
+    let wanted = [movement.x, movement.y, movement.z];
+    let mut moved = collide(obstacles, *bounding_box, wanted);
+    let landed = moved[1] != wanted[1] && wanted[1] < 0.0;
+    let blocked = moved[0] != wanted[0] || moved[2] != wanted[2];
+    if step_height > 0.0 && (on_ground || landed) && blocked {
+        let stepped = step_up(obstacles, *bounding_box, wanted, step_height);
+        if horizontal_distance_squared(stepped) > horizontal_distance_squared(moved) {
+            moved = stepped;
+        }
+    }
+
+    let [x, y, z] = moved;
+    Collision {
+        movement: Velocity::new(x, y, z),
+        collided_x: x != wanted[0],
+        collided_y: y != wanted[1],
+        collided_z: z != wanted[2],
+        on_ground: y != wanted[1] && wanted[1] < 0.0,
+    }
+}
+
+/// Clips `movement` against `obstacles`, one axis at a time.
+fn collide(
+    obstacles: &[BoundingBox],
+    mut bounding_box: BoundingBox,
+    movement: [f64; 3],
+) -> [f64; 3] {
+    let order = if movement[0].abs() < movement[2].abs() {
+        [1, 2, 0]
+    } else {
+        [1, 0, 2]
+    };
+    let mut moved = [0.0; 3];
+    for axis in order {
+        if movement[axis] == 0.0 {
+            continue;
+        }
+        let delta = obstacles.iter().fold(movement[axis], |delta, obstacle| {
+            bounding_box.clip(obstacle, axis, delta)
+        });
+        moved[axis] = delta;
+        let mut offset = [0.0; 3];
+        offset[axis] = delta;
+        bounding_box = bounding_box.offset(offset[0], offset[1], offset[2]);
+    }
+    moved
+}
+
+/// Moves `movement` horizontally after going up by up to `step_height`, then back down onto the
+/// blocks stepped onto.
+fn step_up(
+    obstacles: &[BoundingBox],
+    bounding_box: BoundingBox,
+    movement: [f64; 3],
+    step_height: f64,
+) -> [f64; 3] {
+    let [_, up, _] = collide(obstacles, bounding_box, [0.0, step_height, 0.0]);
+    let raised = bounding_box.offset(0.0, up, 0.0);
+    let [x, _, z] = collide(obstacles, raised, [movement[0], 0.0, movement[2]]);
+    let moved = raised.offset(x, 0.0, z);
+    let [_, down, _] = collide(obstacles, moved, [0.0, movement[1] - up, 0.0]);
+    [x, up + down, z]
+}
+
+fn horizontal_distance_squared(movement: [f64; 3]) -> f64 {
+    movement[0] * movement[0] + movement[2] * movement[2]
+}
+
+/// Advances `entity` by a tick: applies gravity, moves it by its velocity unless blocks are in
+/// the way, then slows it down by its drag, and by friction when on the ground.
+pub fn tick_entity(world: &World, entity: &mut Entity) {
+    let physics = entity.kind().physics;
+    let no_gravity =
+        entity.metadata.get(metadata::NO_GRAVITY) == Some(&MetadataValue::Boolean(true));
+    if !no_gravity {
+        entity.velocity.y -= physics.gravity;
+    }
+
+    let collision = move_box(
+        world,
+        &entity.bounding_box(),
+        entity.velocity,
+        physics.step_height,
+        entity.on_ground,
+    );
+    let moved = collision.movement;
+    entity.position = Position::new(
+        entity.position.x + moved.x,
+        entity.position.y + moved.y,
+        entity.position.z + moved.z,
+    );
+    entity.on_ground = collision.on_ground;
+
+    let velocity = &mut entity.velocity;
+    if collision.collided_x {
+        velocity.x = 0.0;
+    }
+    if collision.collided_y {
+        velocity.y = 0.0;
+    }
+    if collision.collided_z {
+        velocity.z = 0.0;
+    }
+    let friction = if entity.on_ground {
+        GROUND_FRICTION
+    } else {
+        1.0
+    };
+    velocity.x *= physics.drag * friction;
+    velocity.y *= physics.drag;
+    velocity.z *= physics.drag * friction;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A player-sized box standing at `x`, `y`, `z`.
+    fn player(x: f64, y: f64, z: f64) -> BoundingBox {
+        BoundingBox::new(
+            Position::new(x - 0.3, y, z - 0.3),
+            Position::new(x + 0.3, y + 1.8, z + 0.3),
+        )
+    }
+
+    fn block(x: i32, y: i32, z: i32) -> BoundingBox {
+        BoundingBox::FULL_BLOCK.offset(x as f64, y as f64, z as f64)
+    }
+
+    /// Full blocks from 0, 0 to 4, 4, with their top at Y 1.
+    fn floor() -> Vec<BoundingBox> {
+        (0..4)
+            .flat_map(|x| (0..4).map(move |z| block(x, 0, z)))
+            .collect()
+    }
+
+    #[test]
+    fn lands_on_floor() {
+        let collision = move_among(
+            &floor(),
+            &player(1.5, 1.5, 1.5),
+            Velocity::new(0.0, -1.0, 0.0),
+            0.6,
+            false,
+        );
+        assert_eq!(collision.movement, Velocity::new(0.0, -0.5, 0.0));
+        assert!(collision.on_ground);
+        assert!(collision.collided_y);
+        assert!(!collision.collided_x && !collision.collided_z);
+    }
+
+    #[test]
+    fn stops_at_wall() {
+        let mut obstacles = floor();
+        obstacles.extend([block(2, 1, 1), block(2, 2, 1)]);
+        let collision = move_among(
+            &obstacles,
+            &player(1.5, 1.0, 1.5),
+            Velocity::new(1.0, 0.0, 0.25),
+            0.0,
+            true,
+        );
+        assert!((collision.movement.x - 0.2).abs() < 1.0e-9);
+        assert_eq!(collision.movement.z, 0.25);
+        assert!(collision.collided_x);
+        assert!(!collision.collided_z);
+    }
+
+    #[test]
+    fn steps_up_half_blocks_only() {
+        let half_block =
+            BoundingBox::new(Position::new(0.0, 0.0, 0.0), Position::new(1.0, 0.5, 1.0));
+        let movement = Velocity::new(0.5, -0.08, 0.0);
+
+        let mut obstacles = floor();
+        obstacles.push(half_block.offset(2.0, 1.0, 1.0));
+        let collision = move_among(&obstacles, &player(1.5, 1.0, 1.5), movement, 0.6, true);
+        assert_eq!(collision.movement.x, 0.5);
+        assert!((collision.movement.y - 0.5).abs() < 1.0e-9);
+        assert!(collision.on_ground);
+        assert!(!collision.collided_x);
+
+        let mut obstacles = floor();
+        obstacles.push(block(2, 1, 1));
+        let collision = move_among(&obstacles, &player(1.5, 1.0, 1.5), movement, 0.6, true);
+        assert!((collision.movement.x - 0.2).abs() < 1.0e-9);
+        assert_eq!(collision.movement.y, 0.0);
+        assert!(collision.collided_x);
+    }
+
+    #[test]
+    fn touching_is_not_colliding() {
+        // Standing on the floor, against the side of a block.
+        let obstacles = [block(1, 0, 1), block(2, 1, 1)];
+        let standing = BoundingBox::new(
+            Position::new(1.5, 1.0, 1.25),
+            Position::new(2.0, 2.75, 1.75),
+        );
+        assert!(obstacles.iter().all(|block| !block.intersects(&standing)));
+
+        let collision = move_among(
+            &obstacles,
+            &standing,
+            Velocity::new(0.0, 0.0, 0.2),
+            0.0,
+            true,
+        );
+        assert_eq!(collision.movement, Velocity::new(0.0, 0.0, 0.2));
+        assert!(!collision.collided_x && !collision.collided_y && !collision.collided_z);
+        // Moving into the block is stopped right away.
+        let collision = move_among(
+            &obstacles,
+            &standing,
+            Velocity::new(0.1, 0.0, 0.0),
+            0.0,
+            true,
+        );
+        assert_eq!(collision.movement.x, 0.0);
+        assert!(collision.collided_x);
+    }
+}
@@ -0,0 +1,703 @@
+use std::sync::{
+    atomic::{AtomicI32, AtomicU8, Ordering},
+    Arc, Mutex,
+};
+
+use md5::{Digest, Md5};
+use packet::{client::*, server::ChatMessagePacket, ChatSessionData, PlayerAbilityFlags, Slot};
+use protocol::{text::TextComponent, GameMode};
+use server_assets::Registries;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    chat::{self, ChatError, ChatRecipient, ChatSession, ChatSettings},
+    chunk_view::{ChunkView, MIN_VIEW_DISTANCE},
+    command::{CommandDispatcher, CommandSender},
+    connection::{PacketSendError, PacketSender},
+    context::ServerContext,
+    inventory::PlayerInventory,
+    movement::{self, MovementOutcome},
+    player_list::PlayerEntry,
+    tick::Scheduler,
+    world::{ChunkPos, Position, Rotation, Ticket, World},
+};
+
+/// Game event telling the client to start waiting for level chunks.
+const GAME_EVENT_START_WAITING_FOR_CHUNKS: u8 = 13;
+
+/// Entity status telling the client its permission level, add the level (0-4) to it.
+pub(crate) const ENTITY_STATUS_OP_PERMISSION_LEVEL_0: i8 = 24;
+
+/// Highest permission level, same as vanilla operators with `op-permission-level=4`.
+pub const MAX_PERMISSION_LEVEL: u8 = 4;
+
+pub const MAX_HEALTH: f32 = 20.0;
+pub const MAX_FOOD: i32 = 20;
+
+static NEXT_ENTITY_ID: AtomicI32 = AtomicI32::new(1);
+
+/// Allocates a new, unique entity ID.
+pub fn next_entity_id() -> i32 {
+    NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Adds then removes chunk tickets on the tick thread, as the chunk manager may wait for its
+/// workers, which connections shouldn't do. Changes are made in the order they are scheduled in.
+pub fn change_ticket(scheduler: &Scheduler, add: Option<Ticket>, remove: Option<Ticket>) {
+    scheduler.run_next_tick(move |server| {
+        let chunks = server.world.chunk_manager();
+        if let Some(ticket) = add {
+            chunks.add_ticket(ticket);
+        }
+        if let Some(ticket) = remove {
+            chunks.remove_ticket(ticket);
+        }
+    });
+}
+
+/// A player's profile, as sent in [`LoginSuccessPacket`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameProfile {
+    pub uuid: Uuid,
+    pub username: String,
+    pub properties: Vec<GameProfileProperty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameProfileProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+impl GameProfile {
+    /// Makes the profile of a player that isn't authenticated, with the same UUID as vanilla
+    /// gives it in offline mode.
+    pub fn offline(username: impl Into<String>) -> Self {
+        let username = username.into();
+        let hash = Md5::digest(format!("OfflinePlayer:{username}"));
+        Self {
+            uuid: uuid::Builder::from_md5_bytes(hash.into()).into_uuid(),
+            username,
+            properties: Vec::new(),
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&GameProfileProperty> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+}
+
+/// A player in the world.
+///
+/// This is the gameplay side of a player, the network side being its
+/// [`Connection`](crate::connection::Connection), which it sends packets to through a [`PacketSender`].
+#[derive(Debug)]
+pub struct Player {
+    sender: PacketSender,
+    entity_id: i32,
+    profile: GameProfile,
+    game_mode: GameMode,
+    position: Position,
+    rotation: Rotation,
+    on_ground: bool,
+    /// Y coordinate the player last stood on a block or was in a fluid at, which it may only
+    /// rise a jump above while in the air.
+    ///
+    /// Teleports leave it unchanged, so that players teleported back for floating stay rejected.
+    ground_y: f64,
+    /// The chunks sent to the client, around the chunk the player was last in.
+    chunk_view: ChunkView,
+    /// View distance requested by the client, if it sent its settings.
+    client_view_distance: Option<u8>,
+    health: f32,
+    food: i32,
+    food_saturation: f32,
+    inventory: PlayerInventory,
+    /// Selected hotbar slot (0-8).
+    held_slot: u8,
+    /// Permission level (0-4), same as vanilla operator levels.
+    ///
+    /// Shared with the player list, so that operators can be made while the player is online.
+    permission_level: Arc<AtomicU8>,
+    next_teleport_id: i32,
+    /// ID of the last teleport sent to the client, if it hasn't been confirmed yet.
+    pending_teleport: Option<i32>,
+    spawned: bool,
+    chat: Arc<Mutex<ChatRecipient>>,
+    chat_session: Option<ChatSession>,
+    /// Timestamp of the last chat message or command, in epoch milliseconds.
+    last_chat_timestamp: i64,
+}
+
+impl Player {
+    pub fn new(profile: GameProfile, game_mode: GameMode, sender: PacketSender) -> Self {
+        Self {
+            sender,
+            entity_id: next_entity_id(),
+            profile,
+            game_mode,
+            position: Position::default(),
+            rotation: Rotation::default(),
+            on_ground: false,
+            ground_y: 0.0,
+            chunk_view: ChunkView::new(ChunkPos::default(), MIN_VIEW_DISTANCE),
+            client_view_distance: None,
+            health: MAX_HEALTH,
+            food: MAX_FOOD,
+            food_saturation: 5.0,
+            inventory: PlayerInventory::new(),
+            held_slot: 0,
+            permission_level: Arc::default(),
+            next_teleport_id: 0,
+            pending_teleport: None,
+            spawned: false,
+            chat: Arc::default(),
+            chat_session: None,
+            last_chat_timestamp: i64::MIN,
+        }
+    }
+
+    pub fn entity_id(&self) -> i32 {
+        self.entity_id
+    }
+
+    pub fn profile(&self) -> &GameProfile {
+        &self.profile
+    }
+
+    pub fn game_mode(&self) -> GameMode {
+        self.game_mode
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.profile.uuid
+    }
+
+    pub fn username(&self) -> &str {
+        &self.profile.username
+    }
+
+    pub fn on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    /// The chunk the player is in.
+    pub fn chunk(&self) -> ChunkPos {
+        self.chunk_view.center()
+    }
+
+    pub fn chunk_view(&self) -> &ChunkView {
+        &self.chunk_view
+    }
+
+    pub fn sender(&self) -> &PacketSender {
+        &self.sender
+    }
+
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    pub fn food(&self) -> i32 {
+        self.food
+    }
+
+    pub fn food_saturation(&self) -> f32 {
+        self.food_saturation
+    }
+
+    pub fn inventory(&self) -> &PlayerInventory {
+        &self.inventory
+    }
+
+    pub fn inventory_mut(&mut self) -> &mut PlayerInventory {
+        &mut self.inventory
+    }
+
+    pub fn held_slot(&self) -> u8 {
+        self.held_slot
+    }
+
+    /// Sets the selected hotbar slot, as requested by the client. Returns `false` if `slot` is invalid.
+    pub fn set_held_slot(&mut self, slot: i16) -> bool {
+        match u8::try_from(slot) {
+            Ok(slot @ 0..=8) => {
+                self.held_slot = slot;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn permission_level(&self) -> u8 {
+        self.permission_level.load(Ordering::Relaxed)
+    }
+
+    /// Sets the permission level, which is sent to the client when it spawns.
+    ///
+    /// Use [`PlayerEntry::update_permission_level`] once the player is spawned.
+    pub fn set_permission_level(&mut self, permission_level: u8) {
+        self.permission_level.store(
+            permission_level.min(MAX_PERMISSION_LEVEL),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Handles a movement packet, updating the player's position, rotation and on-ground flag
+    /// and broadcasting the movement to the other players in the world.
+    ///
+    /// Movement is ignored while a teleport is pending, since the client has not moved to the new position yet.
+    /// Invalid movement is corrected by teleporting the player back.
+    pub fn handle_movement(
+        &mut self,
+        server: &ServerContext,
+        position: Option<Position>,
+        rotation: Option<Rotation>,
+        on_ground: bool,
+    ) -> Result<MovementOutcome, PacketSendError> {
+        if self.is_teleport_pending() {
+            return Ok(MovementOutcome::Ignored);
+        }
+
+        if let Some(position) = position {
+            let can_fly = self.abilities().contains(PlayerAbilityFlags::ALLOW_FLYING);
+            if let Err(rejection) = movement::validate_movement(
+                &server.movement,
+                &server.world,
+                self.position,
+                position,
+                self.ground_y,
+                can_fly,
+            ) {
+                tracing::debug!("{} {}, teleporting back.", self.profile.username, rejection);
+                self.teleport(self.position, self.rotation)?;
+                return Ok(MovementOutcome::Rejected(rejection));
+            }
+
+            self.position = position;
+            if can_fly || movement::is_supported(&server.world, position, on_ground) {
+                self.ground_y = position.y;
+            }
+        }
+        if let Some(rotation) = rotation {
+            self.rotation = rotation;
+        }
+        self.on_ground = on_ground;
+
+        server.world.players().update_movement(
+            self.entity_id,
+            self.position,
+            self.rotation,
+            on_ground,
+        );
+
+        let chunk_changed = self.position_chunk() != self.chunk_view.center();
+        self.update_chunk_view(server)?;
+
+        Ok(MovementOutcome::Accepted { chunk_changed })
+    }
+
+    /// Whether the client has confirmed its initial teleport, and is now in the world.
+    pub fn is_spawned(&self) -> bool {
+        self.spawned
+    }
+
+    /// Whether a teleport was sent to the client which it has not confirmed yet.
+    pub fn is_teleport_pending(&self) -> bool {
+        self.pending_teleport.is_some()
+    }
+
+    pub fn abilities(&self) -> PlayerAbilityFlags {
+        match self.game_mode {
+            GameMode::Survival | GameMode::Adventure => PlayerAbilityFlags::empty(),
+            GameMode::Creative => {
+                PlayerAbilityFlags::INVULNERABLE
+                    | PlayerAbilityFlags::ALLOW_FLYING
+                    | PlayerAbilityFlags::CREATIVE_MODE
+            }
+            GameMode::Spectator => {
+                PlayerAbilityFlags::INVULNERABLE
+                    | PlayerAbilityFlags::ALLOW_FLYING
+                    | PlayerAbilityFlags::FLYING
+            }
+        }
+    }
+
+    /// Runs the join sequence, spawning the player at the spawn point of the server's world.
+    ///
+    /// The player is only added to the player list of the world once it's sent the initial state,
+    /// so it isn't if this fails.
+    ///
+    /// The player is only [spawned](Self::is_spawned) once the client confirms the initial teleport.
+    pub fn spawn_into(&mut self, server: &ServerContext) -> Result<(), SpawnError> {
+        let (world, chat) = (&server.world, &server.chat);
+        let dimension_type = world.dimension_type.to_string();
+        let dimension_type_id = Registries::get()
+            .registry("minecraft:dimension_type")
+            .and_then(|registry| registry.id_of(&dimension_type))
+            .ok_or(SpawnError::UnknownDimensionType(dimension_type))?;
+
+        self.position = Position::from_block(world.spawn_position);
+        self.rotation = Rotation::new(world.spawn_angle, 0.0);
+        self.chunk_view = ChunkView::new(self.position_chunk(), self.view_distance(world));
+        self.spawned = false;
+
+        self.sender.send_packet(&PlayLoginPacket {
+            entity_id: self.entity_id,
+            is_hardcore: false,
+            dimension_names: vec![world.name.to_string().into()].into(),
+            // Ignored by the client.
+            max_players: 0,
+            view_distance: world.view_distance,
+            simulation_distance: world.simulation_distance,
+            reduced_debug_info: false,
+            enable_respawn_screen: true,
+            do_limited_crafting: false,
+            dimension_type: dimension_type_id,
+            dimension_name: world.name.clone(),
+            hashed_seed: world.hashed_seed,
+            game_mode: self.game_mode,
+            previous_game_mode: -1,
+            is_debug: false,
+            is_flat: world.is_flat,
+            death_location: None,
+            portal_cooldown: 0,
+            enforces_secure_chat: chat.enforce_secure_chat,
+        })?;
+
+        self.sender
+            .send_packet(&ClientboundChangeDifficultyPacket {
+                difficulty: world.difficulty,
+                difficulty_locked: false,
+            })?;
+
+        self.sender.send_packet(&ClientboundPlayerAbilitiesPacket {
+            flags: self.abilities(),
+            flying_speed: 0.05,
+            field_of_view_modifier: 0.1,
+        })?;
+
+        self.sender.send_packet(&ClientboundSetHeldItemPacket {
+            slot: self.held_slot as i8,
+        })?;
+
+        self.sender.send_packet(&SetDefaultSpawnPositionPacket {
+            location: world.spawn_position,
+            angle: world.spawn_angle,
+        })?;
+
+        self.sender.send_packet(&world.time_packet())?;
+
+        self.sender.send_packet(&EntityEventPacket {
+            entity_id: self.entity_id,
+            entity_status: ENTITY_STATUS_OP_PERMISSION_LEVEL_0 + self.permission_level() as i8,
+        })?;
+
+        self.teleport(self.position, self.rotation)?;
+
+        self.sender.send_packet(&SetContainerContentPacket {
+            window_id: 0,
+            state_id: self.inventory.state_id(),
+            slot_data: self.inventory.slots().into(),
+            carried_item: Slot::EMPTY,
+        })?;
+
+        self.sender.send_packet(&SetHealthPacket {
+            health: self.health,
+            food: self.food,
+            food_saturation: self.food_saturation,
+        })?;
+
+        self.sender.send_packet(&GameEventPacket {
+            event: GAME_EVENT_START_WAITING_FOR_CHUNKS,
+            value: 0.0,
+        })?;
+
+        self.sender.send_packet(&SetCenterChunkPacket {
+            chunk_x: self.chunk_view.center().x,
+            chunk_z: self.chunk_view.center().z,
+        })?;
+
+        // Only added once the client knows it's in the world, as the other players and the
+        // entities are sent to the players in the list.
+        world.players().add(PlayerEntry {
+            entity_id: self.entity_id,
+            profile: self.profile.clone(),
+            game_mode: self.game_mode,
+            position: self.position,
+            rotation: self.rotation,
+            on_ground: self.on_ground,
+            chat_session: self.chat_session.clone(),
+            chat: Arc::clone(&self.chat),
+            permission_level: Arc::clone(&self.permission_level),
+            sender: self.sender.clone(),
+        });
+        // The chunks are sent as they are loaded.
+        change_ticket(&server.scheduler, Some(self.chunk_view.ticket()), None);
+
+        Ok(())
+    }
+
+    /// The chunk the player's position is in.
+    fn position_chunk(&self) -> ChunkPos {
+        ChunkPos::new(self.position.chunk_x(), self.position.chunk_z())
+    }
+
+    /// The view distance chunks are sent within: the client's, capped by the server's.
+    pub fn view_distance(&self, world: &World) -> i32 {
+        let max = world.view_distance.max(MIN_VIEW_DISTANCE);
+        self.client_view_distance.map_or(max, |distance| {
+            i32::from(distance).clamp(MIN_VIEW_DISTANCE, max)
+        })
+    }
+
+    /// Sets the view distance requested by the client, taking effect on the next
+    /// [`update_chunk_view`](Self::update_chunk_view).
+    pub fn set_client_view_distance(&mut self, view_distance: u8) {
+        self.client_view_distance = Some(view_distance);
+    }
+
+    /// Moves the chunk view to the player's current chunk and view distance, unloading the
+    /// chunks that left it on the client and loading the ones that entered it, which are sent by
+    /// [`send_chunks`](Self::send_chunks).
+    pub fn update_chunk_view(&mut self, server: &ServerContext) -> Result<(), PacketSendError> {
+        let previous = self.chunk_view.ticket();
+        let view_distance = self.view_distance(&server.world);
+        let result = self
+            .chunk_view
+            .update(&self.sender, self.position_chunk(), view_distance);
+
+        // The new ticket is added first, so that the chunks in both stay loaded.
+        let ticket = self.chunk_view.ticket();
+        if ticket != previous {
+            change_ticket(&server.scheduler, Some(ticket), Some(previous));
+        }
+        result
+    }
+
+    /// Ticks the player, once per server tick.
+    pub fn tick(&mut self, world: &World) -> Result<(), PacketSendError> {
+        self.send_chunks(world)
+    }
+
+    /// Sends the next batch of chunks the client is missing, if it is ready for one, and the
+    /// light of its chunks that changed.
+    ///
+    /// Called every tick once the player is in the world.
+    pub fn send_chunks(&mut self, world: &World) -> Result<(), PacketSendError> {
+        self.chunk_view.send_light_updates(&self.sender, world)?;
+        self.chunk_view.send_batch(&self.sender, world)?;
+        Ok(())
+    }
+
+    /// Handles the client acknowledging a chunk batch, with the rate it can receive chunks at.
+    pub fn chunk_batch_received(&mut self, chunks_per_tick: f32) {
+        self.chunk_view.batch_received(chunks_per_tick);
+    }
+
+    pub fn chat_session(&self) -> Option<&ChatSession> {
+        self.chat_session.as_ref()
+    }
+
+    /// Handles the client starting a new chat session, sharing it with the other players in `world`.
+    pub fn handle_chat_session(
+        &mut self,
+        world: &World,
+        session: &ChatSessionData<'_>,
+    ) -> Result<(), ChatError> {
+        let session = ChatSession::from_packet(session)?;
+        self.chat_session = Some(session.clone());
+        world.players().update_chat_session(self.entity_id, session);
+        Ok(())
+    }
+
+    /// Handles the client acknowledging chat messages without sending one.
+    pub fn acknowledge_messages(&mut self, message_count: i32) -> Result<(), ChatError> {
+        self.chat
+            .lock()
+            .unwrap()
+            .last_seen
+            .apply_offset(message_count)
+    }
+
+    /// Handles a chat message from the client, broadcasting it to the players in `world`.
+    pub fn handle_chat_message(
+        &mut self,
+        settings: &ChatSettings,
+        world: &World,
+        packet: &ChatMessagePacket<'_>,
+    ) -> Result<(), ChatError> {
+        chat::validate_message(&packet.message)?;
+        self.update_chat_timestamp(packet.timestamp)?;
+        self.update_last_seen(packet.message_count, &packet.acknowledged)?;
+
+        match &packet.signature {
+            Some(_) => {
+                let session = self
+                    .chat_session
+                    .as_ref()
+                    .ok_or(ChatError::MissingSession)?;
+                if session.is_expired() {
+                    return Err(ChatError::ExpiredPublicKey);
+                }
+            }
+            None if settings.enforce_secure_chat => return Err(ChatError::MissingSignature),
+            None => {}
+        }
+
+        let Some(sender) = world.players().get(self.entity_id) else {
+            return Ok(());
+        };
+        tracing::info!("<{}> {}", self.profile.username, packet.message);
+        chat::broadcast_chat_message(world.players(), &sender, &packet.message);
+        Ok(())
+    }
+
+    /// Handles the last seen messages and timestamp of a chat command from the client.
+    pub fn handle_chat_command(
+        &mut self,
+        timestamp: Option<i64>,
+        message_count: i32,
+        acknowledged: &[u8],
+    ) -> Result<(), ChatError> {
+        if let Some(timestamp) = timestamp {
+            self.update_chat_timestamp(timestamp)?;
+        }
+        self.update_last_seen(message_count, acknowledged)?;
+        Ok(())
+    }
+
+    fn update_chat_timestamp(&mut self, timestamp: i64) -> Result<(), ChatError> {
+        if timestamp < self.last_chat_timestamp {
+            return Err(ChatError::OutOfOrder);
+        }
+
+        self.last_chat_timestamp = timestamp;
+        Ok(())
+    }
+
+    fn update_last_seen(
+        &mut self,
+        message_count: i32,
+        acknowledged: &[u8],
+    ) -> Result<Vec<chat::MessageSignature>, ChatError> {
+        self.chat
+            .lock()
+            .unwrap()
+            .last_seen
+            .apply_update(message_count, acknowledged)
+    }
+
+    /// Sends a system chat message to the player.
+    pub fn send_system_message(&self, message: &TextComponent<'_>) -> Result<(), PacketSendError> {
+        self.sender.send_packet(&SystemChatMessagePacket {
+            content: message.to_network_nbt(),
+            overlay: false,
+        })
+    }
+
+    /// Sends the commands the player may use, which the client highlights and completes.
+    pub fn send_commands(&self, commands: &CommandDispatcher) -> Result<(), PacketSendError> {
+        self.sender.send_packet(&commands.commands_packet(self))
+    }
+
+    /// Teleports the player, which has to be confirmed by the client.
+    ///
+    /// Until then, movement from the client should be ignored.
+    pub fn teleport(
+        &mut self,
+        position: Position,
+        rotation: Rotation,
+    ) -> Result<(), PacketSendError> {
+        let teleport_id = self.next_teleport_id;
+        self.next_teleport_id = self.next_teleport_id.wrapping_add(1);
+        self.pending_teleport = Some(teleport_id);
+        self.position = position;
+        self.rotation = rotation;
+
+        self.sender.send_packet(&SynchronizePlayerPositionPacket {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            yaw: rotation.yaw,
+            pitch: rotation.pitch,
+            flags: TeleportFlags::empty(),
+            teleport_id,
+        })
+    }
+
+    /// Handles a teleport confirmation from the client. Returns `false` if `teleport_id` was not expected.
+    pub fn confirm_teleport(&mut self, teleport_id: i32) -> bool {
+        if self.pending_teleport != Some(teleport_id) {
+            return false;
+        }
+
+        self.pending_teleport = None;
+        if !self.spawned {
+            tracing::info!("{} joined the game.", self.profile.username);
+            self.spawned = true;
+        }
+
+        true
+    }
+}
+
+impl CommandSender for Player {
+    fn name(&self) -> &str {
+        &self.profile.username
+    }
+
+    fn send_message(&mut self, message: &TextComponent<'_>) {
+        if let Err(err) = self.send_system_message(message) {
+            tracing::trace!(
+                "Unable to send message to {}: {}.",
+                self.profile.username,
+                err
+            );
+        }
+    }
+
+    fn permission_level(&self) -> u8 {
+        self.permission_level()
+    }
+
+    fn profile(&self) -> Option<&GameProfile> {
+        Some(&self.profile)
+    }
+
+    fn entity_id(&self) -> Option<i32> {
+        Some(self.entity_id)
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(self.position)
+    }
+
+    fn rotation(&self) -> Option<Rotation> {
+        Some(self.rotation)
+    }
+
+    fn as_player(&mut self) -> Option<&mut Player> {
+        Some(self)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SpawnError {
+    #[error("unknown dimension type: {0}")]
+    UnknownDimensionType(String),
+    #[error(transparent)]
+    PacketSend(#[from] PacketSendError),
+}
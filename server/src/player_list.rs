@@ -0,0 +1,347 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use packet::{client::*, Packet};
+use protocol::{text::TextComponent, GameMode};
+
+use crate::{
+    chat::{ChatRecipient, ChatSession},
+    command::{CommandDispatcher, CommandSender},
+    connection::PacketSender,
+    entity::EntityType,
+    player::{GameProfile, ENTITY_STATUS_OP_PERMISSION_LEVEL_0, MAX_PERMISSION_LEVEL},
+    world::{movement_delta, Position, Rotation},
+};
+
+/// A player, as seen by other players in the same world.
+#[derive(Debug, Clone)]
+pub struct PlayerEntry {
+    pub entity_id: i32,
+    pub profile: GameProfile,
+    pub game_mode: GameMode,
+    pub position: Position,
+    pub rotation: Rotation,
+    pub on_ground: bool,
+    pub chat_session: Option<ChatSession>,
+    pub chat: Arc<Mutex<ChatRecipient>>,
+    /// Permission level (0-4), shared with the [`Player`](crate::player::Player).
+    pub permission_level: Arc<AtomicU8>,
+    pub sender: PacketSender,
+}
+
+impl PlayerEntry {
+    fn info_entry(&self) -> PlayerInfoEntry<'_> {
+        let mut entry = PlayerInfoEntry::new(self.profile.uuid);
+        entry.name = self.profile.username.as_str().into();
+        entry.properties = self
+            .profile
+            .properties
+            .iter()
+            .map(|property| ClientLoginSuccessProperty {
+                name: property.name.as_str().into(),
+                value: property.value.as_str().into(),
+                signature: property.signature.as_deref().map(Into::into),
+            })
+            .collect::<Vec<_>>()
+            .into();
+        entry.chat_session = self.chat_session.as_ref().map(ChatSession::to_packet);
+        entry.game_mode = self.game_mode;
+        entry.listed = true;
+        entry
+    }
+
+    fn spawn_packet(&self) -> SpawnEntityPacket {
+        SpawnEntityPacket {
+            entity_id: self.entity_id,
+            entity_uuid: self.profile.uuid,
+            entity_type: EntityType::PLAYER.id,
+            x: self.position.x,
+            y: self.position.y,
+            z: self.position.z,
+            pitch: self.rotation.pitch_angle(),
+            yaw: self.rotation.yaw_angle(),
+            head_yaw: self.rotation.yaw_angle(),
+            data: 0,
+            velocity_x: 0,
+            velocity_y: 0,
+            velocity_z: 0,
+        }
+    }
+
+    /// Sends a packet to this player, ignoring errors if its connection is closed.
+    pub fn send_packet<P: Packet + Debug>(&self, packet: &P) {
+        if let Err(err) = self.sender.send_packet(packet) {
+            tracing::trace!(
+                "Unable to send packet to {}: {}.",
+                self.profile.username,
+                err
+            );
+        }
+    }
+
+    /// Changes the permission level of the player, sending it the commands it may now use.
+    pub fn update_permission_level(&self, permission_level: u8, commands: &CommandDispatcher) {
+        let permission_level = permission_level.min(MAX_PERMISSION_LEVEL);
+        self.permission_level
+            .store(permission_level, Ordering::Relaxed);
+
+        self.send_packet(&EntityEventPacket {
+            entity_id: self.entity_id,
+            entity_status: ENTITY_STATUS_OP_PERMISSION_LEVEL_0 + permission_level as i8,
+        });
+        self.send_packet(&commands.commands_packet(self));
+    }
+
+    /// Disconnects the player with `reason`.
+    pub fn kick(&self, reason: &TextComponent<'_>) {
+        tracing::info!("Kicked {}.", self.profile.username);
+        self.send_packet(&PlayDisconnectPacket {
+            reason: reason.to_network_nbt(),
+        });
+        self.sender.close();
+    }
+}
+
+/// A player as a command sender, e.g. to check which commands it may use.
+impl CommandSender for PlayerEntry {
+    fn name(&self) -> &str {
+        &self.profile.username
+    }
+
+    fn send_message(&mut self, message: &TextComponent<'_>) {
+        self.send_packet(&SystemChatMessagePacket {
+            content: message.to_network_nbt(),
+            overlay: false,
+        });
+    }
+
+    fn permission_level(&self) -> u8 {
+        self.permission_level.load(Ordering::Relaxed)
+    }
+
+    fn profile(&self) -> Option<&GameProfile> {
+        Some(&self.profile)
+    }
+
+    fn entity_id(&self) -> Option<i32> {
+        Some(self.entity_id)
+    }
+
+    fn position(&self) -> Option<Position> {
+        Some(self.position)
+    }
+
+    fn rotation(&self) -> Option<Rotation> {
+        Some(self.rotation)
+    }
+}
+
+/// The players in a world, which all see each other.
+#[derive(Debug, Default)]
+pub struct PlayerList {
+    players: Mutex<HashMap<i32, PlayerEntry>>,
+}
+
+impl PlayerList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.players.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a snapshot of all players.
+    pub fn entries(&self) -> Vec<PlayerEntry> {
+        self.players.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Adds a player, spawning it for all other players and the other players for it.
+    pub fn add(&self, entry: PlayerEntry) {
+        let mut players = self.players.lock().unwrap();
+
+        let all_info = PlayerInfoUpdatePacket {
+            update: PlayerInfoUpdate {
+                actions: PlayerInfoActions::ADD_PLAYER
+                    | PlayerInfoActions::INITIALIZE_CHAT
+                    | PlayerInfoActions::UPDATE_GAME_MODE
+                    | PlayerInfoActions::UPDATE_LISTED,
+                entries: players
+                    .values()
+                    .chain([&entry])
+                    .map(PlayerEntry::info_entry)
+                    .collect::<Vec<_>>()
+                    .into(),
+            },
+        };
+        entry.send_packet(&all_info);
+
+        let new_info = PlayerInfoUpdatePacket {
+            update: PlayerInfoUpdate {
+                actions: all_info.update.actions,
+                entries: vec![entry.info_entry()].into(),
+            },
+        };
+        let new_spawn = entry.spawn_packet();
+        for other in players.values() {
+            other.send_packet(&new_info);
+            other.send_packet(&new_spawn);
+            entry.send_packet(&other.spawn_packet());
+        }
+
+        players.insert(entry.entity_id, entry);
+    }
+
+    /// Sends a packet to all players.
+    pub fn broadcast<P: Packet + Debug>(&self, packet: &P) {
+        for entry in self.players.lock().unwrap().values() {
+            entry.send_packet(packet);
+        }
+    }
+
+    /// Sends a system chat message to all players.
+    pub fn broadcast_system_message(&self, message: &TextComponent<'_>) {
+        self.broadcast(&SystemChatMessagePacket {
+            content: message.to_network_nbt(),
+            overlay: false,
+        });
+    }
+
+    /// Updates the chat session of a player, sending it to all players so they can verify its messages.
+    pub fn update_chat_session(&self, entity_id: i32, chat_session: ChatSession) {
+        let mut players = self.players.lock().unwrap();
+        let Some(entry) = players.get_mut(&entity_id) else {
+            return;
+        };
+        entry.chat_session = Some(chat_session);
+
+        let entry = &players[&entity_id];
+        let packet = PlayerInfoUpdatePacket {
+            update: PlayerInfoUpdate {
+                actions: PlayerInfoActions::INITIALIZE_CHAT,
+                entries: vec![entry.info_entry()].into(),
+            },
+        };
+        for other in players.values() {
+            other.send_packet(&packet);
+        }
+    }
+
+    /// Returns a snapshot of a player.
+    pub fn get(&self, entity_id: i32) -> Option<PlayerEntry> {
+        self.players.lock().unwrap().get(&entity_id).cloned()
+    }
+
+    /// Returns a snapshot of the player named `name`, ignoring case.
+    pub fn find_by_name(&self, name: &str) -> Option<PlayerEntry> {
+        let players = self.players.lock().unwrap();
+        players
+            .values()
+            .find(|entry| entry.profile.username.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Removes a player, despawning it for all other players.
+    pub fn remove(&self, entity_id: i32) -> Option<PlayerEntry> {
+        let mut players = self.players.lock().unwrap();
+        let entry = players.remove(&entity_id)?;
+
+        let info_remove = PlayerInfoRemovePacket {
+            uuids: vec![entry.profile.uuid].into(),
+        };
+        let remove_entity = RemoveEntitiesPacket {
+            entity_ids: vec![entity_id.into()].into(),
+        };
+        for other in players.values() {
+            other.send_packet(&info_remove);
+            other.send_packet(&remove_entity);
+        }
+
+        Some(entry)
+    }
+
+    /// Updates the position and rotation of a player, broadcasting the movement to all other players.
+    pub fn update_movement(
+        &self,
+        entity_id: i32,
+        position: Position,
+        rotation: Rotation,
+        on_ground: bool,
+    ) {
+        let mut players = self.players.lock().unwrap();
+        let Some(entry) = players.get_mut(&entity_id) else {
+            return;
+        };
+
+        let moved = entry.position != position;
+        let rotated = entry.rotation != rotation;
+        let old_position = entry.position;
+        entry.position = position;
+        entry.rotation = rotation;
+        entry.on_ground = on_ground;
+
+        let (yaw, pitch) = (rotation.yaw_angle(), rotation.pitch_angle());
+        let delta = movement_delta(old_position.to_protocol(), position.to_protocol());
+        let is_far = delta.is_none();
+        let [delta_x, delta_y, delta_z] = delta.unwrap_or_default();
+
+        let others = players
+            .values()
+            .filter(|other| other.entity_id != entity_id);
+        for other in others {
+            if is_far {
+                other.send_packet(&TeleportEntityPacket {
+                    entity_id,
+                    x: position.x,
+                    y: position.y,
+                    z: position.z,
+                    yaw,
+                    pitch,
+                    on_ground,
+                });
+            } else if moved && rotated {
+                other.send_packet(&UpdateEntityPositionAndRotationPacket {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    yaw,
+                    pitch,
+                    on_ground,
+                });
+            } else if moved {
+                other.send_packet(&UpdateEntityPositionPacket {
+                    entity_id,
+                    delta_x,
+                    delta_y,
+                    delta_z,
+                    on_ground,
+                });
+            } else if rotated {
+                other.send_packet(&UpdateEntityRotationPacket {
+                    entity_id,
+                    yaw,
+                    pitch,
+                    on_ground,
+                });
+            }
+
+            if rotated {
+                other.send_packet(&SetHeadRotationPacket {
+                    entity_id,
+                    head_yaw: yaw,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! The GameSpy4 query protocol over UDP, used by server lists and monitoring scripts.
+//!
+//! See <https://wiki.vg/Query>.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{Buf, BufMut, BytesMut};
+use thiserror::Error;
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+use crate::{context::ServerContext, status::VERSION_NAME};
+
+const MAGIC: u16 = 0xFEFD;
+
+const HANDSHAKE: u8 = 9;
+const STAT: u8 = 0;
+
+/// Length of a basic stat request: magic, type, session ID and challenge token.
+const BASIC_STAT_LENGTH: usize = 11;
+/// Length of a full stat request, which is a basic stat request with 4 bytes of padding.
+const FULL_STAT_LENGTH: usize = 15;
+
+/// Only the lower 4 bits of each byte of session IDs are used.
+const SESSION_ID_MASK: i32 = 0x0F0F0F0F;
+
+/// How long a challenge token stays valid, same as vanilla.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Constant padding before the key-value section of full stat responses.
+const KEY_VALUE_PADDING: &[u8] = b"splitnum\0\x80\0";
+/// Constant padding before the player list of full stat responses.
+const PLAYERS_PADDING: &[u8] = b"\x01player_\0\0";
+
+const GAME_TYPE: &str = "SMP";
+const GAME_ID: &str = "MINECRAFT";
+/// The world name reported to clients.
+const MAP: &str = "world";
+
+struct Challenge {
+    token: i32,
+    issued: Instant,
+}
+
+/// What stat responses report about the server.
+struct ServerStats {
+    motd: String,
+    max_players: i32,
+    /// Names of the players online.
+    players: Vec<String>,
+    /// Address of the game server.
+    game_address: SocketAddr,
+}
+
+/// Answers query requests with the same data as the server list.
+pub struct QueryListener {
+    socket: UdpSocket,
+    /// Address of the game server, which is reported to clients.
+    game_address: SocketAddr,
+    server: Arc<ServerContext>,
+}
+
+impl QueryListener {
+    pub async fn new<A>(
+        address: A,
+        game_address: SocketAddr,
+        server: Arc<ServerContext>,
+    ) -> std::io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        Ok(Self {
+            socket: UdpSocket::bind(address).await?,
+            game_address,
+            server,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub async fn listen(&self) -> ! {
+        let mut challenges: HashMap<SocketAddr, Challenge> = HashMap::new();
+        let mut buf = [0; 1460];
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(err) => {
+                    tracing::debug!("Unable to receive query request: {}.", err);
+                    continue;
+                }
+            };
+
+            let stats = || self.stats();
+            let response = match handle_request(&buf[..len], addr, &mut challenges, stats) {
+                Ok(response) => response,
+                Err(err) => {
+                    tracing::trace!("Invalid query request from {}: {}.", addr, err);
+                    continue;
+                }
+            };
+            if let Err(err) = self.socket.send_to(&response, addr).await {
+                tracing::debug!("Unable to send query response to {}: {}.", addr, err);
+            }
+        }
+    }
+
+    fn stats(&self) -> ServerStats {
+        let config = self.server.config.get();
+        let players = self.server.world.players().entries();
+        ServerStats {
+            motd: config.motd.clone(),
+            max_players: config.max_players,
+            players: players
+                .into_iter()
+                .map(|player| player.profile.username)
+                .collect(),
+            game_address: self.game_address,
+        }
+    }
+}
+
+/// Answers a request from `addr`, with `stats` read only for stat requests.
+fn handle_request(
+    mut request: &[u8],
+    addr: SocketAddr,
+    challenges: &mut HashMap<SocketAddr, Challenge>,
+    stats: impl FnOnce() -> ServerStats,
+) -> Result<BytesMut, QueryError> {
+    let len = request.len();
+    if len < 7 {
+        return Err(QueryError::InvalidLength(len));
+    }
+    if request.get_u16() != MAGIC {
+        return Err(QueryError::InvalidMagic);
+    }
+    let kind = request.get_u8();
+    let session_id = request.get_i32() & SESSION_ID_MASK;
+
+    let mut response = BytesMut::new();
+    response.put_u8(kind);
+    response.put_i32(session_id);
+
+    match kind {
+        HANDSHAKE => {
+            challenges.retain(|_, challenge| challenge.issued.elapsed() < CHALLENGE_LIFETIME);
+            let token = rand::random::<i32>() & i32::MAX;
+            challenges.insert(
+                addr,
+                Challenge {
+                    token,
+                    issued: Instant::now(),
+                },
+            );
+            put_string(&mut response, &token.to_string());
+        }
+        STAT => {
+            if len != BASIC_STAT_LENGTH && len != FULL_STAT_LENGTH {
+                return Err(QueryError::InvalidLength(len));
+            }
+            let token = request.get_i32();
+            let valid = challenges.get(&addr).is_some_and(|challenge| {
+                challenge.token == token && challenge.issued.elapsed() < CHALLENGE_LIFETIME
+            });
+            if !valid {
+                return Err(QueryError::InvalidChallenge);
+            }
+
+            if len == FULL_STAT_LENGTH {
+                put_full_stat(&mut response, &stats());
+            } else {
+                put_basic_stat(&mut response, &stats());
+            }
+        }
+        kind => return Err(QueryError::UnknownType(kind)),
+    }
+
+    Ok(response)
+}
+
+fn put_basic_stat(buf: &mut BytesMut, stats: &ServerStats) {
+    put_string(buf, &stats.motd);
+    put_string(buf, GAME_TYPE);
+    put_string(buf, MAP);
+    put_string(buf, &stats.players.len().to_string());
+    put_string(buf, &stats.max_players.to_string());
+    buf.put_u16_le(stats.game_address.port());
+    put_string(buf, &stats.game_address.ip().to_string());
+}
+
+fn put_full_stat(buf: &mut BytesMut, stats: &ServerStats) {
+    buf.put_slice(KEY_VALUE_PADDING);
+    let values = [
+        ("hostname", stats.motd.clone()),
+        ("gametype", GAME_TYPE.to_owned()),
+        ("game_id", GAME_ID.to_owned()),
+        ("version", VERSION_NAME.to_owned()),
+        // Same as vanilla, which has no plugins.
+        ("plugins", String::new()),
+        ("map", MAP.to_owned()),
+        ("numplayers", stats.players.len().to_string()),
+        ("maxplayers", stats.max_players.to_string()),
+        ("hostport", stats.game_address.port().to_string()),
+        ("hostip", stats.game_address.ip().to_string()),
+    ];
+    for (key, value) in values {
+        put_string(buf, key);
+        put_string(buf, &value);
+    }
+    buf.put_u8(0);
+
+    buf.put_slice(PLAYERS_PADDING);
+    for player in &stats.players {
+        put_string(buf, player);
+    }
+    buf.put_u8(0);
+}
+
+/// Writes a null-terminated string.
+fn put_string(buf: &mut BytesMut, string: &str) {
+    buf.put_slice(string.as_bytes());
+    buf.put_u8(0);
+}
+
+#[derive(Error, Debug)]
+pub enum QueryError {
+    #[error("invalid request length {0}")]
+    InvalidLength(usize),
+    #[error("invalid magic")]
+    InvalidMagic,
+    #[error("unknown request type {0}")]
+    UnknownType(u8),
+    #[error("invalid or expired challenge token")]
+    InvalidChallenge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> SocketAddr {
+        "127.0.0.1:50000".parse().unwrap()
+    }
+
+    fn stats() -> ServerStats {
+        ServerStats {
+            motd: "A server".to_owned(),
+            max_players: 20,
+            players: vec!["Steve".to_owned(), "Alex".to_owned()],
+            game_address: "10.0.0.1:25565".parse().unwrap(),
+        }
+    }
+
+    fn request(kind: u8, session_id: i32, payload: &[u8]) -> Vec<u8> {
+        let mut request = vec![0xFE, 0xFD, kind];
+        request.extend_from_slice(&session_id.to_be_bytes());
+        request.extend_from_slice(payload);
+        request
+    }
+
+    /// Does the handshake, returning the challenge token.
+    fn handshake(challenges: &mut HashMap<SocketAddr, Challenge>) -> i32 {
+        let request = request(HANDSHAKE, 0x01020304, &[]);
+        let response = handle_request(&request, client(), challenges, stats).unwrap();
+        assert_eq!(&response[..5], [HANDSHAKE, 0x01, 0x02, 0x03, 0x04]);
+        let (&0, token) = response[5..].split_last().unwrap() else {
+            panic!("the token should be null-terminated");
+        };
+        std::str::from_utf8(token).unwrap().parse().unwrap()
+    }
+
+    /// Splits a response into its null-terminated strings.
+    fn strings(bytes: &[u8]) -> Vec<&str> {
+        bytes
+            .split(|&byte| byte == 0)
+            .map(|string| std::str::from_utf8(string).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn session_id_is_masked() {
+        let mut challenges = HashMap::new();
+        let request = request(HANDSHAKE, 0x7FFF_FFFF, &[]);
+        let response = handle_request(&request, client(), &mut challenges, stats).unwrap();
+        assert_eq!(&response[1..5], [0x0F; 4]);
+    }
+
+    #[test]
+    fn basic_stat() {
+        let mut challenges = HashMap::new();
+        let token = handshake(&mut challenges);
+        let request = request(STAT, 1, &token.to_be_bytes());
+        let response = handle_request(&request, client(), &mut challenges, stats).unwrap();
+
+        assert_eq!(&response[..5], [STAT, 0, 0, 0, 1]);
+        let mut expected = b"A server\0SMP\0world\x002\x0020\0".to_vec();
+        expected.extend_from_slice(&25565u16.to_le_bytes());
+        expected.extend_from_slice(b"10.0.0.1\0");
+        assert_eq!(&response[5..], expected);
+    }
+
+    #[test]
+    fn full_stat() {
+        let mut challenges = HashMap::new();
+        let token = handshake(&mut challenges);
+        let mut payload = token.to_be_bytes().to_vec();
+        payload.extend_from_slice(&[0; 4]);
+        let request = request(STAT, 1, &payload);
+        let response = handle_request(&request, client(), &mut challenges, stats).unwrap();
+
+        let response = response[5..]
+            .strip_prefix(KEY_VALUE_PADDING)
+            .expect("the key-values should be padded");
+        let players_start = response
+            .windows(PLAYERS_PADDING.len())
+            .position(|window| window == PLAYERS_PADDING)
+            .unwrap();
+        let (values, players) = response.split_at(players_start);
+        assert_eq!(
+            strings(values),
+            [
+                "hostname",
+                "A server",
+                "gametype",
+                "SMP",
+                "game_id",
+                "MINECRAFT",
+                "version",
+                VERSION_NAME,
+                "plugins",
+                "",
+                "map",
+                "world",
+                "numplayers",
+                "2",
+                "maxplayers",
+                "20",
+                "hostport",
+                "25565",
+                "hostip",
+                "10.0.0.1",
+                "",
+                ""
+            ]
+        );
+        assert_eq!(
+            strings(&players[PLAYERS_PADDING.len()..]),
+            ["Steve", "Alex", "", ""]
+        );
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let mut challenges = HashMap::new();
+        let token = handshake(&mut challenges);
+
+        let wrong_token = request(STAT, 1, &(token ^ 1).to_be_bytes());
+        assert!(matches!(
+            handle_request(&wrong_token, client(), &mut challenges, stats),
+            Err(QueryError::InvalidChallenge)
+        ));
+        let other_client = "127.0.0.2:50000".parse().unwrap();
+        let valid = request(STAT, 1, &token.to_be_bytes());
+        assert!(matches!(
+            handle_request(&valid, other_client, &mut challenges, stats),
+            Err(QueryError::InvalidChallenge)
+        ));
+        assert!(matches!(
+            handle_request(&valid[..9], client(), &mut challenges, stats),
+            Err(QueryError::InvalidLength(9))
+        ));
+        let mut wrong_magic = valid.clone();
+        wrong_magic[0] = 0;
+        assert!(matches!(
+            handle_request(&wrong_magic, client(), &mut challenges, stats),
+            Err(QueryError::InvalidMagic)
+        ));
+        assert!(matches!(
+            handle_request(&request(1, 1, &[]), client(), &mut challenges, stats),
+            Err(QueryError::UnknownType(1))
+        ));
+    }
+}
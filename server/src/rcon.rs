@@ -0,0 +1,343 @@
+//! The RCON protocol, for running commands remotely.
+//!
+//! See <https://developer.valvesoftware.com/wiki/Source_RCON_Protocol>, which Minecraft follows
+//! apart from encoding bodies as UTF-8.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use bytes::{Buf, BufMut, BytesMut};
+use protocol::text::TextComponent;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    command::{self, CommandSender},
+    connection::ACCEPT_ERROR_DELAY,
+    context::ServerContext,
+    player::MAX_PERMISSION_LEVEL,
+};
+
+/// Packet type of responses to commands.
+pub const RESPONSE_VALUE: i32 = 0;
+/// Packet type of commands, and of responses to logins.
+pub const EXEC_COMMAND: i32 = 2;
+pub const AUTH_RESPONSE: i32 = 2;
+pub const LOGIN: i32 = 3;
+
+/// Request ID sent back when a login fails or a command is sent before logging in.
+const AUTH_FAILURE_ID: i32 = -1;
+
+/// Request ID and type, plus the two null terminators.
+const MIN_PACKET_LENGTH: i32 = 10;
+/// Longest packet accepted from clients, same as vanilla.
+const MAX_PACKET_LENGTH: i32 = 1460;
+/// Longest body in a single response; longer output is split over several packets.
+const MAX_RESPONSE_BODY_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket {
+    pub request_id: i32,
+    pub kind: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    pub fn new(request_id: i32, kind: i32, body: impl Into<String>) -> Self {
+        Self {
+            request_id,
+            kind,
+            body: body.into(),
+        }
+    }
+
+    /// Encodes the packet, prefixed with its length.
+    pub fn encode(&self, buf: &mut BytesMut) {
+        buf.put_i32_le(MIN_PACKET_LENGTH + self.body.len() as i32);
+        buf.put_i32_le(self.request_id);
+        buf.put_i32_le(self.kind);
+        buf.put_slice(self.body.as_bytes());
+        buf.put_u8(0);
+        buf.put_u8(0);
+    }
+
+    /// Decodes a packet without its length prefix.
+    pub fn decode(mut buf: &[u8]) -> Result<Self, RconError> {
+        if buf.len() < MIN_PACKET_LENGTH as usize {
+            return Err(RconError::InvalidLength(buf.len() as i32));
+        }
+
+        let request_id = buf.get_i32_le();
+        let kind = buf.get_i32_le();
+        // The body is null-terminated, followed by an empty string.
+        let body_len = buf.iter().position(|&byte| byte == 0).unwrap_or(buf.len());
+        let body = String::from_utf8_lossy(&buf[..body_len]).into_owned();
+
+        Ok(Self {
+            request_id,
+            kind,
+            body,
+        })
+    }
+
+    /// Reads a packet from `stream`, returning `None` if it was closed between packets.
+    pub async fn read(stream: &mut TcpStream) -> Result<Option<Self>, RconError> {
+        let length = match stream.read_i32_le().await {
+            Ok(length) => length,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if !(MIN_PACKET_LENGTH..=MAX_PACKET_LENGTH).contains(&length) {
+            return Err(RconError::InvalidLength(length));
+        }
+
+        let mut buf = vec![0; length as usize];
+        stream.read_exact(&mut buf).await?;
+        Self::decode(&buf).map(Some)
+    }
+}
+
+/// Collects the feedback of commands run over RCON, as plain text.
+#[derive(Debug, Default)]
+pub struct RconSender {
+    output: String,
+}
+
+impl CommandSender for RconSender {
+    fn name(&self) -> &str {
+        "Rcon"
+    }
+
+    fn send_message(&mut self, message: &TextComponent<'_>) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output
+            .push_str(&message.to_plain_text(&server_assets::translation));
+    }
+
+    fn permission_level(&self) -> u8 {
+        MAX_PERMISSION_LEVEL
+    }
+}
+
+/// Accepts RCON connections, running their commands once they log in with the password.
+pub struct RconListener {
+    tcp_listener: TcpListener,
+    password: Arc<str>,
+    server: Arc<ServerContext>,
+}
+
+impl RconListener {
+    pub async fn new<A>(
+        address: A,
+        password: impl Into<String>,
+        server: Arc<ServerContext>,
+    ) -> Result<Self, RconError>
+    where
+        A: ToSocketAddrs,
+    {
+        let password = password.into();
+        // Like vanilla, refuse to let anyone in without a password.
+        if password.is_empty() {
+            return Err(RconError::EmptyPassword);
+        }
+
+        Ok(Self {
+            tcp_listener: TcpListener::bind(address).await?,
+            password: password.into(),
+            server,
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.tcp_listener.local_addr()
+    }
+
+    pub async fn listen(&self) -> ! {
+        loop {
+            let (socket, addr) = match self.tcp_listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!("Unable to accept RCON connection: {}.", err);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            tracing::info!("RCON connection from {}.", addr);
+
+            let client = RconClient {
+                stream: socket,
+                password: Arc::clone(&self.password),
+                server: Arc::clone(&self.server),
+                authenticated: false,
+            };
+            tokio::spawn(async move {
+                if let Err(err) = client.process().await {
+                    tracing::debug!("RCON connection from {} failed: {}.", addr, err);
+                }
+                tracing::info!("RCON connection from {} closed.", addr);
+            });
+        }
+    }
+}
+
+struct RconClient {
+    stream: TcpStream,
+    password: Arc<str>,
+    server: Arc<ServerContext>,
+    authenticated: bool,
+}
+
+impl RconClient {
+    async fn process(mut self) -> Result<(), RconError> {
+        while let Some(packet) = RconPacket::read(&mut self.stream).await? {
+            match packet.kind {
+                LOGIN => {
+                    self.authenticated = packet.body == *self.password;
+                    let request_id = if self.authenticated {
+                        packet.request_id
+                    } else {
+                        tracing::warn!("RCON login with wrong password.");
+                        AUTH_FAILURE_ID
+                    };
+                    self.send(&RconPacket::new(request_id, AUTH_RESPONSE, ""))
+                        .await?;
+                }
+                EXEC_COMMAND if self.authenticated => {
+                    let mut sender = RconSender::default();
+                    let input = packet.body.strip_prefix('/').unwrap_or(&packet.body);
+                    command::run_command(&self.server, &mut sender, input);
+                    self.send_response(packet.request_id, &sender.output)
+                        .await?;
+                }
+                EXEC_COMMAND => {
+                    self.send(&RconPacket::new(AUTH_FAILURE_ID, AUTH_RESPONSE, ""))
+                        .await?;
+                }
+                kind => {
+                    let message = format!("Unknown request {kind:x}");
+                    self.send_response(packet.request_id, &message).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends `body` as the response to `request_id`, split over as many packets as needed.
+    async fn send_response(&mut self, request_id: i32, body: &str) -> Result<(), RconError> {
+        let mut rest = body;
+        loop {
+            let mut split = rest.len().min(MAX_RESPONSE_BODY_LENGTH);
+            while !rest.is_char_boundary(split) {
+                split -= 1;
+            }
+            let (chunk, remaining) = rest.split_at(split);
+            self.send(&RconPacket::new(request_id, RESPONSE_VALUE, chunk))
+                .await?;
+
+            rest = remaining;
+            if rest.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn send(&mut self, packet: &RconPacket) -> Result<(), RconError> {
+        let mut buf = BytesMut::with_capacity(14 + packet.body.len());
+        packet.encode(&mut buf);
+        self.stream.write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("RCON password must not be empty")]
+    EmptyPassword,
+    #[error("invalid packet length {0}")]
+    InvalidLength(i32),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let mut buf = BytesMut::new();
+        RconPacket::new(0x01020304, EXEC_COMMAND, "list").encode(&mut buf);
+        assert_eq!(
+            &buf[..],
+            b"\x0e\0\0\0\x04\x03\x02\x01\x02\0\0\0list\0\0".as_slice()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for packet in [
+            RconPacket::new(7, LOGIN, "password"),
+            RconPacket::new(-1, AUTH_RESPONSE, ""),
+            RconPacket::new(i32::MAX, RESPONSE_VALUE, "§aThere are 0 players"),
+        ] {
+            let mut buf = BytesMut::new();
+            packet.encode(&mut buf);
+            let length = buf.get_i32_le();
+            assert_eq!(length as usize, buf.len());
+            assert_eq!(RconPacket::decode(&buf).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn decode_stops_at_null() {
+        let packet = RconPacket::decode(b"\x01\0\0\0\x02\0\0\0say hi\0\0").unwrap();
+        assert_eq!(packet, RconPacket::new(1, EXEC_COMMAND, "say hi"));
+        // Some clients leave out the second null.
+        let packet = RconPacket::decode(b"\x01\0\0\0\x02\0\0\0say hi\0").unwrap();
+        assert_eq!(packet.body, "say hi");
+        assert!(matches!(
+            RconPacket::decode(b"\x01\0\0\0\x02\0\0\0\0"),
+            Err(RconError::InvalidLength(9))
+        ));
+    }
+
+    /// Writes `bytes` to a connection, then reads a packet from the other end.
+    async fn read(bytes: Vec<u8>) -> Result<Option<RconPacket>, RconError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        client.write_all(&bytes).await.unwrap();
+        drop(client);
+        RconPacket::read(&mut server).await
+    }
+
+    #[tokio::test]
+    async fn max_payload() {
+        let longest = "a".repeat((MAX_PACKET_LENGTH - MIN_PACKET_LENGTH) as usize);
+        let mut buf = BytesMut::new();
+        RconPacket::new(1, EXEC_COMMAND, longest.clone()).encode(&mut buf);
+        let packet = read(buf.to_vec()).await.unwrap().unwrap();
+        assert_eq!(packet.body, longest);
+
+        let mut buf = BytesMut::new();
+        RconPacket::new(1, EXEC_COMMAND, longest + "a").encode(&mut buf);
+        assert!(matches!(
+            read(buf.to_vec()).await,
+            Err(RconError::InvalidLength(1461))
+        ));
+        assert!(matches!(
+            read(9i32.to_le_bytes().to_vec()).await,
+            Err(RconError::InvalidLength(9))
+        ));
+        // Closed between packets.
+        assert!(read(Vec::new()).await.unwrap().is_none());
+    }
+}
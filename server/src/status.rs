@@ -0,0 +1,45 @@
+//! What the server tells the server list and query clients about itself.
+
+use packet::client::{
+    StatusResponse, StatusResponseDescription, StatusResponsePlayers, StatusResponsePlayersSample,
+    StatusResponseVersion,
+};
+
+use crate::{connection::TARGET_PROTOCOL_VERSION, context::ServerContext};
+
+/// Name of the Minecraft version the server supports.
+pub const VERSION_NAME: &str = "1.21.1";
+
+/// Maximum number of players shown when hovering the player count, same as vanilla.
+const MAX_SAMPLE_SIZE: usize = 12;
+
+/// Builds the status shown in the server list, with the players currently online.
+pub fn status_response(server: &ServerContext) -> StatusResponse {
+    let config = server.config.get();
+    let players = server.world.players().entries();
+    let sample = players
+        .iter()
+        .take(MAX_SAMPLE_SIZE)
+        .map(|entry| StatusResponsePlayersSample {
+            name: entry.profile.username.clone(),
+            id: entry.profile.uuid,
+        })
+        .collect();
+
+    StatusResponse {
+        version: StatusResponseVersion {
+            name: VERSION_NAME.to_owned(),
+            protocol: TARGET_PROTOCOL_VERSION,
+        },
+        players: StatusResponsePlayers {
+            max: config.max_players,
+            online: players.len() as i32,
+            sample,
+        },
+        description: StatusResponseDescription {
+            text: config.motd.clone(),
+        },
+        favicon: "".into(),
+        enforces_secure_chat: server.chat.enforce_secure_chat,
+    }
+}
@@ -0,0 +1,209 @@
+//! Limits on how many connections clients open and how much they send, configured by
+//! [`ThrottleConfig`](crate::config::ThrottleConfig).
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// Window connection attempts are counted in.
+const CONNECTION_WINDOW: Duration = Duration::from_secs(60);
+
+/// Counts events in fixed windows, e.g. packets per second.
+#[derive(Debug)]
+pub struct RateCounter {
+    window: Duration,
+    start: Instant,
+    count: u32,
+}
+
+impl RateCounter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Records `amount` events, returning whether the count in the current window is still
+    /// within `limit`, where 0 means no limit.
+    pub fn record(&mut self, amount: u32, limit: u32) -> bool {
+        if self.is_expired() {
+            self.start = Instant::now();
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(amount);
+        limit == 0 || self.count <= limit
+    }
+
+    /// Whether the current window has ended.
+    pub fn is_expired(&self) -> bool {
+        self.start.elapsed() >= self.window
+    }
+}
+
+/// Limits how often each IP address may connect.
+#[derive(Debug)]
+pub struct ConnectionThrottle {
+    attempts: HashMap<IpAddr, RateCounter>,
+    /// When the addresses that stopped connecting were last forgotten.
+    pruned: Instant,
+}
+
+impl ConnectionThrottle {
+    /// Records a connection from `ip`, returning whether it is within `per_minute` connections
+    /// per minute, where 0 means no limit.
+    pub fn allow(&mut self, ip: IpAddr, per_minute: u32) -> bool {
+        // Addresses that haven't connected for a while are forgotten once per window, keeping
+        // the map small without going through it for every connection.
+        if self.pruned.elapsed() >= CONNECTION_WINDOW {
+            self.attempts.retain(|_, attempts| !attempts.is_expired());
+            self.pruned = Instant::now();
+        }
+        self.attempts
+            .entry(ip)
+            .or_insert_with(|| RateCounter::new(CONNECTION_WINDOW))
+            .record(1, per_minute)
+    }
+}
+
+impl Default for ConnectionThrottle {
+    fn default() -> Self {
+        Self {
+            attempts: HashMap::new(),
+            pruned: Instant::now(),
+        }
+    }
+}
+
+/// Counts open connections against a maximum.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLimit {
+    open: Arc<AtomicU32>,
+}
+
+impl ConnectionLimit {
+    /// Number of connections open.
+    pub fn open(&self) -> u32 {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    /// Takes a slot for a new connection, unless `max` connections are open already, where 0
+    /// means no limit.
+    pub fn try_acquire(&self, max: u32) -> Option<ConnectionSlot> {
+        self.open
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |open| {
+                (max == 0 || open < max).then_some(open + 1)
+            })
+            .ok()?;
+        Some(ConnectionSlot {
+            open: Arc::clone(&self.open),
+        })
+    }
+}
+
+/// A connection counted by a [`ConnectionLimit`] until it is dropped.
+#[derive(Debug)]
+pub struct ConnectionSlot {
+    open: Arc<AtomicU32>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tokio::time;
+
+    use super::*;
+
+    const FIRST: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const SECOND: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    #[tokio::test(start_paused = true)]
+    async fn counts_in_windows() {
+        let mut counter = RateCounter::new(Duration::from_secs(1));
+        assert!(counter.record(3, 5));
+        assert!(counter.record(2, 5));
+        assert!(!counter.record(1, 5));
+        assert!(!counter.is_expired());
+
+        // The count starts over with the next window.
+        time::advance(Duration::from_secs(1)).await;
+        assert!(counter.is_expired());
+        assert!(counter.record(5, 5));
+        assert!(!counter.is_expired());
+        assert!(!counter.record(u32::MAX, 5));
+        // 0 means no limit.
+        assert!(counter.record(u32::MAX, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttles_connections() {
+        let mut throttle = ConnectionThrottle::default();
+        assert!(throttle.allow(FIRST, 2));
+        assert!(throttle.allow(FIRST, 2));
+        assert!(!throttle.allow(FIRST, 2));
+        // Each address is counted on its own.
+        assert!(throttle.allow(SECOND, 2));
+
+        time::advance(CONNECTION_WINDOW).await;
+        assert!(throttle.allow(FIRST, 2));
+        assert!((0..100).all(|_| throttle.allow(SECOND, 0)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forgets_addresses() {
+        let mut throttle = ConnectionThrottle::default();
+        throttle.allow(FIRST, 1);
+        time::advance(CONNECTION_WINDOW / 2).await;
+        throttle.allow(SECOND, 1);
+        assert_eq!(throttle.attempts.len(), 2);
+
+        // Addresses are only forgotten once their window is over.
+        time::advance(CONNECTION_WINDOW / 2).await;
+        throttle.allow(SECOND, 1);
+        assert_eq!(throttle.attempts.keys().collect::<Vec<_>>(), [&SECOND]);
+
+        // Pruning waits for the next window.
+        time::advance(CONNECTION_WINDOW / 2).await;
+        throttle.allow(FIRST, 1);
+        assert_eq!(throttle.attempts.len(), 2);
+        time::advance(CONNECTION_WINDOW / 2).await;
+        throttle.allow(FIRST, 1);
+        assert_eq!(throttle.attempts.keys().collect::<Vec<_>>(), [&FIRST]);
+    }
+
+    #[test]
+    fn limits_open_connections() {
+        let limit = ConnectionLimit::default();
+        let first = limit.try_acquire(2).unwrap();
+        // Clones share the count.
+        let second = limit.clone().try_acquire(2).unwrap();
+        assert_eq!(limit.open(), 2);
+        assert!(limit.try_acquire(2).is_none());
+
+        drop(first);
+        assert_eq!(limit.open(), 1);
+        let third = limit.try_acquire(2).unwrap();
+        // 0 means no limit.
+        let unlimited: Vec<_> = (0..10).map(|_| limit.try_acquire(0).unwrap()).collect();
+        assert_eq!(limit.open(), 12);
+
+        drop((second, third, unlimited));
+        assert_eq!(limit.open(), 0);
+    }
+}
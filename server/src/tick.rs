@@ -0,0 +1,476 @@
+//! The game loop, ticking the server 20 times per second like vanilla.
+//!
+//! Ticks run on their own thread: each one ticks the world, lets the connections tick their
+//! players, and runs the tasks that are due. Async code, e.g. packet handlers, hands work over to
+//! the tick thread through the [`Scheduler`].
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{oneshot, watch};
+
+use crate::{context::ServerContext, metrics::TICK_SPAN};
+
+pub const TICKS_PER_SECOND: u32 = 20;
+
+/// Time between the starts of two ticks.
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+
+/// How far behind the loop may fall before skipping ticks instead of catching up, same as
+/// vanilla.
+const MAX_LAG: Duration = Duration::from_secs(2);
+
+/// Minimum time between two warnings that the server can't keep up, same as vanilla.
+const LAG_WARNING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Number of ticks MSPT and TPS are averaged over, same as vanilla.
+const STATS_WINDOW: usize = 100;
+
+type Task = Box<dyn FnMut(&ServerContext) + Send>;
+
+/// A task waiting to run.
+struct ScheduledTask {
+    task: Task,
+    /// Ticks between runs, if the task repeats.
+    period: Option<u64>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    /// Tasks by the tick they are due on, then the order they were scheduled in.
+    tasks: BTreeMap<(u64, u64), ScheduledTask>,
+    next_id: u64,
+}
+
+/// Runs tasks on the tick thread, on later ticks.
+///
+/// Tasks run at the end of the tick they are due on, in the order they were scheduled in.
+pub struct Scheduler {
+    state: Mutex<SchedulerState>,
+    /// Number of the last tick that started.
+    tick: watch::Sender<u64>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::default(),
+            tick: watch::Sender::new(0),
+        }
+    }
+
+    /// Number of the last tick that started, counting from 1.
+    pub fn current_tick(&self) -> u64 {
+        *self.tick.borrow()
+    }
+
+    /// Returns a receiver of the number of the last tick, to wait for the next ones.
+    pub fn ticks(&self) -> watch::Receiver<u64> {
+        self.tick.subscribe()
+    }
+
+    /// Runs `task` on the next tick.
+    pub fn run_next_tick<F>(&self, task: F) -> TaskHandle
+    where
+        F: FnOnce(&ServerContext) + Send + 'static,
+    {
+        self.run_later(1, task)
+    }
+
+    /// Runs `task` once `delay` ticks from now have passed, on the next tick if `delay` is 0.
+    pub fn run_later<F>(&self, delay: u64, task: F) -> TaskHandle
+    where
+        F: FnOnce(&ServerContext) + Send + 'static,
+    {
+        let mut task = Some(task);
+        self.schedule(
+            delay,
+            None,
+            Box::new(move |server| {
+                if let Some(task) = task.take() {
+                    task(server);
+                }
+            }),
+        )
+    }
+
+    /// Runs `task` once `delay` ticks from now have passed, then every `period` ticks until it
+    /// is cancelled. A `delay` or `period` of 0 is taken as 1.
+    pub fn run_repeating<F>(&self, delay: u64, period: u64, task: F) -> TaskHandle
+    where
+        F: FnMut(&ServerContext) + Send + 'static,
+    {
+        self.schedule(delay, Some(period.max(1)), Box::new(task))
+    }
+
+    /// Runs `task` on the next tick, resolving to its result. Resolves to `None` if the task
+    /// doesn't run, because the server stopped first.
+    pub fn call<T, F>(&self, task: F) -> impl Future<Output = Option<T>> + Send + 'static
+    where
+        T: Send + 'static,
+        F: FnOnce(&ServerContext) -> T + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        self.run_next_tick(move |server| {
+            // The caller may have stopped waiting.
+            let _ = sender.send(task(server));
+        });
+        async move { receiver.await.ok() }
+    }
+
+    fn schedule(&self, delay: u64, period: Option<u64>, task: Task) -> TaskHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let due = self.current_tick() + delay.max(1);
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.tasks.insert(
+            (due, id),
+            ScheduledTask {
+                task,
+                period,
+                cancelled: Arc::clone(&cancelled),
+            },
+        );
+        TaskHandle { cancelled }
+    }
+
+    /// Starts the next tick, returning its number.
+    fn advance(&self) -> u64 {
+        let mut tick = 0;
+        self.tick.send_modify(|current| {
+            *current += 1;
+            tick = *current;
+        });
+        tick
+    }
+
+    /// Runs the tasks due on `tick` or before.
+    fn run_due(&self, tick: u64, server: &ServerContext) {
+        loop {
+            // The lock isn't held while tasks run, so that they can schedule more.
+            let entry = {
+                let mut state = self.state.lock().unwrap();
+                let next_due = state.tasks.first_key_value().map(|(&(due, _), _)| due);
+                match next_due {
+                    Some(due) if due <= tick => state.tasks.pop_first(),
+                    _ => None,
+                }
+            };
+            let Some(((_, id), mut scheduled)) = entry else {
+                return;
+            };
+            if scheduled.cancelled.load(Ordering::Relaxed) {
+                continue;
+            }
+            (scheduled.task)(server);
+
+            if let Some(period) = scheduled.period {
+                if !scheduled.cancelled.load(Ordering::Relaxed) {
+                    let mut state = self.state.lock().unwrap();
+                    state.tasks.insert((tick + period, id), scheduled);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("tasks", &self.state.lock().unwrap().tasks.len())
+            .field("tick", &self.current_tick())
+            .finish()
+    }
+}
+
+/// A scheduled task, to cancel it.
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskHandle {
+    /// Stops the task from running again. It may still run once if it is running already.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Durations and start times of the last ticks, to measure how the server keeps up.
+#[derive(Debug, Default)]
+pub struct TickStats {
+    ticks: Mutex<VecDeque<(Instant, Duration)>>,
+}
+
+impl TickStats {
+    /// Records a tick that started at `start` and took `duration`.
+    fn record(&self, start: Instant, duration: Duration) {
+        let mut ticks = self.ticks.lock().unwrap();
+        if ticks.len() == STATS_WINDOW {
+            ticks.pop_front();
+        }
+        ticks.push_back((start, duration));
+    }
+
+    /// Average time ticks took recently, in milliseconds per tick (MSPT).
+    pub fn mspt(&self) -> f64 {
+        let ticks = self.ticks.lock().unwrap();
+        if ticks.is_empty() {
+            return 0.0;
+        }
+        let total: Duration = ticks.iter().map(|&(_, duration)| duration).sum();
+        total.as_secs_f64() * 1000.0 / ticks.len() as f64
+    }
+
+    /// Ticks per second (TPS) recently, at most [`TICKS_PER_SECOND`] unless catching up.
+    pub fn tps(&self) -> f64 {
+        let ticks = self.ticks.lock().unwrap();
+        let (Some(&(first, _)), Some(&(last, _))) = (ticks.front(), ticks.back()) else {
+            return TICKS_PER_SECOND as f64;
+        };
+        let elapsed = last.duration_since(first).as_secs_f64();
+        if elapsed == 0.0 {
+            return TICKS_PER_SECOND as f64;
+        }
+        (ticks.len() - 1) as f64 / elapsed
+    }
+}
+
+/// Ticks the server at a fixed rate.
+#[derive(Debug)]
+pub struct TickLoop {
+    server: Arc<ServerContext>,
+}
+
+impl TickLoop {
+    pub fn new(server: Arc<ServerContext>) -> Self {
+        Self { server }
+    }
+
+    /// Starts ticking on a new thread, until the server is asked to stop.
+    pub fn spawn(self) -> std::io::Result<thread::JoinHandle<()>> {
+        thread::Builder::new()
+            .name("server-tick".to_owned())
+            .spawn(move || self.run())
+    }
+
+    /// Ticks until the server is asked to stop.
+    ///
+    /// Ticks that start late run right away to catch up, unless the loop fell too far behind,
+    /// in which case they are skipped.
+    pub fn run(&self) {
+        let mut next_tick = Instant::now();
+        let mut last_warning: Option<Instant> = None;
+        while !self.server.is_shutdown_requested() {
+            let start = Instant::now();
+            self.tick();
+            self.server.tick_stats.record(start, start.elapsed());
+
+            next_tick += TICK_DURATION;
+            let now = Instant::now();
+            if let Some(wait) = next_tick.checked_duration_since(now) {
+                thread::sleep(wait);
+                continue;
+            }
+            let behind = now - next_tick;
+            if behind > MAX_LAG {
+                if last_warning.map_or(true, |warning| now - warning >= LAG_WARNING_INTERVAL) {
+                    tracing::warn!(
+                        "Can't keep up! Is the server overloaded? Running {}ms or {} ticks behind.",
+                        behind.as_millis(),
+                        behind.as_millis() / TICK_DURATION.as_millis()
+                    );
+                    last_warning = Some(now);
+                }
+                next_tick = now;
+            }
+        }
+    }
+
+    fn tick(&self) {
+        let tick = self.server.scheduler.advance();
+        let _span = tracing::trace_span!(TICK_SPAN, tick).entered();
+        self.server.world.tick();
+        self.server.scheduler.run_due(tick, &self.server);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::tests::test_server;
+
+    /// A scheduler whose tasks record their names when they run.
+    struct Recorder {
+        scheduler: Arc<Scheduler>,
+        server: ServerContext,
+        ran: Arc<Mutex<Vec<&'static str>>>,
+        _directory: tempfile::TempDir,
+    }
+
+    impl Recorder {
+        fn new() -> Self {
+            let directory = tempfile::tempdir().unwrap();
+            Self {
+                scheduler: Arc::new(Scheduler::new()),
+                server: test_server(directory.path()),
+                ran: Arc::default(),
+                _directory: directory,
+            }
+        }
+
+        fn task(&self, name: &'static str) -> impl FnMut(&ServerContext) + Send + 'static {
+            let ran = Arc::clone(&self.ran);
+            move |_| ran.lock().unwrap().push(name)
+        }
+
+        /// Runs the next tick, returning the names of the tasks that ran.
+        fn tick(&self) -> Vec<&'static str> {
+            let tick = self.scheduler.advance();
+            self.scheduler.run_due(tick, &self.server);
+            std::mem::take(&mut self.ran.lock().unwrap())
+        }
+    }
+
+    #[test]
+    fn runs_tasks_in_order() {
+        let recorder = Recorder::new();
+        let scheduler = &recorder.scheduler;
+        scheduler.run_later(2, recorder.task("later"));
+        scheduler.run_next_tick(recorder.task("first"));
+        scheduler.run_later(0, recorder.task("second"));
+        scheduler.run_later(1, recorder.task("third"));
+
+        assert_eq!(scheduler.current_tick(), 0);
+        assert_eq!(recorder.tick(), ["first", "second", "third"]);
+        assert_eq!(recorder.tick(), ["later"]);
+        assert!(recorder.tick().is_empty());
+        assert_eq!(scheduler.current_tick(), 3);
+    }
+
+    #[test]
+    fn runs_overdue_tasks() {
+        let recorder = Recorder::new();
+        recorder.scheduler.run_later(1, recorder.task("late"));
+        recorder.scheduler.run_later(2, recorder.task("later"));
+        recorder.scheduler.advance();
+        recorder.scheduler.advance();
+        assert_eq!(recorder.tick(), ["late", "later"]);
+    }
+
+    #[test]
+    fn runs_scheduled_tasks_on_later_ticks() {
+        let recorder = Recorder::new();
+        let scheduler = Arc::clone(&recorder.scheduler);
+        let ran = Arc::clone(&recorder.ran);
+        let again = recorder.task("again");
+        recorder.scheduler.run_next_tick(move |_| {
+            ran.lock().unwrap().push("first");
+            scheduler.run_later(0, again);
+        });
+        // Tasks scheduled while ticking wait for the next tick.
+        assert_eq!(recorder.tick(), ["first"]);
+        assert_eq!(recorder.tick(), ["again"]);
+    }
+
+    #[test]
+    fn cancels_tasks() {
+        let recorder = Recorder::new();
+        let cancelled = recorder.scheduler.run_later(1, recorder.task("cancelled"));
+        recorder.scheduler.run_later(1, recorder.task("kept"));
+        cancelled.cancel();
+        assert!(cancelled.is_cancelled());
+        assert_eq!(recorder.tick(), ["kept"]);
+        assert!(recorder.tick().is_empty());
+    }
+
+    #[test]
+    fn repeats_tasks() {
+        let recorder = Recorder::new();
+        let handle = recorder
+            .scheduler
+            .run_repeating(2, 3, recorder.task("repeating"));
+        let ran: Vec<_> = (0..8).map(|_| recorder.tick().len()).collect();
+        assert_eq!(ran, [0, 1, 0, 0, 1, 0, 0, 1]);
+
+        handle.cancel();
+        assert!((0..6).all(|_| recorder.tick().is_empty()));
+
+        // Tasks can cancel themselves, and periods of 0 are taken as 1.
+        let handle = Arc::new(Mutex::new(None::<TaskHandle>));
+        let runs = Arc::new(Mutex::new(0));
+        let task = {
+            let (handle, runs) = (Arc::clone(&handle), Arc::clone(&runs));
+            move |_: &ServerContext| {
+                let mut runs = runs.lock().unwrap();
+                *runs += 1;
+                if *runs == 3 {
+                    handle.lock().unwrap().as_ref().unwrap().cancel();
+                }
+            }
+        };
+        *handle.lock().unwrap() = Some(recorder.scheduler.run_repeating(0, 0, task));
+        for _ in 0..5 {
+            recorder.tick();
+        }
+        assert_eq!(*runs.lock().unwrap(), 3);
+        assert_eq!(
+            format!("{:?}", recorder.scheduler),
+            "Scheduler { tasks: 0, tick: 19 }"
+        );
+    }
+
+    #[tokio::test]
+    async fn calls_tasks() {
+        let recorder = Recorder::new();
+        let result = recorder.scheduler.call(|_| 42);
+        recorder.tick();
+        assert_eq!(result.await, Some(42));
+
+        // Tasks that never run resolve to nothing.
+        let result = recorder.scheduler.call(|_| 42);
+        drop(recorder);
+        assert_eq!(result.await, None);
+    }
+
+    #[test]
+    fn measures_ticks() {
+        let stats = TickStats::default();
+        assert_eq!(stats.mspt(), 0.0);
+        assert_eq!(stats.tps(), 20.0);
+
+        let start = Instant::now();
+        stats.record(start, Duration::from_millis(10));
+        assert_eq!(stats.mspt(), 10.0);
+        assert_eq!(stats.tps(), 20.0);
+
+        // Only the last ticks count.
+        for tick in 1..=STATS_WINDOW as u32 {
+            stats.record(start + TICK_DURATION * 2 * tick, Duration::from_millis(30));
+        }
+        assert_eq!(stats.ticks.lock().unwrap().len(), STATS_WINDOW);
+        assert!((stats.mspt() - 30.0).abs() < 1e-9);
+        assert!((stats.tps() - 10.0).abs() < 1e-9);
+    }
+}
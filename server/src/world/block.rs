@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use crate::physics::BoundingBox;
+
+/// Properties of a block state, as `(name, value)` pairs.
+pub type BlockProperties = &'static [(&'static str, &'static str)];
+
+/// A block state, by its network ID.
+///
+/// The server has no block data yet, so only the states it needs have constants. The IDs are
+/// those of Minecraft 1.21.1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockState(pub u16);
+
+impl BlockState {
+    pub const AIR: Self = Self(0);
+    pub const STONE: Self = Self(1);
+    /// `minecraft:grass_block[snowy=false]`.
+    pub const GRASS_BLOCK: Self = Self(9);
+    pub const DIRT: Self = Self(10);
+    pub const COBBLESTONE: Self = Self(14);
+    pub const OAK_PLANKS: Self = Self(15);
+    pub const BEDROCK: Self = Self(79);
+    /// `minecraft:water[level=0]`, a source block.
+    pub const WATER: Self = Self(80);
+    /// `minecraft:lava[level=0]`, a source block.
+    pub const LAVA: Self = Self(96);
+    pub const SAND: Self = Self(112);
+    pub const GRAVEL: Self = Self(118);
+
+    /// Water and lava states, of all levels.
+    const FLUIDS: std::ops::Range<u16> = 80..112;
+    /// Lava states, of all levels.
+    const LAVAS: std::ops::Range<u16> = 96..112;
+
+    /// Names and properties of the states with constants, as stored in chunk NBT.
+    const NAMES: &'static [(Self, &'static str, BlockProperties)] = &[
+        (Self::AIR, "minecraft:air", &[]),
+        (Self::STONE, "minecraft:stone", &[]),
+        (
+            Self::GRASS_BLOCK,
+            "minecraft:grass_block",
+            &[("snowy", "false")],
+        ),
+        (Self::DIRT, "minecraft:dirt", &[]),
+        (Self::COBBLESTONE, "minecraft:cobblestone", &[]),
+        (Self::OAK_PLANKS, "minecraft:oak_planks", &[]),
+        (Self::BEDROCK, "minecraft:bedrock", &[]),
+        (Self::WATER, "minecraft:water", &[("level", "0")]),
+        (Self::LAVA, "minecraft:lava", &[("level", "0")]),
+        (Self::SAND, "minecraft:sand", &[]),
+        (Self::GRAVEL, "minecraft:gravel", &[]),
+    ];
+
+    /// Returns the state of the block named `name`, ignoring its properties.
+    ///
+    /// Only blocks with a constant are known.
+    pub fn from_name(name: &str) -> Option<Self> {
+        // TODO: Cave air and void air are only told apart from air by their name for now.
+        if let "minecraft:cave_air" | "minecraft:void_air" = name {
+            return Some(Self::AIR);
+        }
+        Self::NAMES
+            .iter()
+            .find(|(_, state_name, _)| *state_name == name)
+            .map(|&(state, _, _)| state)
+    }
+
+    /// Returns the state of the block named `name` with exactly `properties`, as stored in chunk
+    /// NBT.
+    ///
+    /// Only states with a constant are known. Cave air and void air are read as air, which they
+    /// only differ from while chunks are generated.
+    pub fn from_nbt(name: &str, properties: &HashMap<String, String>) -> Option<Self> {
+        if let "minecraft:cave_air" | "minecraft:void_air" = name {
+            return properties.is_empty().then_some(Self::AIR);
+        }
+        Self::NAMES
+            .iter()
+            .find(|(_, state_name, state_properties)| {
+                *state_name == name
+                    && state_properties.len() == properties.len()
+                    && state_properties
+                        .iter()
+                        .all(|&(key, value)| properties.get(key).is_some_and(|got| got == value))
+            })
+            .map(|&(state, _, _)| state)
+    }
+
+    /// Returns the name and properties of this state, if it has a constant.
+    pub fn name(self) -> Option<(&'static str, BlockProperties)> {
+        Self::NAMES
+            .iter()
+            .find(|(state, _, _)| *state == self)
+            .map(|&(_, name, properties)| (name, properties))
+    }
+
+    pub const fn id(self) -> u16 {
+        self.0
+    }
+
+    /// Whether this is air, which isn't counted as a block in chunk sections.
+    pub fn is_air(self) -> bool {
+        // TODO: Cave air and void air, once there is block data.
+        self == Self::AIR
+    }
+
+    /// Whether this block has a full collision box.
+    pub fn is_solid(self) -> bool {
+        // TODO: Plants, slabs etc., once there is block data.
+        !self.is_air() && !self.is_fluid()
+    }
+
+    /// The boxes entities collide with, relative to the block.
+    pub fn collision_shape(self) -> &'static [BoundingBox] {
+        // TODO: Slabs, stairs, fences etc., once there is block data.
+        if self.is_solid() {
+            &[BoundingBox::FULL_BLOCK]
+        } else {
+            &[]
+        }
+    }
+
+    /// Whether this block is water or lava.
+    pub fn is_fluid(self) -> bool {
+        // TODO: Waterlogged blocks, once there is block data.
+        Self::FLUIDS.contains(&self.0)
+    }
+
+    /// Light level (0-15) this block emits.
+    pub fn light_emission(self) -> u8 {
+        // TODO: Torches, glowstone etc., once there is block data.
+        if Self::LAVAS.contains(&self.0) {
+            15
+        } else {
+            0
+        }
+    }
+
+    /// Light levels (0-15) light loses going into this block, though it always loses at least
+    /// one. Sky light only keeps its level going down through blocks with none.
+    pub fn light_opacity(self) -> u8 {
+        // TODO: Leaves, glass, slabs etc., once there is block data.
+        if self.is_solid() {
+            15
+        } else if self.is_fluid() {
+            1
+        } else {
+            0
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use packet::client::{ChunkDataAndUpdateLightPacket, LightArray, LightData, UpdateLightPacket};
+use protocol::{BlockPosition, NetworkNbt};
+
+use super::{
+    block::BlockState,
+    heightmap::{Heightmap, HeightmapKind},
+    light::{LightKind, LightSection},
+    palette::{self, PalettedContainer},
+    storage::StoredData,
+};
+
+/// Width, depth and height of a chunk section, in blocks.
+pub const SECTION_SIZE: i32 = 16;
+
+/// The position of a chunk, in chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ChunkPos {
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// The chunk `block` is in.
+    pub const fn from_block(block: BlockPosition) -> Self {
+        Self::new(block.x >> 4, block.z >> 4)
+    }
+}
+
+/// The chunks within `radius` of `center`, in a square spiral going outwards from the center.
+pub fn spiral(center: ChunkPos, radius: i32) -> impl Iterator<Item = ChunkPos> {
+    std::iter::once(center).chain((1..=radius.max(0)).flat_map(move |ring| {
+        // Each side of the ring has `2 * ring` chunks, starting after the previous side's corner.
+        (0..8 * ring).map(move |index| {
+            let step = index % (2 * ring) + 1;
+            let (x, z) = match index / (2 * ring) {
+                0 => (ring, step - ring),
+                1 => (ring - step, ring),
+                2 => (-ring, ring - step),
+                _ => (step - ring, -ring),
+            };
+            ChunkPos::new(center.x + x, center.z + z)
+        })
+    }))
+}
+
+/// A 16x16x16 section of a chunk, storing block states and biomes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkSection {
+    /// Number of blocks that aren't air.
+    block_count: u16,
+    block_states: PalettedContainer,
+    biomes: PalettedContainer,
+}
+
+impl ChunkSection {
+    /// Makes a section of air in `biome`.
+    pub fn new(biome: u32) -> Self {
+        Self {
+            block_count: 0,
+            block_states: PalettedContainer::filled(palette::BLOCK_STATES, 0),
+            biomes: PalettedContainer::filled(palette::BIOMES, biome),
+        }
+    }
+
+    /// Makes a section from its block states and biomes.
+    pub fn from_containers(block_states: PalettedContainer, biomes: PalettedContainer) -> Self {
+        let block_count = block_states
+            .iter()
+            .filter(|&id| !BlockState(id as u16).is_air())
+            .count() as u16;
+        Self {
+            block_count,
+            block_states,
+            biomes,
+        }
+    }
+
+    /// Returns the block at `x`, `y` and `z` (0-15) in this section.
+    pub fn block(&self, x: usize, y: usize, z: usize) -> BlockState {
+        BlockState(self.block_states.get(block_index(x, y, z)) as u16)
+    }
+
+    /// Sets the block at `x`, `y` and `z` (0-15) in this section, returning the previous one.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: BlockState) -> BlockState {
+        let previous = BlockState(
+            self.block_states
+                .set(block_index(x, y, z), block.id() as u32) as u16,
+        );
+        match (previous.is_air(), block.is_air()) {
+            (true, false) => self.block_count += 1,
+            (false, true) => self.block_count -= 1,
+            _ => {}
+        }
+        previous
+    }
+
+    /// Sets every block of this section to `block`.
+    pub fn fill(&mut self, block: BlockState) {
+        self.block_states.fill(block.id() as u32);
+        self.block_count = if block.is_air() { 0 } else { 4096 };
+    }
+
+    /// Returns the biome (its ID in the `minecraft:worldgen/biome` registry) at `x`, `y` and `z`
+    /// (0-3), in cells of 4x4x4 blocks.
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> u32 {
+        self.biomes.get(biome_index(x, y, z))
+    }
+
+    /// Sets the biome at `x`, `y` and `z` (0-3), in cells of 4x4x4 blocks.
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: u32) {
+        self.biomes.set(biome_index(x, y, z), biome);
+    }
+
+    /// Number of blocks that aren't air.
+    pub fn block_count(&self) -> u16 {
+        self.block_count
+    }
+
+    /// Whether this section is only air.
+    pub fn is_empty(&self) -> bool {
+        self.block_count == 0
+    }
+
+    pub fn block_states(&self) -> &PalettedContainer {
+        &self.block_states
+    }
+
+    pub fn biomes(&self) -> &PalettedContainer {
+        &self.biomes
+    }
+
+    /// Encodes this section as sent in chunk data: the block count, block states and biomes.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&(self.block_count as i16).to_be_bytes());
+        self.block_states.encode(buf);
+        self.biomes.encode(buf);
+    }
+}
+
+fn block_index(x: usize, y: usize, z: usize) -> usize {
+    (y << 8) | (z << 4) | x
+}
+
+fn biome_index(x: usize, y: usize, z: usize) -> usize {
+    (y << 4) | (z << 2) | x
+}
+
+/// A column of chunk sections spanning the height of the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pos: ChunkPos,
+    /// Lowest Y coordinate, a multiple of 16.
+    min_y: i32,
+    /// Sections from the bottom to the top.
+    sections: Vec<ChunkSection>,
+    /// Light of the sections, and of one more section below and above the world.
+    sky_light: Vec<LightSection>,
+    block_light: Vec<LightSection>,
+    /// A heightmap of each [`HeightmapKind`], in the order of [`HeightmapKind::ALL`].
+    heightmaps: [Heightmap; HeightmapKind::ALL.len()],
+    /// What the chunk was read from storage with, to save it back without losing anything.
+    stored: StoredData,
+}
+
+impl Chunk {
+    /// Makes a chunk of air in `biome`, from `min_y` up to (excluding) `min_y + height`, which
+    /// must both be multiples of 16.
+    pub fn new(pos: ChunkPos, min_y: i32, height: u32, biome: u32) -> Self {
+        let section_count = height as usize / SECTION_SIZE as usize;
+        let light_sections = section_count + 2;
+        Self {
+            pos,
+            min_y,
+            sections: vec![ChunkSection::new(biome); section_count],
+            sky_light: vec![LightSection::Uniform(0); light_sections],
+            block_light: vec![LightSection::Uniform(0); light_sections],
+            heightmaps: HeightmapKind::ALL.map(Heightmap::new),
+            stored: StoredData::default(),
+        }
+    }
+
+    /// Makes a chunk from its sections, from the bottom at `min_y` to the top, computing its
+    /// heightmaps. It is dark until it is lit by the [`LightEngine`](super::LightEngine).
+    pub fn from_sections(pos: ChunkPos, min_y: i32, sections: Vec<ChunkSection>) -> Self {
+        let light_sections = sections.len() + 2;
+        let mut chunk = Self {
+            pos,
+            min_y,
+            sections,
+            sky_light: vec![LightSection::Uniform(0); light_sections],
+            block_light: vec![LightSection::Uniform(0); light_sections],
+            heightmaps: HeightmapKind::ALL.map(Heightmap::new),
+            stored: StoredData::default(),
+        };
+        chunk.compute_heightmaps();
+        chunk
+    }
+
+    pub fn pos(&self) -> ChunkPos {
+        self.pos
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// Height in blocks.
+    pub fn height(&self) -> u32 {
+        self.sections.len() as u32 * SECTION_SIZE as u32
+    }
+
+    pub fn stored_data(&self) -> &StoredData {
+        &self.stored
+    }
+
+    pub(super) fn set_stored_data(&mut self, stored: StoredData) {
+        self.stored = stored;
+    }
+
+    pub fn sections(&self) -> &[ChunkSection] {
+        &self.sections
+    }
+
+    pub fn sections_mut(&mut self) -> &mut [ChunkSection] {
+        &mut self.sections
+    }
+
+    /// Returns the section containing `y`, and the coordinate within it.
+    fn section_index(&self, y: i32) -> Option<(usize, usize)> {
+        let relative = y.checked_sub(self.min_y)?;
+        let index = usize::try_from(relative / SECTION_SIZE).ok()?;
+        (relative >= 0 && index < self.sections.len())
+            .then_some((index, (relative % SECTION_SIZE) as usize))
+    }
+
+    /// Returns the block at `position`, of which only the Y coordinate and the position within
+    /// the chunk are used. Blocks outside the world's height are air.
+    pub fn block(&self, position: BlockPosition) -> BlockState {
+        let Some((section, y)) = self.section_index(position.y) else {
+            return BlockState::AIR;
+        };
+        let (x, z) = local_xz(position);
+        self.sections[section].block(x, y, z)
+    }
+
+    /// Sets the block at `position`, of which only the Y coordinate and the position within the
+    /// chunk are used. Returns the previous block, or `None` if `position` is outside the
+    /// world's height.
+    pub fn set_block(&mut self, position: BlockPosition, block: BlockState) -> Option<BlockState> {
+        let (section, y) = self.section_index(position.y)?;
+        let (x, z) = local_xz(position);
+        let previous = self.sections[section].set_block(x, y, z, block);
+        if previous != block {
+            self.update_heightmaps(x, position.y, z, block);
+        }
+        Some(previous)
+    }
+
+    /// Returns the heightmap of `kind`.
+    pub fn heightmap(&self, kind: HeightmapKind) -> &Heightmap {
+        &self.heightmaps[heightmap_index(kind)]
+    }
+
+    /// Returns the Y coordinate of the highest block of the column at `x` and `z` (0-15) in the
+    /// heightmap of `kind`, or `None` if the column has no such block.
+    pub fn top_y(&self, kind: HeightmapKind, x: usize, z: usize) -> Option<i32> {
+        let height = self.heightmap(kind).get(x, z);
+        (height > 0).then(|| self.min_y + height as i32 - 1)
+    }
+
+    /// Computes the heightmaps from the blocks.
+    fn compute_heightmaps(&mut self) {
+        for x in 0..SECTION_SIZE as usize {
+            for z in 0..SECTION_SIZE as usize {
+                for kind in HeightmapKind::ALL {
+                    let height = self.column_height(kind, x, z, self.height() as usize);
+                    self.heightmaps[heightmap_index(kind)].set(x, z, height);
+                }
+            }
+        }
+    }
+
+    /// Updates the heightmaps after the block at `y` of the column at `x` and `z` (0-15) became
+    /// `block`.
+    fn update_heightmaps(&mut self, x: usize, y: i32, z: usize, block: BlockState) {
+        let relative_y = (y - self.min_y) as usize;
+        for kind in HeightmapKind::ALL {
+            let current = self.heightmap(kind).get(x, z) as usize;
+            let height = if kind.is_top(block) {
+                current.max(relative_y + 1)
+            } else if current == relative_y + 1 {
+                // The top block was removed, the new top is further down.
+                self.column_height(kind, x, z, relative_y) as usize
+            } else {
+                continue;
+            };
+            self.heightmaps[heightmap_index(kind)].set(x, z, height as u16);
+        }
+    }
+
+    /// Returns the height of the column at `x` and `z` (0-15) in the heightmap of `kind`, only
+    /// looking at the blocks below `below`, relative to the bottom of the world.
+    fn column_height(&self, kind: HeightmapKind, x: usize, z: usize, below: usize) -> u16 {
+        let section_size = SECTION_SIZE as usize;
+        for relative_y in (0..below).rev() {
+            let section = &self.sections[relative_y / section_size];
+            // Sections of air have no top blocks, for any kind.
+            if section.is_empty() {
+                continue;
+            }
+            if kind.is_top(section.block(x, relative_y % section_size, z)) {
+                return relative_y as u16 + 1;
+            }
+        }
+        0
+    }
+
+    /// Returns the biome at `position`, like [`Chunk::block`].
+    pub fn biome(&self, position: BlockPosition) -> Option<u32> {
+        let (section, y) = self.section_index(position.y)?;
+        let (x, z) = local_xz(position);
+        Some(self.sections[section].biome(x / 4, y / 4, z / 4))
+    }
+
+    /// Sets the biome of the 4x4x4 cell containing `position`, like [`Chunk::set_block`].
+    pub fn set_biome(&mut self, position: BlockPosition, biome: u32) -> Option<()> {
+        let (section, y) = self.section_index(position.y)?;
+        let (x, z) = local_xz(position);
+        self.sections[section].set_biome(x / 4, y / 4, z / 4, biome);
+        Some(())
+    }
+
+    /// Light sections of `kind`, from the one below the world to the one above it.
+    pub fn light_sections(&self, kind: LightKind) -> &[LightSection] {
+        match kind {
+            LightKind::Sky => &self.sky_light,
+            LightKind::Block => &self.block_light,
+        }
+    }
+
+    pub fn light_sections_mut(&mut self, kind: LightKind) -> &mut [LightSection] {
+        match kind {
+            LightKind::Sky => &mut self.sky_light,
+            LightKind::Block => &mut self.block_light,
+        }
+    }
+
+    /// The Y coordinates light is stored for, one section further than blocks below and above
+    /// the world, as a range excluding the end.
+    pub fn light_range(&self) -> (i32, i32) {
+        let bottom = self.min_y - SECTION_SIZE;
+        (bottom, bottom + self.sky_light.len() as i32 * SECTION_SIZE)
+    }
+
+    /// Returns the light section containing `y`, and the coordinate within it.
+    fn light_section_index(&self, y: i32) -> Option<(usize, usize)> {
+        let (bottom, top) = self.light_range();
+        (bottom..top).contains(&y).then(|| {
+            let relative = y - bottom;
+            (
+                (relative / SECTION_SIZE) as usize,
+                (relative % SECTION_SIZE) as usize,
+            )
+        })
+    }
+
+    /// Returns the light level of `kind` at `position`, like [`Chunk::block`], or `None` if it
+    /// is outside [`Chunk::light_range`].
+    pub fn light(&self, kind: LightKind, position: BlockPosition) -> Option<u8> {
+        let (section, y) = self.light_section_index(position.y)?;
+        let (x, z) = local_xz(position);
+        Some(self.light_sections(kind)[section].get(x, y, z))
+    }
+
+    /// Sets the light level of `kind` at `position`, like [`Chunk::set_block`]. Returns the
+    /// previous level, or `None` if it is outside [`Chunk::light_range`].
+    pub fn set_light(&mut self, kind: LightKind, position: BlockPosition, level: u8) -> Option<u8> {
+        let (section, y) = self.light_section_index(position.y)?;
+        let (x, z) = local_xz(position);
+        let section = &mut self.light_sections_mut(kind)[section];
+        let previous = section.get(x, y, z);
+        section.set(x, y, z, level);
+        Some(previous)
+    }
+
+    /// Sets the light level of `kind` everywhere in the chunk.
+    pub fn fill_light(&mut self, kind: LightKind, level: u8) {
+        self.light_sections_mut(kind)
+            .fill(LightSection::Uniform(level));
+    }
+
+    /// Encodes all sections, as sent in chunk data.
+    pub fn encode_sections(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for section in &self.sections {
+            section.encode(&mut buf);
+        }
+        buf
+    }
+
+    /// Makes the packet sending this chunk to clients.
+    pub fn to_packet(&self) -> ChunkDataAndUpdateLightPacket<'static> {
+        ChunkDataAndUpdateLightPacket {
+            chunk_x: self.pos.x,
+            chunk_z: self.pos.z,
+            heightmaps: NetworkNbt::from_serializable(&self.heightmaps_nbt())
+                .expect("a map should serialize into a compound"),
+            data: self.encode_sections().into(),
+            block_entities: Cow::Borrowed(&[]),
+            light: self.light_data(),
+        }
+    }
+
+    /// The heightmaps as stored in chunk NBT and sent to clients, by their names.
+    pub fn heightmaps_nbt(&self) -> HashMap<String, Vec<i64>> {
+        self.heightmaps
+            .iter()
+            .map(|heightmap| {
+                let longs = heightmap.to_longs(self.height());
+                (heightmap.kind().name().to_owned(), longs)
+            })
+            .collect()
+    }
+
+    /// Makes the packet updating the light of this chunk on clients that have it.
+    pub fn light_packet(&self) -> UpdateLightPacket<'static> {
+        UpdateLightPacket {
+            chunk_x: self.pos.x,
+            chunk_z: self.pos.z,
+            light: self.light_data(),
+        }
+    }
+
+    /// Light of this chunk, with one section of light below and above the world.
+    fn light_data(&self) -> LightData<'static> {
+        let (sky_light_mask, empty_sky_light_mask, sky_light_arrays) =
+            encode_light(&self.sky_light);
+        let (block_light_mask, empty_block_light_mask, block_light_arrays) =
+            encode_light(&self.block_light);
+        LightData {
+            sky_light_mask: sky_light_mask.into(),
+            block_light_mask: block_light_mask.into(),
+            empty_sky_light_mask: empty_sky_light_mask.into(),
+            empty_block_light_mask: empty_block_light_mask.into(),
+            sky_light_arrays: sky_light_arrays.into(),
+            block_light_arrays: block_light_arrays.into(),
+        }
+    }
+}
+
+fn heightmap_index(kind: HeightmapKind) -> usize {
+    HeightmapKind::ALL
+        .iter()
+        .position(|&other| other == kind)
+        .expect("every kind should be in the list")
+}
+
+/// The X and Z coordinates of `position` within its chunk.
+fn local_xz(position: BlockPosition) -> (usize, usize) {
+    ((position.x & 15) as usize, (position.z & 15) as usize)
+}
+
+/// Encodes light sections as sent to clients: the bit set of the sections that are sent, the
+/// one of the sections that are dark, and the light of the sections that are sent.
+fn encode_light(sections: &[LightSection]) -> (Vec<i64>, Vec<i64>, Vec<LightArray<'static>>) {
+    let longs = sections.len().div_ceil(64);
+    let (mut mask, mut empty_mask) = (vec![0; longs], vec![0; longs]);
+    let mut arrays = Vec::new();
+    for (index, section) in sections.iter().enumerate() {
+        let bit = 1 << (index % 64);
+        if section.is_empty() {
+            empty_mask[index / 64] |= bit;
+        } else {
+            mask[index / 64] |= bit;
+            arrays.push(LightArray {
+                data: section.to_bytes().into(),
+            });
+        }
+    }
+    (mask, empty_mask, arrays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_Y: i32 = -64;
+
+    fn chunk() -> Chunk {
+        Chunk::new(ChunkPos::new(0, 0), MIN_Y, 384, 0)
+    }
+
+    #[test]
+    fn places_top_blocks() {
+        let mut chunk = chunk();
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 1, 2), None);
+
+        chunk.set_block(BlockPosition::new(1, 10, 2), BlockState::STONE);
+        chunk.set_block(BlockPosition::new(1, -30, 2), BlockState::WATER);
+        for kind in HeightmapKind::ALL {
+            assert_eq!(chunk.top_y(kind, 1, 2), Some(10));
+            assert_eq!(chunk.heightmap(kind).get(1, 2), 75);
+        }
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 2, 1), None);
+        // Positions are taken within the chunk.
+        chunk.set_block(BlockPosition::new(-15, 300, 18), BlockState::STONE);
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 1, 2), Some(300));
+    }
+
+    #[test]
+    fn removes_top_blocks() {
+        let mut chunk = chunk();
+        for y in [-64, -40, 0, 1] {
+            chunk.set_block(BlockPosition::new(3, y, 4), BlockState::STONE);
+        }
+
+        // Removing blocks below the top leaves it.
+        chunk.set_block(BlockPosition::new(3, 0, 4), BlockState::AIR);
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 3, 4), Some(1));
+        // Removing the top finds the next one down, across sections of air.
+        chunk.set_block(BlockPosition::new(3, 1, 4), BlockState::AIR);
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 3, 4), Some(-40));
+        chunk.set_block(BlockPosition::new(3, -40, 4), BlockState::AIR);
+        assert_eq!(chunk.top_y(HeightmapKind::MotionBlocking, 3, 4), Some(-64));
+        chunk.set_block(BlockPosition::new(3, -64, 4), BlockState::AIR);
+        for kind in HeightmapKind::ALL {
+            assert_eq!(chunk.top_y(kind, 3, 4), None);
+            assert_eq!(chunk.heightmap(kind).get(3, 4), 0);
+        }
+    }
+
+    #[test]
+    fn updates_like_computing() {
+        let mut chunk = chunk();
+        let blocks = [
+            (0, -64, 0, BlockState::BEDROCK),
+            (0, 100, 0, BlockState::STONE),
+            (0, 100, 0, BlockState::AIR),
+            (5, 319, 7, BlockState::DIRT),
+            (5, 200, 7, BlockState::LAVA),
+            (5, 319, 7, BlockState::AIR),
+            (15, 15, 15, BlockState::WATER),
+            (15, 16, 15, BlockState::SAND),
+            (15, 16, 15, BlockState::AIR),
+        ];
+        for (x, y, z, block) in blocks {
+            chunk.set_block(BlockPosition::new(x, y, z), block);
+        }
+
+        let heightmaps = chunk.heightmaps.clone();
+        chunk.compute_heightmaps();
+        assert_eq!(chunk.heightmaps, heightmaps);
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 0, 0), Some(-64));
+        assert_eq!(chunk.top_y(HeightmapKind::WorldSurface, 5, 7), Some(200));
+        assert_eq!(chunk.top_y(HeightmapKind::MotionBlocking, 15, 15), Some(15));
+    }
+}
@@ -0,0 +1,241 @@
+use super::{biome_id, ChunkGenerator, GeneratorError};
+use crate::world::{BlockState, Chunk, ChunkPos, ChunkSection, DEFAULT_BIOME, SECTION_SIZE};
+
+/// Layers of vanilla's classic flat preset.
+const DEFAULT_LAYERS: &str = "minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block";
+/// Most layers in total, the height limit of vanilla's dimensions.
+const MAX_LAYERS: usize = 4064;
+
+/// Generates the same layers of blocks in every chunk, from the bottom of the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlatGenerator {
+    /// A block per layer, from the bottom.
+    layers: Vec<BlockState>,
+    biome: u32,
+}
+
+impl FlatGenerator {
+    /// Makes a generator of `layers`, from the bottom, in `biome`.
+    pub fn new(layers: Vec<BlockState>, biome: u32) -> Self {
+        Self { layers, biome }
+    }
+
+    /// Parses layers in the format of vanilla's flat world presets, e.g.
+    /// `minecraft:bedrock,2*minecraft:dirt,minecraft:grass_block;minecraft:plains`.
+    ///
+    /// The layers are listed from the bottom, optionally followed by the biome, and then by
+    /// structures, which are ignored. There can be at most 4064 layers, like in vanilla.
+    pub fn from_layers(settings: &str) -> Result<Self, GeneratorError> {
+        let mut parts = settings.split(';');
+        let layers = parts.next().unwrap_or_default();
+        let biome = match parts.next().map(str::trim) {
+            Some(name) if !name.is_empty() => {
+                biome_id(name).ok_or_else(|| GeneratorError::UnknownBiome(name.to_owned()))?
+            }
+            _ => biome_id(DEFAULT_BIOME).unwrap_or_default(),
+        };
+
+        let mut blocks = Vec::new();
+        for layer in layers
+            .split(',')
+            .map(str::trim)
+            .filter(|layer| !layer.is_empty())
+        {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => {
+                    let count = count
+                        .trim()
+                        .parse()
+                        .map_err(|_| GeneratorError::InvalidLayer(layer.to_owned()))?;
+                    (count, name.trim())
+                }
+                None => (1, layer),
+            };
+            let block = BlockState::from_name(name)
+                .ok_or_else(|| GeneratorError::UnknownBlock(name.to_owned()))?;
+            let total = blocks.len().saturating_add(count);
+            if total > MAX_LAYERS {
+                return Err(GeneratorError::TooManyLayers(total));
+            }
+            blocks.resize(total, block);
+        }
+        Ok(Self::new(blocks, biome))
+    }
+
+    pub fn layers(&self) -> &[BlockState] {
+        &self.layers
+    }
+}
+
+impl Default for FlatGenerator {
+    fn default() -> Self {
+        Self::from_layers(DEFAULT_LAYERS).expect("the default layers should be valid")
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, pos: ChunkPos, min_y: i32, height: u32) -> Chunk {
+        let section_size = SECTION_SIZE as usize;
+        let mut sections = vec![ChunkSection::new(self.biome); height as usize / section_size];
+        // Layers above the top of the world are left out.
+        for (section, layers) in sections.iter_mut().zip(self.layers.chunks(section_size)) {
+            // Sections covered by a single layer, as most are in deep worlds, are filled at once.
+            if layers.len() == section_size && layers.iter().all(|&block| block == layers[0]) {
+                section.fill(layers[0]);
+                continue;
+            }
+            for (y, &block) in layers.iter().enumerate() {
+                if block.is_air() {
+                    continue;
+                }
+                for x in 0..section_size {
+                    for z in 0..section_size {
+                        section.set_block(x, y, z, block);
+                    }
+                }
+            }
+        }
+        Chunk::from_sections(pos, min_y, sections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::BlockPosition;
+
+    use super::*;
+    use crate::world::HeightmapKind;
+
+    #[test]
+    fn parses_layers() {
+        let generator =
+            FlatGenerator::from_layers(" minecraft:bedrock, 3 * minecraft:stone ,,minecraft:sand")
+                .unwrap();
+        assert_eq!(
+            generator.layers(),
+            [
+                BlockState::BEDROCK,
+                BlockState::STONE,
+                BlockState::STONE,
+                BlockState::STONE,
+                BlockState::SAND,
+            ]
+        );
+        assert_eq!(generator.biome, biome_id(DEFAULT_BIOME).unwrap());
+
+        assert_eq!(
+            FlatGenerator::default().layers(),
+            [
+                BlockState::BEDROCK,
+                BlockState::DIRT,
+                BlockState::DIRT,
+                BlockState::GRASS_BLOCK,
+            ]
+        );
+        // Worlds can have no layers at all.
+        assert!(FlatGenerator::from_layers("").unwrap().layers().is_empty());
+        assert!(FlatGenerator::from_layers("0*minecraft:stone")
+            .unwrap()
+            .layers()
+            .is_empty());
+    }
+
+    #[test]
+    fn parses_biomes_and_structures() {
+        let generator =
+            FlatGenerator::from_layers("minecraft:stone;minecraft:desert;village,mineshaft")
+                .unwrap();
+        assert_eq!(generator.layers(), [BlockState::STONE]);
+        assert_eq!(generator.biome, biome_id("minecraft:desert").unwrap());
+
+        // Structures are ignored, even without a biome.
+        let generator = FlatGenerator::from_layers("minecraft:stone;;village").unwrap();
+        assert_eq!(generator.biome, biome_id(DEFAULT_BIOME).unwrap());
+
+        assert!(matches!(
+            FlatGenerator::from_layers("minecraft:stone;minecraft:moon"),
+            Err(GeneratorError::UnknownBiome(name)) if name == "minecraft:moon"
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_layers() {
+        assert!(matches!(
+            FlatGenerator::from_layers("minecraft:bedrock,minecraft:cheese"),
+            Err(GeneratorError::UnknownBlock(name)) if name == "minecraft:cheese"
+        ));
+        assert!(matches!(
+            FlatGenerator::from_layers("two*minecraft:stone"),
+            Err(GeneratorError::InvalidLayer(layer)) if layer == "two*minecraft:stone"
+        ));
+        assert!(matches!(
+            FlatGenerator::from_layers("-1*minecraft:stone"),
+            Err(GeneratorError::InvalidLayer(_))
+        ));
+
+        assert_eq!(
+            FlatGenerator::from_layers("4000*minecraft:stone,64*minecraft:dirt")
+                .unwrap()
+                .layers()
+                .len(),
+            MAX_LAYERS
+        );
+        assert!(matches!(
+            FlatGenerator::from_layers("4000*minecraft:stone,65*minecraft:dirt"),
+            Err(GeneratorError::TooManyLayers(4065))
+        ));
+        // Huge counts are refused without allocating them.
+        assert!(matches!(
+            FlatGenerator::from_layers(&format!("1*minecraft:stone,{}*minecraft:dirt", usize::MAX)),
+            Err(GeneratorError::TooManyLayers(usize::MAX))
+        ));
+    }
+
+    #[test]
+    fn generates_layers() {
+        let generator = FlatGenerator::from_layers(
+            "minecraft:bedrock,20*minecraft:stone,minecraft:air,minecraft:dirt,30*minecraft:sand",
+        )
+        .unwrap();
+        let chunk = generator.generate(ChunkPos::new(3, -2), -16, 48);
+
+        for (x, z) in [(0, 0), (7, 12), (15, 15)] {
+            let block = |y| chunk.block(BlockPosition::new(x, y, z));
+            assert_eq!(block(-16), BlockState::BEDROCK);
+            assert_eq!(block(-15), BlockState::STONE);
+            assert_eq!(block(4), BlockState::STONE);
+            assert_eq!(block(5), BlockState::AIR);
+            assert_eq!(block(6), BlockState::DIRT);
+            // Layers above the top of the world are left out.
+            assert_eq!(block(31), BlockState::SAND);
+            assert_eq!(
+                chunk.top_y(HeightmapKind::WorldSurface, x as usize, z as usize),
+                Some(31)
+            );
+        }
+        let counts: Vec<_> = chunk
+            .sections()
+            .iter()
+            .map(ChunkSection::block_count)
+            .collect();
+        assert_eq!(counts, [4096, 15 * 256, 4096]);
+        assert!(chunk
+            .sections()
+            .iter()
+            .all(|section| section.biome(1, 2, 3) == generator.biome));
+    }
+
+    #[test]
+    fn fills_sections() {
+        let generator = FlatGenerator::from_layers("32*minecraft:stone,16*minecraft:air").unwrap();
+        let chunk = generator.generate(ChunkPos::new(0, 0), 0, 64);
+        let sections = chunk.sections();
+        // Whole sections of a block take no more room than a single block.
+        assert_eq!(sections[0].block_states().bits(), 0);
+        assert_eq!(sections[0].block(5, 6, 7), BlockState::STONE);
+        assert_eq!(sections[1].block_count(), 4096);
+        assert!(sections[2].is_empty());
+        assert!(sections[3].is_empty());
+        assert_eq!(chunk.top_y(HeightmapKind::MotionBlocking, 8, 8), Some(31));
+    }
+}
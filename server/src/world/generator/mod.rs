@@ -0,0 +1,147 @@
+//! Generation of the terrain of chunks that aren't stored yet.
+//!
+//! Generators are deterministic: the same settings and seed always make the same chunks, so
+//! that chunks generated at different times fit together.
+
+mod flat;
+mod noise;
+
+use std::fmt::Debug;
+
+use server_assets::Registries;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use super::{Chunk, ChunkPos};
+
+pub use flat::FlatGenerator;
+pub use noise::NoiseGenerator;
+
+/// Makes the blocks and biomes of new chunks.
+pub trait ChunkGenerator: Debug + Send + Sync {
+    /// Generates the chunk at `pos`, from `min_y` up to (excluding) `min_y + height`.
+    fn generate(&self, pos: ChunkPos, min_y: i32, height: u32) -> Chunk;
+}
+
+/// Generates chunks of air.
+#[derive(Debug)]
+pub struct VoidGenerator {
+    biome: u32,
+}
+
+impl VoidGenerator {
+    pub fn new() -> Self {
+        Self {
+            // Unlike the other biomes, the void has no fog or sky color changes.
+            biome: biome_id("minecraft:the_void").unwrap_or_default(),
+        }
+    }
+}
+
+impl Default for VoidGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkGenerator for VoidGenerator {
+    fn generate(&self, pos: ChunkPos, min_y: i32, height: u32) -> Chunk {
+        Chunk::new(pos, min_y, height, self.biome)
+    }
+}
+
+/// The generators that can be chosen in the configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelType {
+    /// Terrain made from noise, see [`NoiseGenerator`].
+    Normal,
+    /// Layers of blocks, see [`FlatGenerator`].
+    Flat,
+    /// No blocks at all, see [`VoidGenerator`].
+    Void,
+}
+
+impl LevelType {
+    /// Returns the type named `name`, with or without the `minecraft:` namespace vanilla uses.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.strip_prefix("minecraft:").unwrap_or(name) {
+            "normal" => Some(Self::Normal),
+            "flat" => Some(Self::Flat),
+            "void" => Some(Self::Void),
+            _ => None,
+        }
+    }
+
+    /// Makes the generator of this type. `settings` are the layers of flat worlds, the
+    /// default ones if empty, and are ignored by the other types.
+    pub fn generator(
+        self,
+        settings: &str,
+        seed: i64,
+    ) -> Result<Box<dyn ChunkGenerator>, GeneratorError> {
+        Ok(match self {
+            Self::Normal => Box::new(NoiseGenerator::new(seed)),
+            Self::Flat if settings.is_empty() => Box::new(FlatGenerator::default()),
+            Self::Flat => Box::new(FlatGenerator::from_layers(settings)?),
+            Self::Void => Box::new(VoidGenerator::new()),
+        })
+    }
+}
+
+/// Returns the seed given as `seed` in the configuration, like vanilla: numbers are used as
+/// they are, and other text is hashed.
+pub fn parse_seed(seed: &str) -> i64 {
+    let seed = seed.trim();
+    seed.parse()
+        .unwrap_or_else(|_| java_string_hash(seed) as i64)
+}
+
+/// Hashes a seed to send it to clients, which blend biomes with it, like vanilla: the first 8
+/// bytes of the SHA-256 hash of the seed. Both are little-endian, as vanilla hashes it with
+/// Guava.
+pub fn hash_seed(seed: i64) -> i64 {
+    let hash = Sha256::digest(seed.to_le_bytes());
+    i64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+/// Java's `String.hashCode`, which vanilla hashes text seeds with.
+fn java_string_hash(text: &str) -> i32 {
+    text.encode_utf16().fold(0i32, |hash, char| {
+        hash.wrapping_mul(31).wrapping_add(char as i32)
+    })
+}
+
+/// Returns the ID of the biome named `name` in the registry.
+pub fn biome_id(name: &str) -> Option<u32> {
+    Registries::get()
+        .registry("minecraft:worldgen/biome")
+        .and_then(|registry| registry.id_of(name))
+        .map(|id| id as u32)
+}
+
+#[derive(Error, Debug)]
+pub enum GeneratorError {
+    #[error("invalid layer {0:?}, expected a block optionally preceded by a count and `*`")]
+    InvalidLayer(String),
+    #[error("unknown block {0}")]
+    UnknownBlock(String),
+    #[error("{0} layers are more than the 4064 allowed")]
+    TooManyLayers(usize),
+    #[error("unknown biome {0}")]
+    UnknownBiome(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_void() {
+        let chunk = VoidGenerator::new().generate(ChunkPos::new(1, 2), -64, 384);
+        assert_eq!(chunk.sections().len(), 24);
+        assert!(chunk.sections().iter().all(|section| section.is_empty()));
+
+        let void = biome_id("minecraft:the_void").unwrap();
+        assert_eq!(chunk.sections()[0].biome(0, 0, 0), void);
+    }
+}
@@ -0,0 +1,449 @@
+use protocol::BlockPosition;
+
+use super::{biome_id, ChunkGenerator};
+use crate::world::{BlockState, Chunk, ChunkPos, DEFAULT_BIOME, SECTION_SIZE};
+
+/// Water fills the space below this Y coordinate, same as vanilla.
+pub const SEA_LEVEL: i32 = 63;
+
+/// Size of the cells the density is interpolated over, in blocks, same as vanilla.
+const CELL_WIDTH: i32 = 4;
+const CELL_HEIGHT: i32 = 8;
+const CELLS_PER_CHUNK: usize = (SECTION_SIZE / CELL_WIDTH) as usize;
+
+/// Biomes are stored in cells of 4x4x4 blocks.
+const BIOME_CELL_SIZE: i32 = 4;
+const BIOME_CELLS_PER_CHUNK: usize = (SECTION_SIZE / BIOME_CELL_SIZE) as usize;
+
+/// Layers above the bottom of the world that may be bedrock.
+const BEDROCK_LAYERS: i32 = 5;
+
+/// Temperature below which biomes are snowy, and above which they are hot.
+const COLD: f64 = -0.35;
+const HOT: f64 = 0.35;
+
+/// Generates overworld-like terrain from Perlin noise: continents and oceans, hills, a few
+/// overhangs, and biomes by temperature and humidity.
+///
+/// There are no caves, features or structures yet.
+#[derive(Debug)]
+pub struct NoiseGenerator {
+    seed: i64,
+    /// Large scale elevation, telling oceans from land.
+    continentalness: OctaveNoise,
+    hills: OctaveNoise,
+    /// 3D noise added to the density, making cliffs and overhangs.
+    detail: OctaveNoise,
+    temperature: OctaveNoise,
+    humidity: OctaveNoise,
+    /// Depth of the dirt and sand below the surface.
+    surface_depth: OctaveNoise,
+    biomes: Biomes,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: i64) -> Self {
+        let mut random = Random::new(seed as u64);
+        Self {
+            seed,
+            continentalness: OctaveNoise::new(&mut random, 4),
+            hills: OctaveNoise::new(&mut random, 4),
+            detail: OctaveNoise::new(&mut random, 3),
+            temperature: OctaveNoise::new(&mut random, 3),
+            humidity: OctaveNoise::new(&mut random, 3),
+            surface_depth: OctaveNoise::new(&mut random, 2),
+            biomes: Biomes::new(),
+        }
+    }
+
+    pub fn seed(&self) -> i64 {
+        self.seed
+    }
+
+    /// Height the terrain tends to at `x`, `z`, before the 3D detail is added.
+    fn base_height(&self, x: f64, z: f64) -> f64 {
+        let continentalness = self.continentalness.sample(x / 512.0, 0.0, z / 512.0);
+        // Hills get higher further inland.
+        let roughness = 6.0 + 32.0 * continentalness.max(0.0);
+        let hills = self.hills.sample(x / 128.0, 0.0, z / 128.0);
+        SEA_LEVEL as f64 + 2.0 + continentalness * 64.0 + hills * roughness
+    }
+
+    /// Density at `x`, `y`, `z`: blocks are solid where it is positive.
+    fn density(&self, base_height: f64, x: f64, y: f64, z: f64) -> f64 {
+        base_height - y + self.detail.sample(x / 48.0, y / 24.0, z / 48.0) * 10.0
+    }
+
+    /// Computes the density at the corners of the cells of the chunk at `pos`, indexed by
+    /// `[x][z][y]` corner.
+    fn cell_densities(&self, pos: ChunkPos, min_y: i32, height: u32) -> Vec<Vec<Vec<f64>>> {
+        let corners_y = height as i32 / CELL_HEIGHT + 1;
+        (0..=CELLS_PER_CHUNK as i32)
+            .map(|cell_x| {
+                let x = (pos.x * SECTION_SIZE + cell_x * CELL_WIDTH) as f64;
+                (0..=CELLS_PER_CHUNK as i32)
+                    .map(|cell_z| {
+                        let z = (pos.z * SECTION_SIZE + cell_z * CELL_WIDTH) as f64;
+                        let base_height = self.base_height(x, z);
+                        (0..corners_y)
+                            .map(|cell_y| {
+                                let y = (min_y + cell_y * CELL_HEIGHT) as f64;
+                                self.density(base_height, x, y, z)
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the biome at the cell containing `x`, `z`.
+    fn biome(&self, x: i32, z: i32) -> u32 {
+        let (x, z) = (x as f64, z as f64);
+        let height = self.base_height(x, z);
+        let temperature = self.temperature.sample(x / 1024.0, 0.0, z / 1024.0);
+        let humidity = self.humidity.sample(x / 1024.0, 0.0, z / 1024.0);
+        let biomes = &self.biomes;
+
+        if height < (SEA_LEVEL - 18) as f64 {
+            if temperature < COLD {
+                biomes.frozen_ocean
+            } else {
+                biomes.deep_ocean
+            }
+        } else if height < (SEA_LEVEL - 1) as f64 {
+            match temperature {
+                t if t < COLD => biomes.frozen_ocean,
+                t if t > HOT => biomes.warm_ocean,
+                _ => biomes.ocean,
+            }
+        } else if height < (SEA_LEVEL + 3) as f64 {
+            if temperature < COLD {
+                biomes.snowy_beach
+            } else {
+                biomes.beach
+            }
+        } else {
+            match temperature {
+                t if t < COLD => biomes.snowy_plains,
+                t if t < -0.1 && humidity > 0.0 => biomes.taiga,
+                t if t < HOT && humidity > 0.0 => biomes.forest,
+                t if t < HOT => biomes.plains,
+                _ if humidity < 0.0 => biomes.desert,
+                _ => biomes.savanna,
+            }
+        }
+    }
+
+    /// Returns the blocks at the top and right below the surface of `biome`.
+    fn surface_blocks(&self, biome: u32) -> (BlockState, BlockState) {
+        let biomes = &self.biomes;
+        let sandy = [
+            biomes.desert,
+            biomes.beach,
+            biomes.snowy_beach,
+            biomes.warm_ocean,
+        ];
+        let gravelly = [biomes.ocean, biomes.deep_ocean, biomes.frozen_ocean];
+        if sandy.contains(&biome) {
+            (BlockState::SAND, BlockState::SAND)
+        } else if gravelly.contains(&biome) {
+            (BlockState::GRAVEL, BlockState::GRAVEL)
+        } else {
+            (BlockState::GRASS_BLOCK, BlockState::DIRT)
+        }
+    }
+
+    /// Returns how many blocks below the surface at `x`, `z` are dirt or sand.
+    fn surface_depth(&self, x: i32, z: i32) -> i32 {
+        let noise = self
+            .surface_depth
+            .sample(x as f64 / 16.0, 0.0, z as f64 / 16.0);
+        3 + (noise * 2.0).round() as i32
+    }
+
+    /// Whether the block at `x`, `y`, `z`, `y` layers above the bottom of the world, is
+    /// bedrock, which gets rarer upwards.
+    fn is_bedrock(&self, x: i32, y: i32, z: i32) -> bool {
+        y == 0 || (y < BEDROCK_LAYERS && position_hash(self.seed, x, y, z) % 5 >= y as u64)
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, pos: ChunkPos, min_y: i32, height: u32) -> Chunk {
+        let mut chunk = Chunk::new(pos, min_y, height, self.biomes.plains);
+        let (base_x, base_z) = (pos.x * SECTION_SIZE, pos.z * SECTION_SIZE);
+
+        let mut biomes = [[0; BIOME_CELLS_PER_CHUNK]; BIOME_CELLS_PER_CHUNK];
+        for (cell_x, column) in biomes.iter_mut().enumerate() {
+            for (cell_z, biome) in column.iter_mut().enumerate() {
+                let x = cell_x as i32 * BIOME_CELL_SIZE;
+                let z = cell_z as i32 * BIOME_CELL_SIZE;
+                *biome = self.biome(base_x + x + 2, base_z + z + 2);
+                for y in (min_y..min_y + height as i32).step_by(BIOME_CELL_SIZE as usize) {
+                    chunk.set_biome(BlockPosition::new(x, y, z), *biome);
+                }
+            }
+        }
+
+        let densities = self.cell_densities(pos, min_y, height);
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                let (world_x, world_z) = (base_x + x, base_z + z);
+                let biome = biomes[(x / BIOME_CELL_SIZE) as usize][(z / BIOME_CELL_SIZE) as usize];
+                let (top, under) = self.surface_blocks(biome);
+                let surface_depth = self.surface_depth(world_x, world_z);
+
+                // Blocks below the last surface met going down, if in solid ground.
+                let mut depth = None;
+                for relative_y in (0..height as i32).rev() {
+                    let y = min_y + relative_y;
+                    let position = BlockPosition::new(x, y, z);
+                    if self.is_bedrock(world_x, relative_y, world_z) {
+                        chunk.set_block(position, BlockState::BEDROCK);
+                        continue;
+                    }
+                    if interpolate(&densities, x, relative_y, z) <= 0.0 {
+                        depth = None;
+                        if y < SEA_LEVEL {
+                            chunk.set_block(position, BlockState::WATER);
+                        }
+                        continue;
+                    }
+
+                    let block_depth = depth.map_or(0, |depth| depth + 1);
+                    depth = Some(block_depth);
+                    let block = match block_depth {
+                        // Grass doesn't grow under water.
+                        0 if top == BlockState::GRASS_BLOCK && y < SEA_LEVEL - 1 => under,
+                        0 => top,
+                        depth if depth < surface_depth => under,
+                        _ => BlockState::STONE,
+                    };
+                    chunk.set_block(position, block);
+                }
+            }
+        }
+        chunk
+    }
+}
+
+/// Interpolates the density at the block `x`, `y`, `z` of a chunk from its cell corners.
+fn interpolate(densities: &[Vec<Vec<f64>>], x: i32, y: i32, z: i32) -> f64 {
+    let (cell_x, cell_y, cell_z) = (x / CELL_WIDTH, y / CELL_HEIGHT, z / CELL_WIDTH);
+    let tx = (x % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+    let ty = (y % CELL_HEIGHT) as f64 / CELL_HEIGHT as f64;
+    let tz = (z % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        densities[(cell_x + dx) as usize][(cell_z + dz) as usize][(cell_y + dy) as usize]
+    };
+
+    let lerp_z = |dx, dy| lerp(tz, corner(dx, dy, 0), corner(dx, dy, 1));
+    let lerp_y = |dx| lerp(ty, lerp_z(dx, 0), lerp_z(dx, 1));
+    lerp(tx, lerp_y(0), lerp_y(1))
+}
+
+fn lerp(t: f64, from: f64, to: f64) -> f64 {
+    from + t * (to - from)
+}
+
+/// Hashes a block position, for random but reproducible choices.
+fn position_hash(seed: i64, x: i32, y: i32, z: i32) -> u64 {
+    let mut random = Random::new(
+        (seed as u64)
+            ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9),
+    );
+    random.next_u64()
+}
+
+/// The IDs of the biomes that are generated.
+#[derive(Debug)]
+struct Biomes {
+    ocean: u32,
+    deep_ocean: u32,
+    frozen_ocean: u32,
+    warm_ocean: u32,
+    beach: u32,
+    snowy_beach: u32,
+    plains: u32,
+    forest: u32,
+    taiga: u32,
+    snowy_plains: u32,
+    desert: u32,
+    savanna: u32,
+}
+
+impl Biomes {
+    fn new() -> Self {
+        let plains = biome_id(DEFAULT_BIOME).unwrap_or_default();
+        let id = |name| biome_id(name).unwrap_or(plains);
+        Self {
+            ocean: id("minecraft:ocean"),
+            deep_ocean: id("minecraft:deep_ocean"),
+            frozen_ocean: id("minecraft:frozen_ocean"),
+            warm_ocean: id("minecraft:warm_ocean"),
+            beach: id("minecraft:beach"),
+            snowy_beach: id("minecraft:snowy_beach"),
+            plains,
+            forest: id("minecraft:forest"),
+            taiga: id("minecraft:taiga"),
+            snowy_plains: id("minecraft:snowy_plains"),
+            desert: id("minecraft:desert"),
+            savanna: id("minecraft:savanna"),
+        }
+    }
+}
+
+/// A SplitMix64 generator, small and good enough to seed the noise with.
+#[derive(Debug)]
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Octaves of Perlin noise, each of twice the frequency and half the amplitude of the previous.
+#[derive(Debug)]
+struct OctaveNoise {
+    octaves: Vec<PerlinNoise>,
+}
+
+impl OctaveNoise {
+    fn new(random: &mut Random, octaves: usize) -> Self {
+        Self {
+            octaves: (0..octaves).map(|_| PerlinNoise::new(random)).collect(),
+        }
+    }
+
+    /// Samples the noise at `x`, `y`, `z`, giving a value in `-1.0..=1.0`.
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut value = 0.0;
+        let mut total_amplitude = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for octave in &self.octaves {
+            value += octave.sample(x * frequency, y * frequency, z * frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= 2.0;
+            amplitude /= 2.0;
+        }
+        value / total_amplitude
+    }
+}
+
+/// Ken Perlin's improved noise, with a shuffled permutation and a random offset.
+#[derive(Debug)]
+struct PerlinNoise {
+    permutation: [u8; 256],
+    offset: [f64; 3],
+}
+
+impl PerlinNoise {
+    fn new(random: &mut Random) -> Self {
+        let offset = [(); 3].map(|()| random.next_f64() * 256.0);
+        let mut permutation = [0; 256];
+        for (index, value) in permutation.iter_mut().enumerate() {
+            *value = index as u8;
+        }
+        for index in (1..permutation.len()).rev() {
+            let other = (random.next_u64() % (index as u64 + 1)) as usize;
+            permutation.swap(index, other);
+        }
+        Self {
+            permutation,
+            offset,
+        }
+    }
+
+    fn hash(&self, value: i32) -> i32 {
+        self.permutation[(value & 0xFF) as usize] as i32
+    }
+
+    /// Samples the noise at `x`, `y`, `z`, giving a value in `-1.0..=1.0`.
+    fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x, y, z) = (x + self.offset[0], y + self.offset[1], z + self.offset[2]);
+        let (floor_x, floor_y, floor_z) = (x.floor(), y.floor(), z.floor());
+        let (cell_x, cell_y, cell_z) = (floor_x as i32, floor_y as i32, floor_z as i32);
+        let (x, y, z) = (x - floor_x, y - floor_y, z - floor_z);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let hash = self.hash(self.hash(self.hash(cell_x + dx) + cell_y + dy) + cell_z + dz);
+            gradient(hash, x - dx as f64, y - dy as f64, z - dz as f64)
+        };
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let lerp_x = |dy, dz| lerp(u, corner(0, dy, dz), corner(1, dy, dz));
+        let lerp_y = |dz| lerp(v, lerp_x(0, dz), lerp_x(1, dz));
+        lerp(w, lerp_y(0), lerp_y(1))
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Dot product of `x`, `y`, `z` with one of the 12 gradients of improved noise, by `hash`.
+fn gradient(hash: i32, x: f64, y: f64, z: f64) -> f64 {
+    let hash = hash & 15;
+    let u = if hash < 8 { x } else { y };
+    let v = match hash {
+        0..=3 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    (if hash & 1 == 0 { u } else { -u }) + (if hash & 2 == 0 { v } else { -v })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::HeightmapKind;
+
+    const MIN_Y: i32 = -64;
+    const HEIGHT: u32 = 384;
+
+    #[test]
+    fn same_seed_same_chunks() {
+        let pos = ChunkPos::new(3, -7);
+        let chunk = NoiseGenerator::new(1234).generate(pos, MIN_Y, HEIGHT);
+        assert_eq!(
+            NoiseGenerator::new(1234).generate(pos, MIN_Y, HEIGHT),
+            chunk
+        );
+        assert_ne!(
+            NoiseGenerator::new(4321).generate(pos, MIN_Y, HEIGHT),
+            chunk
+        );
+    }
+
+    #[test]
+    fn surface_heights() {
+        let generator = NoiseGenerator::new(42);
+        let heights: Vec<Option<i32>> = [(0, 0), (5, -2), (-40, 17), (100, 100)]
+            .into_iter()
+            .map(|(chunk_x, chunk_z)| {
+                let chunk = generator.generate(ChunkPos::new(chunk_x, chunk_z), MIN_Y, HEIGHT);
+                chunk.top_y(HeightmapKind::WorldSurface, 7, 9)
+            })
+            .collect();
+        // Changing these changes the terrain of new chunks of existing worlds, which then don't
+        // fit with the chunks generated before.
+        assert_eq!(heights, [Some(79), Some(74), Some(79), Some(89)]);
+    }
+}
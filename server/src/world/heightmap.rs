@@ -0,0 +1,129 @@
+//! Heightmaps: the highest block of each column of a chunk that matters for some purpose.
+
+use super::{block::BlockState, chunk::SECTION_SIZE};
+
+/// Number of columns in a chunk.
+const COLUMNS: usize = (SECTION_SIZE * SECTION_SIZE) as usize;
+
+/// The heightmaps chunks keep, named like vanilla's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HeightmapKind {
+    /// The highest block that isn't air.
+    WorldSurface,
+    /// The highest block that blocks movement or contains a fluid.
+    MotionBlocking,
+}
+
+impl HeightmapKind {
+    pub const ALL: [Self; 2] = [Self::WorldSurface, Self::MotionBlocking];
+
+    /// Name of the heightmap in chunk NBT.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::WorldSurface => "WORLD_SURFACE",
+            Self::MotionBlocking => "MOTION_BLOCKING",
+        }
+    }
+
+    /// Whether `block` can be the top of a column in this heightmap.
+    pub fn is_top(self, block: BlockState) -> bool {
+        match self {
+            Self::WorldSurface => !block.is_air(),
+            Self::MotionBlocking => block.is_solid() || block.is_fluid(),
+        }
+    }
+}
+
+/// The highest block of each column of a chunk matching a [`HeightmapKind`].
+///
+/// Heights are relative to the bottom of the world, and one above the highest block, so that
+/// columns without any block have a height of 0, like vanilla.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    kind: HeightmapKind,
+    /// Heights by `z * 16 + x`.
+    heights: [u16; COLUMNS],
+}
+
+impl Heightmap {
+    /// Makes a heightmap of columns without any block.
+    pub fn new(kind: HeightmapKind) -> Self {
+        Self {
+            kind,
+            heights: [0; COLUMNS],
+        }
+    }
+
+    pub fn kind(&self) -> HeightmapKind {
+        self.kind
+    }
+
+    /// Returns the height of the column at `x` and `z` (0-15).
+    pub fn get(&self, x: usize, z: usize) -> u16 {
+        self.heights[column_index(x, z)]
+    }
+
+    /// Sets the height of the column at `x` and `z` (0-15).
+    pub fn set(&mut self, x: usize, z: usize, height: u16) {
+        self.heights[column_index(x, z)] = height;
+    }
+
+    /// Encodes the heights as stored in chunk NBT and sent to clients, for a world `height`
+    /// blocks high: packed in longs with as few bits as possible, without entries spanning two
+    /// longs.
+    pub fn to_longs(&self, height: u32) -> Vec<i64> {
+        // Worlds without any height still need a bit for their heights of 0.
+        let bits = (u32::BITS - height.leading_zeros()).max(1);
+        let per_long = (u64::BITS / bits) as usize;
+        self.heights
+            .chunks(per_long)
+            .map(|heights| {
+                let long = heights
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |long, (index, &height)| {
+                        long | ((height as u64) << (index as u32 * bits))
+                    });
+                long as i64
+            })
+            .collect()
+    }
+}
+
+fn column_index(x: usize, z: usize) -> usize {
+    (z << 4) | x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_heights() {
+        let mut heightmap = Heightmap::new(HeightmapKind::WorldSurface);
+        heightmap.set(0, 0, 384);
+        heightmap.set(1, 0, 1);
+        heightmap.set(6, 0, 0x155);
+        heightmap.set(7, 0, 5);
+        heightmap.set(15, 15, 300);
+
+        // Heights up to 384 take 9 bits, 7 to a long, the last 1 bit left unused.
+        let longs = heightmap.to_longs(384);
+        assert_eq!(longs.len(), COLUMNS.div_ceil(7));
+        assert_eq!(longs[0], 384 | (1 << 9) | (0x155 << 54));
+        assert_eq!(longs[1], 5);
+        // The last long only holds the last 4 columns.
+        assert_eq!(longs[36], 300 << 27);
+        assert!(longs[2..36].iter().all(|&long| long == 0));
+
+        // Heights up to 256 still take 9 bits, for one above the highest block.
+        assert_eq!(heightmap.to_longs(256).len(), 37);
+        assert_eq!(heightmap.to_longs(128).len(), 32);
+    }
+
+    #[test]
+    fn packs_empty_worlds() {
+        let heightmap = Heightmap::new(HeightmapKind::MotionBlocking);
+        assert_eq!(heightmap.to_longs(0), vec![0; 4]);
+    }
+}
@@ -0,0 +1,568 @@
+//! Sky light and block light, spread like vanilla.
+//!
+//! Light spreads from the sky and from blocks emitting it, losing a level for every block it
+//! goes through, or more for blocks filtering it. Sky light keeps its full level going straight
+//! down through transparent blocks. Chunks are lit when they are loaded, and light is updated
+//! around blocks that change, across the borders of the loaded chunks.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use protocol::BlockPosition;
+
+use super::chunk::{Chunk, ChunkPos, SECTION_SIZE};
+
+/// Highest light level.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Size of a section's light array, with 4 bits per block.
+pub const LIGHT_ARRAY_LEN: usize = 2048;
+
+/// The directions light spreads in.
+const DIRECTIONS: [(i32, i32, i32); 6] = [
+    (0, -1, 0),
+    (0, 1, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+    (-1, 0, 0),
+    (1, 0, 0),
+];
+
+/// The two kinds of light, stored separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+/// Light levels of a 16x16x16 section, 4 bits per block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LightSection {
+    /// Every block has the same level, so that dark and fully lit sections take no space.
+    Uniform(u8),
+    Levels(Box<[u8; LIGHT_ARRAY_LEN]>),
+}
+
+impl LightSection {
+    /// Returns the level at `x`, `y` and `z` (0-15) in this section.
+    pub fn get(&self, x: usize, y: usize, z: usize) -> u8 {
+        match self {
+            Self::Uniform(level) => *level,
+            Self::Levels(levels) => {
+                let index = light_index(x, y, z);
+                (levels[index >> 1] >> ((index & 1) << 2)) & 0xF
+            }
+        }
+    }
+
+    /// Sets the level at `x`, `y` and `z` (0-15) in this section.
+    pub fn set(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        if let Self::Uniform(uniform) = *self {
+            if uniform == level {
+                return;
+            }
+            *self = Self::Levels(Box::new([uniform | (uniform << 4); LIGHT_ARRAY_LEN]));
+        }
+        let Self::Levels(levels) = self else {
+            unreachable!("the section should have levels");
+        };
+        let index = light_index(x, y, z);
+        let shift = (index & 1) << 2;
+        levels[index >> 1] = (levels[index >> 1] & !(0xF << shift)) | ((level & 0xF) << shift);
+    }
+
+    /// Whether every block of this section is dark.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Self::Uniform(level) => *level == 0,
+            Self::Levels(levels) => levels.iter().all(|&byte| byte == 0),
+        }
+    }
+
+    /// The levels as sent to clients and stored in chunk NBT, two per byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Uniform(level) => vec![level | (level << 4); LIGHT_ARRAY_LEN],
+            Self::Levels(levels) => levels.to_vec(),
+        }
+    }
+}
+
+fn light_index(x: usize, y: usize, z: usize) -> usize {
+    (y << 8) | (z << 4) | x
+}
+
+/// Computes the light of the loaded chunks of a world.
+#[derive(Debug, Clone, Copy)]
+pub struct LightEngine {
+    has_sky_light: bool,
+}
+
+impl LightEngine {
+    /// Makes an engine for a world lit by the sky if `has_sky_light`, like the overworld.
+    pub fn new(has_sky_light: bool) -> Self {
+        Self { has_sky_light }
+    }
+
+    pub fn has_sky_light(&self) -> bool {
+        self.has_sky_light
+    }
+
+    /// Lights `chunk` on its own, as if no chunks were loaded around it.
+    ///
+    /// This is most of the work of lighting a chunk, and doesn't need the loaded chunks, so it is
+    /// done before adding it to them. Light is then spread across its borders by
+    /// [`spread_borders`](Self::spread_borders).
+    pub fn light_chunk(&self, chunk: Chunk) -> Chunk {
+        let pos = chunk.pos();
+        let mut chunks = HashMap::from([(pos, chunk)]);
+        for kind in self.kinds() {
+            let mut propagation = Propagation::new(&mut chunks, kind);
+            match kind {
+                LightKind::Sky => propagation.light_sky(pos),
+                LightKind::Block => propagation.light_sources(pos),
+            }
+            propagation.increase();
+        }
+        chunks
+            .remove(&pos)
+            .expect("the chunk should still be there")
+    }
+
+    /// Spreads light between the chunk at `pos`, lit by [`light_chunk`](Self::light_chunk) and
+    /// then added to `chunks`, and the loaded chunks around it. Returns the chunks whose light
+    /// changed.
+    pub fn spread_borders(
+        &self,
+        chunks: &mut HashMap<ChunkPos, Chunk>,
+        pos: ChunkPos,
+    ) -> HashSet<ChunkPos> {
+        let mut changed = HashSet::new();
+        for kind in self.kinds() {
+            let mut propagation = Propagation::new(chunks, kind);
+            propagation.seed_borders(pos);
+            propagation.increase();
+            changed.extend(propagation.changed);
+        }
+        changed
+    }
+
+    /// Updates the light around `position`, whose block changed. Returns the chunks whose light
+    /// changed.
+    pub fn update_block(
+        &self,
+        chunks: &mut HashMap<ChunkPos, Chunk>,
+        position: BlockPosition,
+    ) -> HashSet<ChunkPos> {
+        let mut changed = HashSet::new();
+        for kind in self.kinds() {
+            let mut propagation = Propagation::new(chunks, kind);
+            propagation.relight(position);
+            changed.extend(propagation.changed);
+        }
+        changed
+    }
+
+    fn kinds(&self) -> impl Iterator<Item = LightKind> {
+        let sky = self.has_sky_light.then_some(LightKind::Sky);
+        sky.into_iter().chain([LightKind::Block])
+    }
+}
+
+/// Spreading of one kind of light through the loaded chunks, by breadth-first search.
+struct Propagation<'a> {
+    chunks: &'a mut HashMap<ChunkPos, Chunk>,
+    kind: LightKind,
+    /// Blocks whose light has to spread to their neighbors, with their level.
+    increase: VecDeque<(BlockPosition, u8)>,
+    /// Blocks that were darkened, with their previous level, whose neighbors may have been lit
+    /// by them.
+    decrease: VecDeque<(BlockPosition, u8)>,
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'a> Propagation<'a> {
+    fn new(chunks: &'a mut HashMap<ChunkPos, Chunk>, kind: LightKind) -> Self {
+        Self {
+            chunks,
+            kind,
+            increase: VecDeque::new(),
+            decrease: VecDeque::new(),
+            changed: HashSet::new(),
+        }
+    }
+
+    /// Returns the level at `position`, or `None` if its chunk isn't loaded or it is outside
+    /// the light sections.
+    fn get(&self, position: BlockPosition) -> Option<u8> {
+        self.chunks
+            .get(&ChunkPos::from_block(position))?
+            .light(self.kind, position)
+    }
+
+    fn set(&mut self, position: BlockPosition, level: u8) {
+        let pos = ChunkPos::from_block(position);
+        if let Some(chunk) = self.chunks.get_mut(&pos) {
+            if chunk
+                .set_light(self.kind, position, level)
+                .is_some_and(|previous| previous != level)
+            {
+                self.changed.insert(pos);
+            }
+        }
+    }
+
+    /// Returns the opacity of the block at `position`, which is loaded.
+    fn opacity(&self, position: BlockPosition) -> u8 {
+        self.chunks[&ChunkPos::from_block(position)]
+            .block(position)
+            .light_opacity()
+    }
+
+    /// Returns the level of block light emitted at `position`, which is loaded.
+    fn emission(&self, position: BlockPosition) -> u8 {
+        match self.kind {
+            LightKind::Sky => 0,
+            LightKind::Block => self.chunks[&ChunkPos::from_block(position)]
+                .block(position)
+                .light_emission(),
+        }
+    }
+
+    /// Lights the chunk at `pos` by the sky from above, queuing the blocks that are lit to
+    /// spread their light sideways and into blocks filtering it.
+    fn light_sky(&mut self, pos: ChunkPos) {
+        let chunk = self
+            .chunks
+            .get_mut(&pos)
+            .expect("the chunk should be loaded");
+        let (bottom, top) = chunk.light_range();
+        chunk.fill_light(LightKind::Sky, 0);
+        let section_count = chunk.light_sections(LightKind::Sky).len();
+        // The section above the world is open to the sky.
+        chunk.light_sections_mut(LightKind::Sky)[section_count - 1] =
+            LightSection::Uniform(MAX_LIGHT);
+        let world_top = top - SECTION_SIZE;
+
+        // Lowest block of each column lit straight from the sky.
+        let mut lowest = [[world_top; SECTION_SIZE as usize]; SECTION_SIZE as usize];
+        let (base_x, base_z) = (pos.x * SECTION_SIZE, pos.z * SECTION_SIZE);
+        for (x, column) in lowest.iter_mut().enumerate() {
+            for (z, column_lowest) in column.iter_mut().enumerate() {
+                for y in (bottom..world_top).rev() {
+                    let position = BlockPosition::new(base_x + x as i32, y, base_z + z as i32);
+                    if chunk.block(position).light_opacity() != 0 {
+                        break;
+                    }
+                    chunk.set_light(LightKind::Sky, position, MAX_LIGHT);
+                    *column_lowest = y;
+                }
+            }
+        }
+
+        // Light only spreads sideways into columns that are lit less far down, or down into
+        // blocks filtering it. Light spreads into the chunks around from the borders instead,
+        // see `seed_borders`.
+        let in_chunk =
+            |x: i32, z: i32| (0..SECTION_SIZE).contains(&x) && (0..SECTION_SIZE).contains(&z);
+        for x in 0..SECTION_SIZE {
+            for z in 0..SECTION_SIZE {
+                let here = lowest[x as usize][z as usize];
+                let neighbors_lowest = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .into_iter()
+                    .map(|(dx, dz)| (x + dx, z + dz))
+                    .filter(|&(x, z)| in_chunk(x, z))
+                    .map(|(x, z)| lowest[x as usize][z as usize])
+                    .max()
+                    .unwrap_or(here);
+                for y in here..neighbors_lowest.max(here + 1) {
+                    let position = BlockPosition::new(base_x + x, y, base_z + z);
+                    self.increase.push_back((position, MAX_LIGHT));
+                }
+            }
+        }
+    }
+
+    /// Lights the blocks emitting light in the chunk at `pos`, queuing them to spread it.
+    fn light_sources(&mut self, pos: ChunkPos) {
+        let chunk = self
+            .chunks
+            .get_mut(&pos)
+            .expect("the chunk should be loaded");
+        chunk.fill_light(LightKind::Block, 0);
+        let min_y = chunk.min_y();
+        let (base_x, base_z) = (pos.x * SECTION_SIZE, pos.z * SECTION_SIZE);
+
+        let mut sources = Vec::new();
+        for (index, section) in chunk.sections().iter().enumerate() {
+            if section.is_empty() {
+                continue;
+            }
+            let section_y = min_y + index as i32 * SECTION_SIZE;
+            for y in 0..SECTION_SIZE as usize {
+                for z in 0..SECTION_SIZE as usize {
+                    for x in 0..SECTION_SIZE as usize {
+                        let emission = section.block(x, y, z).light_emission();
+                        if emission > 0 {
+                            let position = BlockPosition::new(
+                                base_x + x as i32,
+                                section_y + y as i32,
+                                base_z + z as i32,
+                            );
+                            sources.push((position, emission));
+                        }
+                    }
+                }
+            }
+        }
+        for (position, emission) in sources {
+            chunk.set_light(LightKind::Block, position, emission);
+            self.increase.push_back((position, emission));
+        }
+    }
+
+    /// Queues the lit blocks on both sides of the borders between the chunk at `pos` and the
+    /// loaded chunks next to it, so that light spreads across them.
+    fn seed_borders(&mut self, pos: ChunkPos) {
+        let (bottom, top) = self.chunks[&pos].light_range();
+        let (base_x, base_z) = (pos.x * SECTION_SIZE, pos.z * SECTION_SIZE);
+        // Pairs of blocks on both sides of each border, by their offset along it.
+        type Offset = fn(i32) -> (i32, i32);
+        let borders: [(ChunkPos, Offset, Offset); 4] = [
+            (ChunkPos::new(pos.x - 1, pos.z), |i| (0, i), |i| (-1, i)),
+            (ChunkPos::new(pos.x + 1, pos.z), |i| (15, i), |i| (16, i)),
+            (ChunkPos::new(pos.x, pos.z - 1), |i| (i, 0), |i| (i, -1)),
+            (ChunkPos::new(pos.x, pos.z + 1), |i| (i, 15), |i| (i, 16)),
+        ];
+
+        for (neighbor, inside, outside) in borders {
+            if !self.chunks.contains_key(&neighbor) {
+                continue;
+            }
+            for i in 0..SECTION_SIZE {
+                for (x, z) in [inside(i), outside(i)] {
+                    for y in bottom..top {
+                        let position = BlockPosition::new(base_x + x, y, base_z + z);
+                        match self.get(position) {
+                            Some(level) if level > 0 => {
+                                self.increase.push_back((position, level));
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lights the blocks around the queued ones, as far as their light reaches.
+    fn increase(&mut self) {
+        while let Some((position, level)) = self.increase.pop_front() {
+            // The block may have been darkened or lit more since it was queued.
+            if self.get(position) != Some(level) {
+                continue;
+            }
+            for (dx, dy, dz) in DIRECTIONS {
+                let neighbor =
+                    BlockPosition::new(position.x + dx, position.y + dy, position.z + dz);
+                let Some(current) = self.get(neighbor) else {
+                    continue;
+                };
+                let opacity = self.opacity(neighbor);
+                let new = if self.is_straight_down(dy, level, opacity) {
+                    MAX_LIGHT
+                } else {
+                    level.saturating_sub(opacity.max(1))
+                };
+                if new > current {
+                    self.set(neighbor, new);
+                    self.increase.push_back((neighbor, new));
+                }
+            }
+        }
+    }
+
+    /// Darkens the blocks lit by the queued ones, queuing the blocks lit otherwise to light
+    /// them again.
+    fn decrease(&mut self) {
+        while let Some((position, level)) = self.decrease.pop_front() {
+            for (dx, dy, dz) in DIRECTIONS {
+                let neighbor =
+                    BlockPosition::new(position.x + dx, position.y + dy, position.z + dz);
+                let current = match self.get(neighbor) {
+                    Some(current) if current > 0 => current,
+                    _ => continue,
+                };
+                let lit_by_position = current < level
+                    || (current == MAX_LIGHT
+                        && self.is_straight_down(dy, level, self.opacity(neighbor)));
+                if !lit_by_position {
+                    self.increase.push_back((neighbor, current));
+                    continue;
+                }
+
+                self.set(neighbor, 0);
+                self.decrease.push_back((neighbor, current));
+                let emission = self.emission(neighbor);
+                if emission > 0 {
+                    self.set(neighbor, emission);
+                    self.increase.push_back((neighbor, emission));
+                }
+            }
+        }
+    }
+
+    /// Lights the block at `position` and around it again, after it changed.
+    fn relight(&mut self, position: BlockPosition) {
+        let Some(level) = self.get(position) else {
+            return;
+        };
+        self.set(position, 0);
+        self.decrease.push_back((position, level));
+        self.decrease();
+
+        let emission = self.emission(position);
+        if emission > 0 {
+            self.set(position, emission);
+            self.increase.push_back((position, emission));
+        }
+        // The block may let more light through than before.
+        for (dx, dy, dz) in DIRECTIONS {
+            let neighbor = BlockPosition::new(position.x + dx, position.y + dy, position.z + dz);
+            if let Some(level) = self.get(neighbor).filter(|&level| level > 0) {
+                self.increase.push_back((neighbor, level));
+            }
+        }
+        self.increase();
+    }
+
+    /// Whether sky light at `level` keeps its level going in the direction of `dy` into a block
+    /// with `opacity`.
+    fn is_straight_down(&self, dy: i32, level: u8, opacity: u8) -> bool {
+        self.kind == LightKind::Sky && dy < 0 && level == MAX_LIGHT && opacity == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockState;
+
+    const ORIGIN: ChunkPos = ChunkPos::new(0, 0);
+
+    /// Lights a chunk of air from Y 0 to 32 at `pos` on its own, after placing `blocks`.
+    fn lit_chunk(
+        engine: LightEngine,
+        pos: ChunkPos,
+        blocks: impl IntoIterator<Item = (BlockPosition, BlockState)>,
+    ) -> HashMap<ChunkPos, Chunk> {
+        let mut chunk = Chunk::new(pos, 0, 32, 0);
+        for (position, block) in blocks {
+            chunk.set_block(position, block);
+        }
+        HashMap::from([(pos, engine.light_chunk(chunk))])
+    }
+
+    fn set_block(
+        engine: LightEngine,
+        chunks: &mut HashMap<ChunkPos, Chunk>,
+        position: BlockPosition,
+        block: BlockState,
+    ) -> HashSet<ChunkPos> {
+        let pos = ChunkPos::from_block(position);
+        chunks.get_mut(&pos).unwrap().set_block(position, block);
+        engine.update_block(chunks, position)
+    }
+
+    fn light(
+        chunks: &HashMap<ChunkPos, Chunk>,
+        kind: LightKind,
+        x: i32,
+        y: i32,
+        z: i32,
+    ) -> Option<u8> {
+        let position = BlockPosition::new(x, y, z);
+        chunks[&ChunkPos::from_block(position)].light(kind, position)
+    }
+
+    #[test]
+    fn sky_light_goes_straight_down() {
+        // A stone roof with a hole at 8, 8.
+        let roof = (0..16)
+            .flat_map(|x| (0..16).map(move |z| (x, z)))
+            .filter(|&(x, z)| (x, z) != (8, 8))
+            .map(|(x, z)| (BlockPosition::new(x, 20, z), BlockState::STONE));
+        let chunks = lit_chunk(LightEngine::new(true), ORIGIN, roof);
+
+        assert_eq!(light(&chunks, LightKind::Sky, 0, 21, 0), Some(15));
+        assert_eq!(light(&chunks, LightKind::Sky, 0, 20, 0), Some(0));
+        for y in 0..20 {
+            assert_eq!(light(&chunks, LightKind::Sky, 8, y, 8), Some(15), "Y {y}");
+        }
+        // Then loses a level per block sideways.
+        assert_eq!(light(&chunks, LightKind::Sky, 9, 10, 8), Some(14));
+        assert_eq!(light(&chunks, LightKind::Sky, 11, 10, 7), Some(11));
+        assert_eq!(light(&chunks, LightKind::Sky, 0, 10, 0), Some(0));
+        // Below the world too.
+        assert_eq!(light(&chunks, LightKind::Sky, 8, -1, 8), Some(15));
+    }
+
+    #[test]
+    fn light_spreads_when_block_placed() {
+        let engine = LightEngine::new(false);
+        let mut chunks = lit_chunk(engine, ORIGIN, []);
+        let changed = set_block(
+            engine,
+            &mut chunks,
+            BlockPosition::new(8, 8, 8),
+            BlockState::LAVA,
+        );
+
+        assert_eq!(changed, HashSet::from([ORIGIN]));
+        assert_eq!(light(&chunks, LightKind::Block, 8, 8, 8), Some(15));
+        assert_eq!(light(&chunks, LightKind::Block, 9, 8, 8), Some(14));
+        assert_eq!(light(&chunks, LightKind::Block, 8, 12, 8), Some(11));
+        assert_eq!(light(&chunks, LightKind::Block, 15, 8, 15), Some(1));
+        assert_eq!(light(&chunks, LightKind::Block, 0, 8, 0), Some(0));
+    }
+
+    #[test]
+    fn light_removed_when_block_broken() {
+        let engine = LightEngine::new(true);
+        let lava = BlockPosition::new(8, 8, 8);
+        let mut chunks = lit_chunk(engine, ORIGIN, [(lava, BlockState::LAVA)]);
+        assert_eq!(light(&chunks, LightKind::Block, 9, 8, 8), Some(14));
+
+        set_block(engine, &mut chunks, lava, BlockState::AIR);
+        let (bottom, top) = chunks[&ORIGIN].light_range();
+        for y in bottom..top {
+            for x in 0..16 {
+                for z in 0..16 {
+                    assert_eq!(light(&chunks, LightKind::Block, x, y, z), Some(0));
+                }
+            }
+        }
+
+        // Blocking the sky darkens the column below, until the block is broken again.
+        let stone = BlockPosition::new(3, 25, 3);
+        set_block(engine, &mut chunks, stone, BlockState::STONE);
+        assert_eq!(light(&chunks, LightKind::Sky, 3, 24, 3), Some(14));
+        assert_eq!(light(&chunks, LightKind::Sky, 3, 10, 3), Some(14));
+        set_block(engine, &mut chunks, stone, BlockState::AIR);
+        assert_eq!(light(&chunks, LightKind::Sky, 3, 10, 3), Some(15));
+    }
+
+    #[test]
+    fn light_spreads_across_borders() {
+        let engine = LightEngine::new(false);
+        let lava = BlockPosition::new(15, 8, 8);
+        let mut chunks = lit_chunk(engine, ORIGIN, [(lava, BlockState::LAVA)]);
+        let east = ChunkPos::new(1, 0);
+        chunks.extend(lit_chunk(engine, east, []));
+        assert_eq!(light(&chunks, LightKind::Block, 16, 8, 8), Some(0));
+
+        let changed = engine.spread_borders(&mut chunks, east);
+        assert_eq!(changed, HashSet::from([east]));
+        assert_eq!(light(&chunks, LightKind::Block, 16, 8, 8), Some(14));
+        assert_eq!(light(&chunks, LightKind::Block, 20, 8, 8), Some(10));
+    }
+}
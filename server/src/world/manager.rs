@@ -0,0 +1,775 @@
+//! Loading, generation and unloading of chunks, driven by tickets.
+//!
+//! Chunks are kept loaded while they have a ticket, a reason to be loaded like a player seeing
+//! them. As in vanilla, tickets give the chunks around them a load level, lower being more
+//! important, and chunks are loaded while their level is at most [`MAX_LOAD_LEVEL`].
+//!
+//! Chunks are loaded from storage or generated by a pool of worker threads, so that neither the
+//! tick nor the connections wait for them, and lit once loaded. Chunks that lose all their
+//! tickets are unloaded, and saved by the workers if they changed.
+
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        mpsc, Arc, Mutex, OnceLock, RwLock, RwLockReadGuard,
+    },
+    thread,
+};
+
+use protocol::BlockPosition;
+use tokio::sync::watch;
+
+use super::{
+    block::BlockState,
+    chunk::{self, Chunk, ChunkPos},
+    generator::ChunkGenerator,
+    light::LightEngine,
+    storage::{ChunkStorage, ChunkStorageError},
+};
+
+/// Highest load level at which chunks are loaded, the level of vanilla's border chunks.
+pub const MAX_LOAD_LEVEL: u32 = 33;
+
+/// Radius of the chunks kept loaded around the spawn, same as the default of vanilla's
+/// `spawnChunkRadius` game rule.
+pub const SPAWN_CHUNK_RADIUS: u32 = 2;
+
+/// Why chunks are kept loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TicketKind {
+    /// A player seeing the chunks.
+    Player,
+    /// The chunks around the world spawn.
+    Spawn,
+    /// Chunks forced to stay loaded.
+    Forced,
+}
+
+/// A reason to keep the chunks within `radius` of `pos` loaded.
+///
+/// Identical tickets can be added several times, and are only gone once removed as many times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ticket {
+    pub kind: TicketKind,
+    pub pos: ChunkPos,
+    /// At most [`MAX_LOAD_LEVEL`].
+    pub radius: u32,
+}
+
+impl Ticket {
+    pub const fn new(kind: TicketKind, pos: ChunkPos, radius: u32) -> Self {
+        Self { kind, pos, radius }
+    }
+
+    /// Load level of the chunk at the ticket's position, rising by one for each chunk away.
+    pub fn level(&self) -> u32 {
+        MAX_LOAD_LEVEL.saturating_sub(self.radius)
+    }
+
+    /// Load level this ticket gives the chunk at `pos`, or `None` if it doesn't load it.
+    pub fn level_at(&self, pos: ChunkPos) -> Option<u32> {
+        let distance = (pos.x - self.pos.x)
+            .unsigned_abs()
+            .max((pos.z - self.pos.z).unsigned_abs());
+        let level = self.level().saturating_add(distance);
+        (level <= MAX_LOAD_LEVEL).then_some(level)
+    }
+
+    /// The chunks this ticket loads, from its position outwards.
+    fn chunks(&self) -> impl Iterator<Item = ChunkPos> {
+        chunk::spiral(self.pos, self.radius.min(MAX_LOAD_LEVEL) as i32)
+    }
+}
+
+/// How far a chunk is in being loaded, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    /// The chunk is waiting for a worker.
+    Empty,
+    /// The chunk's terrain is generated, but it isn't ready for players yet.
+    Generated,
+    /// The chunk is loaded, and can be sent to players.
+    Full,
+}
+
+/// A chunk with tickets, or one that is still being loaded after losing them.
+#[derive(Debug)]
+struct ChunkHolder {
+    /// Lowest load level the chunk's tickets give it, `None` once it has none.
+    level: Option<u32>,
+    status: watch::Sender<ChunkStatus>,
+    /// Whether the chunk was given to a worker, so that it isn't loaded twice.
+    loading: bool,
+}
+
+#[derive(Debug, Default)]
+struct TicketState {
+    /// Tickets, with how many times they were added.
+    tickets: HashMap<Ticket, usize>,
+    holders: HashMap<ChunkPos, ChunkHolder>,
+}
+
+/// Changes of the light of loaded chunks, for clients to be sent the new light.
+#[derive(Debug, Default)]
+struct LightChanges {
+    /// Incremented whenever light changes.
+    version: u64,
+    /// Version at which the light of chunks last changed, if it did since they were loaded.
+    chunks: HashMap<ChunkPos, u64>,
+}
+
+#[derive(Debug)]
+enum Job {
+    Load(ChunkPos),
+    /// Saves an unloaded chunk.
+    Save(ChunkPos),
+    /// Updates the light around a block that changed.
+    Relight(BlockPosition),
+}
+
+/// State shared with the workers.
+///
+/// Locks are always taken in the order of the fields, so that they can't deadlock.
+#[derive(Debug)]
+struct Shared {
+    min_y: i32,
+    height: u32,
+    generator: Box<dyn ChunkGenerator>,
+    light: LightEngine,
+    storage: Option<ChunkStorage>,
+    /// Game time of the world, stored in the chunks saved.
+    game_time: AtomicI64,
+    state: Mutex<TicketState>,
+    chunks: RwLock<HashMap<ChunkPos, Chunk>>,
+    light_changes: Mutex<LightChanges>,
+    /// Loaded chunks that changed since they were last saved.
+    dirty: Mutex<HashSet<ChunkPos>>,
+    /// Chunks that changed and were unloaded, until a worker saves them.
+    unloading: Mutex<HashMap<ChunkPos, Chunk>>,
+}
+
+/// Loads and unloads the chunks of a world according to their tickets.
+#[derive(Debug)]
+pub struct ChunkManager {
+    shared: Arc<Shared>,
+    /// Queue of the workers, started with the first job.
+    jobs: OnceLock<mpsc::Sender<Job>>,
+}
+
+impl ChunkManager {
+    /// Makes a manager of the chunks from `min_y` up to `min_y + height`, generating them with
+    /// `generator` and lighting them with `light`.
+    pub fn new(
+        min_y: i32,
+        height: u32,
+        generator: Box<dyn ChunkGenerator>,
+        light: LightEngine,
+    ) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                min_y,
+                height,
+                generator,
+                light,
+                storage: None,
+                game_time: AtomicI64::new(0),
+                state: Mutex::default(),
+                chunks: RwLock::default(),
+                light_changes: Mutex::default(),
+                dirty: Mutex::default(),
+                unloading: Mutex::default(),
+            }),
+            jobs: OnceLock::new(),
+        }
+    }
+
+    /// Loads chunks from `storage` when possible, and saves them to it.
+    ///
+    /// # Panics
+    /// Panics if chunks were already requested.
+    pub fn set_storage(&mut self, storage: ChunkStorage) {
+        Arc::get_mut(&mut self.shared)
+            .expect("the storage should be set before loading chunks")
+            .storage = Some(storage);
+    }
+
+    /// Sets the game time of the world, stored in the chunks saved from now on.
+    pub fn set_game_time(&self, game_time: i64) {
+        self.shared.game_time.store(game_time, Ordering::Relaxed);
+    }
+
+    /// Returns the loaded chunks.
+    pub fn chunks(&self) -> RwLockReadGuard<'_, HashMap<ChunkPos, Chunk>> {
+        self.shared.chunks.read().unwrap()
+    }
+
+    pub fn is_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks().contains_key(&pos)
+    }
+
+    /// Sets the block at `position`, like [`Chunk::set_block`], if its chunk is loaded. The
+    /// light around it is updated by the workers.
+    pub fn set_block(&self, position: BlockPosition, block: BlockState) -> Option<BlockState> {
+        let pos = ChunkPos::from_block(position);
+        let previous = {
+            let mut chunks = self.shared.chunks.write().unwrap();
+            chunks.get_mut(&pos)?.set_block(position, block)?
+        };
+        if previous != block {
+            self.shared.dirty.lock().unwrap().insert(pos);
+            self.schedule(Job::Relight(position));
+        }
+        Some(previous)
+    }
+
+    /// Returns the current light version, and the loaded chunks whose light changed since the
+    /// `since` version, to send their new light to the clients that have them.
+    pub fn light_changes(&self, since: u64) -> (u64, Vec<ChunkPos>) {
+        let changes = self.shared.light_changes.lock().unwrap();
+        if since >= changes.version {
+            return (changes.version, Vec::new());
+        }
+        let chunks = changes
+            .chunks
+            .iter()
+            .filter(|&(_, &version)| version > since)
+            .map(|(&pos, _)| pos)
+            .collect();
+        (changes.version, chunks)
+    }
+
+    /// Adds a ticket, loading the chunks it covers that aren't loaded yet.
+    ///
+    /// Changing tickets may wait for the workers, so async code does it on the tick thread.
+    pub fn add_ticket(&self, ticket: Ticket) {
+        let mut state = self.shared.state.lock().unwrap();
+        *state.tickets.entry(ticket).or_default() += 1;
+
+        for pos in ticket.chunks() {
+            let Some(level) = ticket.level_at(pos) else {
+                continue;
+            };
+            let holder = state.holders.entry(pos).or_insert_with(|| ChunkHolder {
+                level: None,
+                status: watch::Sender::new(ChunkStatus::Empty),
+                loading: false,
+            });
+            holder.level = Some(holder.level.map_or(level, |current| current.min(level)));
+            if !holder.loading && *holder.status.borrow() == ChunkStatus::Empty {
+                holder.loading = true;
+                self.schedule(Job::Load(pos));
+            }
+        }
+    }
+
+    /// Removes a ticket, unloading the chunks that are left without any. Returns `false` if
+    /// there was no such ticket.
+    pub fn remove_ticket(&self, ticket: Ticket) -> bool {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.tickets.get_mut(&ticket) {
+            Some(count) if *count > 1 => {
+                // The chunks keep the same levels.
+                *count -= 1;
+                return true;
+            }
+            Some(_) => {
+                state.tickets.remove(&ticket);
+            }
+            None => return false,
+        }
+
+        let tickets: Vec<Ticket> = state.tickets.keys().copied().collect();
+        for pos in ticket.chunks() {
+            let Some(holder) = state.holders.get_mut(&pos) else {
+                continue;
+            };
+            holder.level = tickets.iter().filter_map(|other| other.level_at(pos)).min();
+            // Chunks being loaded are unloaded by their worker.
+            if holder.level.is_none() && !holder.loading {
+                state.holders.remove(&pos);
+                if self.shared.unload(pos) {
+                    self.schedule(Job::Save(pos));
+                }
+            }
+        }
+        true
+    }
+
+    /// Load level of the chunk at `pos`, or `None` if it has no tickets.
+    pub fn load_level(&self, pos: ChunkPos) -> Option<u32> {
+        let state = self.shared.state.lock().unwrap();
+        state.holders.get(&pos)?.level
+    }
+
+    /// Status of the chunk at `pos`, or `None` if it has no tickets.
+    pub fn status(&self, pos: ChunkPos) -> Option<ChunkStatus> {
+        let state = self.shared.state.lock().unwrap();
+        let holder = state.holders.get(&pos)?;
+        holder.level.map(|_| *holder.status.borrow())
+    }
+
+    /// Waits until the chunk at `pos` reaches `status`, resolving to `false` if it has no
+    /// tickets or loses them first.
+    pub fn wait_for(
+        &self,
+        pos: ChunkPos,
+        status: ChunkStatus,
+    ) -> impl Future<Output = bool> + Send + 'static {
+        let receiver = {
+            let state = self.shared.state.lock().unwrap();
+            state
+                .holders
+                .get(&pos)
+                .filter(|holder| holder.level.is_some())
+                .map(|holder| holder.status.subscribe())
+        };
+        async move {
+            match receiver {
+                Some(mut receiver) => receiver
+                    .wait_for(|&current| current >= status)
+                    .await
+                    .is_ok(),
+                None => false,
+            }
+        }
+    }
+
+    /// Saves the chunks that changed, returning how many were saved. Chunks with unknown blocks
+    /// are skipped, see [`ChunkStorage::save`].
+    pub fn save_all(&self) -> Result<usize, ChunkStorageError> {
+        let Some(storage) = &self.shared.storage else {
+            return Ok(0);
+        };
+        let game_time = self.shared.game_time.load(Ordering::Relaxed);
+        let mut saved = 0;
+
+        {
+            // Unloaded chunks don't wait for the workers, which may be busy.
+            let mut unloading = self.shared.unloading.lock().unwrap();
+            let positions: Vec<ChunkPos> = unloading.keys().copied().collect();
+            for pos in positions {
+                if storage.save(&unloading[&pos], game_time)? {
+                    saved += 1;
+                }
+                unloading.remove(&pos);
+            }
+        }
+        {
+            let chunks = self.chunks();
+            let mut dirty = self.shared.dirty.lock().unwrap();
+            let positions: Vec<ChunkPos> = dirty.iter().copied().collect();
+            for pos in positions {
+                if let Some(chunk) = chunks.get(&pos) {
+                    if storage.save(chunk, game_time)? {
+                        saved += 1;
+                    }
+                }
+                dirty.remove(&pos);
+            }
+        }
+        storage.sync()?;
+        Ok(saved)
+    }
+
+    fn schedule(&self, job: Job) {
+        let jobs = self.jobs.get_or_init(|| self.start_workers());
+        // The workers only stop once the sender is dropped with the manager.
+        let _ = jobs.send(job);
+    }
+
+    /// Starts one worker per core, leaving one for the rest of the server.
+    fn start_workers(&self) -> mpsc::Sender<Job> {
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let count = thread::available_parallelism()
+            .map_or(1, |cores| cores.get().saturating_sub(1))
+            .max(1);
+        for index in 0..count {
+            let shared = Arc::clone(&self.shared);
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("chunk-worker-{index}"))
+                .spawn(move || shared.work(&receiver))
+                .expect("should be able to spawn chunk workers");
+        }
+        sender
+    }
+}
+
+impl Shared {
+    /// Runs jobs until the manager is dropped.
+    fn work(&self, jobs: &Mutex<mpsc::Receiver<Job>>) {
+        loop {
+            let job = jobs.lock().unwrap().recv();
+            match job {
+                Ok(Job::Load(pos)) => self.load(pos),
+                Ok(Job::Save(pos)) => self.save_unloaded(pos),
+                Ok(Job::Relight(position)) => self.relight(position),
+                Err(mpsc::RecvError) => return,
+            }
+        }
+    }
+
+    fn load(&self, pos: ChunkPos) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let Some(holder) = state.holders.get(&pos) else {
+                return;
+            };
+            if holder.level.is_none() {
+                // The chunk lost its tickets while waiting, there is nothing to unload yet.
+                state.holders.remove(&pos);
+                return;
+            }
+        }
+
+        let (chunk, changed) = self.read_or_generate(pos);
+        // Lit before taking any lock, only the light crossing its borders needs the others.
+        let chunk = self.light.light_chunk(chunk);
+
+        let mut state = self.state.lock().unwrap();
+        let Some(holder) = state.holders.get_mut(&pos) else {
+            return;
+        };
+        holder.loading = false;
+        self.chunks.write().unwrap().insert(pos, chunk);
+        if changed {
+            self.dirty.lock().unwrap().insert(pos);
+        }
+
+        if holder.level.is_none() {
+            state.holders.remove(&pos);
+            drop(state);
+            if self.unload(pos) {
+                self.save_unloaded(pos);
+            }
+            return;
+        }
+        holder.status.send_replace(ChunkStatus::Full);
+        drop(state);
+
+        // Clients that were sent the chunk meanwhile are sent its light again.
+        let mut chunks = self.chunks.write().unwrap();
+        // The chunk may have been unloaded since.
+        if chunks.contains_key(&pos) {
+            let lit = self.light.spread_borders(&mut chunks, pos);
+            self.light_changed(&lit);
+        }
+    }
+
+    fn relight(&self, position: BlockPosition) {
+        let mut chunks = self.chunks.write().unwrap();
+        let lit = self.light.update_block(&mut chunks, position);
+        self.light_changed(&lit);
+    }
+
+    /// Reads the chunk at `pos`, or generates it if it isn't stored. Also returns whether it
+    /// has to be saved.
+    fn read_or_generate(&self, pos: ChunkPos) -> (Chunk, bool) {
+        // A chunk unloaded before being saved is newer than the stored one.
+        if let Some(chunk) = self.unloading.lock().unwrap().remove(&pos) {
+            return (chunk, true);
+        }
+
+        let stored = self.storage.as_ref().and_then(|storage| {
+            storage
+                .load(pos, self.min_y, self.height)
+                .unwrap_or_else(|err| {
+                    tracing::error!("Unable to load chunk {}, {}: {}.", pos.x, pos.z, err);
+                    None
+                })
+        });
+        if let Some(chunk) = stored {
+            return (chunk, false);
+        }
+
+        let chunk = self.generator.generate(pos, self.min_y, self.height);
+        self.set_status(pos, ChunkStatus::Generated);
+        (chunk, true)
+    }
+
+    /// Records that the light of the `changed` chunks changed.
+    fn light_changed(&self, changed: &HashSet<ChunkPos>) {
+        if changed.is_empty() {
+            return;
+        }
+        let mut changes = self.light_changes.lock().unwrap();
+        changes.version += 1;
+        let version = changes.version;
+        changes
+            .chunks
+            .extend(changed.iter().map(|&pos| (pos, version)));
+    }
+
+    fn set_status(&self, pos: ChunkPos, status: ChunkStatus) {
+        if let Some(holder) = self.state.lock().unwrap().holders.get(&pos) {
+            holder.status.send_replace(status);
+        }
+    }
+
+    /// Unloads the chunk at `pos`, keeping it until it is saved if it changed. Returns whether
+    /// it has to be saved.
+    fn unload(&self, pos: ChunkPos) -> bool {
+        let Some(chunk) = self.chunks.write().unwrap().remove(&pos) else {
+            return false;
+        };
+        self.light_changes.lock().unwrap().chunks.remove(&pos);
+        if !self.dirty.lock().unwrap().remove(&pos) {
+            return false;
+        }
+        self.unloading.lock().unwrap().insert(pos, chunk);
+        true
+    }
+
+    fn save_unloaded(&self, pos: ChunkPos) {
+        let Some(storage) = &self.storage else {
+            self.unloading.lock().unwrap().remove(&pos);
+            return;
+        };
+        // The chunk stays there while it's saved, in case it's loaded again meanwhile.
+        let mut unloading = self.unloading.lock().unwrap();
+        let Some(chunk) = unloading.get(&pos) else {
+            // Already saved, or loaded again.
+            return;
+        };
+        match storage.save(chunk, self.game_time.load(Ordering::Relaxed)) {
+            // Chunks that can't be saved are dropped, leaving the stored ones as they were.
+            Ok(_) => {
+                unloading.remove(&pos);
+            }
+            // The chunk is saved again with the others when the server stops.
+            Err(err) => tracing::error!("Unable to save chunk {}, {}: {}.", pos.x, pos.z, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicUsize, Condvar};
+
+    use anvil::Compression;
+
+    use super::*;
+
+    const MIN_Y: i32 = -64;
+    const HEIGHT: u32 = 384;
+    const ORIGIN: ChunkPos = ChunkPos::new(0, 0);
+
+    /// Lets chunks be generated once opened, counting them.
+    #[derive(Debug, Default)]
+    struct Gate {
+        open: Mutex<bool>,
+        opened: Condvar,
+        generated: AtomicUsize,
+    }
+
+    impl Gate {
+        fn open(&self) {
+            *self.open.lock().unwrap() = true;
+            self.opened.notify_all();
+        }
+
+        fn generated(&self) -> usize {
+            self.generated.load(Ordering::SeqCst)
+        }
+    }
+
+    /// Generates chunks of air once its gate is open.
+    #[derive(Debug)]
+    struct GatedGenerator(Arc<Gate>);
+
+    impl ChunkGenerator for GatedGenerator {
+        fn generate(&self, pos: ChunkPos, min_y: i32, height: u32) -> Chunk {
+            let gate = &self.0;
+            let _open = gate
+                .opened
+                .wait_while(gate.open.lock().unwrap(), |open| !*open)
+                .unwrap();
+            gate.generated.fetch_add(1, Ordering::SeqCst);
+            Chunk::new(pos, min_y, height, 0)
+        }
+    }
+
+    /// A manager whose chunks are generated once the returned gate is opened.
+    fn manager() -> (ChunkManager, Arc<Gate>) {
+        let gate = Arc::new(Gate::default());
+        let generator = Box::new(GatedGenerator(Arc::clone(&gate)));
+        let manager = ChunkManager::new(MIN_Y, HEIGHT, generator, LightEngine::new(false));
+        (manager, gate)
+    }
+
+    fn ticket(x: i32, z: i32, radius: u32) -> Ticket {
+        Ticket::new(TicketKind::Forced, ChunkPos::new(x, z), radius)
+    }
+
+    #[test]
+    fn ticket_levels() {
+        let ticket = ticket(0, 0, 2);
+        assert_eq!(ticket.level(), 31);
+        assert_eq!(ticket.level_at(ORIGIN), Some(31));
+        assert_eq!(ticket.level_at(ChunkPos::new(1, -1)), Some(32));
+        assert_eq!(ticket.level_at(ChunkPos::new(-2, 1)), Some(33));
+        assert_eq!(ticket.level_at(ChunkPos::new(3, 0)), None);
+
+        let chunks: Vec<_> = ticket.chunks().collect();
+        assert_eq!(chunks.len(), 25);
+        assert_eq!(chunks[0], ORIGIN);
+        assert!(chunks.iter().all(|&pos| ticket.level_at(pos).is_some()));
+
+        // Levels can't go below 0.
+        let wide = Ticket::new(TicketKind::Spawn, ChunkPos::new(5, 5), 40);
+        assert_eq!(wide.level(), 0);
+        assert_eq!(wide.level_at(ChunkPos::new(38, 5)), Some(33));
+        assert_eq!(wide.level_at(ChunkPos::new(5, -29)), None);
+    }
+
+    #[test]
+    fn tickets_set_levels() {
+        let (manager, _gate) = manager();
+        let near = ticket(0, 0, 1);
+        let far = ticket(2, 0, 2);
+        manager.add_ticket(near);
+        manager.add_ticket(far);
+
+        // Chunks get the lowest level of their tickets.
+        assert_eq!(manager.load_level(ORIGIN), Some(32));
+        assert_eq!(manager.load_level(ChunkPos::new(1, 0)), Some(32));
+        assert_eq!(manager.load_level(ChunkPos::new(2, 0)), Some(31));
+        assert_eq!(manager.load_level(ChunkPos::new(-1, 1)), Some(33));
+        assert_eq!(manager.load_level(ChunkPos::new(-2, 0)), None);
+        assert_eq!(manager.status(ORIGIN), Some(ChunkStatus::Empty));
+
+        assert!(manager.remove_ticket(far));
+        assert_eq!(manager.load_level(ChunkPos::new(1, 0)), Some(33));
+        assert_eq!(manager.load_level(ChunkPos::new(3, 0)), None);
+        assert_eq!(manager.status(ChunkPos::new(3, 0)), None);
+        assert!(!manager.remove_ticket(far));
+    }
+
+    #[test]
+    fn tickets_are_counted() {
+        let (manager, _gate) = manager();
+        let ticket = ticket(0, 0, 0);
+        manager.add_ticket(ticket);
+        manager.add_ticket(ticket);
+
+        assert!(manager.remove_ticket(ticket));
+        assert_eq!(manager.load_level(ORIGIN), Some(33));
+        assert!(manager.remove_ticket(ticket));
+        assert_eq!(manager.load_level(ORIGIN), None);
+        assert!(!manager.remove_ticket(ticket));
+    }
+
+    #[tokio::test]
+    async fn loads_chunks() {
+        let (manager, gate) = manager();
+        // Chunks without tickets are never loaded.
+        assert!(!manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+
+        manager.add_ticket(ticket(0, 0, 1));
+        gate.open();
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Generated).await);
+        for pos in ticket(0, 0, 1).chunks() {
+            assert!(manager.wait_for(pos, ChunkStatus::Full).await);
+            assert!(manager.is_loaded(pos));
+            assert_eq!(manager.status(pos), Some(ChunkStatus::Full));
+        }
+        assert_eq!(gate.generated(), 9);
+
+        // Chunks that are already loaded resolve right away.
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+    }
+
+    #[tokio::test]
+    async fn loads_chunks_once() {
+        let (manager, gate) = manager();
+        let ticket = ticket(0, 0, 0);
+        manager.add_ticket(ticket);
+        // Losing the ticket while loading and getting it again doesn't load the chunk twice.
+        assert!(manager.remove_ticket(ticket));
+        manager.add_ticket(ticket);
+        manager.add_ticket(ticket);
+
+        gate.open();
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+        assert_eq!(gate.generated(), 1);
+    }
+
+    #[tokio::test]
+    async fn waiting_fails_when_tickets_are_lost() {
+        let (manager, gate) = manager();
+        let ticket = ticket(0, 0, 0);
+        manager.add_ticket(ticket);
+        let loaded = manager.wait_for(ORIGIN, ChunkStatus::Full);
+        assert!(manager.remove_ticket(ticket));
+        assert_eq!(manager.status(ORIGIN), None);
+
+        gate.open();
+        assert!(!loaded.await);
+    }
+
+    #[tokio::test]
+    async fn reloads_unloaded_chunks() {
+        let (manager, gate) = manager();
+        gate.open();
+        manager.add_ticket(ticket(0, 0, 0));
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+
+        let position = BlockPosition::new(1, 2, 3);
+        assert_eq!(
+            manager.set_block(position, BlockState::STONE),
+            Some(BlockState::AIR)
+        );
+        // Unloading the changed chunk keeps it until it is saved.
+        assert!(manager.shared.unload(ORIGIN));
+        assert!(!manager.is_loaded(ORIGIN));
+        assert!(manager
+            .shared
+            .unloading
+            .lock()
+            .unwrap()
+            .contains_key(&ORIGIN));
+        // Loading it again meanwhile takes it back, rather than generating it.
+        let (chunk, changed) = manager.shared.read_or_generate(ORIGIN);
+        assert!(changed);
+        assert_eq!(chunk.block(position), BlockState::STONE);
+        assert!(manager.shared.unloading.lock().unwrap().is_empty());
+        assert_eq!(gate.generated(), 1);
+    }
+
+    #[tokio::test]
+    async fn saves_unloaded_chunks() {
+        let directory = tempfile::tempdir().unwrap();
+        let (mut manager, gate) = manager();
+        manager.set_storage(ChunkStorage::new(directory.path(), Compression::Zlib));
+        gate.open();
+
+        let ticket = ticket(0, 0, 0);
+        manager.add_ticket(ticket);
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+        let position = BlockPosition::new(1, 2, 3);
+        manager.set_block(position, BlockState::STONE);
+
+        // The change survives unloading, whether the chunk was saved yet or not.
+        assert!(manager.remove_ticket(ticket));
+        manager.add_ticket(ticket);
+        assert!(manager.wait_for(ORIGIN, ChunkStatus::Full).await);
+        assert_eq!(manager.chunks()[&ORIGIN].block(position), BlockState::STONE);
+        assert_eq!(gate.generated(), 1);
+
+        // Only changed chunks are saved.
+        manager.save_all().unwrap();
+        let above = BlockPosition::new(1, 3, 3);
+        manager.set_block(above, BlockState::STONE);
+        assert_eq!(manager.save_all().unwrap(), 1);
+        assert_eq!(manager.save_all().unwrap(), 0);
+        let stored = ChunkStorage::new(directory.path(), Compression::Zlib)
+            .load(ORIGIN, MIN_Y, HEIGHT)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.block(position), BlockState::STONE);
+        assert_eq!(stored.block(above), BlockState::STONE);
+    }
+}
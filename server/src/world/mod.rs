@@ -0,0 +1,359 @@
+mod block;
+mod chunk;
+pub mod generator;
+pub mod heightmap;
+pub mod light;
+pub mod manager;
+pub mod palette;
+pub mod storage;
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        RwLockReadGuard,
+    },
+};
+
+use packet::client::{ChunkDataAndUpdateLightPacket, UpdateTimePacket};
+use protocol::{identifier::Identifier, BlockPosition, Difficulty, GameMode};
+use server_assets::Registries;
+
+use crate::{entity::EntityStore, player_list::PlayerList, tick::TICKS_PER_SECOND};
+
+pub use block::BlockState;
+pub use chunk::{spiral, Chunk, ChunkPos, ChunkSection, SECTION_SIZE};
+pub use generator::ChunkGenerator;
+pub use heightmap::{Heightmap, HeightmapKind};
+pub use light::{LightEngine, LightKind};
+pub use manager::{ChunkManager, ChunkStatus, Ticket, TicketKind};
+pub use storage::{ChunkStorage, ChunkStorageError};
+
+/// Biome of chunks that aren't given another one.
+const DEFAULT_BIOME: &str = "minecraft:plains";
+
+/// Y coordinate players spawn at if there are no blocks to spawn on.
+const DEFAULT_SPAWN_Y: i32 = 64;
+
+/// Positions are sent to clients in 1/4096 of a block in entity movement deltas.
+const POSITION_SCALE: f64 = 4096.0;
+
+/// A position in the world.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Position {
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The position at the bottom center of `block`.
+    pub fn from_block(block: BlockPosition) -> Self {
+        Self::new(block.x as f64 + 0.5, block.y as f64, block.z as f64 + 0.5)
+    }
+
+    pub fn block_position(&self) -> BlockPosition {
+        BlockPosition::new(
+            self.x.floor() as i32,
+            self.y.floor() as i32,
+            self.z.floor() as i32,
+        )
+    }
+
+    pub fn chunk_x(&self) -> i32 {
+        (self.x.floor() as i32) >> 4
+    }
+
+    pub fn chunk_z(&self) -> i32 {
+        (self.z.floor() as i32) >> 4
+    }
+
+    /// The position in 1/4096 of a block, rounded like vanilla, which clients apply entity
+    /// movement deltas to.
+    pub fn to_protocol(&self) -> [i64; 3] {
+        [self.x, self.y, self.z].map(|coordinate| (coordinate * POSITION_SCALE).round() as i64)
+    }
+
+    /// The position given in 1/4096 of a block.
+    pub fn from_protocol(position: [i64; 3]) -> Self {
+        let [x, y, z] = position.map(|units| units as f64 / POSITION_SCALE);
+        Self::new(x, y, z)
+    }
+}
+
+/// The delta an entity movement packet moves an entity by from `from` to `to`, both in 1/4096
+/// of a block, or `None` if it moved 8 blocks or more along an axis, which takes a teleport.
+pub fn movement_delta(from: [i64; 3], to: [i64; 3]) -> Option<[i16; 3]> {
+    let mut delta = [0; 3];
+    for axis in 0..3 {
+        delta[axis] = i16::try_from(to[axis] - from[axis]).ok()?;
+    }
+    Some(delta)
+}
+
+/// A rotation, in degrees.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rotation {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Rotation {
+    pub const fn new(yaw: f32, pitch: f32) -> Self {
+        Self { yaw, pitch }
+    }
+
+    /// The yaw as a protocol angle, in steps of 1/256 of a full turn.
+    pub fn yaw_angle(&self) -> u8 {
+        to_angle(self.yaw)
+    }
+
+    /// The pitch as a protocol angle, in steps of 1/256 of a full turn.
+    pub fn pitch_angle(&self) -> u8 {
+        to_angle(self.pitch)
+    }
+}
+
+fn to_angle(degrees: f32) -> u8 {
+    (degrees.rem_euclid(360.0) / 360.0 * 256.0) as u8
+}
+
+/// A world (dimension) that players can be spawned into.
+#[derive(Debug)]
+pub struct World {
+    /// The name of the world, e.g. `minecraft:overworld`.
+    pub name: Identifier<'static>,
+    /// Entry in the `minecraft:dimension_type` registry.
+    pub dimension_type: Identifier<'static>,
+    /// First 8 bytes of the SHA-256 hash of the world seed.
+    pub hashed_seed: i64,
+    pub is_flat: bool,
+    pub difficulty: Difficulty,
+    pub default_game_mode: GameMode,
+    pub spawn_position: BlockPosition,
+    pub spawn_angle: f32,
+    /// View distance (in chunks) sent to players joining this world.
+    pub view_distance: i32,
+    /// Simulation distance (in chunks) sent to players joining this world.
+    pub simulation_distance: i32,
+    /// Lowest Y coordinate of blocks, from the dimension type.
+    min_y: i32,
+    /// Height in blocks, from the dimension type.
+    height: u32,
+    /// Ticks the world existed for.
+    game_time: AtomicI64,
+    /// Time of the day-night cycle, in ticks.
+    time_of_day: AtomicI64,
+    players: PlayerList,
+    entities: EntityStore,
+    chunks: ChunkManager,
+}
+
+impl World {
+    /// Makes a world whose new chunks are made by `generator`.
+    pub fn new(
+        name: Identifier<'static>,
+        dimension_type: Identifier<'static>,
+        generator: Box<dyn ChunkGenerator>,
+    ) -> Self {
+        let (min_y, height, has_sky_light) = dimension_properties(&dimension_type.to_string());
+        Self {
+            name,
+            dimension_type,
+            hashed_seed: 0,
+            is_flat: false,
+            difficulty: Difficulty::Normal,
+            default_game_mode: GameMode::Survival,
+            spawn_position: spawn_position(generator.as_ref(), min_y, height),
+            spawn_angle: 0.0,
+            view_distance: 10,
+            simulation_distance: 10,
+            min_y,
+            height,
+            game_time: AtomicI64::new(0),
+            time_of_day: AtomicI64::new(0),
+            players: PlayerList::new(),
+            entities: EntityStore::new(),
+            chunks: ChunkManager::new(min_y, height, generator, LightEngine::new(has_sky_light)),
+        }
+    }
+
+    pub fn players(&self) -> &PlayerList {
+        &self.players
+    }
+
+    /// The entities in this world, other than players.
+    pub fn entities(&self) -> &EntityStore {
+        &self.entities
+    }
+
+    /// Advances the world by a tick.
+    pub fn tick(&self) {
+        let game_time = self.game_time.fetch_add(1, Ordering::Relaxed) + 1;
+        self.time_of_day.fetch_add(1, Ordering::Relaxed);
+        self.chunks.set_game_time(game_time);
+        // Clients advance the time on their own, it's only sent to keep them in sync, like
+        // vanilla.
+        if game_time % TICKS_PER_SECOND as i64 == 0 {
+            self.players.broadcast(&self.time_packet());
+        }
+        self.entities.tick(self);
+    }
+
+    /// Ticks the world existed for.
+    pub fn game_time(&self) -> i64 {
+        self.game_time.load(Ordering::Relaxed)
+    }
+
+    /// Time of the day-night cycle, in ticks, 24000 being a day.
+    pub fn time_of_day(&self) -> i64 {
+        self.time_of_day.load(Ordering::Relaxed)
+    }
+
+    /// Makes the packet sending the time of this world to clients.
+    pub fn time_packet(&self) -> UpdateTimePacket {
+        UpdateTimePacket {
+            world_age: self.game_time(),
+            time_of_day: self.time_of_day(),
+        }
+    }
+
+    /// Lowest Y coordinate of blocks.
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    /// Height in blocks, from [`World::min_y`].
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the loaded chunks.
+    pub fn chunks(&self) -> RwLockReadGuard<'_, HashMap<ChunkPos, Chunk>> {
+        self.chunks.chunks()
+    }
+
+    /// The manager loading and unloading chunks, by their tickets.
+    pub fn chunk_manager(&self) -> &ChunkManager {
+        &self.chunks
+    }
+
+    pub fn is_chunk_loaded(&self, pos: ChunkPos) -> bool {
+        self.chunks.is_loaded(pos)
+    }
+
+    /// Stores chunks in `storage`, loading them from it when possible.
+    ///
+    /// # Panics
+    /// Panics if chunks were already requested.
+    pub fn set_storage(&mut self, storage: ChunkStorage) {
+        self.chunks.set_storage(storage);
+    }
+
+    /// Keeps the chunks around the spawn loaded.
+    pub fn load_spawn_chunks(&self) {
+        self.chunks.add_ticket(Ticket::new(
+            TicketKind::Spawn,
+            ChunkPos::from_block(self.spawn_position),
+            manager::SPAWN_CHUNK_RADIUS,
+        ));
+    }
+
+    /// Saves the chunks that changed to the storage, returning how many were saved.
+    pub fn save_chunks(&self) -> Result<usize, ChunkStorageError> {
+        self.chunks.save_all()
+    }
+
+    /// Makes the packet sending the chunk at `pos` to clients, if it's loaded.
+    pub fn chunk_packet(&self, pos: ChunkPos) -> Option<ChunkDataAndUpdateLightPacket<'static>> {
+        self.chunks().get(&pos).map(Chunk::to_packet)
+    }
+
+    /// Returns the block at `position`, or `None` if its chunk isn't loaded.
+    pub fn block(&self, position: BlockPosition) -> Option<BlockState> {
+        self.chunks()
+            .get(&ChunkPos::from_block(position))
+            .map(|chunk| chunk.block(position))
+    }
+
+    /// Sets the block at `position`, returning the previous one, or `None` if its chunk isn't
+    /// loaded or it's outside the world's height.
+    pub fn set_block(&self, position: BlockPosition, block: BlockState) -> Option<BlockState> {
+        self.chunks.set_block(position, block)
+    }
+
+    /// Whether the block at `position` is solid, i.e. has a full collision box.
+    ///
+    /// Blocks that aren't loaded are never solid.
+    pub fn is_solid_block(&self, position: BlockPosition) -> bool {
+        self.block(position).is_some_and(BlockState::is_solid)
+    }
+
+    /// Makes a new overworld.
+    pub fn overworld(generator: Box<dyn ChunkGenerator>) -> Self {
+        Self::new(
+            Identifier::from_string("minecraft:overworld").unwrap(),
+            Identifier::from_string("minecraft:overworld").unwrap(),
+            generator,
+        )
+    }
+}
+
+/// Returns where players spawn: on top of the highest block generated at 0, 0.
+fn spawn_position(generator: &dyn ChunkGenerator, min_y: i32, height: u32) -> BlockPosition {
+    let chunk = generator.generate(ChunkPos::new(0, 0), min_y, height);
+    let top = chunk.top_y(HeightmapKind::MotionBlocking, 0, 0);
+    BlockPosition::new(0, top.map_or(DEFAULT_SPAWN_Y, |y| y + 1), 0)
+}
+
+/// Returns the lowest Y coordinate and height of `dimension_type`, and whether it has sky light,
+/// defaulting to the overworld's.
+fn dimension_properties(dimension_type: &str) -> (i32, u32, bool) {
+    let entry = Registries::get()
+        .registry("minecraft:dimension_type")
+        .and_then(|registry| registry.entries().get(dimension_type));
+    let value = |key: &str| entry.and_then(|entry| entry.get(key)?.as_i64());
+    let has_sky_light = value("has_skylight") != Some(0);
+    match (value("min_y"), value("height")) {
+        (Some(min_y), Some(height)) => (min_y as i32, height as u32, has_sky_light),
+        _ => (-64, 384, has_sky_light),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_positions() {
+        let position = Position::new(1.5, -64.0, 0.1);
+        assert_eq!(position.to_protocol(), [6144, -262144, 410]);
+        // Rounded, not truncated.
+        assert_eq!(Position::new(-0.0001, 0.0002, 0.0).to_protocol(), [0, 1, 0]);
+        assert_eq!(
+            Position::from_protocol([6144, -262144, 2048]),
+            Position::new(1.5, -64.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn movement_deltas() {
+        let from = Position::new(0.0, 64.0, 0.0).to_protocol();
+        let to = Position::new(0.5, 63.0, -7.99).to_protocol();
+        assert_eq!(movement_delta(from, to), Some([2048, -4096, -32727]));
+        assert_eq!(movement_delta(to, from), Some([-2048, 4096, 32727]));
+        assert_eq!(movement_delta(from, from), Some([0; 3]));
+
+        // Moving 8 blocks or more doesn't fit in a delta.
+        let far = Position::new(0.0, 72.0, 0.0).to_protocol();
+        assert_eq!(movement_delta(from, far), None);
+        let far = Position::new(0.0, 64.0, -8.0).to_protocol();
+        assert_eq!(movement_delta(from, far), Some([0, 0, -32768]));
+        let far = Position::new(-8.001, 64.0, 0.0).to_protocol();
+        assert_eq!(movement_delta(from, far), None);
+    }
+}
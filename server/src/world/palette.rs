@@ -0,0 +1,484 @@
+//! Paletted containers, the vanilla format for storing the block states and biomes of a chunk
+//! section compactly.
+//!
+//! A container holds a fixed number of IDs packed into longs, each long holding as many
+//! entries of `bits` bits as fit (entries never span two longs). Depending on how many
+//! different IDs there are, the entries are either:
+//! - absent, when every entry is the same single value,
+//! - indices into a palette of the IDs in use (indirect), or
+//! - the IDs themselves (direct), once the palette would be too large.
+
+use bytes::BufMut;
+use protocol::buf;
+use thiserror::Error;
+
+/// Sizes of a kind of paletted container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteKind {
+    /// Number of entries in the container.
+    pub entries: usize,
+    /// Bits per entry of the smallest indirect palette.
+    pub min_indirect_bits: u8,
+    /// Bits per entry of the largest indirect palette, above which IDs are stored directly.
+    pub max_indirect_bits: u8,
+    /// Bits per entry when storing IDs directly, enough for every ID.
+    pub direct_bits: u8,
+}
+
+/// Block states of a section, one per block.
+pub const BLOCK_STATES: PaletteKind = PaletteKind {
+    entries: 16 * 16 * 16,
+    min_indirect_bits: 4,
+    max_indirect_bits: 8,
+    // There are fewer than 2^15 block states.
+    direct_bits: 15,
+};
+
+/// Biomes of a section, one per 4x4x4 blocks.
+pub const BIOMES: PaletteKind = PaletteKind {
+    entries: 4 * 4 * 4,
+    min_indirect_bits: 1,
+    max_indirect_bits: 3,
+    // The `minecraft:worldgen/biome` registry has 64 entries.
+    direct_bits: 6,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Palette {
+    Single(u32),
+    Indirect(Vec<u32>),
+    Direct,
+}
+
+/// A container of IDs in the paletted format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PalettedContainer {
+    kind: PaletteKind,
+    palette: Palette,
+    /// Bits per entry, 0 for a single value.
+    bits: u8,
+    data: Vec<u64>,
+}
+
+impl PalettedContainer {
+    /// Makes a container of `kind` with every entry set to `value`.
+    pub fn filled(kind: PaletteKind, value: u32) -> Self {
+        Self {
+            kind,
+            palette: Palette::Single(value),
+            bits: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn kind(&self) -> PaletteKind {
+        self.kind
+    }
+
+    /// Bits per entry, 0 if every entry is the same.
+    pub fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// Returns the entry at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> u32 {
+        assert!(index < self.kind.entries, "index {index} out of bounds");
+        match &self.palette {
+            Palette::Single(value) => *value,
+            Palette::Indirect(palette) => palette[self.raw(index) as usize],
+            Palette::Direct => self.raw(index),
+        }
+    }
+
+    /// Sets the entry at `index` to `value`, returning the previous one.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: u32) -> u32 {
+        assert!(index < self.kind.entries, "index {index} out of bounds");
+        let previous = self.get(index);
+        if previous == value {
+            return previous;
+        }
+
+        let raw = match &mut self.palette {
+            Palette::Single(_) => {
+                // Grows into an indirect palette where everything else stays the first entry.
+                self.bits = self.kind.min_indirect_bits;
+                self.palette = Palette::Indirect(vec![previous, value]);
+                self.data = vec![0; longs_needed(self.kind.entries, self.bits)];
+                1
+            }
+            Palette::Indirect(palette) => match palette.iter().position(|&id| id == value) {
+                Some(raw) => raw as u32,
+                None if palette.len() < 1 << self.bits => {
+                    palette.push(value);
+                    palette.len() as u32 - 1
+                }
+                None => {
+                    self.resize(self.bits + 1, value);
+                    return self.set(index, value);
+                }
+            },
+            Palette::Direct => value,
+        };
+        self.set_raw(index, raw);
+        previous
+    }
+
+    /// Makes a container of `kind` from a palette and the packed indices into it, as stored in
+    /// chunk NBT, where the bits per index are derived from the length of the palette.
+    pub fn from_palette(
+        kind: PaletteKind,
+        palette: &[u32],
+        data: &[u64],
+    ) -> Result<Self, PaletteError> {
+        let bits = storage_bits(kind, palette.len());
+        match palette {
+            [] => return Err(PaletteError::EmptyPalette),
+            &[value] => return Ok(Self::filled(kind, value)),
+            _ => {}
+        }
+        let expected = longs_needed(kind.entries, bits);
+        if data.len() != expected {
+            return Err(PaletteError::DataLength {
+                expected,
+                actual: data.len(),
+            });
+        }
+
+        let indices = Self {
+            kind,
+            palette: Palette::Direct,
+            bits,
+            data: data.to_vec(),
+        };
+        if let Some(index) = indices
+            .iter()
+            .find(|&index| index as usize >= palette.len())
+        {
+            return Err(PaletteError::IndexOutOfBounds(index));
+        }
+        if bits <= kind.max_indirect_bits {
+            return Ok(Self {
+                palette: Palette::Indirect(palette.to_vec()),
+                ..indices
+            });
+        }
+
+        // Palettes on disk can be larger than on the network, where IDs are stored directly.
+        let mut container = Self {
+            kind,
+            palette: Palette::Direct,
+            bits: kind.direct_bits,
+            data: vec![0; longs_needed(kind.entries, kind.direct_bits)],
+        };
+        for (index, raw) in indices.iter().enumerate() {
+            container.set_raw(index, palette[raw as usize]);
+        }
+        Ok(container)
+    }
+
+    /// Returns the palette and the packed indices into it, as stored in chunk NBT.
+    pub fn to_palette(&self) -> (Vec<u32>, Vec<u64>) {
+        match &self.palette {
+            Palette::Single(value) => (vec![*value], Vec::new()),
+            Palette::Indirect(palette) => (palette.clone(), self.data.clone()),
+            Palette::Direct => {
+                let mut palette = Vec::new();
+                let mut indices = Vec::with_capacity(self.kind.entries);
+                for id in self.iter() {
+                    let index = match palette.iter().position(|&entry| entry == id) {
+                        Some(index) => index,
+                        None => {
+                            palette.push(id);
+                            palette.len() - 1
+                        }
+                    };
+                    indices.push(index as u32);
+                }
+                if palette.len() == 1 {
+                    return (palette, Vec::new());
+                }
+
+                let mut packed = Self {
+                    kind: self.kind,
+                    palette: Palette::Direct,
+                    bits: storage_bits(self.kind, palette.len()),
+                    data: Vec::new(),
+                };
+                packed.data = vec![0; longs_needed(self.kind.entries, packed.bits)];
+                for (index, raw) in indices.into_iter().enumerate() {
+                    packed.set_raw(index, raw);
+                }
+                (palette, packed.data)
+            }
+        }
+    }
+
+    /// Sets every entry to `value`.
+    pub fn fill(&mut self, value: u32) {
+        *self = Self::filled(self.kind, value);
+    }
+
+    /// Iterates over all entries, in index order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.kind.entries).map(|index| self.get(index))
+    }
+
+    /// Repacks the entries with `bits` bits each, storing IDs directly when an indirect palette
+    /// would need more than the maximum. `added` is added to the palette.
+    fn resize(&mut self, bits: u8, added: u32) {
+        let entries: Vec<u32> = self.iter().collect();
+        let (palette, bits) = match &self.palette {
+            Palette::Indirect(palette) if bits <= self.kind.max_indirect_bits => {
+                let mut palette = palette.clone();
+                palette.push(added);
+                (Palette::Indirect(palette), bits)
+            }
+            _ => (Palette::Direct, self.kind.direct_bits),
+        };
+
+        self.palette = palette;
+        self.bits = bits;
+        self.data = vec![0; longs_needed(self.kind.entries, bits)];
+        for (index, entry) in entries.into_iter().enumerate() {
+            let raw = match &self.palette {
+                Palette::Indirect(palette) => {
+                    palette.iter().position(|&id| id == entry).unwrap() as u32
+                }
+                _ => entry,
+            };
+            self.set_raw(index, raw);
+        }
+    }
+
+    fn raw(&self, index: usize) -> u32 {
+        let (long, offset) = self.locate(index);
+        ((self.data[long] >> offset) & self.mask()) as u32
+    }
+
+    fn set_raw(&mut self, index: usize, raw: u32) {
+        let (long, offset) = self.locate(index);
+        let mask = self.mask();
+        self.data[long] = (self.data[long] & !(mask << offset)) | ((raw as u64 & mask) << offset);
+    }
+
+    /// Returns the long the entry at `index` is in, and its offset in bits in that long.
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let per_long = 64 / self.bits as usize;
+        (index / per_long, index % per_long * self.bits as usize)
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    /// Encodes the container in the network format: the bits per entry, the palette (a single
+    /// value, the IDs of an indirect palette or nothing) and the length-prefixed longs.
+    pub fn encode(&self, buf: &mut dyn BufMut) {
+        buf.put_u8(self.bits);
+        match &self.palette {
+            Palette::Single(value) => buf::put_varint(buf, *value as i32),
+            Palette::Indirect(palette) => {
+                buf::put_varint(buf, palette.len() as i32);
+                for &id in palette {
+                    buf::put_varint(buf, id as i32);
+                }
+            }
+            Palette::Direct => {}
+        }
+        buf::put_varint(buf, self.data.len() as i32);
+        for &long in &self.data {
+            buf.put_u64(long);
+        }
+    }
+}
+
+/// Bits per index into a palette of `len` IDs when stored in chunk NBT.
+fn storage_bits(kind: PaletteKind, len: usize) -> u8 {
+    if len <= 1 {
+        return 0;
+    }
+    let bits = (usize::BITS - (len - 1).leading_zeros()) as u8;
+    bits.max(kind.min_indirect_bits)
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PaletteError {
+    #[error("empty palette")]
+    EmptyPalette,
+    #[error("expected {expected} longs of data, got {actual}")]
+    DataLength { expected: usize, actual: usize },
+    #[error("palette index {0} out of bounds")]
+    IndexOutOfBounds(u32),
+}
+
+/// Number of longs needed to store `entries` entries of `bits` bits.
+fn longs_needed(entries: usize, bits: u8) -> usize {
+    entries.div_ceil(64 / bits as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a container of `kind` holding `count` different IDs, repeating over the entries.
+    fn with_ids(kind: PaletteKind, count: u32) -> PalettedContainer {
+        let mut container = PalettedContainer::filled(kind, id(0));
+        for index in 0..kind.entries {
+            container.set(index, id(index as u32 % count));
+        }
+        container
+    }
+
+    /// Arbitrary ID, so that IDs and palette indices differ.
+    fn id(n: u32) -> u32 {
+        n * 3 + 5
+    }
+
+    fn assert_ids(container: &PalettedContainer, count: u32) {
+        for index in 0..container.kind().entries {
+            assert_eq!(
+                container.get(index),
+                id(index as u32 % count),
+                "entry {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn single_value() {
+        let mut container = PalettedContainer::filled(BLOCK_STATES, 7);
+        assert_eq!(container.bits(), 0);
+        assert!(container.iter().all(|value| value == 7));
+        // Setting the same value keeps it single.
+        assert_eq!(container.set(10, 7), 7);
+        assert_eq!(container.bits(), 0);
+    }
+
+    #[test]
+    fn single_to_indirect() {
+        for kind in [BLOCK_STATES, BIOMES] {
+            let mut container = PalettedContainer::filled(kind, 7);
+            assert_eq!(container.set(3, 9), 7);
+            assert_eq!(container.bits(), kind.min_indirect_bits);
+            assert_eq!(container.get(3), 9);
+            assert!((0..kind.entries)
+                .filter(|&index| index != 3)
+                .all(|index| container.get(index) == 7));
+        }
+    }
+
+    #[test]
+    fn round_trips_at_each_bit_width() {
+        for kind in [BLOCK_STATES, BIOMES] {
+            for bits in kind.min_indirect_bits..=kind.max_indirect_bits {
+                // The most IDs that fit, then one more than the previous width.
+                for count in [1 << bits, (1 << (bits - 1)) + 1] {
+                    let container = with_ids(kind, count);
+                    assert_eq!(container.bits(), bits.max(kind.min_indirect_bits));
+                    assert_ids(&container, count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn indirect_to_direct() {
+        for kind in [BLOCK_STATES, BIOMES] {
+            // One more ID than the largest indirect palette holds.
+            let count = (1 << kind.max_indirect_bits) + 1;
+            let mut container = PalettedContainer::filled(kind, id(0));
+            for n in 1..count - 1 {
+                container.set(n as usize, id(n));
+            }
+            assert_eq!(container.bits(), kind.max_indirect_bits);
+
+            container.set(count as usize - 1, id(count - 1));
+            assert_eq!(container.bits(), kind.direct_bits);
+            assert!((0..count).all(|n| container.get(n as usize) == id(n)));
+            assert!((count as usize..kind.entries).all(|index| container.get(index) == id(0)));
+        }
+    }
+
+    #[test]
+    fn entries_dont_span_longs() {
+        // 12 entries of 5 bits per long, leaving 4 bits unused.
+        let palette: Vec<u32> = (0..32).map(id).collect();
+        let mut data = vec![0; 342];
+        data[0] = 31 << 55;
+        data[1] = 1;
+        let container = PalettedContainer::from_palette(BLOCK_STATES, &palette, &data).unwrap();
+        assert_eq!(container.bits(), 5);
+        assert_eq!(container.get(0), id(0));
+        assert_eq!(container.get(11), id(31));
+        assert_eq!(container.get(12), id(1));
+        assert_eq!(container.get(13), id(0));
+
+        // 4 entries of 15 bits per long.
+        let mut container = with_ids(BLOCK_STATES, 300);
+        container.set(3, 0x7FFF);
+        container.set(4, 0x7FFE);
+        assert_eq!(container.data.len(), 1024);
+        assert_eq!(container.data[0] >> 45, 0x7FFF);
+        assert_eq!(container.data[1] & 0x7FFF, 0x7FFE);
+        assert_eq!(container.get(2), id(2));
+        assert_eq!(container.get(5), id(5));
+    }
+
+    #[test]
+    fn palette_round_trip() {
+        for (kind, count) in [
+            (BLOCK_STATES, 1),
+            (BLOCK_STATES, 2),
+            (BLOCK_STATES, 100),
+            (BLOCK_STATES, 1000),
+            (BIOMES, 5),
+            (BIOMES, 20),
+        ] {
+            let container = with_ids(kind, count);
+            let (palette, data) = container.to_palette();
+            assert_eq!(palette.len(), count as usize);
+            let read = PalettedContainer::from_palette(kind, &palette, &data).unwrap();
+            assert_eq!(read, container, "{count} IDs");
+        }
+    }
+
+    #[test]
+    fn invalid_palettes() {
+        assert_eq!(
+            PalettedContainer::from_palette(BIOMES, &[], &[]),
+            Err(PaletteError::EmptyPalette)
+        );
+        assert_eq!(
+            PalettedContainer::from_palette(BIOMES, &[1, 2], &[]),
+            Err(PaletteError::DataLength {
+                expected: 1,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            PalettedContainer::from_palette(BIOMES, &[1, 2, 3], &[0, 3 << 2]),
+            Err(PaletteError::IndexOutOfBounds(3))
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let mut buf = Vec::new();
+        PalettedContainer::filled(BIOMES, 7).encode(&mut buf);
+        assert_eq!(buf, [0, 7, 0]);
+
+        let mut container = PalettedContainer::filled(BIOMES, 7);
+        container.set(1, 9);
+        let mut buf = Vec::new();
+        container.encode(&mut buf);
+        let mut expected = vec![1, 2, 7, 9, 1];
+        expected.extend_from_slice(&2u64.to_be_bytes());
+        assert_eq!(buf, expected);
+    }
+}
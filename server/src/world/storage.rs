@@ -0,0 +1,857 @@
+//! Persistence of chunks in Anvil region files, in vanilla's chunk NBT format.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anvil::{Compression, RegionError, RegionStorage};
+use nbt::NbtParser;
+use serde::{Deserialize, Serialize};
+use server_assets::Registries;
+use thiserror::Error;
+
+use super::{
+    block::BlockState,
+    chunk::{Chunk, ChunkPos, ChunkSection, SECTION_SIZE},
+    light::{LightKind, LightSection},
+    palette::{self, PaletteError, PalettedContainer},
+};
+
+/// Data version of the chunks written, the one of Minecraft 1.21.1.
+pub const DATA_VERSION: i32 = 3955;
+/// Oldest data version of chunks that can be read, from when sections moved out of the `Level`
+/// compound (21w43a, before 1.18).
+const MIN_DATA_VERSION: i32 = 2844;
+
+/// Status of chunks that are fully generated. Chunks from before 1.20 store it without the
+/// namespace.
+const STATUS_FULL: &str = "minecraft:full";
+
+/// Block state of unknown blocks, solid so that players don't fall through them. Chunks with
+/// unknown blocks aren't saved, so that the replacement doesn't overwrite them.
+const UNKNOWN_BLOCK: BlockState = BlockState::STONE;
+
+/// Entries of the root compound written by [`write_chunk`]. The others are kept as they were
+/// read, in [`StoredData`].
+const WRITTEN_ENTRIES: &[&str] = &[
+    "DataVersion",
+    "xPos",
+    "zPos",
+    "yPos",
+    "Status",
+    "LastUpdate",
+    "sections",
+    "Heightmaps",
+    "isLightOn",
+];
+
+/// The directory of the region files of `dimension` in the world at `level`, like vanilla.
+pub fn region_directory(level: &Path, dimension: &str) -> PathBuf {
+    match dimension {
+        "minecraft:overworld" => level.join("region"),
+        "minecraft:the_nether" => level.join("DIM-1").join("region"),
+        "minecraft:the_end" => level.join("DIM1").join("region"),
+        _ => {
+            let (namespace, path) = dimension
+                .split_once(':')
+                .unwrap_or(("minecraft", dimension));
+            level
+                .join("dimensions")
+                .join(namespace)
+                .join(path)
+                .join("region")
+        }
+    }
+}
+
+/// Chunk storage of a world, in region files.
+#[derive(Debug)]
+pub struct ChunkStorage {
+    regions: Mutex<RegionStorage>,
+}
+
+impl ChunkStorage {
+    /// Makes a storage for the region files in `directory`, writing chunks with `compression`.
+    pub fn new(directory: impl Into<PathBuf>, compression: Compression) -> Self {
+        Self {
+            regions: Mutex::new(RegionStorage::new(directory, compression)),
+        }
+    }
+
+    /// Loads the chunk at `pos`, returning `None` if it isn't stored or isn't fully generated.
+    pub fn load(
+        &self,
+        pos: ChunkPos,
+        min_y: i32,
+        height: u32,
+    ) -> Result<Option<Chunk>, ChunkStorageError> {
+        let nbt = self.regions.lock().unwrap().read_chunk(pos.x, pos.z)?;
+        match nbt {
+            Some(nbt) => read_chunk(&nbt, min_y, height),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves a chunk at `game_time`, returning `false` if it was skipped because it has blocks
+    /// or biomes the server doesn't know, which it would overwrite.
+    pub fn save(&self, chunk: &Chunk, game_time: i64) -> Result<bool, ChunkStorageError> {
+        let pos = chunk.pos();
+        let nbt = match write_chunk(chunk, game_time) {
+            Err(
+                err @ (ChunkStorageError::UnknownBlocks(_) | ChunkStorageError::UnknownBiomes(_)),
+            ) => {
+                tracing::warn!("Not saving chunk {}, {}: {}.", pos.x, pos.z, err);
+                return Ok(false);
+            }
+            result => result?,
+        };
+        self.regions
+            .lock()
+            .unwrap()
+            .write_chunk(pos.x, pos.z, &nbt)?;
+        Ok(true)
+    }
+
+    /// Flushes saved chunks to the disk.
+    pub fn sync(&self) -> Result<(), ChunkStorageError> {
+        self.regions.lock().unwrap().sync()?;
+        Ok(())
+    }
+}
+
+/// What is kept of a chunk read from storage, to save it back without losing what the server
+/// doesn't use yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredData {
+    /// The entries of the root compound not in [`WRITTEN_ENTRIES`], e.g. block entities or
+    /// scheduled ticks, encoded as NBT.
+    other_entries: Vec<u8>,
+    /// The blocks that were replaced with [`UNKNOWN_BLOCK`], as their names and properties.
+    unknown_blocks: Vec<String>,
+    /// The biomes that were replaced with the [default biome](super::DEFAULT_BIOME).
+    unknown_biomes: Vec<String>,
+}
+
+impl StoredData {
+    /// Whether the chunk can be saved without losing blocks or biomes.
+    pub fn is_lossless(&self) -> bool {
+        self.unknown_blocks.is_empty() && self.unknown_biomes.is_empty()
+    }
+}
+
+/// A chunk as stored in region files, with the entries the server reads or writes. The others
+/// are kept in [`StoredData`].
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkNbt {
+    #[serde(rename = "DataVersion")]
+    data_version: i32,
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    /// Lowest section.
+    #[serde(rename = "yPos")]
+    y_pos: i32,
+    #[serde(rename = "Status")]
+    status: String,
+    /// Game time when the chunk was last saved.
+    #[serde(rename = "LastUpdate", default)]
+    last_update: i64,
+    #[serde(default)]
+    sections: Vec<SectionNbt>,
+    /// Heightmaps by name. They are computed again when chunks are read, in case they are
+    /// missing or were computed with blocks the server doesn't know.
+    #[serde(rename = "Heightmaps", default)]
+    heightmaps: HashMap<String, Vec<i64>>,
+    /// Whether the light of the sections is stored. Light is computed again when chunks are
+    /// read, so only written.
+    #[serde(rename = "isLightOn", skip_deserializing)]
+    is_light_on: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SectionNbt {
+    #[serde(rename = "Y")]
+    y: i8,
+    /// Absent in sections only storing light, above or below the world.
+    #[serde(default, skip_serializing_if = "PalettedNbt::is_empty")]
+    block_states: PalettedNbt<BlockStateNbt>,
+    #[serde(default, skip_serializing_if = "PalettedNbt::is_empty")]
+    biomes: PalettedNbt<String>,
+    /// Light levels, two per byte. Only written, like [`ChunkNbt::is_light_on`].
+    #[serde(
+        rename = "BlockLight",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    block_light: Option<Vec<i8>>,
+    #[serde(
+        rename = "SkyLight",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    sky_light: Option<Vec<i8>>,
+}
+
+/// A paletted container as stored in region files, which always has a palette.
+#[derive(Debug, Serialize, Deserialize)]
+struct PalettedNbt<T> {
+    palette: Vec<T>,
+    /// Indices into the palette, absent if it has a single entry.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    data: Vec<i64>,
+}
+
+impl<T> PalettedNbt<T> {
+    fn is_empty(&self) -> bool {
+        self.palette.is_empty()
+    }
+}
+
+impl<T> Default for PalettedNbt<T> {
+    fn default() -> Self {
+        Self {
+            palette: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlockStateNbt {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(
+        rename = "Properties",
+        default,
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    properties: HashMap<String, String>,
+}
+
+impl fmt::Display for BlockStateNbt {
+    /// Formats the state like in commands, e.g. `minecraft:water[level=0]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if !self.properties.is_empty() {
+            let mut properties: Vec<String> = self
+                .properties
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            properties.sort();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the biomes, in network ID order.
+fn biome_names() -> impl Iterator<Item = &'static str> {
+    Registries::get()
+        .registry("minecraft:worldgen/biome")
+        .into_iter()
+        .flat_map(|registry| registry.entries().keys())
+        .map(String::as_str)
+}
+
+/// Reads a chunk from its NBT, returning `None` if it isn't fully generated.
+///
+/// Sections outside of `min_y` and `height` are dropped, and missing ones are left empty.
+/// Unknown blocks are replaced with stone and unknown biomes with the default biome, which
+/// make the chunk [not lossless](StoredData).
+pub fn read_chunk(nbt: &[u8], min_y: i32, height: u32) -> Result<Option<Chunk>, ChunkStorageError> {
+    let parser = NbtParser::parse(nbt, false)?;
+    let chunk: ChunkNbt = nbt::serde::from_parser(&parser)?;
+    if chunk.data_version < MIN_DATA_VERSION {
+        return Err(ChunkStorageError::UnsupportedVersion(chunk.data_version));
+    }
+    if !is_full(&chunk.status) {
+        return Ok(None);
+    }
+
+    let biome_ids: HashMap<&str, u32> = biome_names().zip(0..).collect();
+    let default_biome = biome_ids
+        .get(super::DEFAULT_BIOME)
+        .copied()
+        .unwrap_or_default();
+    let pos = ChunkPos::new(chunk.x_pos, chunk.z_pos);
+    let section_count = height as usize / SECTION_SIZE as usize;
+    let mut sections = vec![ChunkSection::new(default_biome); section_count];
+    let mut stored = StoredData {
+        other_entries: other_entries(nbt).ok_or(ChunkStorageError::Malformed)?,
+        unknown_blocks: Vec::new(),
+        unknown_biomes: Vec::new(),
+    };
+
+    for section in chunk.sections {
+        let index = (section.y as i32 * SECTION_SIZE - min_y) / SECTION_SIZE;
+        let Some(slot) = usize::try_from(index)
+            .ok()
+            .and_then(|index| sections.get_mut(index))
+        else {
+            continue;
+        };
+        if section.block_states.palette.is_empty() {
+            continue;
+        }
+        let section_error = |source| ChunkStorageError::Section {
+            y: section.y,
+            source,
+        };
+
+        let block_palette: Vec<u32> = section
+            .block_states
+            .palette
+            .iter()
+            .map(|state| {
+                let block = BlockState::from_nbt(&state.name, &state.properties);
+                let block = block.unwrap_or_else(|| {
+                    let name = state.to_string();
+                    if !stored.unknown_blocks.contains(&name) {
+                        stored.unknown_blocks.push(name);
+                    }
+                    UNKNOWN_BLOCK
+                });
+                block.id() as u32
+            })
+            .collect();
+        let block_states = container(
+            palette::BLOCK_STATES,
+            &block_palette,
+            &section.block_states.data,
+        )
+        .map_err(section_error)?;
+
+        let biome_palette: Vec<u32> = section
+            .biomes
+            .palette
+            .iter()
+            .map(|name| {
+                biome_ids.get(name.as_str()).copied().unwrap_or_else(|| {
+                    if !stored.unknown_biomes.contains(name) {
+                        stored.unknown_biomes.push(name.clone());
+                    }
+                    default_biome
+                })
+            })
+            .collect();
+        let biomes = if biome_palette.is_empty() {
+            PalettedContainer::filled(palette::BIOMES, default_biome)
+        } else {
+            container(palette::BIOMES, &biome_palette, &section.biomes.data)
+                .map_err(section_error)?
+        };
+
+        *slot = ChunkSection::from_containers(block_states, biomes);
+    }
+    if !stored.unknown_blocks.is_empty() {
+        tracing::debug!(
+            "Replacing unknown blocks {} in chunk {}, {} with {:?}.",
+            stored.unknown_blocks.join(", "),
+            pos.x,
+            pos.z,
+            UNKNOWN_BLOCK
+        );
+    }
+    if !stored.unknown_biomes.is_empty() {
+        tracing::debug!(
+            "Replacing unknown biomes {} in chunk {}, {} with {}.",
+            stored.unknown_biomes.join(", "),
+            pos.x,
+            pos.z,
+            super::DEFAULT_BIOME
+        );
+    }
+
+    let mut chunk = Chunk::from_sections(pos, min_y, sections);
+    chunk.set_stored_data(stored);
+    Ok(Some(chunk))
+}
+
+/// Whether `status` is the one of fully generated chunks, with or without its namespace.
+fn is_full(status: &str) -> bool {
+    status.strip_prefix("minecraft:").unwrap_or(status) == "full"
+}
+
+fn container(
+    kind: palette::PaletteKind,
+    palette: &[u32],
+    data: &[i64],
+) -> Result<PalettedContainer, PaletteError> {
+    let data: Vec<u64> = data.iter().map(|&long| long as u64).collect();
+    PalettedContainer::from_palette(kind, palette, &data)
+}
+
+/// Writes a chunk into NBT, as a fully generated chunk saved at `game_time`, along with what
+/// was kept of it when it was read.
+///
+/// Fails with [`ChunkStorageError::UnknownBlocks`] or [`ChunkStorageError::UnknownBiomes`] if
+/// the chunk has blocks or biomes the server doesn't know, which would be lost.
+pub fn write_chunk(chunk: &Chunk, game_time: i64) -> Result<Vec<u8>, ChunkStorageError> {
+    let stored = chunk.stored_data();
+    if !stored.unknown_blocks.is_empty() {
+        return Err(ChunkStorageError::UnknownBlocks(
+            stored.unknown_blocks.clone(),
+        ));
+    }
+    if !stored.unknown_biomes.is_empty() {
+        return Err(ChunkStorageError::UnknownBiomes(
+            stored.unknown_biomes.clone(),
+        ));
+    }
+
+    let biome_names: Vec<&str> = biome_names().collect();
+    let (light_bottom, _) = chunk.light_range();
+    let block_light = chunk.light_sections(LightKind::Block);
+    let sky_light = chunk.light_sections(LightKind::Sky);
+    let mut sections = Vec::with_capacity(sky_light.len());
+    for (index, (block_light, sky_light)) in block_light.iter().zip(sky_light).enumerate() {
+        let mut section = SectionNbt {
+            y: ((light_bottom >> 4) + index as i32) as i8,
+            block_states: PalettedNbt::default(),
+            biomes: PalettedNbt::default(),
+            block_light: Some(light_nbt(block_light)),
+            sky_light: Some(light_nbt(sky_light)),
+        };
+        // The light sections start one section below the blocks.
+        if let Some(blocks) = index
+            .checked_sub(1)
+            .and_then(|index| chunk.sections().get(index))
+        {
+            let (block_palette, block_data) = blocks.block_states().to_palette();
+            let (biome_palette, biome_data) = blocks.biomes().to_palette();
+            section.block_states = PalettedNbt {
+                palette: block_palette
+                    .into_iter()
+                    .map(|id| block_state_nbt(BlockState(id as u16)))
+                    .collect::<Result<_, ChunkStorageError>>()?,
+                data: block_data.into_iter().map(|long| long as i64).collect(),
+            };
+            section.biomes = PalettedNbt {
+                palette: biome_palette
+                    .into_iter()
+                    .map(|id| {
+                        let name = biome_names.get(id as usize).copied();
+                        name.unwrap_or(super::DEFAULT_BIOME).to_owned()
+                    })
+                    .collect(),
+                data: biome_data.into_iter().map(|long| long as i64).collect(),
+            };
+        }
+        sections.push(section);
+    }
+
+    let chunk_nbt = ChunkNbt {
+        data_version: DATA_VERSION,
+        x_pos: chunk.pos().x,
+        z_pos: chunk.pos().z,
+        y_pos: chunk.min_y() >> 4,
+        status: STATUS_FULL.to_owned(),
+        last_update: game_time,
+        sections,
+        heightmaps: chunk.heightmaps_nbt(),
+        is_light_on: true,
+    };
+    let mut nbt = nbt::serde::to_bytes(&chunk_nbt)?;
+    // Put the other entries back at the end of the root compound, before its end tag.
+    nbt.pop();
+    nbt.extend_from_slice(&stored.other_entries);
+    nbt.push(TAG_END);
+    Ok(nbt)
+}
+
+fn light_nbt(section: &LightSection) -> Vec<i8> {
+    section
+        .to_bytes()
+        .into_iter()
+        .map(|byte| byte as i8)
+        .collect()
+}
+
+fn block_state_nbt(block: BlockState) -> Result<BlockStateNbt, ChunkStorageError> {
+    // Only blocks with names can be set for now, but this keeps an unknown state from being
+    // saved as something else.
+    let (name, properties) = block
+        .name()
+        .ok_or_else(|| ChunkStorageError::UnknownBlocks(vec![format!("{block:?}")]))?;
+    Ok(BlockStateNbt {
+        name: name.to_owned(),
+        properties: properties
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect(),
+    })
+}
+
+// Tags of encoded NBT, for reading the entries of the root compound as they are.
+const TAG_END: u8 = 0;
+const TAG_BYTE: u8 = 1;
+const TAG_SHORT: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_LONG: u8 = 4;
+const TAG_FLOAT: u8 = 5;
+const TAG_DOUBLE: u8 = 6;
+const TAG_BYTE_ARRAY: u8 = 7;
+const TAG_STRING: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_COMPOUND: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_LONG_ARRAY: u8 = 12;
+
+/// Deepest nesting of lists and compounds read, same as vanilla.
+const MAX_DEPTH: usize = 512;
+
+/// Returns the entries of the root compound of `nbt` not in [`WRITTEN_ENTRIES`], as they are
+/// encoded, or `None` if `nbt` is malformed.
+fn other_entries(nbt: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = RawNbtReader { nbt, pos: 0 };
+    if reader.byte()? != TAG_COMPOUND {
+        return None;
+    }
+    reader.string()?;
+
+    let mut entries = Vec::new();
+    loop {
+        let start = reader.pos;
+        let tag = reader.byte()?;
+        if tag == TAG_END {
+            return Some(entries);
+        }
+        let name = reader.string()?;
+        reader.skip_payload(tag, 0)?;
+        if !WRITTEN_ENTRIES
+            .iter()
+            .any(|written| written.as_bytes() == name)
+        {
+            entries.extend_from_slice(&nbt[start..reader.pos]);
+        }
+    }
+}
+
+/// Reads through encoded NBT without decoding it.
+struct RawNbtReader<'a> {
+    nbt: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RawNbtReader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.nbt.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    /// Reads the length of an array or list.
+    fn length(&mut self) -> Option<usize> {
+        let length = i32::from_be_bytes(self.take(4)?.try_into().ok()?);
+        usize::try_from(length).ok()
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let length = u16::from_be_bytes(self.take(2)?.try_into().ok()?);
+        self.take(length as usize)
+    }
+
+    /// Skips the value of a tag, `depth` lists and compounds deep.
+    fn skip_payload(&mut self, tag: u8, depth: usize) -> Option<()> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match tag {
+            TAG_BYTE => self.take(1),
+            TAG_SHORT => self.take(2),
+            TAG_INT | TAG_FLOAT => self.take(4),
+            TAG_LONG | TAG_DOUBLE => self.take(8),
+            TAG_BYTE_ARRAY => {
+                let length = self.length()?;
+                self.take(length)
+            }
+            TAG_STRING => self.string(),
+            TAG_LIST => {
+                let element = self.byte()?;
+                for _ in 0..self.length()? {
+                    self.skip_payload(element, depth + 1)?;
+                }
+                return Some(());
+            }
+            TAG_COMPOUND => loop {
+                let tag = self.byte()?;
+                if tag == TAG_END {
+                    return Some(());
+                }
+                self.string()?;
+                self.skip_payload(tag, depth + 1)?;
+            },
+            TAG_INT_ARRAY => {
+                let length = self.length()?;
+                self.take(length.checked_mul(4)?)
+            }
+            TAG_LONG_ARRAY => {
+                let length = self.length()?;
+                self.take(length.checked_mul(8)?)
+            }
+            _ => None,
+        }
+        .map(|_| ())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ChunkStorageError {
+    #[error(transparent)]
+    Region(#[from] RegionError),
+    #[error("invalid chunk NBT: {0}")]
+    Parse(#[from] nbt::NbtParseError),
+    #[error("invalid chunk NBT: {0}")]
+    Serde(#[from] nbt::serde::Error),
+    #[error("chunk data version {0} is too old")]
+    UnsupportedVersion(i32),
+    #[error("invalid section {y}: {source}")]
+    Section { y: i8, source: PaletteError },
+    #[error("invalid chunk NBT")]
+    Malformed,
+    #[error("chunk has unknown blocks {}", .0.join(", "))]
+    UnknownBlocks(Vec<String>),
+    #[error("chunk has unknown biomes {}", .0.join(", "))]
+    UnknownBiomes(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::BlockPosition;
+
+    use super::*;
+    use crate::world::generator::{ChunkGenerator, FlatGenerator};
+
+    const MIN_Y: i32 = -64;
+    const HEIGHT: u32 = 384;
+
+    /// A chunk of a small flat world, with a pool of water that differs between chunks.
+    fn generate(pos: ChunkPos) -> Chunk {
+        let generator = FlatGenerator::default();
+        let mut chunk = generator.generate(pos, MIN_Y, HEIGHT);
+        let water = BlockPosition::new(pos.x.rem_euclid(16), MIN_Y + 3, pos.z.rem_euclid(16));
+        chunk.set_block(water, BlockState::WATER);
+        chunk
+    }
+
+    /// Changes the entries the server reads of chunk NBT.
+    fn modify(nbt: &[u8], modify: impl FnOnce(&mut ChunkNbt)) -> Vec<u8> {
+        let parser = NbtParser::parse(nbt, false).unwrap();
+        let mut chunk: ChunkNbt = nbt::serde::from_parser(&parser).unwrap();
+        modify(&mut chunk);
+        nbt::serde::to_bytes(&chunk).unwrap()
+    }
+
+    /// Encodes an entry of a compound.
+    fn entry(tag: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut entry = vec![tag];
+        entry.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        entry.extend_from_slice(name.as_bytes());
+        entry.extend_from_slice(payload);
+        entry
+    }
+
+    /// Entries the server doesn't read, like those of block entities.
+    fn other_test_entries() -> Vec<u8> {
+        let mut block_entity = entry(TAG_STRING, "id", b"\0\x0Fminecraft:chest");
+        block_entity.extend(entry(TAG_INT, "x", &3i32.to_be_bytes()));
+        block_entity.extend(entry(TAG_LIST, "Items", &[TAG_END, 0, 0, 0, 0]));
+        block_entity.push(TAG_END);
+        let mut block_entities = vec![TAG_COMPOUND, 0, 0, 0, 1];
+        block_entities.extend(block_entity);
+
+        let mut entries = entry(TAG_LIST, "block_entities", &block_entities);
+        entries.extend(entry(TAG_BYTE_ARRAY, "bytes", &[0, 0, 0, 2, 1, 2]));
+        entries.extend(entry(
+            TAG_LONG_ARRAY,
+            "longs",
+            &[0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 7],
+        ));
+        entries.extend(entry(TAG_DOUBLE, "double", &1.5f64.to_be_bytes()));
+        entries
+    }
+
+    fn with_entries(nbt: &[u8], entries: &[u8]) -> Vec<u8> {
+        let mut nbt = nbt[..nbt.len() - 1].to_vec();
+        nbt.extend_from_slice(entries);
+        nbt.push(TAG_END);
+        nbt
+    }
+
+    #[test]
+    fn saves_and_loads_generated_world() {
+        let directory = tempfile::tempdir().unwrap();
+        let positions = [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(1, 0),
+            ChunkPos::new(-1, -1),
+            ChunkPos::new(40, -7),
+        ];
+        let storage = ChunkStorage::new(directory.path(), Compression::Zlib);
+        for pos in positions {
+            assert!(storage.save(&generate(pos), 100).unwrap());
+        }
+        storage.sync().unwrap();
+
+        let storage = ChunkStorage::new(directory.path(), Compression::Zlib);
+        for pos in positions {
+            let chunk = storage.load(pos, MIN_Y, HEIGHT).unwrap();
+            assert_eq!(chunk, Some(generate(pos)), "chunk {pos:?}");
+        }
+        assert_eq!(
+            storage.load(ChunkPos::new(2, 0), MIN_Y, HEIGHT).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_status_with_and_without_namespace() {
+        let nbt = write_chunk(&generate(ChunkPos::new(0, 0)), 0).unwrap();
+        for (status, is_full) in [
+            ("minecraft:full", true),
+            ("full", true),
+            ("minecraft:features", false),
+            ("features", false),
+        ] {
+            let nbt = modify(&nbt, |chunk| chunk.status = status.to_owned());
+            let chunk = read_chunk(&nbt, MIN_Y, HEIGHT).unwrap();
+            assert_eq!(chunk.is_some(), is_full, "status {status}");
+        }
+    }
+
+    #[test]
+    fn keeps_other_entries() {
+        let nbt = write_chunk(&generate(ChunkPos::new(0, 0)), 0).unwrap();
+        let entries = other_test_entries();
+        let nbt = with_entries(&nbt, &entries);
+        assert_eq!(other_entries(&nbt).unwrap(), entries);
+
+        let chunk = read_chunk(&nbt, MIN_Y, HEIGHT).unwrap().unwrap();
+        let written = write_chunk(&chunk, 0).unwrap();
+        assert_eq!(other_entries(&written).unwrap(), entries);
+    }
+
+    #[test]
+    fn reads_through_nbt() {
+        let nbt = write_chunk(&generate(ChunkPos::new(0, 0)), 0).unwrap();
+        assert_eq!(other_entries(&nbt), Some(Vec::new()));
+        // Truncated in the middle of the entries.
+        let entries = other_test_entries();
+        let nbt = with_entries(&nbt, &entries[..entries.len() - 4]);
+        assert_eq!(other_entries(&nbt[..nbt.len() - 1]), None);
+
+        // Not a compound.
+        assert_eq!(other_entries(&[TAG_INT, 0, 0, 0, 0, 0, 0]), None);
+        // Negative length.
+        let negative = with_entries(
+            &[TAG_COMPOUND, 0, 0, TAG_END],
+            &entry(TAG_INT_ARRAY, "a", &[0xFF; 4]),
+        );
+        assert_eq!(other_entries(&negative), None);
+        // Unknown tag.
+        let unknown = with_entries(&[TAG_COMPOUND, 0, 0, TAG_END], &entry(13, "a", &[]));
+        assert_eq!(other_entries(&unknown), None);
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth: usize| {
+            let mut payload = Vec::new();
+            for _ in 0..depth {
+                payload.extend(entry(TAG_COMPOUND, "", &[]));
+            }
+            payload.resize(payload.len() + depth + 1, TAG_END);
+            with_entries(
+                &[TAG_COMPOUND, 0, 0, TAG_END],
+                &entry(TAG_COMPOUND, "a", &payload),
+            )
+        };
+        assert!(other_entries(&nested(MAX_DEPTH)).is_some());
+        assert_eq!(other_entries(&nested(MAX_DEPTH + 1)), None);
+    }
+
+    #[test]
+    fn refuses_to_save_unknown_blocks_and_biomes() {
+        let directory = tempfile::tempdir().unwrap();
+        let storage = ChunkStorage::new(directory.path(), Compression::Zlib);
+        let nbt = write_chunk(&generate(ChunkPos::new(0, 0)), 0).unwrap();
+
+        let unknown_block = modify(&nbt, |chunk| {
+            let section = chunk.sections.iter_mut().find(|section| section.y == 0);
+            section.unwrap().block_states = PalettedNbt {
+                palette: vec![BlockStateNbt {
+                    name: "example:block".to_owned(),
+                    properties: HashMap::from([("a".to_owned(), "b".to_owned())]),
+                }],
+                data: Vec::new(),
+            };
+        });
+        let chunk = read_chunk(&unknown_block, MIN_Y, HEIGHT).unwrap().unwrap();
+        assert_eq!(chunk.block(BlockPosition::new(0, 0, 0)), UNKNOWN_BLOCK);
+        assert!(!chunk.stored_data().is_lossless());
+        assert!(matches!(
+            write_chunk(&chunk, 0),
+            Err(ChunkStorageError::UnknownBlocks(blocks)) if blocks == ["example:block[a=b]"]
+        ));
+        assert!(!storage.save(&chunk, 0).unwrap());
+
+        let unknown_biome = modify(&nbt, |chunk| {
+            let section = chunk.sections.iter_mut().find(|section| section.y == 0);
+            section.unwrap().biomes = PalettedNbt {
+                palette: vec!["example:biome".to_owned()],
+                data: Vec::new(),
+            };
+        });
+        let chunk = read_chunk(&unknown_biome, MIN_Y, HEIGHT).unwrap().unwrap();
+        assert!(!chunk.stored_data().is_lossless());
+        assert!(matches!(
+            write_chunk(&chunk, 0),
+            Err(ChunkStorageError::UnknownBiomes(biomes)) if biomes == ["example:biome"]
+        ));
+        assert!(!storage.save(&chunk, 0).unwrap());
+        assert_eq!(
+            storage.load(ChunkPos::new(0, 0), MIN_Y, HEIGHT).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_old_chunks() {
+        let nbt = write_chunk(&generate(ChunkPos::new(0, 0)), 0).unwrap();
+        let old = modify(&nbt, |chunk| chunk.data_version = MIN_DATA_VERSION - 1);
+        assert!(matches!(
+            read_chunk(&old, MIN_Y, HEIGHT),
+            Err(ChunkStorageError::UnsupportedVersion(2843))
+        ));
+    }
+
+    #[test]
+    fn region_directories() {
+        let level = Path::new("world");
+        assert_eq!(
+            region_directory(level, "minecraft:overworld"),
+            Path::new("world/region")
+        );
+        assert_eq!(
+            region_directory(level, "minecraft:the_nether"),
+            Path::new("world/DIM-1/region")
+        );
+        assert_eq!(
+            region_directory(level, "minecraft:the_end"),
+            Path::new("world/DIM1/region")
+        );
+        assert_eq!(
+            region_directory(level, "example:mining"),
+            Path::new("world/dimensions/example/mining/region")
+        );
+    }
+}